@@ -18,12 +18,20 @@ VENV_DIR="${SCRIPT_DIR}/.venv" # Use .venv for convention
 K8S_DIR="${SCRIPT_DIR}/k8s"
 SKIP_SETUP=false
 RUN_NUMBER_OVERRIDE=""
+COMPRESSION="on" # on or off: whether the parent requests gzip-compressed API responses
+SCENARIO=${SCENARIO:-"ring"} # ring, burst or churn
+BURST_SIZE=500 # objects created at once per burst in the burst scenario
+BURSTS=3
+STATE_BYTES=$((64 * 1024 * 1024)) # state kept by the burst operator
+UNLOAD_AFTER=10 # idle seconds after which operators are unloaded in the burst and churn scenarios
+SNAPSHOT_STRATEGY="serialize" # serialize or pre-init: how unloaded operators are reloaded
+CHURN_CYCLES=5
 
 # --- Functions ---
 
 # Function to print usage
 usage() {
-    echo "Usage: $0 [--operator-counts \"10 20 30\"] [--runs-per-count 5] [--operator-type <mixed|go|rust>] [--active-duration 420] [--idle-duration 120] [--output-dir ./results] [--skip-setup]"
+    echo "Usage: $0 [--operator-counts \"10 20 30\"] [--runs-per-count 5] [--operator-type <mixed|go|rust>] [--active-duration 420] [--idle-duration 120] [--output-dir ./results] [--compression <on|off>] [--scenario <ring|burst|churn>] [--snapshot-strategy <serialize|pre-init>] [--churn-cycles 5] [--burst-size 500] [--bursts 3] [--state-bytes 67108864] [--skip-setup]"
     exit 1
 }
 
@@ -111,6 +119,12 @@ build_and_load_images() {
     echo "    Building Go operator..."
     (cd "${SCRIPT_DIR}/operators/go-operator" && ./compile.sh)
     cp "${SCRIPT_DIR}/operators/go-operator/target/ring-operator-go.wasm" "${SCRIPT_DIR}/build/go-operator.wasm"
+    echo "    Building burst operator..."
+    (cd "${SCRIPT_DIR}/operators/burst-operator" && ./compile.sh)
+    cp "${SCRIPT_DIR}/operators/burst-operator/target/burst-operator-rust.wasm" "${SCRIPT_DIR}/build/burst-operator.wasm"
+    echo "    Building load generator..."
+    (cd "${SCRIPT_DIR}/operators/load-generator" && ./compile.sh)
+    cp "${SCRIPT_DIR}/operators/load-generator/target/load-generator-rust.wasm" "${SCRIPT_DIR}/build/load-generator.wasm"
     echo "    Building parent operator image..."
     docker build -t wasm-operator-rework:latest -f "${SCRIPT_DIR}/../Dockerfile" "${SCRIPT_DIR}/.."
     echo "    Loading image into kind..."
@@ -124,7 +138,26 @@ deploy_parent_operator_config() {
     local op_type=$2
     echo "⚙️ Generating and deploying config for ${count} operators (${op_type})..."
 
-    CONFIG_YAML="apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: parent-config\ndata:\n  configuration.yaml: |"
+    local disable_compression="false"
+    if [ "${COMPRESSION}" = "off" ]; then
+        disable_compression="true"
+    fi
+
+    CONFIG_YAML="apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: parent-config\ndata:"
+    CONFIG_YAML+="\n  runtime.yaml: |"
+    CONFIG_YAML+="\n    kubernetes:"
+    CONFIG_YAML+="\n      disable-compression: ${disable_compression}"
+    if [ "${SCENARIO}" = "burst" ]; then
+        # Unload idle operators quickly, so bursts hit unloaded operators and the
+        # snapshot of the burst operator is written and read between bursts.
+        CONFIG_YAML+="\n    idle-unload-secs: ${UNLOAD_AFTER}"
+    elif [ "${SCENARIO}" = "churn" ]; then
+        # The churn driver reads the reload times and snapshot sizes from the metrics.
+        CONFIG_YAML+="\n    idle-unload-secs: ${UNLOAD_AFTER}"
+        CONFIG_YAML+="\n    snapshot-strategy: ${SNAPSHOT_STRATEGY}"
+        CONFIG_YAML+="\n    metrics-enabled: true"
+    fi
+    CONFIG_YAML+="\n  configuration.yaml: |"
 
     for i in $(seq 1 "${count}"); do
         local wasm_module=""
@@ -147,6 +180,21 @@ deploy_parent_operator_config() {
             ACTION_NAMESPACE="ns-$((i + 1))"
         fi
 
+        # In the burst scenario, the first operator absorbs the bursts and the others
+        # are idle ring operators that are unloaded in the background.
+        if [ "${SCENARIO}" = "burst" ] && [ "$i" -eq 1 ]; then
+            CONFIG_YAML+="\n    ---\n    name: burst-operator"
+            CONFIG_YAML+="\n    wasm: /app/wasm/burst-operator.wasm"
+            CONFIG_YAML+="\n    env:"
+            CONFIG_YAML+="\n    - name: WATCH_NAMESPACE"
+            CONFIG_YAML+="\n      value: ns-burst"
+            CONFIG_YAML+="\n    - name: ACTION_NAMESPACE"
+            CONFIG_YAML+="\n      value: ns-final"
+            CONFIG_YAML+="\n    - name: STATE_BYTES"
+            CONFIG_YAML+="\n      value: \"${STATE_BYTES}\""
+            continue
+        fi
+
         CONFIG_YAML+="\n    ---\n    name: operator-${i}"
         CONFIG_YAML+="\n    wasm: /app/wasm/${wasm_module}"
         CONFIG_YAML+="\n    env:"
@@ -180,6 +228,7 @@ run_test_iteration() {
     
     local latency_file="${OUTPUT_DIR}/latency.csv"
     local memory_file="${OUTPUT_DIR}/memory.csv"
+    local network_file="${OUTPUT_DIR}/network.csv"
 
     mkdir -p "${OUTPUT_DIR}"
 
@@ -191,6 +240,10 @@ run_test_iteration() {
     done
     kubectl create ns "ns-final" || true
     kubectl label ns "ns-final" benchmark-ns=true || true
+    if [ "${SCENARIO}" = "burst" ]; then
+        kubectl create ns "ns-burst" || true
+        kubectl label ns "ns-burst" benchmark-ns=true || true
+    fi
 
     kubectl apply -f "${K8S_DIR}/testresource-crd.yaml"
     kubectl apply -f "${K8S_DIR}/rbac.yaml"
@@ -205,6 +258,35 @@ run_test_iteration() {
     start_port_forward
     trap stop_port_forward RETURN
 
+    if [ "${SCENARIO}" = "burst" ]; then
+        echo "🐍 Executing burst driver..."
+        "${VENV_DIR}/bin/python3" "${SCRIPT_DIR}/burst_driver.py" \
+            --operator-count "${count}" \
+            --run-number "${run}" \
+            --burst-size "${BURST_SIZE}" \
+            --bursts "${BURSTS}" \
+            --idle-duration "$((UNLOAD_AFTER + IDLE_DURATION))" \
+            --latency-file "${OUTPUT_DIR}/burst_latency.csv" \
+            --summary-file "${OUTPUT_DIR}/burst_summary.csv"
+        return
+    fi
+
+    if [ "${SCENARIO}" = "churn" ]; then
+        kubectl port-forward deployment/parent-operator 8081:8080 >/dev/null 2>&1 &
+        ADMIN_PF_PID=$!
+        sleep 5
+        echo "🐍 Executing churn driver..."
+        "${VENV_DIR}/bin/python3" "${SCRIPT_DIR}/churn_driver.py" \
+            --operator-count "${count}" \
+            --run-number "${run}" \
+            --strategy "${SNAPSHOT_STRATEGY}" \
+            --cycles "${CHURN_CYCLES}" \
+            --unload-wait "$((UNLOAD_AFTER * 2 + 5))" \
+            --churn-file "${OUTPUT_DIR}/memory_churn.csv"
+        kill "${ADMIN_PF_PID}"
+        return
+    fi
+
     # Run the test driver
     echo "🐍 Executing test driver..."
     "${VENV_DIR}/bin/python3" "${SCRIPT_DIR}/test_driver.py" \
@@ -212,6 +294,8 @@ run_test_iteration() {
         --run-number "${run}" \
         --latency-file "${latency_file}" \
         --memory-file "${memory_file}" \
+        --network-file "${network_file}" \
+        --compression "${COMPRESSION}" \
         --active-duration "${ACTIVE_DURATION}" \
         --idle-duration "${IDLE_DURATION}"
 
@@ -246,6 +330,13 @@ while [[ "$#" -gt 0 ]]; do
         --active-duration) ACTIVE_DURATION="$2"; shift ;;
         --idle-duration) IDLE_DURATION="$2"; shift ;;
         --output-dir) OUTPUT_DIR="$2"; shift ;;
+        --compression) COMPRESSION="$2"; shift ;;
+        --scenario) SCENARIO="$2"; shift ;;
+        --burst-size) BURST_SIZE="$2"; shift ;;
+        --bursts) BURSTS="$2"; shift ;;
+        --state-bytes) STATE_BYTES="$2"; shift ;;
+        --snapshot-strategy) SNAPSHOT_STRATEGY="$2"; shift ;;
+        --churn-cycles) CHURN_CYCLES="$2"; shift ;;
         --skip-setup) SKIP_SETUP=false ;;
         --run-number) RUN_NUMBER_OVERRIDE="$2"; shift ;;
         *) usage ;;
@@ -0,0 +1,150 @@
+import argparse
+import csv
+import logging
+import threading
+import time
+
+from kubernetes import client, config, watch
+
+from test_driver import get_memory_usage, write_header_if_needed
+
+CRD_GROUP = "ring.benchmark.com"
+CRD_VERSION = "v1"
+CRD_PLURAL = "testresources"
+BURST_NAMESPACE = "ns-burst"
+FINAL_NAMESPACE = "ns-final"
+
+
+class CopyWatcher:
+    """Watches the copies the burst operator makes and records when each final nonce arrives."""
+
+    def __init__(self, api, expected):
+        self.api = api
+        # Object name -> nonce of its last write in the burst namespace.
+        self.expected = expected
+        self.seen = {}
+        self.reconciles = 0
+        self.lock = threading.Lock()
+        self.done = threading.Event()
+        self.watch = watch.Watch()
+
+    def run(self, resource_version):
+        for event in self.watch.stream(
+                self.api.list_namespaced_custom_object,
+                group=CRD_GROUP,
+                version=CRD_VERSION,
+                namespace=FINAL_NAMESPACE,
+                plural=CRD_PLURAL,
+                resource_version=resource_version):
+            if event['type'] not in ['ADDED', 'MODIFIED']:
+                continue
+            resource = event['object']
+            name = resource['metadata']['name']
+            spec = resource.get('spec', {})
+            with self.lock:
+                self.reconciles = max(self.reconciles, spec.get('reconciles') or 0)
+                if name in self.expected and name not in self.seen \
+                        and spec.get('nonce') == self.expected[name]:
+                    self.seen[name] = time.perf_counter()
+                    if len(self.seen) == len(self.expected):
+                        self.done.set()
+                        self.watch.stop()
+
+
+def run_burst(api, burst, size, updates, timeout):
+    """Creates `size` objects at once, updates each `updates` times, and waits for all copies."""
+    names = [f"burst-{burst}-{i}" for i in range(size)]
+    final_nonce = f"{burst}-{updates}"
+    listing = api.list_namespaced_custom_object(
+        group=CRD_GROUP, version=CRD_VERSION, namespace=FINAL_NAMESPACE, plural=CRD_PLURAL)
+    watcher = CopyWatcher(api, {name: final_nonce for name in names})
+    thread = threading.Thread(target=watcher.run, args=(listing['metadata']['resourceVersion'],), daemon=True)
+    thread.start()
+
+    start = time.perf_counter()
+    written = {}
+    for name in names:
+        api.create_namespaced_custom_object(
+            group=CRD_GROUP, version=CRD_VERSION, namespace=BURST_NAMESPACE, plural=CRD_PLURAL,
+            body={
+                "apiVersion": f"{CRD_GROUP}/{CRD_VERSION}",
+                "kind": "TestResource",
+                "metadata": {"name": name},
+                "spec": {"nonce": f"{burst}-0"}
+            })
+        written[name] = time.perf_counter()
+    # Updates that arrive while earlier events are still queued may be coalesced.
+    for update in range(1, updates + 1):
+        for name in names:
+            api.patch_namespaced_custom_object(
+                group=CRD_GROUP, version=CRD_VERSION, namespace=BURST_NAMESPACE, plural=CRD_PLURAL,
+                name=name, body={"spec": {"nonce": f"{burst}-{update}"}})
+            written[name] = time.perf_counter()
+
+    if not watcher.done.wait(timeout):
+        logging.warning(f"Burst {burst}: only {len(watcher.seen)} of {size} copies arrived within {timeout}s")
+        watcher.watch.stop()
+    memory = get_memory_usage()
+
+    with watcher.lock:
+        latencies = [(watcher.seen[name] - written[name]) * 1000 for name in names if name in watcher.seen]
+        drain_ms = (max(watcher.seen.values()) - start) * 1000 if watcher.seen else float('nan')
+        reconciles = watcher.reconciles
+    return latencies, drain_ms, reconciles, memory
+
+
+def main():
+    parser = argparse.ArgumentParser(description="Run a burst absorption benchmark against the burst operator.")
+    parser.add_argument("--operator-count", type=int, required=True, help="The number of operators in the parent.")
+    parser.add_argument("--burst-size", type=int, required=True, help="Number of objects created per burst.")
+    parser.add_argument("--bursts", type=int, default=3, help="Number of bursts.")
+    parser.add_argument("--updates-per-object", type=int, default=2, help="Updates made to each object after its creation.")
+    parser.add_argument("--idle-duration", type=int, required=True, help="Pause between bursts in seconds, long enough for the operator to be unloaded.")
+    parser.add_argument("--burst-timeout", type=int, default=600, help="Time to wait for the copies of a burst in seconds.")
+    parser.add_argument("--latency-file", type=str, required=True, help="File to save the per-object latencies.")
+    parser.add_argument("--summary-file", type=str, required=True, help="File to save the per-burst summary.")
+    parser.add_argument("--run-number", type=int, required=True, help="The current run number.")
+    args = parser.parse_args()
+
+    logging.basicConfig(level=logging.INFO, format='%(asctime)s %(levelname)s %(message)s', datefmt='%H:%M:%S')
+
+    logging.info(f"🐍 Burst Driver Started: Operators={args.operator_count}, Burst size={args.burst_size}, Run={args.run_number}")
+
+    write_header_if_needed(args.latency_file, ["operator_count", "run_number", "burst", "burst_size", "latency_ms"])
+    write_header_if_needed(args.summary_file,
+                           ["operator_count", "run_number", "burst", "burst_size", "events", "reconciles",
+                            "drain_ms", "memory_bytes"])
+
+    try:
+        config.load_kube_config()
+    except config.ConfigException:
+        config.load_incluster_config()
+    api = client.CustomObjectsApi()
+
+    previous_reconciles = 0
+    for burst in range(1, args.bursts + 1):
+        logging.info(f"💥 Burst {burst} of {args.bursts}...")
+        latencies, drain_ms, reconciles, memory = run_burst(
+            api, burst, args.burst_size, args.updates_per_object, args.burst_timeout)
+        events = args.burst_size * (1 + args.updates_per_object)
+        logging.info(f"Burst {burst}: drained in {drain_ms:.0f}ms, {reconciles - previous_reconciles} reconciles for {events} events")
+
+        with open(args.latency_file, "a", newline="") as csvfile:
+            writer = csv.writer(csvfile)
+            for latency in latencies:
+                writer.writerow([args.operator_count, args.run_number, burst, args.burst_size, latency])
+        with open(args.summary_file, "a", newline="") as csvfile:
+            writer = csv.writer(csvfile)
+            writer.writerow([args.operator_count, args.run_number, burst, args.burst_size, events,
+                             reconciles - previous_reconciles, drain_ms, memory])
+        previous_reconciles = reconciles
+
+        if burst < args.bursts:
+            logging.info(f"😴 Sleeping for {args.idle_duration} seconds so the operator is unloaded...")
+            time.sleep(args.idle_duration)
+
+    logging.info("🎉 Burst Driver Finished Successfully!")
+
+
+if __name__ == "__main__":
+    main()
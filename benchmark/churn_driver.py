@@ -0,0 +1,123 @@
+import argparse
+import csv
+import logging
+import time
+
+import requests
+from kubernetes import client, config, watch
+
+from test_driver import get_memory_usage, write_header_if_needed
+
+ADMIN_URL = "http://localhost:8081"
+CRD_GROUP = "ring.benchmark.com"
+CRD_VERSION = "v1"
+CRD_PLURAL = "testresources"
+RESOURCE_NAME = "the-resource"
+INITIAL_NAMESPACE = "ns-1"
+FINAL_NAMESPACE = "ns-final"
+
+
+def get_operator_gauge(name):
+    """Returns the values of a per-operator gauge from the admin API of the parent."""
+    values = {}
+    try:
+        response = requests.get(f"{ADMIN_URL}/metrics")
+        response.raise_for_status()
+    except requests.exceptions.RequestException as e:
+        logging.error(f"Error querying the admin API: {e}")
+        return values
+    for line in response.text.splitlines():
+        if line.startswith(name + "{"):
+            labels, value = line[len(name) + 1:].rsplit("} ", 1)
+            operator = labels.split('operator="', 1)[1].split('"', 1)[0]
+            values[operator] = float(value)
+    return values
+
+
+def trigger_ring(api, nonce, timeout):
+    """Sends a change around the ring and returns the time it took in milliseconds, or None."""
+    w = watch.Watch()
+    stream = w.stream(
+        api.list_namespaced_custom_object,
+        group=CRD_GROUP,
+        version=CRD_VERSION,
+        namespace=FINAL_NAMESPACE,
+        plural=CRD_PLURAL,
+        field_selector=f"metadata.name={RESOURCE_NAME}",
+        timeout_seconds=timeout
+    )
+    start = time.perf_counter()
+    api.patch_namespaced_custom_object(
+        group=CRD_GROUP, version=CRD_VERSION, namespace=INITIAL_NAMESPACE, plural=CRD_PLURAL,
+        name=RESOURCE_NAME, body={"spec": {"nonce": nonce}})
+    for event in stream:
+        if event['type'] in ['ADDED', 'MODIFIED'] and event['object'].get('spec', {}).get('nonce') == nonce:
+            w.stop()
+            return (time.perf_counter() - start) * 1000
+    return None
+
+
+def main():
+    parser = argparse.ArgumentParser(description="Measure reloads of unloaded operators under a snapshot strategy.")
+    parser.add_argument("--operator-count", type=int, required=True, help="The number of operators in the ring.")
+    parser.add_argument("--strategy", type=str, required=True, help="The snapshot strategy of the parent.")
+    parser.add_argument("--cycles", type=int, default=5, help="Number of unload and reload cycles.")
+    parser.add_argument("--unload-wait", type=int, required=True, help="Time to wait for all operators to be unloaded in seconds.")
+    parser.add_argument("--ring-timeout", type=int, default=300, help="Time to wait for a change to go around the ring in seconds.")
+    parser.add_argument("--churn-file", type=str, required=True, help="File to save the results.")
+    parser.add_argument("--run-number", type=int, required=True, help="The current run number.")
+    args = parser.parse_args()
+
+    logging.basicConfig(level=logging.INFO, format='%(asctime)s %(levelname)s %(message)s', datefmt='%H:%M:%S')
+
+    logging.info(f"🐍 Churn Driver Started: Operators={args.operator_count}, Strategy={args.strategy}, Run={args.run_number}")
+
+    write_header_if_needed(args.churn_file,
+                           ["operator_count", "run_number", "strategy", "cycle", "ring_latency_ms",
+                            "mean_reload_ms", "max_reload_ms", "snapshot_bytes", "memory_bytes"])
+
+    try:
+        config.load_kube_config()
+    except config.ConfigException:
+        config.load_incluster_config()
+    api = client.CustomObjectsApi()
+
+    try:
+        api.create_namespaced_custom_object(
+            group=CRD_GROUP, version=CRD_VERSION, namespace=INITIAL_NAMESPACE, plural=CRD_PLURAL,
+            body={
+                "apiVersion": f"{CRD_GROUP}/{CRD_VERSION}",
+                "kind": "TestResource",
+                "metadata": {"name": RESOURCE_NAME},
+                "spec": {"nonce": "initial"}
+            })
+    except client.ApiException as e:
+        if e.status != 409:
+            raise
+
+    for cycle in range(1, args.cycles + 1):
+        # Every operator is unloaded before the change reaches it, so each hop includes a reload.
+        logging.info(f"😴 Waiting {args.unload_wait} seconds for the operators to be unloaded...")
+        time.sleep(args.unload_wait)
+        snapshot_bytes = sum(get_operator_gauge("wasm_operator_snapshot_bytes").values())
+        idle_memory = get_memory_usage()
+
+        latency_ms = trigger_ring(api, f"churn-{cycle}-{time.time_ns()}", args.ring_timeout)
+        reloads = [seconds * 1000 for seconds in get_operator_gauge("wasm_operator_reload_seconds").values()]
+        if latency_ms is None:
+            logging.warning(f"Cycle {cycle}: the change did not go around the ring within {args.ring_timeout}s")
+        mean_reload_ms = sum(reloads) / len(reloads) if reloads else float('nan')
+        max_reload_ms = max(reloads) if reloads else float('nan')
+        logging.info(f"Cycle {cycle}: ring took {latency_ms}ms, mean reload {mean_reload_ms:.2f}ms, "
+                     f"{snapshot_bytes:.0f} bytes of snapshots")
+
+        with open(args.churn_file, "a", newline="") as csvfile:
+            writer = csv.writer(csvfile)
+            writer.writerow([args.operator_count, args.run_number, args.strategy, cycle, latency_ms,
+                             mean_reload_ms, max_reload_ms, int(snapshot_bytes), idle_memory])
+
+    logging.info("🎉 Churn Driver Finished Successfully!")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,12 @@
+#!/bin/bash
+
+set -e
+
+echo "Compiling Rust burst-operator component..."
+
+cargo build --release --target wasm32-wasip2
+
+mkdir -p target
+
+# Copy the final artifact to the central build directory
+cp target/wasm32-wasip2/release/burst_operator_rust.wasm target/burst-operator-rust.wasm
@@ -0,0 +1,191 @@
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+// Use the modules generated by wit_bindgen
+use crate::local::operator::kubernetes;
+use crate::local::operator::types;
+use crate::wasi::cli::environment;
+
+wit_bindgen::generate!(
+    {
+        path: "../../../parent/wit",
+        world: "child-world-with-handlers",
+    }
+);
+
+// Size of the state kept when STATE_BYTES is not set.
+const DEFAULT_STATE_BYTES: usize = 64 * 1024 * 1024;
+
+// Structs for parsing the TestResource JSON
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TestResource {
+    api_version: String,
+    kind: String,
+    metadata: ObjectMeta,
+    spec: Spec,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ObjectMeta {
+    name: String,
+    namespace: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Spec {
+    nonce: String,
+    // Number of reconciles this operator ran, so the driver can compare it with the
+    // number of events it caused.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reconciles: Option<u64>,
+}
+
+// The state of the operator: a reconcile counter and a large buffer that stands in for
+// the caches a real operator keeps, so that unloading it exercises the snapshot path.
+struct OperatorState {
+    reconciles: u64,
+    padding: Vec<u8>,
+}
+
+static STATE: Mutex<OperatorState> = Mutex::new(OperatorState {
+    reconciles: 0,
+    padding: Vec::new(),
+});
+
+fn env(name: &str) -> Option<String> {
+    environment::get_environment()
+        .into_iter()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v)
+}
+
+// Fills the buffer with a non-repeating pattern, so that neither the snapshot nor the
+// memory of the instance can be compressed or deduplicated away.
+fn fill_padding(state: &mut OperatorState) {
+    let size = env("STATE_BYTES")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_STATE_BYTES);
+    let mut x: u64 = 0x9e37_79b9_7f4a_7c15;
+    state.padding = (0..size)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x as u8
+        })
+        .collect();
+}
+
+struct Operator;
+
+impl Guest for Operator {
+    fn get_watch_requests() -> Vec<types::WatchRequest> {
+        let Some(ns) = env("WATCH_NAMESPACE") else {
+            kubernetes::log(types::LogLevel::Error, "WATCH_NAMESPACE environment variable not set");
+            return vec![];
+        };
+
+        vec![types::WatchRequest {
+            kind: "TestResource".to_string(),
+            namespace: ns,
+            skip_initial_list: false,
+            finalizer: None,
+            api_version: None,
+        }]
+    }
+
+    fn reconcile(req: types::ReconcileRequest) -> types::ReconcileResult {
+        if matches!(req.event_type, types::EventType::Deleted) {
+            return types::ReconcileResult::Ok;
+        }
+        let Some(action_ns) = env("ACTION_NAMESPACE") else {
+            let msg = "ACTION_NAMESPACE environment variable not set";
+            kubernetes::log(types::LogLevel::Error, msg);
+            return types::ReconcileResult::Error(msg.to_string());
+        };
+
+        let resource: TestResource = match serde_json::from_str(&req.resource_json) {
+            Ok(r) => r,
+            Err(e) => {
+                let msg = format!("Error parsing resource JSON: {}", e);
+                kubernetes::log(types::LogLevel::Error, &msg);
+                return types::ReconcileResult::Error(msg);
+            }
+        };
+
+        let reconciles = {
+            let mut state = STATE.lock().unwrap();
+            if state.padding.is_empty() {
+                fill_padding(&mut state);
+            }
+            state.reconciles += 1;
+            state.reconciles
+        };
+
+        // Copy the object to the action namespace, where the driver watches for it.
+        let copy = TestResource {
+            api_version: resource.api_version,
+            kind: resource.kind,
+            metadata: ObjectMeta {
+                name: resource.metadata.name.clone(),
+                namespace: action_ns.clone(),
+            },
+            spec: Spec {
+                nonce: resource.spec.nonce,
+                reconciles: Some(reconciles),
+            },
+        };
+        let apply_json = match serde_json::to_string(&copy) {
+            Ok(j) => j,
+            Err(e) => {
+                let msg = format!("Error marshalling resource to JSON: {}", e);
+                kubernetes::log(types::LogLevel::Error, &msg);
+                return types::ReconcileResult::Error(msg);
+            }
+        };
+        if let Err(e) = kubernetes::update_resource("TestResource", &resource.metadata.name, &action_ns, &apply_json) {
+            let msg = format!("Error upserting resource: {}", e.message);
+            kubernetes::log(types::LogLevel::Error, &msg);
+            return types::ReconcileResult::Error(msg);
+        }
+
+        types::ReconcileResult::Ok
+    }
+
+    // The state is the reconcile counter followed by the padding.
+    fn serialize() -> Vec<u8> {
+        let state = STATE.lock().unwrap();
+        let mut bytes = Vec::with_capacity(8 + state.padding.len());
+        bytes.extend_from_slice(&state.reconciles.to_le_bytes());
+        bytes.extend_from_slice(&state.padding);
+        bytes
+    }
+
+    fn deserialize(bytes: Vec<u8>) {
+        if bytes.len() < 8 {
+            return;
+        }
+        let mut state = STATE.lock().unwrap();
+        state.reconciles = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        state.padding = bytes[8..].to_vec();
+    }
+
+    fn handle_http(_req: types::HttpRequest) -> types::HttpResponse {
+        let state = STATE.lock().unwrap();
+        types::HttpResponse {
+            status: 200,
+            headers: vec![],
+            body: format!("reconciles={} state_bytes={}\n", state.reconciles, state.padding.len()).into_bytes(),
+        }
+    }
+
+    fn on_timer(_token: String) {}
+
+    fn on_message(_topic: String, _payload: String) {}
+}
+
+export!(Operator);
@@ -0,0 +1,12 @@
+#!/bin/bash
+
+set -e
+
+echo "Compiling Rust load-generator component..."
+
+cargo build --release --target wasm32-wasip2
+
+mkdir -p target
+
+# Copy the final artifact to the central build directory
+cp target/wasm32-wasip2/release/load_generator_rust.wasm target/load-generator-rust.wasm
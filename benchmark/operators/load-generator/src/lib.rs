@@ -0,0 +1,298 @@
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+// Use the modules generated by wit_bindgen
+use crate::local::operator::kubernetes;
+use crate::local::operator::types;
+use crate::wasi::cli::environment;
+
+wit_bindgen::generate!(
+    {
+        path: "../../../parent/wit",
+        world: "child-world-with-handlers",
+    }
+);
+
+// Token of the timer that generates events.
+const TICK: &str = "tick";
+// Number of objects the generated events are spread over when OBJECTS is not set.
+const DEFAULT_OBJECTS: u64 = 10;
+
+// The load this operator generates, read from its environment. Everything is off by
+// default, so the operator only counts the reconciles of the objects it watches.
+struct Settings {
+    // Namespace of the objects this operator watches and generates events for.
+    watch_namespace: String,
+    // Namespace the fan-out writes go to.
+    action_namespace: Option<String>,
+    // Events generated per second by updating the objects in the watched namespace.
+    events_per_second: u64,
+    // Number of objects the generated events are spread over.
+    objects: u64,
+    // Objects written to the action namespace per reconcile.
+    fan_out: u64,
+    // Rounds of a hash function computed per reconcile.
+    burn_iterations: u64,
+    // Memory held while the operator is loaded, which is not part of its state.
+    memory_bytes: usize,
+    // State serialized when the operator is unloaded.
+    state_bytes: usize,
+}
+
+impl Settings {
+    fn from_env() -> Option<Self> {
+        let Some(watch_namespace) = env("WATCH_NAMESPACE") else {
+            kubernetes::log(types::LogLevel::Error, "WATCH_NAMESPACE environment variable not set");
+            return None;
+        };
+        Some(Settings {
+            watch_namespace,
+            action_namespace: env("ACTION_NAMESPACE"),
+            events_per_second: parse_env("EVENTS_PER_SECOND").unwrap_or(0),
+            objects: parse_env("OBJECTS").unwrap_or(DEFAULT_OBJECTS).max(1),
+            fan_out: parse_env("FAN_OUT").unwrap_or(0),
+            burn_iterations: parse_env("BURN_ITERATIONS").unwrap_or(0),
+            memory_bytes: parse_env("MEMORY_BYTES").unwrap_or(0),
+            state_bytes: parse_env("STATE_BYTES").unwrap_or(0),
+        })
+    }
+}
+
+// Structs for parsing the TestResource JSON
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TestResource {
+    api_version: String,
+    kind: String,
+    metadata: ObjectMeta,
+    spec: Spec,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ObjectMeta {
+    name: String,
+    namespace: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Spec {
+    nonce: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reconciles: Option<u64>,
+}
+
+// The state of the operator. The counters and the state buffer survive unloads; the
+// memory buffer stands in for caches that are rebuilt after a reload.
+struct OperatorState {
+    ticks: u64,
+    reconciles: u64,
+    state: Vec<u8>,
+    memory: Vec<u8>,
+}
+
+static STATE: Mutex<OperatorState> = Mutex::new(OperatorState {
+    ticks: 0,
+    reconciles: 0,
+    state: Vec::new(),
+    memory: Vec::new(),
+});
+
+fn env(name: &str) -> Option<String> {
+    environment::get_environment()
+        .into_iter()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v)
+}
+
+fn parse_env<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let value = env(name)?;
+    match value.parse() {
+        Ok(v) => Some(v),
+        Err(_) => {
+            kubernetes::log(types::LogLevel::Warn, &format!("Ignoring invalid {}: {}", name, value));
+            None
+        }
+    }
+}
+
+// Returns a buffer with a non-repeating pattern, so that neither the snapshot nor the
+// memory of the instance can be compressed or deduplicated away.
+fn incompressible(size: usize, seed: u64) -> Vec<u8> {
+    let mut x = seed | 1;
+    (0..size)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x as u8
+        })
+        .collect()
+}
+
+// Keeps the CPU busy for a number of rounds, returning a value so the work is not
+// optimized away.
+fn burn(iterations: u64) -> u64 {
+    let mut x: u64 = 0x9e37_79b9_7f4a_7c15;
+    for i in 0..iterations {
+        x = (x ^ i).wrapping_mul(0x2545_f491_4f6c_dd1d).rotate_left(29);
+    }
+    x
+}
+
+fn test_resource(name: String, namespace: String, nonce: String, reconciles: Option<u64>) -> TestResource {
+    TestResource {
+        api_version: "ring.benchmark.com/v1".to_string(),
+        kind: "TestResource".to_string(),
+        metadata: ObjectMeta { name, namespace },
+        spec: Spec { nonce, reconciles },
+    }
+}
+
+fn write(resource: &TestResource) -> Result<(), String> {
+    let json = serde_json::to_string(resource).map_err(|e| format!("Error marshalling resource to JSON: {}", e))?;
+    kubernetes::update_resource("TestResource", &resource.metadata.name, &resource.metadata.namespace, &json)
+        .map_err(|e| format!("Error upserting resource: {}", e.message))
+}
+
+fn schedule_tick(settings: &Settings) {
+    if settings.events_per_second == 0 {
+        return;
+    }
+    // One object is updated per tick, so rates above 1000 per second are capped.
+    let delay_ms = (1000 / settings.events_per_second).max(1);
+    if let Err(e) = kubernetes::schedule(delay_ms, TICK) {
+        kubernetes::log(types::LogLevel::Error, &format!("Error scheduling the next event: {}", e.message));
+    }
+}
+
+struct Operator;
+
+impl Guest for Operator {
+    fn get_watch_requests() -> Vec<types::WatchRequest> {
+        let Some(settings) = Settings::from_env() else {
+            return vec![];
+        };
+        schedule_tick(&settings);
+
+        vec![types::WatchRequest {
+            kind: "TestResource".to_string(),
+            namespace: settings.watch_namespace,
+            skip_initial_list: false,
+            finalizer: None,
+            api_version: None,
+        }]
+    }
+
+    fn reconcile(req: types::ReconcileRequest) -> types::ReconcileResult {
+        if matches!(req.event_type, types::EventType::Deleted) {
+            return types::ReconcileResult::Ok;
+        }
+        let Some(settings) = Settings::from_env() else {
+            return types::ReconcileResult::Error("WATCH_NAMESPACE environment variable not set".to_string());
+        };
+
+        let reconciles = {
+            let mut state = STATE.lock().unwrap();
+            if state.state.len() != settings.state_bytes {
+                state.state = incompressible(settings.state_bytes, 0x9e37_79b9_7f4a_7c15);
+            }
+            if state.memory.len() != settings.memory_bytes {
+                state.memory = incompressible(settings.memory_bytes, 0x2545_f491_4f6c_dd1d);
+            }
+            state.reconciles += 1;
+            state.reconciles
+        };
+        let nonce = burn(settings.burn_iterations);
+
+        if settings.fan_out > 0 {
+            let Some(action_ns) = settings.action_namespace else {
+                let msg = "FAN_OUT is set but ACTION_NAMESPACE is not";
+                kubernetes::log(types::LogLevel::Error, msg);
+                return types::ReconcileResult::Error(msg.to_string());
+            };
+            for i in 0..settings.fan_out {
+                let copy = test_resource(
+                    format!("{}-{}", req.name, i),
+                    action_ns.clone(),
+                    format!("{:x}", nonce),
+                    Some(reconciles),
+                );
+                if let Err(msg) = write(&copy) {
+                    kubernetes::log(types::LogLevel::Error, &msg);
+                    return types::ReconcileResult::Error(msg);
+                }
+            }
+        }
+
+        types::ReconcileResult::Ok
+    }
+
+    // The state is the tick and reconcile counters followed by the state buffer.
+    fn serialize() -> Vec<u8> {
+        let state = STATE.lock().unwrap();
+        let mut bytes = Vec::with_capacity(16 + state.state.len());
+        bytes.extend_from_slice(&state.ticks.to_le_bytes());
+        bytes.extend_from_slice(&state.reconciles.to_le_bytes());
+        bytes.extend_from_slice(&state.state);
+        bytes
+    }
+
+    fn deserialize(bytes: Vec<u8>) {
+        if bytes.len() < 16 {
+            return;
+        }
+        let mut state = STATE.lock().unwrap();
+        state.ticks = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        state.reconciles = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        state.state = bytes[16..].to_vec();
+    }
+
+    fn handle_http(_req: types::HttpRequest) -> types::HttpResponse {
+        let state = STATE.lock().unwrap();
+        types::HttpResponse {
+            status: 200,
+            headers: vec![],
+            body: format!(
+                "ticks={} reconciles={} state_bytes={} memory_bytes={}\n",
+                state.ticks,
+                state.reconciles,
+                state.state.len(),
+                state.memory.len()
+            )
+            .into_bytes(),
+        }
+    }
+
+    // Updates the next object in the watched namespace, which the watch of this operator
+    // turns into an event, and schedules the next tick.
+    fn on_timer(token: String) {
+        if token != TICK {
+            return;
+        }
+        let Some(settings) = Settings::from_env() else {
+            return;
+        };
+        let tick = {
+            let mut state = STATE.lock().unwrap();
+            state.ticks += 1;
+            state.ticks
+        };
+        let object = test_resource(
+            format!("load-{}", tick % settings.objects),
+            settings.watch_namespace.clone(),
+            tick.to_string(),
+            None,
+        );
+        if let Err(msg) = write(&object) {
+            kubernetes::log(types::LogLevel::Error, &msg);
+        }
+        schedule_tick(&settings);
+    }
+
+    fn on_message(_topic: String, _payload: String) {}
+}
+
+export!(Operator);
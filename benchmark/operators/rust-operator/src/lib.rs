@@ -53,6 +53,9 @@ impl Guest for Operator {
         vec![types::WatchRequest {
             kind: "TestResource".to_string(),
             namespace: ns,
+            skip_initial_list: false,
+            finalizer: None,
+            api_version: None,
         }]
     }
 
@@ -103,7 +106,7 @@ impl Guest for Operator {
 
         // 4. Call UpdateResource to perform a server-side apply.
         if let Err(e) = kubernetes::update_resource("TestResource", &resource.metadata.name, &action_ns, &apply_json) {
-            let msg = format!("Error upserting resource: {}", e);
+            let msg = format!("Error upserting resource: {}", e.message);
             kubernetes::log(types::LogLevel::Error, &msg);
             return types::ReconcileResult::Error(msg);
         }
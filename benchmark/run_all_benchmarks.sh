@@ -11,6 +11,7 @@ ACTIVE_DURATION=90
 IDLE_DURATION=45
 BASE_OUTPUT_DIR="./results"
 RESUME_FROM=""
+MEMORY_CHURN=false
 
 # --- Argument Parsing ---
 while [[ "$#" -gt 0 ]]; do
@@ -35,6 +36,9 @@ while [[ "$#" -gt 0 ]]; do
             RESUME_FROM="$2"
             shift
             ;;
+        --memory-churn)
+            MEMORY_CHURN=true
+            ;;
         *)
             echo "Unknown option: $1"
             exit 1
@@ -76,13 +80,23 @@ update_state() {
 
 # --- Scenario Execution ---
 SCENARIOS=("mixed" "rust" "go")
+# The memory-churn mode runs the same fleet under each snapshot strategy instead.
+if [ "$MEMORY_CHURN" = "true" ]; then
+    SCENARIOS=("churn-serialize" "churn-pre-init")
+fi
 
 for scenario in "${SCENARIOS[@]}"; do
     echo "
 ---
 --- 🚀 Starting ${scenario} Benchmark Scenario
 ---"
-    export OPERATOR_TYPE="$scenario"
+    if [[ "$scenario" == churn-* ]]; then
+        export OPERATOR_TYPE="mixed"
+        SCENARIO_ARGS=(--scenario churn --snapshot-strategy "${scenario#churn-}")
+    else
+        export OPERATOR_TYPE="$scenario"
+        SCENARIO_ARGS=()
+    fi
     
     for count in ${OPERATOR_COUNTS}; do
         completed_runs=$(get_completed_runs "$scenario" "$count")
@@ -102,6 +116,7 @@ for scenario in "${SCENARIOS[@]}"; do
               --run-number "${run}" \
               --active-duration ${ACTIVE_DURATION} \
               --idle-duration ${IDLE_DURATION} \
+              "${SCENARIO_ARGS[@]}" \
               --output-dir "${SESSION_DIR}/${scenario}"
             
             update_state "$scenario" "$count" "$run"
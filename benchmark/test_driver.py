@@ -27,6 +27,22 @@ def get_memory_usage():
     return 0
 
 
+def get_received_bytes():
+    """Queries Prometheus for the total number of bytes received by the parent-operator pod."""
+    query = 'sum(container_network_receive_bytes_total{namespace="default",pod=~"parent-operator-.*"})'
+    try:
+        response = requests.get(f"{PROMETHEUS_URL}/api/v1/query", params={"query": query})
+        response.raise_for_status()
+        result = response.json()['data']['result']
+        if result:
+            return int(float(result[0]['value'][1]))
+    except requests.exceptions.RequestException as e:
+        logging.error(f"Error querying Prometheus: {e}")
+    except (KeyError, IndexError):
+        logging.warning("Could not parse Prometheus response.")
+    return 0
+
+
 def write_header_if_needed(file_path, headers):
     """Writes the header to a CSV file if it doesn't exist or is empty."""
     if not os.path.exists(file_path) or os.path.getsize(file_path) == 0:
@@ -42,6 +58,8 @@ def main():
     parser.add_argument("--idle-duration", type=int, required=True, help="Duration of the idle phase in seconds.")
     parser.add_argument("--latency-file", type=str, required=True, help="File to save the latency results.")
     parser.add_argument("--memory-file", type=str, required=True, help="File to save the memory results.")
+    parser.add_argument("--network-file", type=str, required=True, help="File to save the network results.")
+    parser.add_argument("--compression", type=str, default="on", help="Whether the parent requests compressed responses.")
     parser.add_argument("--run-number", type=int, required=True, help="The current run number.")
     args = parser.parse_args()
 
@@ -52,6 +70,7 @@ def main():
     # Write headers if needed
     write_header_if_needed(args.latency_file, ["operator_count", "run_number", "latency_ms"])
     write_header_if_needed(args.memory_file, ["operator_count", "run_number", "phase", "timestamp", "memory_bytes"])
+    write_header_if_needed(args.network_file, ["operator_count", "run_number", "compression", "received_bytes"])
 
     # Load Kubernetes configuration
     try:
@@ -131,6 +150,7 @@ def main():
 
     # --- Active Phase ---
     logging.info("⏱️ Starting Active Phase...")
+    received_bytes_start = get_received_bytes()
     latencies = []
     with tqdm(total=args.active_duration, desc="Active Phase", unit="s") as pbar:
         start_time = time.time()
@@ -175,6 +195,7 @@ def main():
 
     logging.info(f"--- Active Phase Complete. Total runs: {len(latencies)} ---")
     active_memory = get_memory_usage()
+    received_bytes = get_received_bytes() - received_bytes_start
 
     # --- Idle Phase ---
     logging.info("--- Starting Idle Phase...")
@@ -197,6 +218,11 @@ def main():
         writer.writerow([args.operator_count, args.run_number, "active", int(time.time()), active_memory])
         writer.writerow([args.operator_count, args.run_number, "idle", int(time.time()), idle_memory])
 
+    logging.info(f"📝 Writing network measurements to {args.network_file}")
+    with open(args.network_file, "a", newline="") as csvfile:
+        writer = csv.writer(csvfile)
+        writer.writerow([args.operator_count, args.run_number, args.compression, received_bytes])
+
     logging.info("🎉 Test Driver Finished Successfully!")
 
 
@@ -0,0 +1,16 @@
+//! Reads the version of the `local:operator` WIT package, so the host reports the version
+//! declared in `wit/world.wit` instead of a copy of it.
+
+fn main() {
+    println!("cargo:rerun-if-changed=wit/world.wit");
+    let world = std::fs::read_to_string("wit/world.wit").expect("wit/world.wit is readable");
+    let version = world
+        .lines()
+        .find_map(|line| {
+            line.trim()
+                .strip_prefix("package local:operator@")?
+                .strip_suffix(';')
+        })
+        .expect("wit/world.wit declares the version of the local:operator package");
+    println!("cargo:rustc-env=WIT_PACKAGE_VERSION={}", version);
+}
@@ -0,0 +1,22 @@
+//! Feeds arbitrary bytes to the informer cache checkpoint reader, and compares every
+//! restored object against the checkpoint like a relist after a restart does.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/runtime/informer_cache.rs"]
+mod informer_cache;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(stores) = informer_cache::parse_checkpoint(data) else {
+        return;
+    };
+    for store in stores.values() {
+        let mut restored = store.clone();
+        for object in store.values() {
+            informer_cache::unchanged_since_checkpoint(&mut restored, object);
+        }
+    }
+});
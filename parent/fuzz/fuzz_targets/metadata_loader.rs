@@ -0,0 +1,13 @@
+//! Feeds arbitrary text to the component metadata loader.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/config/metadata.rs"]
+mod metadata;
+
+fuzz_target!(|data: &str| {
+    let _ = metadata::WasmComponentMetadata::parse_yaml(data);
+});
@@ -0,0 +1,16 @@
+//! Feeds arbitrary text to the quantity parser used for node capacity, pod requests and
+//! metric values.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../src/kubernetes/quantity.rs"]
+mod quantity;
+
+fuzz_target!(|data: &str| {
+    if let Some(value) = quantity::parse(data) {
+        // The callers convert to integers with `as`, which saturates instead of panicking.
+        let _ = (value * 1000.0).ceil() as u64;
+    }
+});
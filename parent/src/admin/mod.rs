@@ -0,0 +1,341 @@
+//! # Admin API Module
+//!
+//! This module provides the HTTP server that exposes the runtime to the outside world.
+//! Besides runtime-level endpoints, it forwards requests under `/operators/{id}/ext/...`
+//! to the `handle-http` export of the corresponding Wasm component, so operators can
+//! serve their own debug or API endpoints without running a web server themselves.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use tokio::net::TcpListener;
+use tracing::{debug, info, warn};
+
+use crate::host::api::bindings::local::operator::types::{HttpHeader, HttpRequest};
+use crate::host::message_bus::MAX_PAYLOAD_BYTES;
+use crate::host::ownership::ObjectKey;
+use crate::metrics;
+use crate::runtime::dead_letter::ObjectRef;
+use crate::runtime::resync::{ResyncOutcome, DEFAULT_RESYNC_RATE};
+use crate::runtime::signals::ADMIN_SOURCE;
+use crate::runtime::{OperatorBusy, WasmRuntime};
+
+/// Serves the admin API on the given address until the listener fails.
+///
+/// Connections are handled on the current `LocalSet`, because calls into the Wasm
+/// components are not `Send`.
+pub async fn serve(addr: SocketAddr, runtime: Arc<WasmRuntime>) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Admin API listening on {}", addr);
+    if !addr.ip().is_loopback() {
+        warn!(
+            "The admin API has no authentication and is reachable from other hosts on {}",
+            addr
+        );
+    }
+
+    loop {
+        let (stream, remote) = listener.accept().await?;
+        debug!("Accepted admin API connection from {}", remote);
+
+        let runtime = runtime.clone();
+        tokio::task::spawn_local(async move {
+            let service = service_fn(move |req| handle(runtime.clone(), req));
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                warn!("Admin API connection from {} failed: {}", remote, e);
+            }
+        });
+    }
+}
+
+async fn handle(
+    runtime: Arc<WasmRuntime>,
+    req: Request<Incoming>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let path = req.uri().path().to_string();
+    let query = req.uri().query().unwrap_or_default().to_string();
+    let segments: Vec<&str> = path.trim_start_matches('/').splitn(4, '/').collect();
+
+    let response = match (req.method(), segments.as_slice()) {
+        (_, ["metrics"]) => text_response(StatusCode::OK, &metrics::global().render()),
+        (&Method::POST, ["checkpoint"]) => checkpoint(&runtime, &query).await,
+        (&Method::GET, ["debug-bundle"]) => debug_bundle(&runtime, &query).await,
+        (&Method::GET, ["summary"]) => json_response(StatusCode::OK, &runtime.status_summary()),
+        (&Method::GET, ["ownership"]) => ownership(&runtime, &query),
+        (&Method::POST, ["signals", topic]) => signal(&runtime, topic, req).await,
+        (&Method::GET, ["operators", id, "objects"]) => {
+            json_response(StatusCode::OK, &runtime.managed_objects(id))
+        }
+        (&Method::GET, ["operators", id, "dead-letters"]) => {
+            json_response(StatusCode::OK, &runtime.dead_letters(id))
+        }
+        (&Method::GET, ["operators", id, "shadow"]) => match runtime.shadow_writes(id) {
+            Some(writes) => json_response(StatusCode::OK, &writes),
+            None => text_response(
+                StatusCode::NOT_FOUND,
+                &format!("Operator '{}' not found", id),
+            ),
+        },
+        (&Method::POST, ["operators", id, "dead-letters", "retry"]) => {
+            retry_dead_letter(&runtime, id, &query)
+        }
+        (&Method::POST, ["operators", id, "resync"]) => resync(&runtime, id, &query).await,
+        (_, ["operators", id, "ext", ..]) => {
+            let rest = segments.get(3).copied().unwrap_or_default();
+            forward_to_operator(&runtime, id, rest, req).await
+        }
+        _ => text_response(StatusCode::NOT_FOUND, "Not found"),
+    };
+
+    Ok(response)
+}
+
+/// Checkpoints the runtime to the directory in the `dir` query parameter, relative to the
+/// state directory, or to `checkpoint` in the state directory.
+async fn checkpoint(runtime: &WasmRuntime, query: &str) -> Response<Full<Bytes>> {
+    let dir = match query_param(query, "dir") {
+        Some(dir) => match runtime.checkpoint_dir_in_state(&dir) {
+            Some(dir) => dir,
+            None => {
+                return text_response(
+                    StatusCode::BAD_REQUEST,
+                    "dir must be a relative path inside the state directory",
+                )
+            }
+        },
+        None => runtime.default_checkpoint_dir(),
+    };
+    match runtime.checkpoint(&dir).await {
+        Ok(checkpoint) => json_response(StatusCode::OK, &checkpoint),
+        Err(e) => text_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("Checkpoint failed: {:#}", e),
+        ),
+    }
+}
+
+/// Returns a debug bundle of the runtime, redacted if the `redact` query parameter is `true`.
+async fn debug_bundle(runtime: &WasmRuntime, query: &str) -> Response<Full<Bytes>> {
+    let redact = query_param(query, "redact").as_deref() == Some("true");
+    match runtime.debug_bundle(redact).await {
+        Ok(bundle) => {
+            let mut response = Response::new(Full::new(Bytes::from(bundle)));
+            response
+                .headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("application/gzip"));
+            response
+        }
+        Err(e) => text_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("Debug bundle failed: {:#}", e),
+        ),
+    }
+}
+
+/// Lists the operators that wrote the object identified by the `kind`, `namespace` and
+/// `name` query parameters.
+fn ownership(runtime: &WasmRuntime, query: &str) -> Response<Full<Bytes>> {
+    let (Some(kind), Some(name)) = (query_param(query, "kind"), query_param(query, "name")) else {
+        return text_response(StatusCode::BAD_REQUEST, "kind and name are required");
+    };
+    let object = ObjectKey {
+        kind,
+        namespace: query_param(query, "namespace").unwrap_or_default(),
+        name,
+    };
+    json_response(StatusCode::OK, &runtime.object_managers(&object))
+}
+
+/// Retries the dead letter identified by the `kind`, `namespace` and `name` query parameters.
+fn retry_dead_letter(
+    runtime: &Arc<WasmRuntime>,
+    operator_id: &str,
+    query: &str,
+) -> Response<Full<Bytes>> {
+    let object_ref = ObjectRef {
+        operator: operator_id.to_string(),
+        kind: query_param(query, "kind").unwrap_or_default(),
+        namespace: query_param(query, "namespace").unwrap_or_default(),
+        name: query_param(query, "name").unwrap_or_default(),
+    };
+
+    if runtime.retry_dead_letter(&object_ref) {
+        text_response(StatusCode::ACCEPTED, "Retry scheduled")
+    } else {
+        text_response(StatusCode::NOT_FOUND, "No such dead letter")
+    }
+}
+
+/// Reconciles all watched objects of an operator again, at the number of objects per
+/// second in the `rate` query parameter.
+async fn resync(
+    runtime: &Arc<WasmRuntime>,
+    operator_id: &str,
+    query: &str,
+) -> Response<Full<Bytes>> {
+    let rate = match query_param(query, "rate").map(|rate| rate.parse::<u32>()) {
+        None => DEFAULT_RESYNC_RATE,
+        Some(Ok(rate)) if rate > 0 => rate,
+        Some(_) => return text_response(StatusCode::BAD_REQUEST, "rate must be a positive number"),
+    };
+    match runtime.resync(operator_id, rate).await {
+        Ok(ResyncOutcome::Started(count)) => text_response(
+            StatusCode::ACCEPTED,
+            &format!("Resyncing {} object(s)", count),
+        ),
+        Ok(ResyncOutcome::NotFound) => text_response(
+            StatusCode::NOT_FOUND,
+            &format!("Operator '{}' not found", operator_id),
+        ),
+        Ok(ResyncOutcome::InProgress) => text_response(
+            StatusCode::CONFLICT,
+            &format!("A resync of operator '{}' is in progress", operator_id),
+        ),
+        Err(e) => text_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("Resync failed: {:#}", e),
+        ),
+    }
+}
+
+/// Publishes the body of the request as a signal on a topic.
+async fn signal(
+    runtime: &WasmRuntime,
+    topic: &str,
+    req: Request<Incoming>,
+) -> Response<Full<Bytes>> {
+    let payload = match req.into_body().collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(e) => {
+            return text_response(
+                StatusCode::BAD_REQUEST,
+                &format!("Failed to read request body: {}", e),
+            );
+        }
+    };
+    if payload.len() > MAX_PAYLOAD_BYTES {
+        return text_response(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            &format!("Payloads are limited to {} bytes", MAX_PAYLOAD_BYTES),
+        );
+    }
+    let Ok(payload) = String::from_utf8(payload.to_vec()) else {
+        return text_response(StatusCode::BAD_REQUEST, "The payload must be UTF-8");
+    };
+    match runtime.signal(ADMIN_SOURCE, topic, payload) {
+        Ok(()) => text_response(
+            StatusCode::ACCEPTED,
+            &format!("Signalled topic '{}'", topic),
+        ),
+        Err(e) => text_response(StatusCode::TOO_MANY_REQUESTS, &e),
+    }
+}
+
+/// Forwards an HTTP request to the `handle-http` export of an operator.
+async fn forward_to_operator(
+    runtime: &WasmRuntime,
+    operator_id: &str,
+    rest: &str,
+    req: Request<Incoming>,
+) -> Response<Full<Bytes>> {
+    let (parts, body) = req.into_parts();
+    let body = match body.collect().await {
+        Ok(collected) => collected.to_bytes().to_vec(),
+        Err(e) => {
+            return text_response(
+                StatusCode::BAD_REQUEST,
+                &format!("Failed to read request body: {}", e),
+            );
+        }
+    };
+
+    let request = HttpRequest {
+        method: parts.method.to_string(),
+        path: format!("/{}", rest),
+        query: parts.uri.query().unwrap_or_default().to_string(),
+        headers: parts
+            .headers
+            .iter()
+            .map(|(name, value)| HttpHeader {
+                name: name.to_string(),
+                value: String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            })
+            .collect(),
+        body,
+    };
+
+    match runtime.handle_http(operator_id, request).await {
+        Ok(Some(response)) => {
+            let mut builder = Response::builder().status(
+                StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            );
+            for header in response.headers {
+                builder = builder.header(header.name, header.value);
+            }
+            builder
+                .body(Full::new(Bytes::from(response.body)))
+                .unwrap_or_else(|e| {
+                    text_response(
+                        StatusCode::BAD_GATEWAY,
+                        &format!("Operator returned an invalid response: {}", e),
+                    )
+                })
+        }
+        Ok(None) => text_response(
+            StatusCode::NOT_FOUND,
+            &format!("Operator '{}' not found", operator_id),
+        ),
+        Err(e) if e.is::<OperatorBusy>() => {
+            text_response(StatusCode::SERVICE_UNAVAILABLE, &e.to_string())
+        }
+        Err(e) => text_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("Operator '{}' failed to handle request: {}", operator_id, e),
+        ),
+    }
+}
+
+fn query_param(query: &str, key: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v.to_string())
+}
+
+fn json_response<T: Serialize>(status: StatusCode, value: &T) -> Response<Full<Bytes>> {
+    match serde_json::to_vec(value) {
+        Ok(body) => {
+            let mut response = Response::new(Full::new(Bytes::from(body)));
+            *response.status_mut() = status;
+            response
+                .headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            response
+        }
+        Err(e) => text_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("Failed to serialize response: {}", e),
+        ),
+    }
+}
+
+fn text_response(status: StatusCode, message: &str) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(message.to_string())));
+    *response.status_mut() = status;
+    response
+}
@@ -0,0 +1,302 @@
+//! # Operator Bundle Module
+//!
+//! This module defines the `.wopr` bundle, a single file to distribute a Wasm operator
+//! with. A bundle is a zstd-compressed tarball:
+//!
+//! ```text
+//! manifest.json       format version, name, requirements and SHA-256 digests of the
+//!                     other files
+//! component.wasm      the component
+//! config.yaml         default metadata of the operator
+//! capabilities.json   interfaces the component imports and exports
+//! crds/<file>         custom resource definitions the operator watches
+//! signature           optional Ed25519 signature of manifest.json
+//! ```
+//!
+//! Signing the manifest covers the other files through their digests. A bundle can be
+//! used as the `wasm` of an operator directly; loading it checks the digest of the
+//! component, while signatures are checked when the bundle is installed.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use ring::signature::{Ed25519KeyPair, UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+
+use crate::config::metadata::WasmComponentMetadata;
+use crate::dependencies::Requirement;
+use crate::tarball::{self, Tarball};
+
+/// File extension of operator bundles.
+pub const EXTENSION: &str = "wopr";
+const FORMAT_VERSION: u32 = 1;
+
+const MANIFEST: &str = "manifest.json";
+const COMPONENT: &str = "component.wasm";
+const CONFIG: &str = "config.yaml";
+const CAPABILITIES: &str = "capabilities.json";
+const CRDS: &str = "crds/";
+const SIGNATURE: &str = "signature";
+
+/// DER prefix of an Ed25519 public key in the SubjectPublicKeyInfo format.
+const ED25519_SPKI_PREFIX: [u8; 12] = [
+    0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub format_version: u32,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Other bundles that must be installed along with this one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<Requirement>,
+    /// Hex-encoded SHA-256 digests of the other files, by path.
+    pub digests: BTreeMap<String, String>,
+}
+
+/// What a component needs from the host and offers to it.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    pub imports: Vec<String>,
+    pub exports: Vec<String>,
+}
+
+impl Capabilities {
+    /// Reads the imports and exports of a component, which also validates it.
+    pub fn of_component(component: &[u8]) -> Result<Self> {
+        let engine = wasmtime::Engine::default();
+        let component = wasmtime::component::Component::new(&engine, component)
+            .map_err(|e| anyhow!("Invalid component: {}", e))?;
+        let component_type = component.component_type();
+        Ok(Self {
+            imports: component_type
+                .imports(&engine)
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            exports: component_type
+                .exports(&engine)
+                .map(|(name, _)| name.to_string())
+                .collect(),
+        })
+    }
+}
+
+/// A bundle whose files match the digests in its manifest.
+pub struct Bundle {
+    pub manifest: Manifest,
+    manifest_json: Vec<u8>,
+    files: BTreeMap<String, Vec<u8>>,
+    signature: Option<Vec<u8>>,
+}
+
+impl Bundle {
+    /// Packs the files of an operator into a bundle, signing it if a key is given.
+    pub fn pack(
+        component: &[u8],
+        config: &str,
+        crds: &[(String, Vec<u8>)],
+        version: Option<String>,
+        requires: Vec<Requirement>,
+        signing_key: Option<&Ed25519KeyPair>,
+    ) -> Result<Vec<u8>> {
+        let metadata = single_metadata(config)?;
+        let capabilities = Capabilities::of_component(component)?;
+        let mut files = vec![
+            (COMPONENT.to_string(), component.to_vec()),
+            (CONFIG.to_string(), config.as_bytes().to_vec()),
+            (
+                CAPABILITIES.to_string(),
+                serde_json::to_vec_pretty(&capabilities)?,
+            ),
+        ];
+        for (name, contents) in crds {
+            check_file_name(name)?;
+            files.push((format!("{}{}", CRDS, name), contents.clone()));
+        }
+        let manifest = Manifest {
+            format_version: FORMAT_VERSION,
+            name: metadata.name,
+            version,
+            requires,
+            digests: files
+                .iter()
+                .map(|(path, contents)| (path.clone(), sha256(contents)))
+                .collect(),
+        };
+        let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+
+        // A fixed modification time makes bundles of the same files identical.
+        let mut tarball = Tarball::new(0);
+        tarball.add(MANIFEST, &manifest_json)?;
+        for (path, contents) in &files {
+            tarball.add(path, contents)?;
+        }
+        if let Some(key) = signing_key {
+            tarball.add(SIGNATURE, key.sign(&manifest_json).as_ref())?;
+        }
+        zstd::bulk::compress(&tarball.finish(), zstd::DEFAULT_COMPRESSION_LEVEL)
+            .context("Failed to compress the bundle")
+    }
+
+    /// Reads a bundle from a file, see `from_bytes`.
+    pub fn read(path: &Path) -> Result<Self> {
+        let compressed = std::fs::read(path)
+            .with_context(|| format!("Failed to read bundle {}", path.display()))?;
+        Self::from_bytes(&compressed).with_context(|| format!("Invalid bundle {}", path.display()))
+    }
+
+    /// Reads a bundle and checks its files against the digests in its manifest.
+    pub fn from_bytes(compressed: &[u8]) -> Result<Self> {
+        let data = zstd::stream::decode_all(compressed).context("Failed to decompress bundle")?;
+        let mut files: BTreeMap<String, Vec<u8>> = tarball::read(&data)?.into_iter().collect();
+
+        let manifest_json = files
+            .remove(MANIFEST)
+            .ok_or_else(|| anyhow!("Bundle has no {}", MANIFEST))?;
+        let manifest: Manifest =
+            serde_json::from_slice(&manifest_json).context("Invalid bundle manifest")?;
+        if manifest.format_version != FORMAT_VERSION {
+            bail!(
+                "Unsupported bundle format version {}, expected {}",
+                manifest.format_version,
+                FORMAT_VERSION
+            );
+        }
+        let signature = files.remove(SIGNATURE);
+        for (path, contents) in &files {
+            match manifest.digests.get(path) {
+                Some(digest) if *digest == sha256(contents) => {}
+                Some(_) => bail!("Digest of '{}' does not match the manifest", path),
+                None => bail!("'{}' is not listed in the manifest", path),
+            }
+        }
+        if let Some(path) = manifest.digests.keys().find(|p| !files.contains_key(*p)) {
+            bail!("Bundle is missing '{}'", path);
+        }
+        for name in files.keys().filter_map(|path| path.strip_prefix(CRDS)) {
+            check_file_name(name)?;
+        }
+        if !files.contains_key(COMPONENT) || !files.contains_key(CONFIG) {
+            bail!("Bundle must contain {} and {}", COMPONENT, CONFIG);
+        }
+
+        Ok(Self {
+            manifest,
+            manifest_json,
+            files,
+            signature,
+        })
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.signature.is_some()
+    }
+
+    /// Checks the signature of the bundle with a raw Ed25519 public key.
+    pub fn verify_signature(&self, public_key: &[u8]) -> Result<()> {
+        let signature = self
+            .signature
+            .as_ref()
+            .ok_or_else(|| anyhow!("Bundle is not signed"))?;
+        UnparsedPublicKey::new(&ED25519, public_key)
+            .verify(&self.manifest_json, signature)
+            .map_err(|_| anyhow!("Bundle signature does not match the public key"))
+    }
+
+    pub fn component(&self) -> &[u8] {
+        &self.files[COMPONENT]
+    }
+
+    /// The default metadata of the operator.
+    pub fn metadata(&self) -> Result<WasmComponentMetadata> {
+        single_metadata(std::str::from_utf8(&self.files[CONFIG])?)
+    }
+
+    pub fn capabilities(&self) -> Result<Option<Capabilities>> {
+        self.files
+            .get(CAPABILITIES)
+            .map(|json| serde_json::from_slice(json).context("Invalid capabilities"))
+            .transpose()
+    }
+
+    /// The custom resource definitions, by file name.
+    pub fn crds(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.files
+            .iter()
+            .filter_map(|(path, contents)| Some((path.strip_prefix(CRDS)?, contents.as_slice())))
+    }
+}
+
+/// Whether a path names an operator bundle rather than a component.
+pub fn is_bundle(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == EXTENSION)
+}
+
+/// Reads an Ed25519 private key from a PEM file in the PKCS#8 format, as written by
+/// `openssl genpkey -algorithm ed25519`.
+pub fn load_signing_key(path: &Path) -> Result<Ed25519KeyPair> {
+    let pem = read_pem(path, "PRIVATE KEY")?;
+    Ed25519KeyPair::from_pkcs8_maybe_unchecked(pem.contents())
+        .map_err(|e| anyhow!("Invalid Ed25519 key in {}: {}", path.display(), e))
+}
+
+/// Reads an Ed25519 public key from a PEM file, as written by `openssl pkey -pubout`, and
+/// returns the raw key.
+pub fn load_public_key(path: &Path) -> Result<Vec<u8>> {
+    let pem = read_pem(path, "PUBLIC KEY")?;
+    pem.contents()
+        .strip_prefix(ED25519_SPKI_PREFIX.as_slice())
+        .filter(|key| key.len() == 32)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| anyhow!("{} does not hold an Ed25519 public key", path.display()))
+}
+
+fn read_pem(path: &Path, tag: &str) -> Result<pem::Pem> {
+    let contents =
+        std::fs::read(path).with_context(|| format!("Failed to read key {}", path.display()))?;
+    let pem = pem::parse(contents).with_context(|| format!("Invalid PEM in {}", path.display()))?;
+    if pem.tag() != tag {
+        bail!(
+            "Expected a {} in {}, found {}",
+            tag,
+            path.display(),
+            pem.tag()
+        );
+    }
+    Ok(pem)
+}
+
+/// Parses metadata that describes exactly one operator.
+fn single_metadata(config: &str) -> Result<WasmComponentMetadata> {
+    let mut metadata = WasmComponentMetadata::parse_yaml(config)?;
+    if metadata.len() != 1 {
+        bail!(
+            "The config of a bundle must describe one operator, found {}",
+            metadata.len()
+        );
+    }
+    Ok(metadata.remove(0))
+}
+
+/// Rejects names that would place a file outside its directory when installed.
+fn check_file_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+        bail!("Invalid file name in bundle: '{}'", name);
+    }
+    Ok(())
+}
+
+/// Returns the hex-encoded SHA-256 digest of the contents.
+pub fn sha256(contents: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, contents)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
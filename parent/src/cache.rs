@@ -0,0 +1,313 @@
+//! # Cache Module
+//!
+//! This module keeps the bundles pulled from registries in a local cache keyed by their
+//! OCI digest, so installing or upgrading an operator to a version that was pulled before
+//! does not download it again. The cache is bounded: once it grows beyond its size limit,
+//! the least recently used blobs are removed. A blob is used whenever it is read from or
+//! written to the cache, which is tracked through its modification time.
+//!
+//! The cache lives in `$WASM_OPERATOR_CACHE_DIR`, or else in `wasm-operator` under the
+//! user's cache directory. `parent cache ls` lists it and `parent cache gc` shrinks it.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{anyhow, bail, Context, Result};
+use tracing::warn;
+
+use crate::bundle;
+
+/// Environment variable that overrides the directory of the cache.
+pub const CACHE_DIR_ENV: &str = "WASM_OPERATOR_CACHE_DIR";
+/// Size limit of the cache, unless another is given.
+pub const DEFAULT_MAX_BYTES: u64 = 1 << 30;
+
+const USAGE: &str = "Usage: parent cache (ls | gc [--max-bytes <bytes>]) [--dir <path>]";
+
+/// A blob in the cache.
+#[derive(Debug)]
+pub struct CacheEntry {
+    /// The digest of the blob, `sha256:<hex>`.
+    pub digest: String,
+    pub size: u64,
+    pub last_used: SystemTime,
+}
+
+/// A directory of blobs named by their digests, `<dir>/sha256/<hex>`.
+pub struct ComponentCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl ComponentCache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        Self { dir, max_bytes }
+    }
+
+    /// The cache in its default directory with the default size limit.
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::new(default_dir()?, DEFAULT_MAX_BYTES))
+    }
+
+    /// Returns a blob if it is cached and still matches its digest. Blobs that do not are
+    /// removed.
+    pub fn get(&self, digest: &str) -> Option<Vec<u8>> {
+        let (path, hex) = self.path(digest).ok()?;
+        let data = std::fs::read(&path).ok()?;
+        if bundle::sha256(&data) != hex {
+            warn!("Removing corrupt cache entry {}", path.display());
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+        touch(&path);
+        Some(data)
+    }
+
+    /// Adds a blob that was checked against its digest, then evicts the least recently
+    /// used blobs beyond the size limit.
+    pub fn put(&self, digest: &str, data: &[u8]) -> Result<()> {
+        let (path, _) = self.path(digest)?;
+        let parent = path.parent().expect("cache paths have a parent");
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+        // Write to a temporary file first so readers never see a partial blob.
+        let temporary = path.with_extension("partial");
+        std::fs::write(&temporary, data)
+            .with_context(|| format!("Failed to write {}", temporary.display()))?;
+        std::fs::rename(&temporary, &path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        self.gc(self.max_bytes)?;
+        Ok(())
+    }
+
+    /// Lists the cached blobs, most recently used first.
+    pub fn entries(&self) -> Result<Vec<CacheEntry>> {
+        let dir = self.dir.join("sha256");
+        let read_dir = match std::fs::read_dir(&dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", dir.display())),
+        };
+        let mut entries = Vec::new();
+        for file in read_dir {
+            let file = file?;
+            let name = file.file_name().to_string_lossy().into_owned();
+            if !is_hex_digest(&name) {
+                continue;
+            }
+            let metadata = file.metadata()?;
+            entries.push(CacheEntry {
+                digest: format!("sha256:{}", name),
+                size: metadata.len(),
+                last_used: metadata.modified()?,
+            });
+        }
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.last_used));
+        Ok(entries)
+    }
+
+    /// Removes the least recently used blobs until the cache holds at most `max_bytes`,
+    /// and returns how many blobs and bytes were removed.
+    pub fn gc(&self, max_bytes: u64) -> Result<(usize, u64)> {
+        let entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+        let mut removed = (0, 0);
+        for entry in entries.iter().rev() {
+            if total <= max_bytes {
+                break;
+            }
+            let (path, _) = self.path(&entry.digest)?;
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            total -= entry.size;
+            removed.0 += 1;
+            removed.1 += entry.size;
+        }
+        Ok(removed)
+    }
+
+    /// The file of a digest, and the hex part of the digest.
+    fn path<'a>(&self, digest: &'a str) -> Result<(PathBuf, &'a str)> {
+        let hex = digest
+            .strip_prefix("sha256:")
+            .filter(|hex| is_hex_digest(hex))
+            .ok_or_else(|| anyhow!("Unsupported digest {}", digest))?;
+        Ok((self.dir.join("sha256").join(hex), hex))
+    }
+}
+
+/// Runs the cache subcommand with the arguments that follow it.
+pub fn run(args: &[String]) -> Result<()> {
+    let mut command: Option<&str> = None;
+    let mut dir: Option<PathBuf> = None;
+    let mut max_bytes = DEFAULT_MAX_BYTES;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .ok_or_else(|| anyhow!("{} requires a value", arg))
+        };
+        match arg.as_str() {
+            "--dir" => dir = Some(PathBuf::from(value()?)),
+            "--max-bytes" => {
+                max_bytes = value()?
+                    .parse()
+                    .context("--max-bytes must be a number of bytes")?
+            }
+            "ls" | "gc" if command.is_none() => command = Some(arg),
+            _ => bail!("Unexpected argument: {}\n{}", arg, USAGE),
+        }
+    }
+    let dir = match dir {
+        Some(dir) => dir,
+        None => default_dir()?,
+    };
+    let cache = ComponentCache::new(dir, max_bytes);
+    match command {
+        Some("ls") => {
+            let entries = cache.entries()?;
+            for entry in &entries {
+                let age = entry.last_used.elapsed().unwrap_or_default().as_secs();
+                println!("{} {} bytes, used {}s ago", entry.digest, entry.size, age);
+            }
+            let total: u64 = entries.iter().map(|entry| entry.size).sum();
+            println!(
+                "{} blob(s), {} bytes in {}",
+                entries.len(),
+                total,
+                cache.dir.display()
+            );
+        }
+        Some("gc") => {
+            let (count, bytes) = cache.gc(max_bytes)?;
+            println!("Removed {} blob(s), {} bytes", count, bytes);
+        }
+        _ => bail!(USAGE),
+    }
+    Ok(())
+}
+
+/// The directory of the cache: `$WASM_OPERATOR_CACHE_DIR`, `$XDG_CACHE_HOME/wasm-operator`
+/// or `$HOME/.cache/wasm-operator`.
+pub fn default_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os(CACHE_DIR_ENV) {
+        return Ok(PathBuf::from(dir));
+    }
+    if let Some(dir) = std::env::var_os("XDG_CACHE_HOME") {
+        return Ok(Path::new(&dir).join("wasm-operator"));
+    }
+    let home = std::env::var_os("HOME")
+        .ok_or_else(|| anyhow!("Set {} to the directory of the cache", CACHE_DIR_ENV))?;
+    Ok(Path::new(&home).join(".cache").join("wasm-operator"))
+}
+
+/// Marks a blob as used now. Failing to do so only affects the order of eviction.
+fn touch(path: &Path) {
+    let _ = std::fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+}
+
+fn is_hex_digest(hex: &str) -> bool {
+    hex.len() == 64
+        && hex
+            .bytes()
+            .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// A cache in a directory of its own, removed when the test ends.
+    struct TestCache {
+        cache: ComponentCache,
+    }
+
+    impl TestCache {
+        fn new(name: &str, max_bytes: u64) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "wasm-operator-cache-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            Self {
+                cache: ComponentCache::new(dir, max_bytes),
+            }
+        }
+
+        /// Adds a blob that was last used `age` seconds after the epoch.
+        fn put(&self, data: &[u8], age: u64) -> String {
+            let digest = format!("sha256:{}", bundle::sha256(data));
+            self.cache.put(&digest, data).unwrap();
+            let (path, _) = self.cache.path(&digest).unwrap();
+            if let Ok(file) = std::fs::File::options().write(true).open(path) {
+                file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(age))
+                    .unwrap();
+            }
+            digest
+        }
+
+        fn digests(&self) -> Vec<String> {
+            let entries = self.cache.entries().unwrap();
+            entries.into_iter().map(|entry| entry.digest).collect()
+        }
+    }
+
+    impl Drop for TestCache {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.cache.dir);
+        }
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_blobs() {
+        let test = TestCache::new("lru", 8);
+        let a = test.put(b"aaaa", 1);
+        let b = test.put(b"bbbb", 2);
+        assert_eq!(test.digests(), [b.clone(), a.clone()]);
+
+        // Reading a blob makes it the most recently used one.
+        assert_eq!(test.cache.get(&a).as_deref(), Some(&b"aaaa"[..]));
+        let c = test.put(b"cccc", 3);
+        assert_eq!(test.digests(), [a.clone(), c.clone()]);
+        assert!(test.cache.get(&b).is_none());
+
+        assert_eq!(test.cache.gc(4).unwrap(), (1, 4));
+        assert_eq!(test.digests(), [a]);
+        assert_eq!(test.cache.gc(0).unwrap(), (1, 4));
+        assert!(test.digests().is_empty());
+    }
+
+    #[test]
+    fn blobs_beyond_the_limit_are_not_kept() {
+        let test = TestCache::new("limit", 2);
+        let digest = test.put(b"too large", 1);
+        assert!(test.cache.get(&digest).is_none());
+        assert!(test.digests().is_empty());
+    }
+
+    #[test]
+    fn removes_corrupt_blobs() {
+        let test = TestCache::new("corrupt", 1024);
+        let digest = test.put(b"component", 1);
+        let (path, _) = test.cache.path(&digest).unwrap();
+        std::fs::write(&path, b"tampered").unwrap();
+        assert!(test.cache.get(&digest).is_none());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn rejects_unsupported_digests() {
+        let test = TestCache::new("digests", 1024);
+        assert!(test.cache.put("sha512:abcd", b"data").is_err());
+        assert!(test.cache.put("sha256:../../etc/passwd", b"data").is_err());
+        assert!(test
+            .cache
+            .get(&format!("sha256:{}", "A".repeat(64)))
+            .is_none());
+    }
+}
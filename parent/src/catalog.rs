@@ -0,0 +1,299 @@
+//! # Catalog Module
+//!
+//! This module implements a client for operator catalogs: indexes of `.wopr` bundles
+//! published to an OCI registry, which `parent available` lists and `parent upgrade`
+//! installs from. A catalog is an OCI artifact with one layer of `CATALOG_MEDIA_TYPE`,
+//! holding the index as JSON:
+//!
+//! ```json
+//! {"operators": [{"name": "demo", "version": "1.2.0", "bundle": "ghcr.io/acme/demo:1.2.0"}]}
+//! ```
+//!
+//! An entry can also have a `description`, and the `capabilities` and `requires` of its
+//! bundle, which `parent install` and `parent upgrade` resolve dependencies with. Bundles
+//! are OCI artifacts with one layer of `BUNDLE_MEDIA_TYPE`. Both can be pushed with e.g.
+//! `oras push <reference> <file>:<media type>`. Public repositories are read with
+//! anonymous tokens; registries on localhost are reached over plain HTTP. Bundles are
+//! kept in a `ComponentCache` if the client has one.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty};
+use hyper::header::{ACCEPT, AUTHORIZATION, LOCATION, WWW_AUTHENTICATE};
+use hyper::{Request, StatusCode};
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::bundle::{self, Capabilities};
+use crate::cache::ComponentCache;
+use crate::dependencies::Requirement;
+
+pub const CATALOG_MEDIA_TYPE: &str = "application/vnd.wasm-operator.catalog.v1+json";
+pub const BUNDLE_MEDIA_TYPE: &str = "application/vnd.wasm-operator.bundle.v1.tar+zstd";
+const MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+const MAX_REDIRECTS: usize = 5;
+
+#[derive(Debug, Deserialize)]
+pub struct CatalogIndex {
+    pub operators: Vec<CatalogEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CatalogEntry {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub capabilities: Option<Capabilities>,
+    #[serde(default)]
+    pub requires: Vec<Requirement>,
+    /// Reference to the OCI artifact of the bundle.
+    pub bundle: String,
+}
+
+/// A reference to an OCI artifact, `<registry>/<repository>[:<tag>|@<digest>]`.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub registry: String,
+    pub repository: String,
+    /// A tag or a digest, `latest` if the reference has neither.
+    pub reference: String,
+}
+
+impl FromStr for Reference {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (registry, rest) = s
+            .split_once('/')
+            .ok_or_else(|| anyhow!("Reference '{}' does not name a registry", s))?;
+        let (repository, reference) = match rest.split_once('@') {
+            Some((repository, digest)) => (repository, digest),
+            None => match rest.rsplit_once(':') {
+                Some((repository, tag)) => (repository, tag),
+                None => (rest, "latest"),
+            },
+        };
+        if registry.is_empty() || repository.is_empty() || reference.is_empty() {
+            bail!("Invalid reference '{}'", s);
+        }
+        Ok(Self {
+            registry: registry.to_string(),
+            repository: repository.to_string(),
+            reference: reference.to_string(),
+        })
+    }
+}
+
+impl Reference {
+    fn origin(&self) -> String {
+        let scheme = if self.registry.starts_with("localhost") || self.registry.starts_with("127.")
+        {
+            "http"
+        } else {
+            "https"
+        };
+        format!("{}://{}", scheme, self.registry)
+    }
+
+    fn base_url(&self) -> String {
+        format!("{}/v2/{}", self.origin(), self.repository)
+    }
+}
+
+/// Reads catalogs and bundles from OCI registries.
+pub struct CatalogClient {
+    client: Client<HttpsConnector<HttpConnector>, Empty<Bytes>>,
+    /// Bearer tokens, by registry and repository.
+    tokens: HashMap<(String, String), String>,
+    cache: Option<ComponentCache>,
+}
+
+impl CatalogClient {
+    pub fn new() -> Result<Self> {
+        let connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_native_roots()?
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Ok(Self {
+            client: Client::builder(TokioExecutor::new()).build(connector),
+            tokens: HashMap::new(),
+            cache: None,
+        })
+    }
+
+    /// Reads bundles from the cache when it has them, and adds the bundles it fetches.
+    pub fn with_cache(mut self, cache: ComponentCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Fetches the index of a catalog.
+    pub async fn index(&mut self, catalog: &Reference) -> Result<CatalogIndex> {
+        let index = self.pull(catalog, CATALOG_MEDIA_TYPE).await?;
+        serde_json::from_slice(&index).context("Invalid catalog index")
+    }
+
+    /// Fetches the bundle of a catalog entry.
+    pub async fn bundle(&mut self, entry: &CatalogEntry) -> Result<Bytes> {
+        let reference: Reference = entry.bundle.parse()?;
+        self.pull(&reference, BUNDLE_MEDIA_TYPE)
+            .await
+            .with_context(|| format!("Failed to fetch the bundle of '{}'", entry.name))
+    }
+
+    /// Fetches the layer of the given media type of an artifact, and checks its digest.
+    async fn pull(&mut self, reference: &Reference, media_type: &str) -> Result<Bytes> {
+        let manifest = self
+            .get(
+                reference,
+                &format!("manifests/{}", reference.reference),
+                MANIFEST_MEDIA_TYPE,
+            )
+            .await?;
+        let manifest: Value = serde_json::from_slice(&manifest).context("Invalid OCI manifest")?;
+        let digest = manifest["layers"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|layer| layer["mediaType"] == media_type)
+            .and_then(|layer| layer["digest"].as_str())
+            .ok_or_else(|| {
+                anyhow!(
+                    "'{}/{}' has no layer of type {}",
+                    reference.registry,
+                    reference.repository,
+                    media_type
+                )
+            })?
+            .to_string();
+        let cached = media_type == BUNDLE_MEDIA_TYPE;
+        if let Some(cache) = self.cache.as_ref().filter(|_| cached)
+            && let Some(blob) = cache.get(&digest)
+        {
+            return Ok(Bytes::from(blob));
+        }
+        let blob = self
+            .get(reference, &format!("blobs/{}", digest), media_type)
+            .await?;
+        let expected = digest
+            .strip_prefix("sha256:")
+            .ok_or_else(|| anyhow!("Unsupported digest {}", digest))?;
+        if bundle::sha256(&blob) != expected {
+            bail!("Layer {} does not match its digest", digest);
+        }
+        if let Some(cache) = self.cache.as_ref().filter(|_| cached)
+            && let Err(e) = cache.put(&digest, &blob)
+        {
+            warn!("Failed to cache {}: {:#}", digest, e);
+        }
+        Ok(blob)
+    }
+
+    /// Gets a path of a repository, authenticating with an anonymous token if the registry
+    /// asks for one and following redirects to blob storage.
+    async fn get(&mut self, reference: &Reference, path: &str, accept: &str) -> Result<Bytes> {
+        let key = (reference.registry.clone(), reference.repository.clone());
+        let mut url = format!("{}/{}", reference.base_url(), path);
+        let mut authenticated = false;
+        for _ in 0..=MAX_REDIRECTS {
+            let mut request = Request::get(&url).header(ACCEPT, accept);
+            // Redirects lead to other hosts, which must not see the token.
+            if url.starts_with(&reference.base_url())
+                && let Some(token) = self.tokens.get(&key)
+            {
+                request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+            }
+            let response = self
+                .client
+                .request(request.body(Empty::new())?)
+                .await
+                .with_context(|| format!("Failed to reach {}", reference.registry))?;
+            let status = response.status();
+            if status == StatusCode::UNAUTHORIZED && !authenticated {
+                let challenge = response
+                    .headers()
+                    .get(WWW_AUTHENTICATE)
+                    .and_then(|value| value.to_str().ok())
+                    .ok_or_else(|| anyhow!("{} requires authentication", reference.registry))?
+                    .to_string();
+                let token = self.fetch_token(&challenge).await?;
+                self.tokens.insert(key.clone(), token);
+                authenticated = true;
+                continue;
+            }
+            if status.is_redirection() {
+                let location = response
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|value| value.to_str().ok())
+                    .ok_or_else(|| anyhow!("Redirect without a location from {}", url))?;
+                url = match location.strip_prefix('/') {
+                    // A path on the same registry.
+                    Some(path) => format!("{}/{}", reference.origin(), path),
+                    None => location.to_string(),
+                };
+                continue;
+            }
+            let body = response.into_body().collect().await?.to_bytes();
+            if !status.is_success() {
+                bail!(
+                    "{} returned status {}: {}",
+                    url,
+                    status,
+                    String::from_utf8_lossy(&body)
+                );
+            }
+            return Ok(body);
+        }
+        bail!("Too many redirects for {}", url)
+    }
+
+    /// Fetches an anonymous token for a `Bearer` challenge.
+    async fn fetch_token(&self, challenge: &str) -> Result<String> {
+        let parameters = challenge
+            .strip_prefix("Bearer ")
+            .ok_or_else(|| anyhow!("Unsupported authentication challenge: {}", challenge))?;
+        let parameters: HashMap<&str, &str> = parameters
+            .split(',')
+            .filter_map(|parameter| {
+                let (key, value) = parameter.trim().split_once('=')?;
+                Some((key, value.trim_matches('"')))
+            })
+            .collect();
+        let realm = parameters
+            .get("realm")
+            .ok_or_else(|| anyhow!("Authentication challenge has no realm: {}", challenge))?;
+        let query: Vec<String> = ["service", "scope"]
+            .into_iter()
+            .filter_map(|key| Some(format!("{}={}", key, parameters.get(key)?)))
+            .collect();
+        let request = Request::get(format!("{}?{}", realm, query.join("&"))).body(Empty::new())?;
+        let response = self
+            .client
+            .request(request)
+            .await
+            .with_context(|| format!("Failed to reach {}", realm))?;
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+        if !status.is_success() {
+            bail!("{} returned status {}", realm, status);
+        }
+        let token: Value = serde_json::from_slice(&body).context("Invalid token response")?;
+        token["token"]
+            .as_str()
+            .or_else(|| token["access_token"].as_str())
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Token response from {} has no token", realm))
+    }
+}
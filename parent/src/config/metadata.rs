@@ -15,7 +15,120 @@ pub struct EnvironmentVariable {
     pub value: String,
 }
 
+/// Where reconcile errors are reported on the reconciled object.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorReporting {
+    #[default]
+    None,
+    Annotation,
+    Condition,
+}
+
+/// Where the decision log of an operator is kept.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DecisionLogTarget {
+    /// A ConfigMap named `<object>-decisions` next to the object, owned by it.
+    ConfigMap,
+    /// The `decisionLog` field in the status of the object.
+    Status,
+}
+
+/// How the state of an unloaded component is encoded on disk.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnapshotCodecKind {
+    #[default]
+    Raw,
+    Zstd,
+    Lz4,
+    /// Only the blocks that changed since the first snapshot, which is kept as the base.
+    Delta,
+}
+
+/// The compiler that turns a component into machine code.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum Compiler {
+    /// The optimizing compiler, for operators that run often.
+    #[default]
+    Cranelift,
+    /// The baseline compiler, which compiles much faster but produces slower code, for
+    /// operators that rarely run. Requires the `winch` feature.
+    Winch,
+}
+
+/// How much Cranelift optimizes the code of a component.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum OptLevel {
+    /// No optimizations, for the fastest compile with Cranelift.
+    None,
+    Speed,
+    #[default]
+    SpeedAndSize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct DecisionLog {
+    pub target: DecisionLogTarget,
+    /// Number of decisions kept per object; older ones are dropped.
+    #[serde(default = "default_decision_log_entries")]
+    pub max_entries: usize,
+}
+
+fn default_decision_log_entries() -> usize {
+    20
+}
+
+/// A Secret or ConfigMap a component may read.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReadGrant {
+    pub name: String,
+    pub namespace: String,
+    /// Keys that may be read; all keys when empty.
+    #[serde(default)]
+    pub keys: Vec<String>,
+}
+
+impl ReadGrant {
+    /// Whether one of the grants allows reading the key of the object. `None` stands for
+    /// the whole object, which needs a grant of all keys.
+    pub fn allows(grants: &[ReadGrant], name: &str, namespace: &str, key: Option<&str>) -> bool {
+        grants.iter().any(|grant| {
+            grant.name == name
+                && grant.namespace == namespace
+                && (grant.keys.is_empty()
+                    || key.is_some_and(|key| grant.keys.iter().any(|k| k == key)))
+        })
+    }
+}
+
+/// Objects of a related kind whose changes reconcile the objects of a kind the component
+/// watches, e.g. the Secrets named in the spec of a custom resource.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct WatchMapping {
+    /// Kind of the related objects.
+    pub kind: String,
+    /// Namespace of the related objects; all namespaces when empty.
+    #[serde(default)]
+    pub namespace: String,
+    /// Kind of the objects to reconcile when a related object changes.
+    pub target_kind: String,
+    /// Dot-separated path of the field of a target that holds the name of a related object
+    /// in its namespace, e.g. `spec.secretName`. Lists along the path match if one of
+    /// their elements does. Without a field, the owners of a related object are
+    /// reconciled.
+    #[serde(default)]
+    pub field: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
 pub struct WasmComponentMetadata {
     pub name: String,
     pub wasm: PathBuf,
@@ -23,27 +136,193 @@ pub struct WasmComponentMetadata {
     pub env: Vec<EnvironmentVariable>,
     #[serde(default)]
     pub args: Vec<String>,
+    /// Upper bound on the linear memory a component instance may allocate.
+    #[serde(default)]
+    pub memory_limit_bytes: Option<u64>,
+    /// Namespaces this component may not watch or write to, on top of the global list.
+    #[serde(default)]
+    pub excluded_namespaces: Vec<String>,
+    #[serde(default)]
+    pub error_reporting: ErrorReporting,
+    /// Labels the objects this component creates or updates, so the ones it no longer
+    /// wants can be pruned.
+    #[serde(default)]
+    pub track_applied: bool,
+    /// Reconciles objects applied by this component with a `drift-detected` event when
+    /// someone else changes them. Requires `track-applied`.
+    #[serde(default)]
+    pub detect_drift: bool,
+    /// Lets the component record its reconcile decisions on the objects it reconciles.
+    #[serde(default)]
+    pub decision_log: Option<DecisionLog>,
+    /// How the state of this component is encoded on disk while it is unloaded.
+    #[serde(default)]
+    pub snapshot_codec: SnapshotCodecKind,
+    /// Compares the writes of this component with the live objects instead of applying
+    /// them, to check it against the controller it is meant to replace.
+    #[serde(default)]
+    pub shadow: bool,
+    /// Makes the object being reconciled the owner of the objects this component creates
+    /// in its namespace, so they are garbage collected together with it.
+    #[serde(default)]
+    pub set_owner_references: bool,
+    /// Secrets this component may read with `get-secret`, or with `get-resource` when
+    /// granted without keys. Other Secrets cannot be read or listed.
+    #[serde(default)]
+    pub readable_secrets: Vec<ReadGrant>,
+    /// ConfigMaps this component may read with `get-config-map`.
+    #[serde(default)]
+    pub readable_config_maps: Vec<ReadGrant>,
+    /// Operators this component may trigger reconciles of with `trigger-reconcile`.
+    #[serde(default)]
+    pub may_trigger: Vec<String>,
+    /// Related objects whose changes reconcile the objects they belong to.
+    #[serde(default)]
+    pub watch_mappings: Vec<WatchMapping>,
+    /// Topics whose messages are delivered to the `on-message` export of this component.
+    #[serde(default)]
+    pub subscriptions: Vec<String>,
+    /// Passes the previous state of an object along with `modified` events. The host keeps
+    /// the last state passed to this component of every object it reconciles.
+    #[serde(default)]
+    pub old_objects: bool,
+    /// Passes the object being reconciled as a `k8s-object` handle instead of as JSON, so
+    /// the host does not serialize it and the guest only copies the fields it reads.
+    #[serde(default)]
+    pub lazy_objects: bool,
+    /// The compiler for this component.
+    #[serde(default)]
+    pub compiler: Compiler,
+    /// How much Cranelift optimizes this component. Ignored by Winch.
+    #[serde(default)]
+    pub opt_level: OptLevel,
+    /// Maximum number of objects this component reconciles at the same time. Reconciles
+    /// beyond the first run on extra instances, which do not share the state the
+    /// component keeps in memory, so values above one require `stateless`.
+    #[serde(default = "default_max_concurrent_reconciles")]
+    pub max_concurrent_reconciles: usize,
+    /// Declares that the reconciles of this component do not depend on state it keeps in
+    /// memory, e.g. because it keeps its state in the cluster or in `kv-set`.
+    #[serde(default)]
+    pub stateless: bool,
+}
+
+fn default_max_concurrent_reconciles() -> usize {
+    1
 }
 
 impl WasmComponentMetadata {
     /// Load component metadata from a YAML file
     pub fn load_from_yaml(path: &PathBuf) -> Result<Vec<WasmComponentMetadata>> {
         let contents = fs::read_to_string(path)?;
+        Self::parse_yaml(&contents)
+    }
 
+    /// Parse component metadata from YAML documents separated by `---`
+    pub fn parse_yaml(contents: &str) -> Result<Vec<WasmComponentMetadata>> {
         if contents.trim().is_empty() {
             return Ok(Vec::new());
         }
 
         contents
             .split("\n---")
+            .filter(|yaml_doc| !yaml_doc.trim().is_empty())
             .filter_map(
                 |yaml_doc| match serde_yml::from_str::<WasmComponentMetadata>(yaml_doc) {
                     Err(err) if err.to_string().contains("EOF while parsing a value") => None,
-                    result => {
-                        Some(result.map_err(|e| anyhow::anyhow!("Failed to parse module: {}", e)))
-                    }
+                    result => Some(
+                        result
+                            .map_err(|e| anyhow::anyhow!("Failed to parse module: {}", e))
+                            .and_then(|metadata| metadata.validate().map(|()| metadata)),
+                    ),
                 },
             )
             .collect()
     }
+
+    /// Rejects settings that do not fit together.
+    fn validate(&self) -> Result<()> {
+        if self.max_concurrent_reconciles > 1 && !self.stateless {
+            anyhow::bail!(
+                "Module '{}' sets max-concurrent-reconciles to {}, which requires stateless: \
+                 true since extra instances do not share the state it keeps in memory",
+                self.name,
+                self.max_concurrent_reconciles
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_documents_with_defaults() {
+        let components = WasmComponentMetadata::parse_yaml(
+            "name: first\nwasm: first.wasm\n---\nname: second\nwasm: second.wasm\nsnapshot-codec: zstd\nmax-concurrent-reconciles: 4\nstateless: true\n",
+        )
+        .unwrap();
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[0].name, "first");
+        assert_eq!(components[0].snapshot_codec, SnapshotCodecKind::Raw);
+        assert_eq!(components[0].max_concurrent_reconciles, 1);
+        assert_eq!(components[1].snapshot_codec, SnapshotCodecKind::Zstd);
+        assert_eq!(components[1].max_concurrent_reconciles, 4);
+    }
+
+    #[test]
+    fn skips_empty_documents() {
+        assert!(WasmComponentMetadata::parse_yaml("  \n")
+            .unwrap()
+            .is_empty());
+        let components =
+            WasmComponentMetadata::parse_yaml("name: only\nwasm: only.wasm\n---\n").unwrap();
+        assert_eq!(components.len(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        assert!(WasmComponentMetadata::parse_yaml("name: missing-wasm\n").is_err());
+        assert!(
+            WasmComponentMetadata::parse_yaml("name: a\nwasm: a.wasm\ncompiler: gcc\n").is_err()
+        );
+        assert!(WasmComponentMetadata::parse_yaml(
+            "name: a\nwasm: a.wasm\nmax-concurrent-reconciles: 2\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn read_grants_limit_keys() {
+        let grants = [
+            ReadGrant {
+                name: "whole".to_string(),
+                namespace: "default".to_string(),
+                keys: vec![],
+            },
+            ReadGrant {
+                name: "partial".to_string(),
+                namespace: "default".to_string(),
+                keys: vec!["token".to_string()],
+            },
+        ];
+        assert!(ReadGrant::allows(&grants, "whole", "default", None));
+        assert!(ReadGrant::allows(&grants, "whole", "default", Some("any")));
+        assert!(ReadGrant::allows(
+            &grants,
+            "partial",
+            "default",
+            Some("token")
+        ));
+        assert!(!ReadGrant::allows(
+            &grants,
+            "partial",
+            "default",
+            Some("other")
+        ));
+        assert!(!ReadGrant::allows(&grants, "partial", "default", None));
+        assert!(!ReadGrant::allows(&grants, "whole", "other", None));
+    }
 }
@@ -6,3 +6,5 @@
 //! various sources.
 
 pub mod metadata;
+pub mod profile;
+pub mod runtime;
@@ -0,0 +1,84 @@
+//! # Profile Module
+//!
+//! This module defines the configuration profiles selected with `--profile`. A profile is
+//! a bundle of runtime configuration defaults for one context, so running the parent
+//! during development, in a benchmark, or in production does not take a different set of
+//! flags each time. Settings in the runtime configuration file override the profile.
+
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+use crate::config::runtime::{KubernetesConfig, LogFormat, PoolingConfig, RuntimeConfig};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Profile {
+    /// Readable logs, no idle unloading and no metrics.
+    Dev,
+    /// Deterministic execution and no idle unloading or metrics, so runs are comparable.
+    Bench,
+    /// The defaults for running in a cluster.
+    #[default]
+    Prod,
+    /// Small pools and early unloading for edge clusters with little memory, such as
+    /// single-board aarch64 machines.
+    Edge,
+}
+
+impl Profile {
+    /// Returns the runtime configuration defaults of the profile.
+    pub fn defaults(self) -> RuntimeConfig {
+        let prod = RuntimeConfig::default();
+        match self {
+            Profile::Dev => RuntimeConfig {
+                log_format: LogFormat::Pretty,
+                idle_unload_secs: None,
+                metrics_enabled: false,
+                ..prod
+            },
+            Profile::Bench => RuntimeConfig {
+                idle_unload_secs: None,
+                metrics_enabled: false,
+                deterministic: true,
+                ..prod
+            },
+            Profile::Prod => prod,
+            Profile::Edge => RuntimeConfig {
+                kubernetes: KubernetesConfig {
+                    disable_discovery_cache: true,
+                    ..prod.kubernetes
+                },
+                idle_unload_secs: Some(30),
+                pooling: Some(PoolingConfig {
+                    total_component_instances: 16,
+                    total_core_instances: 160,
+                    total_memories: 32,
+                    total_tables: 160,
+                    max_memory_bytes: 64 << 20,
+                }),
+                batch_concurrency: 2,
+                prewarm_concurrency: 1,
+                reuse_stores: false,
+                compile_concurrency: Some(1),
+                ..prod
+            },
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dev" => Ok(Profile::Dev),
+            "bench" => Ok(Profile::Bench),
+            "prod" => Ok(Profile::Prod),
+            "edge" => Ok(Profile::Edge),
+            _ => Err(anyhow!(
+                "Unknown profile '{}', expected dev, bench, prod or edge",
+                s
+            )),
+        }
+    }
+}
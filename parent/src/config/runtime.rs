@@ -0,0 +1,405 @@
+//! # Runtime Configuration Module
+//!
+//! This module defines the process-wide settings of the parent, as opposed to the
+//! per-component settings in the metadata module. The configuration is loaded from an
+//! optional YAML file; any setting that is not specified falls back to its default.
+
+use std::collections::HashMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::metadata::WasmComponentMetadata;
+use crate::platform;
+
+/// What to do with a resource that exceeds the size limit for guests.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OversizeStrategy {
+    /// Skip the reconcile and log an error.
+    #[default]
+    Reject,
+    /// Drop `managedFields` and the last-applied annotation, and reject if still too large.
+    StripMetadata,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct SizeLimits {
+    /// Maximum size of the resource JSON passed to a guest.
+    pub max_resource_bytes: usize,
+    /// Maximum size of the resource JSON a guest may submit to the host.
+    pub max_guest_body_bytes: usize,
+    pub oversize_strategy: OversizeStrategy,
+}
+
+impl Default for SizeLimits {
+    fn default() -> Self {
+        Self {
+            max_resource_bytes: 2 * 1024 * 1024,
+            max_guest_body_bytes: 2 * 1024 * 1024,
+            oversize_strategy: OversizeStrategy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogFormat {
+    /// One line per event.
+    #[default]
+    Full,
+    /// Multi-line, human-friendly output.
+    Pretty,
+    /// One JSON object per line, for log collectors.
+    Json,
+}
+
+/// How an operator is brought back after it was unloaded.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnapshotStrategy {
+    /// Load and compile the component from its file, and restore the state the guest
+    /// serialized.
+    #[default]
+    Serialize,
+    /// Keep the compiled and linked component in memory, so a reload only instantiates it,
+    /// with the initial memory mapped copy-on-write, before restoring the state the guest
+    /// serialized.
+    PreInit,
+}
+
+/// How failed reconciles are retried before they end up in the dead-letter queue.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay_ms: 500,
+            max_delay_ms: 60_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the exponential backoff delay before the given (1-based) retry attempt.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        Duration::from_millis(
+            self.base_delay_ms
+                .saturating_mul(factor)
+                .min(self.max_delay_ms),
+        )
+    }
+}
+
+/// How long a watch waits before it is re-established after an error.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct WatchBackoff {
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for WatchBackoff {
+    fn default() -> Self {
+        Self {
+            base_delay_ms: 800,
+            max_delay_ms: 30_000,
+        }
+    }
+}
+
+impl WatchBackoff {
+    /// Returns the delay before the given (1-based) consecutive attempt, doubling with
+    /// every attempt and jittered to between half and all of it, so watches that failed
+    /// together do not come back at the same time.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        let delay = self
+            .base_delay_ms
+            .saturating_mul(factor)
+            .min(self.max_delay_ms);
+        Duration::from_millis(delay / 2 + fastrand::u64(0..=delay / 2))
+    }
+}
+
+/// Sizes of the pooling instance allocator, which allocates the memories, tables and
+/// stacks of all instances up front instead of on every load.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct PoolingConfig {
+    /// Maximum number of operator instances that exist at the same time.
+    pub total_component_instances: u32,
+    /// Maximum number of core module instances, across all operator instances.
+    pub total_core_instances: u32,
+    /// Maximum number of linear memories, across all operator instances.
+    pub total_memories: u32,
+    /// Maximum number of tables, across all operator instances.
+    pub total_tables: u32,
+    /// Maximum size of a linear memory. Each memory reserves this much address space.
+    pub max_memory_bytes: usize,
+}
+
+impl Default for PoolingConfig {
+    fn default() -> Self {
+        Self {
+            total_component_instances: 100,
+            total_core_instances: 1000,
+            total_memories: 200,
+            total_tables: 1000,
+            max_memory_bytes: 1 << 30,
+        }
+    }
+}
+
+/// Source of the credentials the parent uses to authenticate against the cluster.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(
+    tag = "provider",
+    rename_all = "kebab-case",
+    rename_all_fields = "kebab-case"
+)]
+pub enum CredentialProvider {
+    /// A bearer token file, re-read whenever it changes (e.g. a projected service account token).
+    TokenFile { path: PathBuf },
+    /// A client-go style exec credential plugin.
+    Exec {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default)]
+        env: HashMap<String, String>,
+    },
+    /// EKS authentication through `aws eks get-token`, using IRSA or Pod Identity credentials.
+    AwsEks {
+        cluster_name: String,
+        #[serde(default)]
+        region: Option<String>,
+    },
+    /// GKE authentication through `gke-gcloud-auth-plugin`, using workload identity.
+    GcpWorkloadIdentity,
+    /// A bearer token stored in a Vault KV secret.
+    Vault {
+        address: String,
+        secret_path: String,
+        #[serde(default = "default_vault_field")]
+        field: String,
+        vault_token_file: PathBuf,
+        #[serde(default = "default_vault_refresh_interval")]
+        refresh_interval_secs: u64,
+    },
+}
+
+fn default_vault_field() -> String {
+    "token".to_string()
+}
+
+fn default_vault_refresh_interval() -> u64 {
+    300
+}
+
+/// Settings for the connection to the Kubernetes API server.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct KubernetesConfig {
+    /// Kubeconfig file to connect with instead of inferring the config from the
+    /// environment, like `kubectl --kubeconfig`.
+    pub kubeconfig: Option<PathBuf>,
+    /// Kubeconfig context to use instead of the current one, like `kubectl --context`.
+    pub context: Option<String>,
+    /// Overrides the default namespace of the config, like `kubectl --namespace`.
+    pub namespace: Option<String>,
+    /// Overrides the credentials found by `Config::infer`.
+    pub credentials: Option<CredentialProvider>,
+    /// HTTP proxy used to reach the API server, e.g. `http://proxy.internal:3128`.
+    pub proxy_url: Option<String>,
+    /// PEM files with additional CA certificates to trust for the API server, on top of
+    /// the cluster CA from the inferred config.
+    pub ca_bundles: Vec<PathBuf>,
+    /// Overrides the API server URL, e.g. `https://[fd00::1]:6443` for an IPv6 endpoint.
+    pub cluster_url: Option<String>,
+    /// Further API server URLs to fail over to, in order, when the cluster URL cannot be
+    /// reached or is not ready.
+    pub failover_urls: Vec<String>,
+    /// How often the active API server is checked when failover URLs are set. Defaults
+    /// to 10 seconds.
+    pub health_check_interval_secs: Option<u64>,
+    /// Overrides the server name used to verify the API server certificate, for when
+    /// `cluster-url` points at an address that is not in the certificate.
+    pub tls_server_name: Option<String>,
+    /// Timeout for establishing a connection to the API server.
+    pub connect_timeout_secs: Option<u64>,
+    /// Timeout for reading a response. Must exceed the watch timeout (290 seconds), or
+    /// watches are cut off before the API server ends them.
+    pub read_timeout_secs: Option<u64>,
+    /// Timeout for writing a request.
+    pub write_timeout_secs: Option<u64>,
+    /// Stops requesting gzip-compressed responses. Compression shrinks large list and
+    /// watch responses at the cost of some CPU time on both ends.
+    pub disable_compression: bool,
+    /// Number of objects per page of a `list-pager`. Defaults to 500.
+    pub list_page_size: Option<u32>,
+    /// Stops keeping the API discovery of the whole cluster in memory. Each kind is then
+    /// discovered the first time it is used, and only the kinds in use are kept.
+    pub disable_discovery_cache: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct RuntimeConfig {
+    /// Address the admin API listens on. The API has no authentication, so it only
+    /// listens on the loopback interface unless another address is set.
+    pub admin_addr: SocketAddr,
+    /// Annotation key that, when set to `"true"` on an object, makes the runtime skip it.
+    pub paused_annotation: String,
+    /// Namespaces no operator may watch or write to.
+    pub excluded_namespaces: Vec<String>,
+    pub size_limits: SizeLimits,
+    pub retry: RetryPolicy,
+    pub watch_backoff: WatchBackoff,
+    pub kubernetes: KubernetesConfig,
+    /// File the objects seen by the watches are checkpointed to on shutdown and restored
+    /// from at startup. Disabled when not set.
+    pub informer_cache_path: Option<PathBuf>,
+    /// Maximum number of requests of a `batch` host call that run at the same time.
+    pub batch_concurrency: usize,
+    /// Directory the memory snapshots of unloaded operators are written to. Defaults to
+    /// `wasm-state` in the temp directory of the platform.
+    pub state_dir: PathBuf,
+    pub log_format: LogFormat,
+    /// Time after which an operator without events is unloaded to disk. Never unloaded
+    /// when not set.
+    pub idle_unload_secs: Option<u64>,
+    pub snapshot_strategy: SnapshotStrategy,
+    /// Allocates instances from a pool sized up front instead of on demand. Each engine
+    /// has its own pool. Not pooled when not set.
+    pub pooling: Option<PoolingConfig>,
+    /// Whether metrics are recorded and served by the admin API.
+    pub metrics_enabled: bool,
+    /// Makes guest execution deterministic across runs and machines, at some cost in
+    /// floating point performance.
+    pub deterministic: bool,
+    /// Checkpoint directory the operators and the informer cache are restored from at
+    /// startup, see the `checkpoint` admin endpoint.
+    pub restore_from: Option<PathBuf>,
+    /// Time a single reconcile may run before the guest is trapped. Guests see the
+    /// remaining budget and can requeue before it runs out. Unlimited when not set.
+    pub reconcile_budget_ms: Option<u64>,
+    /// Lets operators watch and read, but turns their writes into no-ops that fail with a
+    /// `read-only` error, for trialing operators against production clusters.
+    pub read_only: bool,
+    /// Host extensions linked into every operator, by name.
+    pub extensions: Vec<String>,
+    /// Interval at which the status of all operators is summarized into the
+    /// `wasm-operator-status` ConfigMap. Not written when not set.
+    pub status_summary_interval_secs: Option<u64>,
+    /// Checks the objects operators create and update against the OpenAPI schema of their
+    /// kind before sending them, failing invalid ones with an error that names each wrong
+    /// field.
+    pub validate_writes: bool,
+    /// Publishes a message on the topic in the `operator.wasm/signal` label of a ConfigMap
+    /// in the namespace of the parent whenever the ConfigMap changes, to wake the operators
+    /// that subscribe to it.
+    pub signal_config_maps: bool,
+    /// Number of watch events for an unloaded operator after which it is reloaded in the
+    /// background, before the events are dispatched. Not prewarmed when not set.
+    pub prewarm_threshold: Option<usize>,
+    /// Maximum number of operators prewarmed at the same time.
+    pub prewarm_concurrency: usize,
+    /// Keeps the WASI context of unloaded operators and reuses it when they are reloaded,
+    /// instead of building it again.
+    pub reuse_stores: bool,
+    /// Maximum number of components compiled at the same time at startup. The number of
+    /// CPUs when not set.
+    pub compile_concurrency: Option<usize>,
+    /// Interval at which the watchdog restarts the subsystems of the runtime that ended or
+    /// got stuck. Subsystems are not restarted when not set.
+    pub watchdog_interval_secs: Option<u64>,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            admin_addr: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            paused_annotation: "operator.wasm/paused".to_string(),
+            excluded_namespaces: vec!["kube-system".to_string()],
+            size_limits: SizeLimits::default(),
+            retry: RetryPolicy::default(),
+            watch_backoff: WatchBackoff::default(),
+            kubernetes: KubernetesConfig::default(),
+            informer_cache_path: None,
+            batch_concurrency: 8,
+            state_dir: platform::default_state_dir(),
+            log_format: LogFormat::default(),
+            idle_unload_secs: Some(300),
+            snapshot_strategy: SnapshotStrategy::default(),
+            pooling: None,
+            metrics_enabled: true,
+            deterministic: false,
+            restore_from: None,
+            reconcile_budget_ms: None,
+            read_only: false,
+            extensions: Vec::new(),
+            status_summary_interval_secs: None,
+            validate_writes: false,
+            signal_config_maps: false,
+            prewarm_threshold: None,
+            prewarm_concurrency: 4,
+            reuse_stores: true,
+            compile_concurrency: None,
+            watchdog_interval_secs: Some(30),
+        }
+    }
+}
+
+impl RuntimeConfig {
+    /// Load the runtime configuration from a YAML file, on top of the given defaults
+    pub fn load_from_yaml(path: &Path, defaults: RuntimeConfig) -> Result<RuntimeConfig> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read runtime config {}", path.display()))?;
+
+        if contents.trim().is_empty() {
+            return Ok(defaults);
+        }
+
+        let overrides: Value = serde_yml::from_str(&contents)
+            .map_err(|e| anyhow::anyhow!("Failed to parse runtime config: {}", e))?;
+        let mut config = serde_json::to_value(defaults)?;
+        merge(&mut config, overrides);
+        serde_json::from_value(config)
+            .map_err(|e| anyhow::anyhow!("Failed to parse runtime config: {}", e))
+    }
+
+    /// Returns whether the namespace is excluded, either globally or for the given operator.
+    pub fn is_namespace_excluded(&self, namespace: &str, metadata: &WasmComponentMetadata) -> bool {
+        self.excluded_namespaces
+            .iter()
+            .chain(metadata.excluded_namespaces.iter())
+            .any(|excluded| excluded == namespace)
+    }
+}
+
+/// Recursively merges the keys of `overrides` into `base`.
+fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                merge(base.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
@@ -0,0 +1,324 @@
+//! # Conformance Module
+//!
+//! This module implements `parent conformance`, which checks a third-party component
+//! against the contract of the `kube-operator` world before it is admitted into a fleet.
+//! Every check runs on a fresh instance, so a guest that traps in one check does not fail
+//! the others. The component runs against the current cluster, and the probe reconciles
+//! may make it write into the namespace given with `--namespace`.
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::json;
+use wasmtime::{Engine, Store};
+
+use crate::config::metadata::WasmComponentMetadata;
+use crate::config::runtime::RuntimeConfig;
+use crate::host::api::bindings;
+use crate::host::api::bindings::local::operator::types::{
+    EventType, HttpRequest, ReconcileReason, ReconcileRequest, ReconcileResult, ReconcileTrigger,
+    WatchRequest,
+};
+use crate::host::handlers;
+use crate::host::locks::LockTable;
+use crate::host::state::State;
+use crate::kubernetes::lease::LeaseManager;
+use crate::kubernetes::{qualified_kind, KubernetesService};
+use crate::runtime::instance::WasmInstance;
+use crate::runtime::introspection::OperatorIntrospection;
+
+/// Time a single guest call may take before the check fails.
+const CALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Requeue delay from which a requeue is considered a mistake.
+const MAX_REQUEUE_SECS: u32 = 24 * 60 * 60;
+
+const USAGE: &str = "Usage: parent conformance [--namespace <namespace>] <component.wasm>";
+
+struct Conformance {
+    engine: Engine,
+    kubernetes_service: Arc<KubernetesService>,
+    config: Arc<RuntimeConfig>,
+    metadata: WasmComponentMetadata,
+    namespace: String,
+}
+
+/// Runs the conformance checks on the component named in the arguments, prints a report,
+/// and returns whether all checks passed.
+pub fn run(args: &[String]) -> Result<bool> {
+    let mut namespace = "default".to_string();
+    let mut wasm: Option<PathBuf> = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--namespace" {
+            namespace = iter
+                .next()
+                .ok_or_else(|| anyhow!("--namespace requires a value"))?
+                .clone();
+        } else if wasm.is_none() {
+            wasm = Some(PathBuf::from(arg));
+        } else {
+            bail!("Unexpected argument: {}\n{}", arg, USAGE);
+        }
+    }
+    let wasm = wasm.ok_or_else(|| anyhow!(USAGE))?;
+
+    let mut engine_config = wasmtime::Config::new();
+    engine_config.async_support(true);
+    let engine = Engine::new(&engine_config)?;
+    let metadata = WasmComponentMetadata {
+        name: wasm
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "component".to_string()),
+        wasm,
+        env: Vec::new(),
+        args: Vec::new(),
+        memory_limit_bytes: None,
+        excluded_namespaces: Vec::new(),
+        error_reporting: Default::default(),
+        track_applied: false,
+        detect_drift: false,
+        decision_log: None,
+        snapshot_codec: Default::default(),
+        shadow: false,
+        set_owner_references: false,
+        readable_secrets: Vec::new(),
+        readable_config_maps: Vec::new(),
+        may_trigger: Vec::new(),
+        watch_mappings: Vec::new(),
+        subscriptions: Vec::new(),
+        old_objects: false,
+        lazy_objects: false,
+        compiler: Default::default(),
+        opt_level: Default::default(),
+        max_concurrent_reconciles: 1,
+        stateless: false,
+    };
+
+    let tokio_runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    let local = tokio::task::LocalSet::new();
+    local.block_on(&tokio_runtime, async {
+        let config = Arc::new(RuntimeConfig::default());
+        let kubernetes_service = Arc::new(KubernetesService::new(&config.kubernetes).await?);
+        let conformance = Conformance {
+            engine,
+            kubernetes_service,
+            config,
+            metadata,
+            namespace,
+        };
+        Ok(conformance.report().await)
+    })
+}
+
+impl Conformance {
+    /// Runs all checks and prints their outcome.
+    async fn report(&self) -> bool {
+        println!("Conformance of {}", self.metadata.wasm.display());
+
+        let watches = match self.check_watches().await {
+            Ok(watches) => {
+                print_outcome("watch registration", &Ok(()));
+                watches
+            }
+            Err(e) => {
+                print_outcome("watch registration", &Err(e));
+                Vec::new()
+            }
+        };
+        let results = [
+            ("reconcile semantics", self.check_reconcile(&watches).await),
+            ("malformed input", self.check_malformed_input().await),
+            (
+                "serialize round-trip",
+                self.check_serialize_round_trip().await,
+            ),
+            ("http handler", self.check_handle_http().await),
+            ("timer handler", self.check_on_timer().await),
+            ("message handler", self.check_on_message().await),
+        ];
+        for (name, result) in &results {
+            print_outcome(name, result);
+        }
+
+        let failed = results.iter().filter(|(_, result)| result.is_err()).count()
+            + usize::from(watches.is_empty());
+        if failed == 0 {
+            println!("PASSED all checks");
+        } else {
+            println!("FAILED {} check(s)", failed);
+        }
+        failed == 0
+    }
+
+    async fn instantiate(&self) -> Result<(bindings::KubeOperator, Store<State>)> {
+        WasmInstance::new(
+            self.engine.clone(),
+            self.kubernetes_service.clone(),
+            Arc::new(LeaseManager::new(self.kubernetes_service.clone())),
+            Arc::new(LockTable::default()),
+            self.config.clone(),
+            self.metadata.clone(),
+            OperatorIntrospection::new(self.metadata.clone()),
+        )
+        .load()
+        .await
+    }
+
+    /// The component requests at least one watch, for kinds the cluster serves.
+    async fn check_watches(&self) -> Result<Vec<WatchRequest>> {
+        let (operator, mut store) = self.instantiate().await?;
+        let watches = call(operator.call_get_watch_requests(&mut store)).await?;
+        if watches.is_empty() {
+            bail!("the component requests no watches");
+        }
+        for watch in &watches {
+            self.kubernetes_service
+                .resolve_kind(&qualified_kind(&watch.kind, watch.api_version.as_deref()))
+                .await
+                .with_context(|| format!("watch for kind '{}'", watch.kind))?;
+        }
+        Ok(watches)
+    }
+
+    /// Reconciling a new object and its deletion completes, and requeues are sane.
+    async fn check_reconcile(&self, watches: &[WatchRequest]) -> Result<()> {
+        let watch = watches
+            .first()
+            .ok_or_else(|| anyhow!("skipped, no valid watch to reconcile"))?;
+        let (ar, _) = self
+            .kubernetes_service
+            .resolve_kind(&qualified_kind(&watch.kind, watch.api_version.as_deref()))
+            .await?;
+        let object = json!({
+            "apiVersion": ar.api_version,
+            "kind": ar.kind,
+            "metadata": {
+                "name": "conformance-probe",
+                "namespace": self.namespace,
+                "uid": "00000000-0000-0000-0000-000000000000",
+                "resourceVersion": "1",
+            },
+        });
+
+        let (operator, mut store) = self.instantiate().await?;
+        for event_type in [EventType::Added, EventType::Deleted] {
+            let request = probe_request(event_type, &self.namespace, object.to_string());
+            let result = call(operator.call_reconcile(&mut store, &request)).await?;
+            if let ReconcileResult::Requeue(seconds @ (0 | MAX_REQUEUE_SECS..)) = result {
+                bail!("{:?} event requeued after {} seconds", event_type, seconds);
+            }
+        }
+        Ok(())
+    }
+
+    /// Invalid resource JSON is reported as an error result instead of a trap.
+    async fn check_malformed_input(&self) -> Result<()> {
+        let (operator, mut store) = self.instantiate().await?;
+        let request = probe_request(EventType::Added, &self.namespace, "{".to_string());
+        match call(operator.call_reconcile(&mut store, &request)).await {
+            Ok(ReconcileResult::Error(_)) => Ok(()),
+            Ok(result) => bail!("expected an error result, got {:?}", result),
+            Err(e) => Err(e.context("the component trapped instead of returning an error")),
+        }
+    }
+
+    /// State serialized by one instance restores into another and serializes identically.
+    async fn check_serialize_round_trip(&self) -> Result<()> {
+        let (operator, mut store) = self.instantiate().await?;
+        let state = call(operator.call_serialize(&mut store)).await?;
+
+        let (restored, mut restored_store) = self.instantiate().await?;
+        call(restored.call_deserialize(&mut restored_store, &state)).await?;
+        let round_tripped = call(restored.call_serialize(&mut restored_store)).await?;
+        if round_tripped != state {
+            bail!(
+                "serialized {} bytes, but {} bytes after restoring them",
+                state.len(),
+                round_tripped.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// The HTTP handler, if the component exports one, answers with a valid status code.
+    async fn check_handle_http(&self) -> Result<()> {
+        let (_, mut store) = self.instantiate().await?;
+        let request = HttpRequest {
+            method: "GET".to_string(),
+            path: "/".to_string(),
+            query: String::new(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        };
+        let Some(response) = call(handlers::handle_http(&mut store, request)).await? else {
+            return Ok(());
+        };
+        if !(100..=599).contains(&response.status) {
+            bail!("invalid status code {}", response.status);
+        }
+        Ok(())
+    }
+
+    /// A timer with a token the component never scheduled is handled without a trap, if
+    /// the component exports a timer handler.
+    async fn check_on_timer(&self) -> Result<()> {
+        let (_, mut store) = self.instantiate().await?;
+        call(handlers::on_timer(
+            &mut store,
+            "conformance-probe".to_string(),
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// A message on a topic the component does not subscribe to is handled without a trap,
+    /// if the component exports a message handler.
+    async fn check_on_message(&self) -> Result<()> {
+        let (_, mut store) = self.instantiate().await?;
+        let (topic, payload) = ("conformance-probe".to_string(), "{}".to_string());
+        call(handlers::on_message(&mut store, topic, payload)).await?;
+        Ok(())
+    }
+}
+
+/// Awaits a guest call, failing it if it does not finish in time.
+async fn call<T>(future: impl Future<Output = Result<T>>) -> Result<T> {
+    tokio::time::timeout(CALL_TIMEOUT, future)
+        .await
+        .map_err(|_| anyhow!("the call did not finish within {:?}", CALL_TIMEOUT))?
+}
+
+fn probe_request(
+    event_type: EventType,
+    namespace: &str,
+    resource_json: String,
+) -> ReconcileRequest {
+    ReconcileRequest {
+        event_type,
+        name: "conformance-probe".to_string(),
+        namespace: namespace.to_string(),
+        resource_json,
+        old_resource_json: None,
+        reason: ReconcileReason {
+            trigger: ReconcileTrigger::Manual,
+            triggered_by: None,
+            continuation: None,
+            requested_by: None,
+        },
+        budget_ms: None,
+    }
+}
+
+fn print_outcome(name: &str, result: &Result<()>) {
+    match result {
+        Ok(()) => println!("PASS {}", name),
+        Err(e) => println!("FAIL {}: {:#}", name, e),
+    }
+}
@@ -0,0 +1,91 @@
+//! # Debug Bundle Module
+//!
+//! This module implements `parent debug-bundle`, which downloads a debug bundle from the
+//! admin API of a running parent and writes it to a file. The bundle holds the
+//! configuration, the state and recent history of every operator, and the metrics; see
+//! the diagnostics module of the runtime for its layout. Pass `--redact` before attaching
+//! a bundle to a public bug report.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context, Result};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty};
+use hyper::Request;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use k8s_openapi::chrono::Utc;
+
+use crate::config::profile::Profile;
+
+const USAGE: &str = "Usage: parent debug-bundle [--admin-addr <addr>] [--redact] [--output <path>]";
+
+/// Runs the debug-bundle subcommand with the arguments that follow it.
+pub fn run(args: &[String]) -> Result<()> {
+    let mut admin_addr: SocketAddr = Profile::default().defaults().admin_addr;
+    let mut redact = false;
+    let mut output: Option<PathBuf> = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--redact" {
+            redact = true;
+        } else if arg == "--admin-addr" {
+            let value = iter
+                .next()
+                .ok_or_else(|| anyhow!("--admin-addr requires a value"))?;
+            admin_addr = value
+                .parse()
+                .map_err(|e| anyhow!("Invalid --admin-addr '{}': {}", value, e))?;
+        } else if arg == "--output" {
+            output = Some(PathBuf::from(
+                iter.next()
+                    .ok_or_else(|| anyhow!("--output requires a value"))?,
+            ));
+        } else {
+            bail!("Unexpected argument: {}\n{}", arg, USAGE);
+        }
+    }
+    let output = output.unwrap_or_else(|| {
+        PathBuf::from(format!(
+            "debug-bundle-{}.tar.gz",
+            Utc::now().format("%Y%m%d-%H%M%S")
+        ))
+    });
+
+    let tokio_runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let bundle = tokio_runtime.block_on(download(admin_addr, redact))?;
+    std::fs::write(&output, &bundle)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    println!(
+        "Wrote debug bundle of {} bytes to {}",
+        bundle.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+async fn download(admin_addr: SocketAddr, redact: bool) -> Result<Bytes> {
+    let client: Client<_, Empty<Bytes>> = Client::builder(TokioExecutor::new()).build_http();
+    let request = Request::get(format!(
+        "http://{}/debug-bundle?redact={}",
+        admin_addr, redact
+    ))
+    .body(Empty::new())?;
+    let response = client
+        .request(request)
+        .await
+        .with_context(|| format!("Failed to reach the admin API at {}", admin_addr))?;
+    let status = response.status();
+    let body = response.into_body().collect().await?.to_bytes();
+    if !status.is_success() {
+        bail!(
+            "The admin API returned status {}: {}",
+            status,
+            String::from_utf8_lossy(&body)
+        );
+    }
+    Ok(body)
+}
@@ -0,0 +1,522 @@
+//! # Dependencies Module
+//!
+//! This module resolves the dependencies of operator bundles. A bundle declares the
+//! bundles it needs, such as a shared library component, in the `requires` of its
+//! manifest as names with semver requirements, and depends on the version of the
+//! `local:operator` interfaces it was built against through its imports. Before bundles
+//! are installed or upgraded, the resolver checks them against this parent, the installed
+//! operators and the bundles available from a catalog or an export, and computes a plan
+//! that installs or upgrades the missing dependencies first. Plans that would break an
+//! installed operator are rejected.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+
+use crate::bundle::Bundle;
+#[cfg(feature = "oci")]
+use crate::catalog::{CatalogEntry, CatalogIndex};
+use crate::host::api::INTERFACE_VERSION;
+
+/// Prefix of the imports of the interfaces implemented by the host.
+const INTERFACE_PACKAGE: &str = "local:operator/";
+
+/// A dependency on another bundle, written `<name>@<requirement>`, e.g. `shared@^1.2`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Requirement {
+    pub name: String,
+    /// A semver requirement, such as `^1.2` or `>=1.0, <3`.
+    pub version: String,
+}
+
+impl FromStr for Requirement {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, version) = s
+            .split_once('@')
+            .ok_or_else(|| anyhow!("Requirement '{}' must be <name>@<version>", s))?;
+        if name.is_empty() {
+            bail!("Requirement '{}' has no name", s);
+        }
+        VersionReq::parse(version)
+            .with_context(|| format!("Invalid version requirement in '{}'", s))?;
+        Ok(Self {
+            name: name.to_string(),
+            version: version.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.name, self.version)
+    }
+}
+
+impl Requirement {
+    /// Whether a version satisfies the requirement. Missing and non-semver versions never
+    /// do.
+    fn matches(&self, version: Option<&str>) -> Result<bool> {
+        let requirement = VersionReq::parse(&self.version)
+            .with_context(|| format!("Invalid version requirement in '{}'", self))?;
+        Ok(version
+            .and_then(|version| Version::parse(version).ok())
+            .is_some_and(|version| requirement.matches(&version)))
+    }
+}
+
+/// An operator as the resolver sees it: installed, listed in a catalog or about to be
+/// installed.
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    pub version: Option<String>,
+    pub requires: Vec<Requirement>,
+    /// Imports of the component, empty if they are not known.
+    pub imports: Vec<String>,
+}
+
+impl Package {
+    pub fn of_bundle(bundle: &Bundle) -> Result<Self> {
+        Ok(Self {
+            name: bundle.manifest.name.clone(),
+            version: bundle.manifest.version.clone(),
+            requires: bundle.manifest.requires.clone(),
+            imports: bundle
+                .capabilities()?
+                .map(|capabilities| capabilities.imports)
+                .unwrap_or_default(),
+        })
+    }
+
+    #[cfg(feature = "oci")]
+    pub fn of_entry(entry: &CatalogEntry) -> Self {
+        Self {
+            name: entry.name.clone(),
+            version: Some(entry.version.clone()),
+            requires: entry.requires.clone(),
+            imports: entry
+                .capabilities
+                .as_ref()
+                .map(|capabilities| capabilities.imports.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn version(&self) -> &str {
+        self.version.as_deref().unwrap_or("unknown")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Install,
+    Upgrade {
+        from: String,
+    },
+    /// The same version is installed again.
+    Reinstall,
+}
+
+#[derive(Debug)]
+pub struct Step {
+    pub package: Package,
+    pub action: Action,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let package = &self.package;
+        match &self.action {
+            Action::Install => write!(f, "install {} {}", package.name, package.version()),
+            Action::Upgrade { from } => write!(
+                f,
+                "upgrade {} from {} to {}",
+                package.name,
+                from,
+                package.version()
+            ),
+            Action::Reinstall => write!(f, "reinstall {} {}", package.name, package.version()),
+        }
+    }
+}
+
+/// The steps to install or upgrade a set of operators, dependencies first.
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub steps: Vec<Step>,
+}
+
+/// Computes plans against the installed operators and, optionally, the packages that
+/// missing dependencies can be installed from.
+pub struct Resolver {
+    installed: BTreeMap<String, Package>,
+    available: Option<Vec<Package>>,
+}
+
+impl Resolver {
+    pub fn new(installed: Vec<Package>, available: Option<Vec<Package>>) -> Self {
+        Self {
+            installed: installed
+                .into_iter()
+                .map(|package| (package.name.clone(), package))
+                .collect(),
+            available,
+        }
+    }
+
+    /// A resolver that installs missing dependencies from a catalog.
+    #[cfg(feature = "oci")]
+    pub fn with_catalog(installed: Vec<Package>, catalog: &CatalogIndex) -> Self {
+        Self::new(
+            installed,
+            Some(catalog.operators.iter().map(Package::of_entry).collect()),
+        )
+    }
+
+    /// Computes the plan that installs or upgrades the given operators along with the
+    /// dependencies they are missing.
+    pub fn plan(&self, targets: Vec<Package>) -> Result<Plan> {
+        let mut planned = BTreeMap::new();
+        let mut plan = Plan::default();
+        for target in targets {
+            self.add(target, &mut planned, &mut plan, &mut Vec::new())?;
+        }
+        self.check_dependents(&planned)?;
+        Ok(plan)
+    }
+
+    /// Adds a package to the plan after the dependencies it is missing. `path` holds the
+    /// packages that led to this one, to report cycles.
+    fn add(
+        &self,
+        package: Package,
+        planned: &mut BTreeMap<String, Package>,
+        plan: &mut Plan,
+        path: &mut Vec<String>,
+    ) -> Result<()> {
+        if let Some(existing) = planned.get(&package.name) {
+            if existing.version == package.version {
+                return Ok(());
+            }
+            bail!(
+                "Conflict: '{}' is needed at both {} and {}",
+                package.name,
+                existing.version(),
+                package.version()
+            );
+        }
+        if path.contains(&package.name) {
+            bail!(
+                "Dependency cycle: {} -> {}",
+                path.join(" -> "),
+                package.name
+            );
+        }
+        check_interfaces(&package)?;
+
+        path.push(package.name.clone());
+        for requirement in &package.requires {
+            if let Some(dependency) = planned.get(&requirement.name) {
+                if requirement.matches(dependency.version.as_deref())? {
+                    continue;
+                }
+                bail!(
+                    "Conflict: '{}' requires {}, but the plan installs {} {}",
+                    package.name,
+                    requirement,
+                    dependency.name,
+                    dependency.version()
+                );
+            }
+            if let Some(installed) = self.installed.get(&requirement.name)
+                && requirement.matches(installed.version.as_deref())?
+            {
+                continue;
+            }
+            let dependency = self.find(requirement)?.ok_or_else(|| {
+                let installed = match self.installed.get(&requirement.name) {
+                    Some(installed) => {
+                        format!("{} {} is installed", installed.name, installed.version())
+                    }
+                    None => "it is not installed".to_string(),
+                };
+                let available = match self.available {
+                    Some(_) => "no available version matches",
+                    None if cfg!(feature = "oci") => "pass --catalog to install it from a catalog",
+                    None => "install it first",
+                };
+                anyhow!(
+                    "'{}' requires {}, but {}; {}",
+                    package.name,
+                    requirement,
+                    installed,
+                    available
+                )
+            })?;
+            self.add(dependency, planned, plan, path)?;
+        }
+        path.pop();
+
+        let action = match self.installed.get(&package.name) {
+            None => Action::Install,
+            Some(installed) if installed.version == package.version => Action::Reinstall,
+            Some(installed) => Action::Upgrade {
+                from: installed.version().to_string(),
+            },
+        };
+        planned.insert(package.name.clone(), package.clone());
+        plan.steps.push(Step { package, action });
+        Ok(())
+    }
+
+    /// Rejects plans that change an operator that an installed operator outside the plan
+    /// requires at another version.
+    fn check_dependents(&self, planned: &BTreeMap<String, Package>) -> Result<()> {
+        let unchanged = self
+            .installed
+            .values()
+            .filter(|package| !planned.contains_key(&package.name));
+        for package in unchanged {
+            for requirement in &package.requires {
+                if let Some(dependency) = planned.get(&requirement.name)
+                    && !requirement.matches(dependency.version.as_deref())?
+                {
+                    bail!(
+                        "Conflict: {} {} would break '{}', which requires {}",
+                        dependency.name,
+                        dependency.version(),
+                        package.name,
+                        requirement
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the highest available version that satisfies a requirement.
+    fn find(&self, requirement: &Requirement) -> Result<Option<Package>> {
+        let Some(available) = &self.available else {
+            return Ok(None);
+        };
+        let mut best: Option<(Version, &Package)> = None;
+        for package in available {
+            if package.name != requirement.name
+                || !requirement.matches(package.version.as_deref())?
+            {
+                continue;
+            }
+            let version = Version::parse(package.version())?;
+            if best.as_ref().is_none_or(|(highest, _)| version > *highest) {
+                best = Some((version, package));
+            }
+        }
+        Ok(best.map(|(_, package)| package.clone()))
+    }
+}
+
+/// Checks that a component imports versions of the host interfaces this parent
+/// implements. Interfaces are compatible within a semver-compatible range.
+fn check_interfaces(package: &Package) -> Result<()> {
+    check_interface_imports(&package.name, package.imports.iter().map(String::as_str))
+}
+
+/// Checks the imports of a component against the version of the host interfaces, so a
+/// component built against an incompatible version fails with a clear error instead of
+/// failing to link.
+pub fn check_interface_imports<'a>(
+    component: &str,
+    imports: impl IntoIterator<Item = &'a str>,
+) -> Result<()> {
+    let host = Version::parse(INTERFACE_VERSION)?;
+    for import in imports {
+        let Some((_, version)) = import
+            .strip_prefix(INTERFACE_PACKAGE)
+            .and_then(|interface| interface.split_once('@'))
+        else {
+            continue;
+        };
+        let version = Version::parse(version)
+            .with_context(|| format!("Invalid interface version in import {}", import))?;
+        if !VersionReq::parse(&format!("^{}", version))?.matches(&host) {
+            bail!(
+                "'{}' imports {}, but this parent implements local:operator@{}",
+                component,
+                import,
+                INTERFACE_VERSION
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, version: &str, requires: &[&str]) -> Package {
+        Package {
+            name: name.to_string(),
+            version: Some(version.to_string()),
+            requires: requires.iter().map(|r| r.parse().unwrap()).collect(),
+            imports: Vec::new(),
+        }
+    }
+
+    fn steps(plan: &Plan) -> Vec<String> {
+        plan.steps.iter().map(Step::to_string).collect()
+    }
+
+    #[test]
+    fn parses_requirements() {
+        let requirement: Requirement = "shared@>=1.0, <3".parse().unwrap();
+        assert_eq!(requirement.name, "shared");
+        assert_eq!(requirement.version, ">=1.0, <3");
+        assert!("shared".parse::<Requirement>().is_err());
+        assert!("@^1".parse::<Requirement>().is_err());
+        assert!("shared@one".parse::<Requirement>().is_err());
+    }
+
+    #[test]
+    fn installed_dependencies_satisfy_requirements() {
+        let resolver = Resolver::new(vec![package("shared", "1.4.0", &[])], None);
+        let plan = resolver
+            .plan(vec![package("app", "1.0.0", &["shared@^1.2"])])
+            .unwrap();
+        assert_eq!(steps(&plan), ["install app 1.0.0"]);
+    }
+
+    #[test]
+    fn reports_missing_dependencies() {
+        let resolver = Resolver::new(vec![package("shared", "0.9.0", &[])], None);
+        let err = resolver
+            .plan(vec![package("app", "1.0.0", &["shared@^1.2"])])
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("shared 0.9.0 is installed"),
+            "{err}"
+        );
+
+        let resolver = Resolver::new(Vec::new(), Some(vec![package("shared", "2.0.0", &[])]));
+        let err = resolver
+            .plan(vec![package("app", "1.0.0", &["shared@^1.2"])])
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("no available version matches"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn resolves_transitive_dependencies_first() {
+        let available = vec![
+            package("lib", "1.0.0", &["base@^2"]),
+            package("base", "2.0.0", &[]),
+            package("base", "2.1.0", &[]),
+            package("base", "3.0.0", &[]),
+        ];
+        let resolver = Resolver::new(vec![package("base", "1.0.0", &[])], Some(available));
+        let plan = resolver
+            .plan(vec![package("app", "1.0.0", &["lib@^1"])])
+            .unwrap();
+        assert_eq!(
+            steps(&plan),
+            [
+                "upgrade base from 1.0.0 to 2.1.0",
+                "install lib 1.0.0",
+                "install app 1.0.0"
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_conflicting_requirements() {
+        let available = vec![package("shared", "1.0.0", &[])];
+        let resolver = Resolver::new(Vec::new(), Some(available));
+        let err = resolver
+            .plan(vec![
+                package("shared", "2.0.0", &[]),
+                package("app", "1.0.0", &["shared@^1"]),
+            ])
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("but the plan installs shared 2.0.0"),
+            "{err}"
+        );
+
+        let err = resolver
+            .plan(vec![
+                package("shared", "1.0.0", &[]),
+                package("shared", "2.0.0", &[]),
+            ])
+            .unwrap_err();
+        assert!(err.to_string().contains("needed at both"), "{err}");
+
+        let installed = vec![
+            package("shared", "1.0.0", &[]),
+            package("app", "1.0.0", &["shared@^1"]),
+        ];
+        let err = Resolver::new(installed, None)
+            .plan(vec![package("shared", "2.0.0", &[])])
+            .unwrap_err();
+        assert!(err.to_string().contains("would break 'app'"), "{err}");
+    }
+
+    #[test]
+    fn rejects_dependency_cycles() {
+        let available = vec![
+            package("a", "1.0.0", &["b@^1"]),
+            package("b", "1.0.0", &["a@^1"]),
+        ];
+        let err = Resolver::new(Vec::new(), Some(available))
+            .plan(vec![package("a", "1.0.0", &["b@^1"])])
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("Dependency cycle: a -> b -> a"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn orders_pre_releases_below_releases() {
+        let available = vec![
+            package("shared", "1.2.0", &[]),
+            package("shared", "1.3.0-beta.1", &[]),
+            package("shared", "1.3.0-alpha.2", &[]),
+        ];
+        let resolver = Resolver::new(Vec::new(), Some(available.clone()));
+        // Requirements without a pre-release never match one.
+        let plan = resolver
+            .plan(vec![package("app", "1.0.0", &["shared@^1.2"])])
+            .unwrap();
+        assert_eq!(steps(&plan)[0], "install shared 1.2.0");
+        // Requirements on a pre-release pick the highest one of that version.
+        let plan = resolver
+            .plan(vec![package("app", "1.0.0", &["shared@>=1.3.0-alpha.1"])])
+            .unwrap();
+        assert_eq!(steps(&plan)[0], "install shared 1.3.0-beta.1");
+
+        let mut available = available;
+        available.push(package("shared", "1.3.0", &[]));
+        let plan = Resolver::new(Vec::new(), Some(available))
+            .plan(vec![package("app", "1.0.0", &["shared@>=1.3.0-alpha.1"])])
+            .unwrap();
+        assert_eq!(steps(&plan)[0], "install shared 1.3.0");
+    }
+
+    #[test]
+    fn checks_the_interface_version_of_imports() {
+        let current = format!("local:operator/kubernetes@{}", INTERFACE_VERSION);
+        assert!(check_interface_imports("op", [current.as_str(), "wasi:io/poll@0.2.6"]).is_ok());
+        assert!(check_interface_imports("op", ["local:operator/kubernetes@0.2.0"]).is_err());
+        assert!(check_interface_imports("op", ["local:operator/kubernetes@99.0.0"]).is_err());
+        assert!(check_interface_imports("op", ["local:operator/kubernetes@x"]).is_err());
+    }
+}
@@ -5,19 +5,511 @@
 //! the host functions that Wasm modules can call, such as sending requests to the
 //! Kubernetes API and handling asynchronous responses.
 
-use crate::host::state::State;
+// The host functions spell out that their futures are `Send`, as the bindings require.
+#![allow(clippy::manual_async_fn)]
+
 use std::future::Future;
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use kube::api::Patch;
+use kube::discovery::Scope;
+use wasmtime::component::Resource;
+
+use crate::host::api::bindings::local::operator::types::{
+    ErrorReason, FieldError, K8sError, ObjectReference, ResourceInfo, WatchEvent,
+};
+use crate::host::body_stream::BodyStream;
+use crate::host::capabilities;
+use crate::host::decision_log;
+use crate::host::kv;
+use crate::host::message_bus::{Message, MAX_PAYLOAD_BYTES};
+use crate::host::object::K8sObject;
+use crate::host::pager::ListPager;
+use crate::host::requests::{self, PendingRequest};
+use crate::host::shadow::{self, Intent};
+use crate::host::state::State;
+use crate::host::transaction::Transaction;
+use crate::host::watch_stream::{self, WatchStream};
+
+/// Version of the `local:operator` WIT package implemented by this host, as declared in
+/// `wit/world.wit`.
+pub const INTERFACE_VERSION: &str = env!("WIT_PACKAGE_VERSION");
 
 pub mod bindings {
     wasmtime::component::bindgen!({
             async: true,
             path: "wit/",
-            world: "kube-operator"
+            world: "kube-operator",
+            with: {
+                "local:operator/kubernetes/transaction": crate::host::transaction::Transaction,
+                "local:operator/kubernetes/pending-request": crate::host::requests::PendingRequest,
+                "local:operator/kubernetes/list-pager": crate::host::pager::ListPager,
+                "local:operator/kubernetes/body-stream": crate::host::body_stream::BodyStream,
+                "local:operator/kubernetes/watch-stream": crate::host::watch_stream::WatchStream,
+                "local:operator/kubernetes/k8s-object": crate::host::object::K8sObject,
+            },
+            // Calls that hand out a handle without returning a `k8s-error` trap when the
+            // resource table of the guest is full, and the getters of `k8s-object` trap
+            // when called with a handle that is not in it.
+            trappable_imports: [
+                "[constructor]transaction",
+                "[constructor]list-pager",
+                "[method]k8s-object.kind",
+                "[method]k8s-object.name",
+                "[method]k8s-object.namespace",
+                "[method]k8s-object.uid",
+                "[method]k8s-object.resource-version",
+                "[method]k8s-object.generation",
+                "[method]k8s-object.labels",
+                "[method]k8s-object.annotations",
+                "[method]k8s-object.spec-json",
+                "[method]k8s-object.status-json",
+                "reconcile-object",
+                "start-request",
+            ],
     });
 }
 
 impl bindings::local::operator::types::Host for State {}
 
+impl bindings::local::operator::kubernetes::HostTransaction for State {
+    fn new(&mut self) -> impl Future<Output=wasmtime::Result<Resource<Transaction>>> + Send {
+        async move { Ok(self.resources.push(Transaction::default())?) }
+    }
+
+    fn create_resource(
+        &mut self,
+        transaction: Resource<Transaction>,
+        kind: String,
+        namespace: String,
+        resource_json: String,
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
+        async move {
+            self.check_namespace_writable(&namespace)?;
+            self.check_guest_body_size(&resource_json)?;
+            self.check_schema(&kind, &resource_json, false).await?;
+            if self.metadata.shadow {
+                let name = shadow::object_name(&resource_json);
+                let intent = Intent::from_object(&resource_json)?;
+                return self
+                    .shadow_write("create", &kind, &name, &namespace, intent)
+                    .await;
+            }
+            let resource_json = self.label_applied(&kind, &namespace, resource_json)?;
+            let resource_json = self.set_owner_reference(&namespace, resource_json)?;
+            self.resources
+                .get_mut(&transaction)
+                .map_err(|e| e.to_string())?
+                .stage_create(kind, namespace, resource_json);
+            Ok(())
+        }
+    }
+
+    fn update_resource(
+        &mut self,
+        transaction: Resource<Transaction>,
+        kind: String,
+        name: String,
+        namespace: String,
+        resource_json: String,
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
+        async move {
+            self.check_namespace_writable(&namespace)?;
+            self.check_guest_body_size(&resource_json)?;
+            self.check_schema(&kind, &resource_json, true).await?;
+            if self.metadata.shadow {
+                let intent = Intent::from_object(&resource_json)?;
+                return self
+                    .shadow_write("update", &kind, &name, &namespace, intent)
+                    .await;
+            }
+            let resource_json = self.label_applied(&kind, &namespace, resource_json)?;
+            self.resources
+                .get_mut(&transaction)
+                .map_err(|e| e.to_string())?
+                .stage_update(kind, name, namespace, resource_json);
+            Ok(())
+        }
+    }
+
+    fn delete_resource(
+        &mut self,
+        transaction: Resource<Transaction>,
+        kind: String,
+        name: String,
+        namespace: String,
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
+        async move {
+            self.check_namespace_writable(&namespace)?;
+            if self.metadata.shadow {
+                return self
+                    .shadow_write("delete", &kind, &name, &namespace, Intent::Deleted)
+                    .await;
+            }
+            self.resources
+                .get_mut(&transaction)
+                .map_err(|e| e.to_string())?
+                .stage_delete(kind, name, namespace);
+            Ok(())
+        }
+    }
+
+    fn commit(
+        &mut self,
+        transaction: Resource<Transaction>,
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
+        async move {
+            let kubernetes_service = self.kubernetes_service.clone();
+            let transaction = self
+                .resources
+                .get_mut(&transaction)
+                .map_err(|e| e.to_string())?;
+            let staged = transaction.staged_objects();
+            transaction.commit(&kubernetes_service).await?;
+            for object in staged {
+                if object.write == "delete" {
+                    self.record_delete(&object.kind, &object.name, &object.namespace);
+                } else {
+                    self.record_write(
+                        object.write,
+                        &object.kind,
+                        &object.name,
+                        &object.namespace,
+                        &object.resource_json,
+                    );
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn drop(
+        &mut self,
+        transaction: Resource<Transaction>,
+    ) -> impl Future<Output=wasmtime::Result<()>> + Send {
+        async move {
+            self.resources.delete(transaction)?;
+            Ok(())
+        }
+    }
+}
+
+impl bindings::local::operator::kubernetes::HostPendingRequest for State {
+    fn get(
+        &mut self,
+        request: Resource<PendingRequest>,
+    ) -> impl Future<Output=Result<String, K8sError>> + Send {
+        async move {
+            self.resources
+                .get_mut(&request)
+                .map_err(|e| e.to_string())?
+                .wait()
+                .await
+        }
+    }
+
+    fn drop(
+        &mut self,
+        request: Resource<PendingRequest>,
+    ) -> impl Future<Output=wasmtime::Result<()>> + Send {
+        async move {
+            // Dropping the handle detaches the request; it still runs to completion.
+            self.resources.delete(request)?;
+            Ok(())
+        }
+    }
+}
+
+impl bindings::local::operator::kubernetes::HostListPager for State {
+    fn new(
+        &mut self,
+        kind: String,
+        namespace: String,
+        label_selector: String,
+        field_selector: String,
+    ) -> impl Future<Output=wasmtime::Result<Resource<ListPager>>> + Send {
+        async move {
+            Ok(self.resources.push(ListPager::new(
+                kind,
+                namespace,
+                label_selector,
+                field_selector,
+            ))?)
+        }
+    }
+
+    fn next_page(
+        &mut self,
+        pager: Resource<ListPager>,
+    ) -> impl Future<Output=Result<Option<Vec<String>>, K8sError>> + Send {
+        async move {
+            let kind = &self.resources.get(&pager).map_err(|e| e.to_string())?.kind;
+            if self.is_secret(kind) {
+                return Err(K8sError::forbidden(format!(
+                    "Secrets cannot be listed by operator '{}'; read granted keys with get-secret",
+                    self.metadata.name
+                )));
+            }
+            let kubernetes_service = self.kubernetes_service.clone();
+            self.resources
+                .get_mut(&pager)
+                .map_err(|e| e.to_string())?
+                .next_page(&kubernetes_service)
+                .await
+                .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn drop(
+        &mut self,
+        pager: Resource<ListPager>,
+    ) -> impl Future<Output=wasmtime::Result<()>> + Send {
+        async move {
+            self.resources.delete(pager)?;
+            Ok(())
+        }
+    }
+}
+
+impl bindings::local::operator::kubernetes::HostBodyStream for State {
+    fn read(
+        &mut self,
+        stream: Resource<BodyStream>,
+        max_bytes: u32,
+    ) -> impl Future<Output=Result<Option<Vec<u8>>, K8sError>> + Send {
+        async move {
+            if max_bytes == 0 {
+                return Err(K8sError::invalid("Cannot read chunks of 0 bytes"));
+            }
+            self.resources
+                .get_mut(&stream)
+                .map_err(|e| e.to_string())?
+                .read(max_bytes)
+                .await
+                .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn drop(
+        &mut self,
+        stream: Resource<BodyStream>,
+    ) -> impl Future<Output=wasmtime::Result<()>> + Send {
+        async move {
+            self.resources.delete(stream)?;
+            Ok(())
+        }
+    }
+}
+
+impl bindings::local::operator::kubernetes::HostWatchStream for State {
+    fn next_event(
+        &mut self,
+        stream: Resource<WatchStream>,
+    ) -> impl Future<Output=Result<Option<WatchEvent>, K8sError>> + Send {
+        async move {
+            let wait = match self.budget.remaining() {
+                Some(remaining) => remaining.min(watch_stream::MAX_WAIT),
+                None => watch_stream::MAX_WAIT,
+            };
+            let deadline = Instant::now() + wait;
+            let (config, metadata) = (&self.config, &self.metadata);
+            self.resources
+                .get_mut(&stream)
+                .map_err(|e| e.to_string())?
+                .next_event(deadline, |namespace| {
+                    config.is_namespace_excluded(namespace, metadata)
+                })
+                .await
+                .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn drop(
+        &mut self,
+        stream: Resource<WatchStream>,
+    ) -> impl Future<Output=wasmtime::Result<()>> + Send {
+        async move {
+            self.resources.delete(stream)?;
+            Ok(())
+        }
+    }
+}
+
+impl bindings::local::operator::kubernetes::HostK8sObject for State {
+    fn kind(
+        &mut self,
+        object: Resource<K8sObject>,
+    ) -> impl Future<Output=wasmtime::Result<String>> + Send {
+        async move {
+            Ok(self
+                .object(&object)?
+                .types
+                .as_ref()
+                .map(|types| types.kind.clone())
+                .unwrap_or_default())
+        }
+    }
+
+    fn name(
+        &mut self,
+        object: Resource<K8sObject>,
+    ) -> impl Future<Output=wasmtime::Result<String>> + Send {
+        async move {
+            Ok(self
+                .object(&object)?
+                .metadata
+                .name
+                .clone()
+                .unwrap_or_default())
+        }
+    }
+
+    fn namespace(
+        &mut self,
+        object: Resource<K8sObject>,
+    ) -> impl Future<Output=wasmtime::Result<String>> + Send {
+        async move {
+            Ok(self
+                .object(&object)?
+                .metadata
+                .namespace
+                .clone()
+                .unwrap_or_default())
+        }
+    }
+
+    fn uid(
+        &mut self,
+        object: Resource<K8sObject>,
+    ) -> impl Future<Output=wasmtime::Result<String>> + Send {
+        async move {
+            Ok(self
+                .object(&object)?
+                .metadata
+                .uid
+                .clone()
+                .unwrap_or_default())
+        }
+    }
+
+    fn resource_version(
+        &mut self,
+        object: Resource<K8sObject>,
+    ) -> impl Future<Output=wasmtime::Result<String>> + Send {
+        async move {
+            Ok(self
+                .object(&object)?
+                .metadata
+                .resource_version
+                .clone()
+                .unwrap_or_default())
+        }
+    }
+
+    fn generation(
+        &mut self,
+        object: Resource<K8sObject>,
+    ) -> impl Future<Output=wasmtime::Result<Option<i64>>> + Send {
+        async move { Ok(self.object(&object)?.metadata.generation) }
+    }
+
+    fn labels(
+        &mut self,
+        object: Resource<K8sObject>,
+    ) -> impl Future<Output=wasmtime::Result<Vec<(String, String)>>> + Send {
+        async move {
+            Ok(self
+                .object(&object)?
+                .metadata
+                .labels
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .collect())
+        }
+    }
+
+    fn annotations(
+        &mut self,
+        object: Resource<K8sObject>,
+    ) -> impl Future<Output=wasmtime::Result<Vec<(String, String)>>> + Send {
+        async move {
+            Ok(self
+                .object(&object)?
+                .metadata
+                .annotations
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .collect())
+        }
+    }
+
+    fn spec_json(
+        &mut self,
+        object: Resource<K8sObject>,
+    ) -> impl Future<Output=wasmtime::Result<Option<String>>> + Send {
+        async move {
+            Ok(self
+                .object(&object)?
+                .data
+                .get("spec")
+                .map(|spec| spec.to_string()))
+        }
+    }
+
+    fn status_json(
+        &mut self,
+        object: Resource<K8sObject>,
+    ) -> impl Future<Output=wasmtime::Result<Option<String>>> + Send {
+        async move {
+            Ok(self
+                .object(&object)?
+                .data
+                .get("status")
+                .map(|status| status.to_string()))
+        }
+    }
+
+    fn field_json(
+        &mut self,
+        object: Resource<K8sObject>,
+        pointer: String,
+    ) -> impl Future<Output=Option<String>> + Send {
+        async move { self.resources.get(&object).ok()?.field_json(&pointer) }
+    }
+
+    fn to_json(
+        &mut self,
+        object: Resource<K8sObject>,
+    ) -> impl Future<Output=Result<String, K8sError>> + Send {
+        async move {
+            let object = self.object(&object).map_err(|e| e.to_string())?;
+            let json = serde_json::to_string(object).map_err(|e| K8sError::from(e.to_string()))?;
+            let limit = self.config.size_limits.max_resource_bytes;
+            if json.len() > limit {
+                return Err(K8sError::invalid(format!(
+                    "resource JSON of {} bytes exceeds the limit of {} bytes",
+                    json.len(),
+                    limit
+                )));
+            }
+            Ok(json)
+        }
+    }
+
+    fn drop(
+        &mut self,
+        object: Resource<K8sObject>,
+    ) -> impl Future<Output=wasmtime::Result<()>> + Send {
+        async move {
+            self.resources.delete(object)?;
+            Ok(())
+        }
+    }
+}
+
 impl bindings::local::operator::kubernetes::Host for State {
     fn log(
         &mut self,
@@ -35,17 +527,251 @@ impl bindings::local::operator::kubernetes::Host for State {
         }
     }
 
+    fn runtime_info(
+        &mut self,
+    ) -> impl Future<Output=bindings::local::operator::types::RuntimeMetadata> + Send {
+        async move {
+            let cluster_info = self.kubernetes_service.cluster_info();
+            bindings::local::operator::types::RuntimeMetadata {
+                server_version: cluster_info.server_version.clone(),
+                namespace: cluster_info.namespace.clone(),
+                service_account: cluster_info.service_account.clone(),
+                operator_id: self.metadata.name.clone(),
+                interface_version: INTERFACE_VERSION.to_string(),
+            }
+        }
+    }
+
+    fn self_info(
+        &mut self,
+    ) -> impl Future<Output=bindings::local::operator::types::SelfMetadata> + Send {
+        async move { self.introspection.lock().unwrap().to_self_metadata() }
+    }
+
+    fn capabilities(&mut self) -> impl Future<Output=Vec<String>> + Send {
+        async move { capabilities::capabilities(&self.config, &self.metadata) }
+    }
+
+    fn reconcile_object(
+        &mut self,
+    ) -> impl Future<Output=wasmtime::Result<Option<Resource<K8sObject>>>> + Send {
+        async move {
+            let Some(object) = self.reconcile_object.clone() else {
+                return Ok(None);
+            };
+            Ok(Some(self.resources.push(K8sObject::new(object))?))
+        }
+    }
+
+    fn yield_checkpoint(
+        &mut self,
+    ) -> impl Future<Output=bindings::local::operator::types::BudgetStatus> + Send {
+        async move {
+            tokio::task::yield_now().await;
+            self.budget.status()
+        }
+    }
+
+    fn discover(
+        &mut self,
+        kind: String,
+    ) -> impl Future<Output=Result<Option<ResourceInfo>, K8sError>> + Send {
+        async move {
+            let found = self
+                .kubernetes_service
+                .discover(&kind)
+                .await
+                .map_err(K8sError::from_anyhow)?;
+            Ok(found.map(|(resource, capabilities)| ResourceInfo {
+                group: resource.group,
+                version: resource.version,
+                kind: resource.kind,
+                plural: resource.plural,
+                namespaced: capabilities.scope == Scope::Namespaced,
+                verbs: capabilities.operations,
+            }))
+        }
+    }
+
     fn get_resource(
         &mut self,
         kind: String,
         name: String,
         namespace: String,
-    ) -> impl Future<Output=Result<String, String>> + Send {
+    ) -> impl Future<Output=Result<String, K8sError>> + Send {
+        async move {
+            self.check_readable(&kind, &name, &namespace)?;
+            // Report the cause instead of only the outermost context, so guests doing a
+            // read-modify-write can tell a missing object from a failed request.
+            self.kubernetes_service
+                .find_resource(&kind, &name, &namespace)
+                .await
+                .map_err(K8sError::from_anyhow)?
+                .ok_or_else(|| {
+                    K8sError::not_found(format!("{} '{}/{}' not found", kind, namespace, name))
+                })
+        }
+    }
+
+    fn get_pod_logs(
+        &mut self,
+        namespace: String,
+        pod: String,
+        container: String,
+        tail_lines: Option<u32>,
+    ) -> impl Future<Output=Result<String, K8sError>> + Send {
+        async move {
+            self.check_namespace_readable(&namespace)?;
+            let container = Some(container.as_str()).filter(|container| !container.is_empty());
+            self.kubernetes_service
+                .pod_logs(
+                    &namespace,
+                    &pod,
+                    container,
+                    tail_lines,
+                    self.config.size_limits.max_resource_bytes,
+                )
+                .await
+                .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn get_secret(
+        &mut self,
+        name: String,
+        namespace: String,
+        key: String,
+    ) -> impl Future<Output=Result<String, K8sError>> + Send {
+        async move {
+            self.check_read_granted(
+                "Secret",
+                &self.metadata.readable_secrets,
+                &name,
+                &namespace,
+                Some(&key),
+            )?;
+            let value = self
+                .kubernetes_service
+                .get_secret_value(&name, &namespace, &key)
+                .await
+                .map_err(K8sError::from_anyhow)?
+                .ok_or_else(|| {
+                    K8sError::not_found(format!(
+                        "Key '{}' of Secret '{}/{}' not found",
+                        key, namespace, name
+                    ))
+                })?;
+            String::from_utf8(value).map_err(|_| {
+                K8sError::invalid(format!(
+                    "Key '{}' of Secret '{}/{}' is not valid UTF-8",
+                    key, namespace, name
+                ))
+            })
+        }
+    }
+
+    fn get_config_map(
+        &mut self,
+        name: String,
+        namespace: String,
+        key: String,
+    ) -> impl Future<Output=Result<String, K8sError>> + Send {
+        async move {
+            self.check_read_granted(
+                "ConfigMap",
+                &self.metadata.readable_config_maps,
+                &name,
+                &namespace,
+                Some(&key),
+            )?;
+            self.kubernetes_service
+                .get_config_map_value(&name, &namespace, &key)
+                .await
+                .map_err(K8sError::from_anyhow)?
+                .ok_or_else(|| {
+                    K8sError::not_found(format!(
+                        "Key '{}' of ConfigMap '{}/{}' not found",
+                        key, namespace, name
+                    ))
+                })
+        }
+    }
+
+    fn list_resources(
+        &mut self,
+        kind: String,
+        namespace: String,
+        label_selector: String,
+        field_selector: String,
+    ) -> impl Future<Output=Result<Vec<String>, K8sError>> + Send {
         async move {
+            if self.is_secret(&kind) {
+                return Err(K8sError::forbidden(format!(
+                    "Secrets cannot be listed by operator '{}'; read granted keys with get-secret",
+                    self.metadata.name
+                )));
+            }
             self.kubernetes_service
-                .get_resource(&kind, &name, &namespace)
+                .list_resources(&kind, &namespace, &label_selector, &field_selector)
                 .await
-                .map_err(|e| e.to_string())
+                .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn stream_list(
+        &mut self,
+        kind: String,
+        namespace: String,
+        label_selector: String,
+        field_selector: String,
+    ) -> impl Future<Output=Result<Resource<BodyStream>, K8sError>> + Send {
+        async move {
+            if self.is_secret(&kind) {
+                return Err(K8sError::forbidden(format!(
+                    "Secrets cannot be listed by operator '{}'; read granted keys with get-secret",
+                    self.metadata.name
+                )));
+            }
+            let body = self
+                .kubernetes_service
+                .list_body(&kind, &namespace, &label_selector, &field_selector)
+                .await
+                .map_err(K8sError::from_anyhow)?;
+            self.resources.push(BodyStream::new(body)).map_err(|e| {
+                K8sError::new(
+                    0,
+                    ErrorReason::TooManyRequests,
+                    format!("Cannot hand out another body stream: {}", e),
+                )
+            })
+        }
+    }
+
+    fn watch(
+        &mut self,
+        kind: String,
+        namespace: String,
+        label_selector: String,
+    ) -> impl Future<Output=Result<Resource<WatchStream>, K8sError>> + Send {
+        async move {
+            if self.is_secret(&kind) {
+                return Err(K8sError::forbidden(format!(
+                    "Secrets cannot be watched by operator '{}'; read granted keys with get-secret",
+                    self.metadata.name
+                )));
+            }
+            self.check_namespace_readable(&namespace)?;
+            let stream =
+                WatchStream::new(&self.kubernetes_service, kind, &namespace, &label_selector)
+                    .await
+                    .map_err(K8sError::from_anyhow)?;
+            self.resources.push(stream).map_err(|e| {
+                K8sError::new(
+                    0,
+                    ErrorReason::TooManyRequests,
+                    format!("Cannot hand out another watch stream: {}", e),
+                )
+            })
         }
     }
 
@@ -54,12 +780,104 @@ impl bindings::local::operator::kubernetes::Host for State {
         kind: String,
         namespace: String,
         resource_json: String,
-    ) -> impl Future<Output=Result<(), String>> + Send {
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
         async move {
-            self.kubernetes_service
+            self.check_namespace_writable(&namespace)?;
+            self.check_guest_body_size(&resource_json)?;
+            self.check_schema(&kind, &resource_json, false).await?;
+            if self.metadata.shadow {
+                let name = shadow::object_name(&resource_json);
+                let intent = Intent::from_object(&resource_json)?;
+                return self
+                    .shadow_write("create", &kind, &name, &namespace, intent)
+                    .await;
+            }
+            let resource_json = self.label_applied(&kind, &namespace, resource_json)?;
+            let resource_json = self.set_owner_reference(&namespace, resource_json)?;
+            let name = self
+                .kubernetes_service
                 .create_resource(&kind, &namespace, &resource_json)
                 .await
-                .map_err(|e| e.to_string())
+                .map_err(K8sError::from_anyhow)?;
+            self.record_write("create", &kind, &name, &namespace, &resource_json);
+            Ok(())
+        }
+    }
+
+    fn diff(
+        &mut self,
+        current_json: String,
+        desired_json: String,
+    ) -> impl Future<Output=Result<String, K8sError>> + Send {
+        async move {
+            self.check_guest_body_size(&current_json)?;
+            self.check_guest_body_size(&desired_json)?;
+            let current: serde_json::Value = serde_json::from_str(&current_json)
+                .map_err(|e| K8sError::invalid(format!("Invalid current JSON: {}", e)))?;
+            let desired: serde_json::Value = serde_json::from_str(&desired_json)
+                .map_err(|e| K8sError::invalid(format!("Invalid desired JSON: {}", e)))?;
+            serde_json::to_string(&json_patch::diff(&current, &desired))
+                .map_err(|e| K8sError::from(e.to_string()))
+        }
+    }
+
+    fn validate_resource(
+        &mut self,
+        kind: String,
+        resource_json: String,
+    ) -> impl Future<Output=Result<Vec<FieldError>, K8sError>> + Send {
+        async move {
+            self.check_guest_body_size(&resource_json)?;
+            let object: serde_json::Value = serde_json::from_str(&resource_json)
+                .map_err(|e| K8sError::invalid(format!("Invalid resource JSON: {}", e)))?;
+            self.kubernetes_service
+                .validate_object(&kind, &object, false)
+                .await
+                .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn list_namespaces(
+        &mut self,
+        label_selector: String,
+    ) -> impl Future<Output=Result<Vec<String>, K8sError>> + Send {
+        async move {
+            let namespaces = self
+                .kubernetes_service
+                .list_namespaces(&label_selector)
+                .await
+                .map_err(K8sError::from_anyhow)?;
+            Ok(namespaces
+                .into_iter()
+                .filter(|namespace| !self.config.is_namespace_excluded(namespace, &self.metadata))
+                .collect())
+        }
+    }
+
+    fn create_namespace(
+        &mut self,
+        name: String,
+        labels: Vec<(String, String)>,
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
+        async move {
+            // A namespace the operator may not write to is not created by it either.
+            self.check_namespace_writable(&name)?;
+            let labels: serde_json::Map<String, serde_json::Value> = labels
+                .into_iter()
+                .map(|(key, value)| (key, value.into()))
+                .collect();
+            let namespace = serde_json::json!({
+                "apiVersion": "v1",
+                "kind": "Namespace",
+                "metadata": { "name": name, "labels": labels },
+            });
+            bindings::local::operator::kubernetes::Host::create_resource(
+                self,
+                "Namespace".to_string(),
+                String::new(),
+                namespace.to_string(),
+            )
+            .await
         }
     }
 
@@ -69,12 +887,98 @@ impl bindings::local::operator::kubernetes::Host for State {
         name: String,
         namespace: String,
         resource_json: String,
-    ) -> impl Future<Output=Result<(), String>> + Send {
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
         async move {
+            self.check_namespace_writable(&namespace)?;
+            self.check_guest_body_size(&resource_json)?;
+            self.check_schema(&kind, &resource_json, true).await?;
+            if self.metadata.shadow {
+                let intent = Intent::from_object(&resource_json)?;
+                return self
+                    .shadow_write("update", &kind, &name, &namespace, intent)
+                    .await;
+            }
+            let resource_json = self.label_applied(&kind, &namespace, resource_json)?;
             self.kubernetes_service
                 .update_resource(&kind, &name, &namespace, &resource_json)
                 .await
-                .map_err(|e| e.to_string())
+                .map_err(K8sError::from_anyhow)?;
+            self.record_write("update", &kind, &name, &namespace, &resource_json);
+            Ok(())
+        }
+    }
+
+    fn patch_resource(
+        &mut self,
+        kind: String,
+        name: String,
+        namespace: String,
+        patch_json: String,
+        patch_type: bindings::local::operator::types::PatchType,
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
+        async move {
+            use bindings::local::operator::types::PatchType;
+
+            self.check_namespace_writable(&namespace)?;
+            self.check_guest_body_size(&patch_json)?;
+            if self.metadata.shadow {
+                let intent = match patch_type {
+                    PatchType::JsonPatch => Intent::from_json_patch(&patch_json)?,
+                    _ => Intent::from_object(&patch_json)?,
+                };
+                return self
+                    .shadow_write("patch", &kind, &name, &namespace, intent)
+                    .await;
+            }
+            let patch_json = match patch_type {
+                PatchType::Apply => self.label_applied(&kind, &namespace, patch_json)?,
+                _ => patch_json,
+            };
+            let patch: serde_json::Value = serde_json::from_str(&patch_json)
+                .map_err(|e| K8sError::invalid(format!("Invalid patch JSON: {}", e)))?;
+            let patch = match patch_type {
+                PatchType::JsonPatch => Patch::Json(
+                    serde_json::from_value(patch)
+                        .map_err(|e| K8sError::invalid(format!("Invalid JSON patch: {}", e)))?,
+                ),
+                PatchType::MergePatch => Patch::Merge(patch),
+                PatchType::StrategicMerge => Patch::Strategic(patch),
+                PatchType::Apply => Patch::Apply(patch),
+            };
+            self.kubernetes_service
+                .patch_resource(&kind, &name, &namespace, &patch)
+                .await
+                .map_err(K8sError::from_anyhow)?;
+            self.record_write("patch", &kind, &name, &namespace, &patch_json);
+            Ok(())
+        }
+    }
+
+    fn update_status(
+        &mut self,
+        kind: String,
+        name: String,
+        namespace: String,
+        status_json: String,
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
+        async move {
+            self.check_namespace_writable(&namespace)?;
+            self.check_guest_body_size(&status_json)?;
+            if self.metadata.shadow {
+                let status: serde_json::Value = serde_json::from_str(&status_json)
+                    .map_err(|e| K8sError::invalid(format!("Invalid status JSON: {}", e)))?;
+                let intent =
+                    Intent::from_object(&serde_json::json!({ "status": status }).to_string())?;
+                return self
+                    .shadow_write("update-status", &kind, &name, &namespace, intent)
+                    .await;
+            }
+            self.kubernetes_service
+                .update_status(&kind, &name, &namespace, &status_json)
+                .await
+                .map_err(K8sError::from_anyhow)?;
+            self.record_write("update-status", &kind, &name, &namespace, "");
+            Ok(())
         }
     }
 
@@ -83,12 +987,530 @@ impl bindings::local::operator::kubernetes::Host for State {
         kind: String,
         name: String,
         namespace: String,
-    ) -> impl Future<Output=Result<(), String>> + Send {
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
         async move {
+            self.check_namespace_writable(&namespace)?;
+            if self.metadata.shadow {
+                return self
+                    .shadow_write("delete", &kind, &name, &namespace, Intent::Deleted)
+                    .await;
+            }
             self.kubernetes_service
                 .delete_resource(&kind, &name, &namespace)
                 .await
-                .map_err(|e| e.to_string())
+                .map_err(K8sError::from_anyhow)?;
+            self.record_delete(&kind, &name, &namespace);
+            Ok(())
+        }
+    }
+
+    fn delete_collection(
+        &mut self,
+        kind: String,
+        namespace: String,
+        label_selector: String,
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
+        async move {
+            if label_selector.trim().is_empty() {
+                return Err(K8sError::invalid(
+                    "delete-collection requires a label selector",
+                ));
+            }
+            self.check_namespace_writable(&namespace)?;
+            if self.metadata.shadow {
+                return self
+                    .shadow_write(
+                        "delete-collection",
+                        &kind,
+                        "",
+                        &namespace,
+                        Intent::Unchecked,
+                    )
+                    .await;
+            }
+            self.kubernetes_service
+                .delete_collection(&kind, &namespace, &label_selector)
+                .await
+                .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn prune(
+        &mut self,
+        kind: String,
+        namespace: String,
+        selector: String,
+        keep: Vec<String>,
+    ) -> impl Future<Output=Result<Vec<String>, K8sError>> + Send {
+        async move {
+            self.check_namespace_writable(&namespace)?;
+            let selector = self.applied_set_selector(&selector)?;
+            if self.metadata.shadow {
+                return self
+                    .shadow_write("prune", &kind, "", &namespace, Intent::Unchecked)
+                    .await
+                    .map(|()| Vec::new());
+            }
+            let pruned = self
+                .kubernetes_service
+                .prune(&kind, &namespace, &selector, &keep)
+                .await
+                .map_err(K8sError::from_anyhow)?;
+            for name in &pruned {
+                self.record_delete(&kind, name, &namespace);
+            }
+            Ok(pruned)
+        }
+    }
+
+    fn record_decision(
+        &mut self,
+        kind: String,
+        name: String,
+        namespace: String,
+        summary: String,
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
+        async move {
+            let Some(settings) = &self.metadata.decision_log else {
+                return Err(format!(
+                    "The decision log is not enabled for operator '{}'",
+                    self.metadata.name
+                )
+                .into());
+            };
+            self.check_namespace_writable(&namespace)?;
+            if self.metadata.shadow {
+                return self
+                    .shadow_write(
+                        "record-decision",
+                        &kind,
+                        &name,
+                        &namespace,
+                        Intent::Unchecked,
+                    )
+                    .await;
+            }
+            decision_log::record(
+                &self.kubernetes_service,
+                settings,
+                &kind,
+                &name,
+                &namespace,
+                &summary,
+            )
+            .await
+            .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn acquire_lease(
+        &mut self,
+        name: String,
+        namespace: String,
+        ttl_seconds: u32,
+    ) -> impl Future<Output=Result<bool, K8sError>> + Send {
+        async move {
+            self.check_namespace_writable(&namespace)?;
+            let ttl_seconds = i32::try_from(ttl_seconds).map_err(|_| {
+                K8sError::invalid(format!(
+                    "Lease duration of {} seconds is too long",
+                    ttl_seconds
+                ))
+            })?;
+            self.leases
+                .acquire(&self.metadata.name, &name, &namespace, ttl_seconds)
+                .await
+                .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn renew_lease(
+        &mut self,
+        name: String,
+        namespace: String,
+    ) -> impl Future<Output=Result<bool, K8sError>> + Send {
+        async move {
+            self.leases
+                .renew(&self.metadata.name, &name, &namespace)
+                .await
+                .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn release_lease(
+        &mut self,
+        name: String,
+        namespace: String,
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
+        async move {
+            self.leases
+                .release(&self.metadata.name, &name, &namespace)
+                .await
+                .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn kv_get(
+        &mut self,
+        key: String,
+    ) -> impl Future<Output=Result<Option<String>, K8sError>> + Send {
+        async move {
+            kv::check_key(&key)?;
+            kv::get(&self.kubernetes_service, &self.metadata.name, &key)
+                .await
+                .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn kv_set(
+        &mut self,
+        key: String,
+        value: String,
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
+        async move {
+            kv::check_key(&key)?;
+            self.check_kv_writable()?;
+            self.check_guest_body_size(&value)?;
+            kv::set(
+                &self.kubernetes_service,
+                &self.metadata.name,
+                &key,
+                Some(&value),
+            )
+            .await
+            .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn kv_delete(&mut self, key: String) -> impl Future<Output=Result<(), K8sError>> + Send {
+        async move {
+            kv::check_key(&key)?;
+            self.check_kv_writable()?;
+            kv::set(&self.kubernetes_service, &self.metadata.name, &key, None)
+                .await
+                .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn request_unload(&mut self) -> impl Future<Output=()> + Send {
+        async move {
+            // The guest is still running, so the runtime serves the request after the call.
+            self.lifecycle_requests.unload = true;
+        }
+    }
+
+    fn checkpoint(&mut self) -> impl Future<Output=()> + Send {
+        async move {
+            self.lifecycle_requests.checkpoint = true;
+        }
+    }
+
+    fn lock(&mut self, name: String, timeout_ms: u32) -> impl Future<Output=bool> + Send {
+        async move {
+            self.locks
+                .lock(
+                    &self.lock_holder,
+                    &name,
+                    Duration::from_millis(timeout_ms.into()),
+                )
+                .await
+        }
+    }
+
+    fn unlock(&mut self, name: String) -> impl Future<Output=bool> + Send {
+        async move { self.locks.unlock(&self.lock_holder, &name) }
+    }
+
+    fn schedule(
+        &mut self,
+        delay_ms: u64,
+        token: String,
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
+        async move {
+            if !self.handlers.has_on_timer() {
+                return Err(K8sError::invalid(format!(
+                    "Operator '{}' does not export on-timer; include the timer-handler world",
+                    self.metadata.name
+                )));
+            }
+            self.timers
+                .schedule(&self.metadata.name, token, Duration::from_millis(delay_ms))
+                .map_err(|e| K8sError::new(0, ErrorReason::TooManyRequests, e))
+        }
+    }
+
+    fn publish(
+        &mut self,
+        topic: String,
+        payload: String,
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
+        async move {
+            if payload.len() > MAX_PAYLOAD_BYTES {
+                return Err(K8sError::invalid(format!(
+                    "The payload of {} bytes exceeds the limit of {} bytes",
+                    payload.len(),
+                    MAX_PAYLOAD_BYTES
+                )));
+            }
+            self.message_bus
+                .publish(Message {
+                    publisher: self.metadata.name.clone(),
+                    topic,
+                    payload,
+                })
+                .map_err(|e| K8sError::new(0, ErrorReason::TooManyRequests, e))
+        }
+    }
+
+    fn enqueue(
+        &mut self,
+        kind: String,
+        name: String,
+        namespace: String,
+        after_ms: u64,
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
+        async move {
+            // The object is passed to the reconcile, so it must be readable by the operator.
+            self.check_readable(&kind, &name, &namespace)?;
+            let object = ObjectReference {
+                kind,
+                name,
+                namespace,
+            };
+            self.reconcile_queue
+                .enqueue(
+                    &self.metadata.name,
+                    object,
+                    self.reconciling_reference(),
+                    None,
+                    Duration::from_millis(after_ms),
+                )
+                .map_err(|e| K8sError::new(0, ErrorReason::TooManyRequests, e))
+        }
+    }
+
+    fn trigger_reconcile(
+        &mut self,
+        operator: String,
+        kind: String,
+        name: String,
+        namespace: String,
+    ) -> impl Future<Output=Result<(), K8sError>> + Send {
+        async move {
+            if !self.metadata.may_trigger.contains(&operator) {
+                return Err(K8sError::forbidden(format!(
+                    "Operator '{}' may not trigger reconciles of operator '{}'; add it to may-trigger",
+                    self.metadata.name, operator
+                )));
+            }
+            // The grants of the other operator are not known here, so do not pass Secrets.
+            if self.is_secret(&kind) {
+                return Err(K8sError::forbidden(format!(
+                    "Operator '{}' may not trigger reconciles of Secrets",
+                    self.metadata.name
+                )));
+            }
+            let object = ObjectReference {
+                kind,
+                name,
+                namespace,
+            };
+            self.reconcile_queue
+                .enqueue(
+                    &operator,
+                    object,
+                    self.reconciling_reference(),
+                    Some(self.metadata.name.clone()),
+                    Duration::ZERO,
+                )
+                .map_err(|e| K8sError::new(0, ErrorReason::TooManyRequests, e))
+        }
+    }
+
+    fn list_nodes(
+        &mut self,
+    ) -> impl Future<Output=Result<Vec<bindings::local::operator::types::NodeInfo>, K8sError>> + Send
+    {
+        async move {
+            let topology = self
+                .kubernetes_service
+                .topology()
+                .await
+                .map_err(K8sError::from_anyhow)?;
+            Ok(topology.list_nodes())
+        }
+    }
+
+    fn get_node_capacity(
+        &mut self,
+        name: String,
+    ) -> impl Future<Output=Result<bindings::local::operator::types::NodeCapacity, K8sError>> + Send
+    {
+        async move {
+            let topology = self
+                .kubernetes_service
+                .topology()
+                .await
+                .map_err(K8sError::from_anyhow)?;
+            topology
+                .node_capacity(&name)
+                .ok_or_else(|| K8sError::not_found(format!("Node '{}' not found", name)))
+        }
+    }
+
+    fn list_pods_on_node(
+        &mut self,
+        node: String,
+    ) -> impl Future<Output=Result<Vec<bindings::local::operator::types::PodInfo>, K8sError>> + Send
+    {
+        async move {
+            let topology = self
+                .kubernetes_service
+                .topology()
+                .await
+                .map_err(K8sError::from_anyhow)?;
+            Ok(topology.list_pods_on_node(&node))
+        }
+    }
+
+    fn get_pod_metrics(
+        &mut self,
+        namespace: String,
+        selector: String,
+    ) -> impl Future<Output=Result<Vec<bindings::local::operator::types::PodUsage>, K8sError>> + Send
+    {
+        async move {
+            self.kubernetes_service
+                .pod_metrics(&namespace, &selector)
+                .await
+                .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn get_node_metrics(
+        &mut self,
+    ) -> impl Future<Output=Result<Vec<bindings::local::operator::types::NodeUsage>, K8sError>> + Send
+    {
+        async move {
+            self.kubernetes_service
+                .node_metrics()
+                .await
+                .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn get_custom_metric(
+        &mut self,
+        kind: String,
+        name: String,
+        namespace: String,
+        metric: String,
+    ) -> impl Future<Output=Result<Vec<bindings::local::operator::types::MetricValue>, K8sError>> + Send
+    {
+        async move {
+            self.kubernetes_service
+                .custom_metric(&kind, &name, &namespace, &metric)
+                .await
+                .map_err(K8sError::from_anyhow)
+        }
+    }
+
+    fn start_request(
+        &mut self,
+        request: bindings::local::operator::types::ApiRequest,
+        options: bindings::local::operator::types::RequestOptions,
+    ) -> impl Future<Output=wasmtime::Result<Resource<PendingRequest>>> + Send {
+        async move {
+            let request = match self.check_request(request).await {
+                Ok(request) if self.metadata.shadow => match self.shadow_request(&request).await {
+                    Some(result) => Err(result),
+                    None => Ok(request),
+                },
+                Ok(request) => Ok(request),
+                Err(error) => Err(Err(error)),
+            };
+            let pending = match request {
+                Ok(request) => {
+                    let write = self.request_write(&request);
+                    PendingRequest::start(self.kubernetes_service.clone(), request, options, write)
+                }
+                Err(result) => PendingRequest::Finished(result),
+            };
+            Ok(self.resources.push(pending)?)
+        }
+    }
+
+    fn join(
+        &mut self,
+        requests: Vec<Resource<PendingRequest>>,
+    ) -> impl Future<Output=Vec<Result<String, K8sError>>> + Send {
+        async move {
+            let pending: Vec<_> = requests
+                .into_iter()
+                .map(|request| self.resources.delete(request))
+                .collect();
+            futures::future::join_all(pending.into_iter().map(|pending| async move {
+                match pending {
+                    Ok(mut pending) => pending.wait().await,
+                    Err(e) => Err(e.to_string().into()),
+                }
+            }))
+            .await
+        }
+    }
+
+    fn batch(
+        &mut self,
+        requests: Vec<bindings::local::operator::types::ApiRequest>,
+    ) -> impl Future<Output=Vec<Result<String, K8sError>>> + Send {
+        async move {
+            let mut checked = Vec::with_capacity(requests.len());
+            for request in requests {
+                checked.push(self.check_request(request).await);
+            }
+            if self.metadata.shadow {
+                let mut results = Vec::with_capacity(checked.len());
+                for request in checked {
+                    results.push(match request {
+                        Ok(request) => match self.shadow_request(&request).await {
+                            Some(result) => result,
+                            None => {
+                                let write = self.request_write(&request);
+                                let result =
+                                    requests::execute(&self.kubernetes_service, request).await;
+                                if let Some(write) = write {
+                                    write.record(&result);
+                                }
+                                result
+                            }
+                        },
+                        Err(error) => Err(error),
+                    });
+                }
+                return results;
+            }
+            let checked: Vec<_> = checked
+                .into_iter()
+                .map(|request| {
+                    request.map(|request| {
+                        let write = self.request_write(&request);
+                        (request, write)
+                    })
+                })
+                .collect();
+            let kubernetes_service = &self.kubernetes_service;
+            futures::stream::iter(checked)
+                .map(|request| async move {
+                    let (request, write) = request?;
+                    let result = requests::execute(kubernetes_service, request).await;
+                    if let Some(write) = write {
+                        write.record(&result);
+                    }
+                    result
+                })
+                .buffered(self.config.batch_concurrency.max(1))
+                .collect()
+                .await
         }
     }
 }
@@ -0,0 +1,60 @@
+//! # Body Stream Module
+//!
+//! This module implements the `body-stream` resource, which hands a guest the body of a
+//! list response in chunks as it arrives from the API server. `list-resources` holds every
+//! object of the list in the memory of the host and of the guest at once; with a body
+//! stream each holds no more than the chunk being read. Unlike `list-pager`, the list is a
+//! single request, so it is consistent and cannot expire halfway through.
+
+use std::pin::Pin;
+
+use anyhow::{Context, Result};
+use futures::{AsyncBufRead, AsyncReadExt};
+
+/// Largest chunk a single `read` call returns.
+pub const MAX_CHUNK_BYTES: u32 = 1024 * 1024;
+
+pub struct BodyStream {
+    body: Pin<Box<dyn AsyncBufRead + Send>>,
+}
+
+impl BodyStream {
+    pub fn new(body: Pin<Box<dyn AsyncBufRead + Send>>) -> Self {
+        Self { body }
+    }
+
+    /// Reads the next chunk of at most `max_bytes` bytes, or returns `None` at the end of
+    /// the body.
+    pub async fn read(&mut self, max_bytes: u32) -> Result<Option<Vec<u8>>> {
+        let mut chunk = vec![0; max_bytes.min(MAX_CHUNK_BYTES) as usize];
+        let read = self
+            .body
+            .read(&mut chunk)
+            .await
+            .context("Failed to read the list response")?;
+        if read == 0 {
+            return Ok(None);
+        }
+        chunk.truncate(read);
+        Ok(Some(chunk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_body_in_chunks() {
+        let body = futures::io::Cursor::new(br#"{"items":[]}"#.to_vec());
+        let mut stream = BodyStream::new(Box::pin(body));
+        futures::executor::block_on(async {
+            assert_eq!(stream.read(5).await.unwrap(), Some(br#"{"ite"#.to_vec()));
+            assert_eq!(
+                stream.read(100).await.unwrap(),
+                Some(br#"ms":[]}"#.to_vec())
+            );
+            assert_eq!(stream.read(100).await.unwrap(), None);
+        });
+    }
+}
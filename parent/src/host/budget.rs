@@ -0,0 +1,78 @@
+//! # Budget Module
+//!
+//! This module tracks the time budget of the reconcile a guest is running. The budget is
+//! enforced with epoch interruption: the engine epoch advances every [`EPOCH_TICK`], and
+//! on every tick the running guest either yields to the executor, so other operators get
+//! their turn, or is trapped once its budget is spent. Guests can check the remaining
+//! budget with the `yield-checkpoint` host call and requeue with their partial progress
+//! persisted before that happens.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use wasmtime::UpdateDeadline;
+
+use crate::host::api::bindings::local::operator::types::BudgetStatus;
+
+/// Interval at which the engine epoch advances.
+pub const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// Error that traps a guest whose reconcile ran out of budget.
+#[derive(Debug)]
+pub struct BudgetExceeded;
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "reconcile exceeded its execution budget")
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+/// The budget of the current reconcile, if one is running.
+#[derive(Debug, Default)]
+pub struct Budget {
+    total: Option<Duration>,
+    deadline: Option<Instant>,
+}
+
+impl Budget {
+    /// Starts the budget of a reconcile.
+    pub fn start(&mut self, total: Option<Duration>) {
+        self.total = total;
+        self.deadline = total.map(|total| Instant::now() + total);
+    }
+
+    /// Ends the budget, so calls outside a reconcile are not limited.
+    pub fn finish(&mut self) {
+        self.total = None;
+        self.deadline = None;
+    }
+
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Returns the remaining budget and whether the guest should wrap up, which is the
+    /// case once less than a quarter of the budget is left.
+    pub fn status(&self) -> BudgetStatus {
+        let remaining = self.remaining();
+        let should_yield = match (remaining, self.total) {
+            (Some(remaining), Some(total)) => remaining < total / 4,
+            _ => false,
+        };
+        BudgetStatus {
+            remaining_ms: remaining.map(|remaining| remaining.as_millis() as u64),
+            should_yield,
+        }
+    }
+
+    /// Decides what happens to the running guest when the epoch advances.
+    pub fn on_epoch_tick(&self) -> wasmtime::Result<UpdateDeadline> {
+        if self.remaining() == Some(Duration::ZERO) {
+            return Err(BudgetExceeded.into());
+        }
+        Ok(UpdateDeadline::Yield(1))
+    }
+}
@@ -0,0 +1,56 @@
+//! # Capabilities Module
+//!
+//! This module implements the `capabilities` host call, which tells a guest which optional
+//! features of the host it can use in this parent. Some features depend on how the parent
+//! was built or configured, such as serving HTTP requests or applying writes, and a guest
+//! that checks for them can degrade gracefully instead of failing when it first calls
+//! them. Features that every host of this interface version has are listed too, so guests
+//! can check for all features the same way as hosts gain optional ones.
+
+use crate::config::metadata::WasmComponentMetadata;
+use crate::config::runtime::RuntimeConfig;
+
+/// Features every host of this interface version has.
+const ALWAYS: &[&str] = &[
+    "timers",
+    "messages",
+    "kv-store",
+    "leases",
+    "locks",
+    "watch-streams",
+    "list-pager",
+    "body-streams",
+    "batch",
+    "pod-logs",
+    "resource-metrics",
+];
+
+/// Returns the features available to an operator in this parent. Host extensions are
+/// listed as `extension:<name>`.
+pub fn capabilities(config: &RuntimeConfig, metadata: &WasmComponentMetadata) -> Vec<String> {
+    let mut capabilities: Vec<String> = ALWAYS.iter().map(|name| name.to_string()).collect();
+    let optional = [
+        // HTTP requests only reach operators through the admin API.
+        ("http", cfg!(feature = "admin-api")),
+        (
+            "component-model-async",
+            cfg!(feature = "component-model-async"),
+        ),
+        ("writes", !config.read_only && !metadata.shadow),
+        ("reconcile-budget", config.reconcile_budget_ms.is_some()),
+        ("schema-validation", config.validate_writes),
+    ];
+    capabilities.extend(
+        optional
+            .into_iter()
+            .filter(|(_, available)| *available)
+            .map(|(name, _)| name.to_string()),
+    );
+    capabilities.extend(
+        config
+            .extensions
+            .iter()
+            .map(|name| format!("extension:{}", name)),
+    );
+    capabilities
+}
@@ -0,0 +1,118 @@
+//! # Decision Log Module
+//!
+//! This module implements the `record-decision` host call, which keeps a bounded log of
+//! reconcile summaries next to an object, so users can see what an operator decided
+//! without access to the parent logs. The log is kept either in a companion ConfigMap or
+//! in the status of the object.
+
+use anyhow::{anyhow, Context, Result};
+use k8s_openapi::chrono::Utc;
+use kube::api::DynamicObject;
+use serde_json::{json, Value};
+
+use crate::config::metadata::{DecisionLog, DecisionLogTarget};
+use crate::kubernetes::KubernetesService;
+
+/// Key of the ConfigMap data entry holding the log.
+const CONFIG_MAP_KEY: &str = "decisions";
+
+/// Maximum length of a single decision summary.
+const MAX_SUMMARY_LENGTH: usize = 1024;
+
+/// Appends a decision to the log of an object, dropping the oldest entries beyond the limit.
+pub async fn record(
+    kubernetes_service: &KubernetesService,
+    settings: &DecisionLog,
+    kind: &str,
+    name: &str,
+    namespace: &str,
+    summary: &str,
+) -> Result<()> {
+    let object_json = kubernetes_service
+        .get_resource(kind, name, namespace)
+        .await?;
+    let object: DynamicObject =
+        serde_json::from_str(&object_json).context("Failed to parse object")?;
+    let entry = json!({
+        "time": Utc::now().to_rfc3339(),
+        "summary": truncate(summary),
+    });
+
+    match settings.target {
+        DecisionLogTarget::Status => {
+            let mut entries = object.data["status"]["decisionLog"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default();
+            push_bounded(&mut entries, entry, settings.max_entries);
+            let patch = json!({ "status": { "decisionLog": entries } });
+            kubernetes_service
+                .merge_patch_object_status(&object, &patch)
+                .await
+        }
+        DecisionLogTarget::ConfigMap => {
+            let config_map_name = format!("{}-decisions", name);
+            let mut entries: Vec<Value> = match kubernetes_service
+                .find_resource("ConfigMap", &config_map_name, namespace)
+                .await?
+            {
+                Some(config_map) => {
+                    let config_map: Value = serde_json::from_str(&config_map)?;
+                    config_map["data"][CONFIG_MAP_KEY]
+                        .as_str()
+                        .and_then(|log| serde_json::from_str(log).ok())
+                        .unwrap_or_default()
+                }
+                None => Vec::new(),
+            };
+            push_bounded(&mut entries, entry, settings.max_entries);
+
+            let types = object
+                .types
+                .as_ref()
+                .ok_or_else(|| anyhow!("Object has no type metadata"))?;
+            let config_map = json!({
+                "apiVersion": "v1",
+                "kind": "ConfigMap",
+                "metadata": {
+                    "name": config_map_name,
+                    "namespace": namespace,
+                    // Owned by the object, so the log is removed together with it.
+                    "ownerReferences": [{
+                        "apiVersion": types.api_version,
+                        "kind": types.kind,
+                        "name": name,
+                        "uid": object.metadata.uid,
+                    }],
+                },
+                "data": { CONFIG_MAP_KEY: serde_json::to_string_pretty(&entries)? },
+            });
+            kubernetes_service
+                .update_resource(
+                    "ConfigMap",
+                    &config_map_name,
+                    namespace,
+                    &config_map.to_string(),
+                )
+                .await
+        }
+    }
+}
+
+fn push_bounded(entries: &mut Vec<Value>, entry: Value, max_entries: usize) {
+    entries.push(entry);
+    if entries.len() > max_entries {
+        entries.drain(..entries.len() - max_entries);
+    }
+}
+
+fn truncate(summary: &str) -> String {
+    if summary.len() <= MAX_SUMMARY_LENGTH {
+        return summary.to_string();
+    }
+    let mut end = MAX_SUMMARY_LENGTH;
+    while !summary.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...", &summary[..end])
+}
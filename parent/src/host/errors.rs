@@ -0,0 +1,145 @@
+//! # Errors Module
+//!
+//! This module builds the `k8s-error` values host calls return to guests. Errors of the
+//! API server keep their HTTP status and reason, so a guest can retry a `conflict` with a
+//! fresh read while giving up on `forbidden`. Errors raised by the host itself, before a
+//! request reaches the API server, have a status of 0.
+
+use crate::host::api::bindings::local::operator::types::{ErrorReason, K8sError};
+
+impl K8sError {
+    pub fn new(status: u16, reason: ErrorReason, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            reason,
+            message: message.into(),
+        }
+    }
+
+    /// A call the host rejected, for example because the object is not granted to the
+    /// operator.
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(0, ErrorReason::Forbidden, message)
+    }
+
+    /// A write the host did not apply because the runtime is read-only.
+    pub fn read_only(message: impl Into<String>) -> Self {
+        Self::new(0, ErrorReason::ReadOnly, message)
+    }
+
+    /// An object that does not exist, as reported by the API server.
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(404, ErrorReason::NotFound, message)
+    }
+
+    /// An input from the guest the host cannot use.
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::new(0, ErrorReason::Invalid, message)
+    }
+
+    /// Converts an error of the Kubernetes service, keeping the status and reason of an API
+    /// server response anywhere in its chain. The message includes the causes.
+    pub fn from_anyhow(error: anyhow::Error) -> Self {
+        let message = format!("{:#}", error);
+        let response = error.chain().find_map(|cause| match cause.downcast_ref() {
+            Some(kube::Error::Api(response)) => Some(response),
+            _ => None,
+        });
+        match response {
+            Some(response) => {
+                let status = response.code;
+                Self::new(status, reason(status, &response.reason), message)
+            }
+            None => Self::new(0, ErrorReason::Other, message),
+        }
+    }
+}
+
+impl From<String> for K8sError {
+    fn from(message: String) -> Self {
+        Self::new(0, ErrorReason::Other, message)
+    }
+}
+
+impl From<&str> for K8sError {
+    fn from(message: &str) -> Self {
+        Self::from(message.to_string())
+    }
+}
+
+/// Maps the reason of a Kubernetes `Status`, falling back to its HTTP status code.
+fn reason(status: u16, reason: &str) -> ErrorReason {
+    match reason {
+        "BadRequest" => ErrorReason::BadRequest,
+        "Unauthorized" => ErrorReason::Unauthorized,
+        "Forbidden" => ErrorReason::Forbidden,
+        "NotFound" => ErrorReason::NotFound,
+        "AlreadyExists" => ErrorReason::AlreadyExists,
+        "Conflict" => ErrorReason::Conflict,
+        "Gone" | "Expired" => ErrorReason::Gone,
+        "Invalid" => ErrorReason::Invalid,
+        "TooManyRequests" => ErrorReason::TooManyRequests,
+        "Timeout" | "ServerTimeout" => ErrorReason::Timeout,
+        "InternalError" => ErrorReason::InternalError,
+        "ServiceUnavailable" => ErrorReason::ServiceUnavailable,
+        _ => match status {
+            400 => ErrorReason::BadRequest,
+            401 => ErrorReason::Unauthorized,
+            403 => ErrorReason::Forbidden,
+            404 => ErrorReason::NotFound,
+            409 => ErrorReason::Conflict,
+            410 => ErrorReason::Gone,
+            422 => ErrorReason::Invalid,
+            429 => ErrorReason::TooManyRequests,
+            500 => ErrorReason::InternalError,
+            503 => ErrorReason::ServiceUnavailable,
+            504 => ErrorReason::Timeout,
+            _ => ErrorReason::Other,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_status_reasons() {
+        assert!(matches!(reason(409, "Conflict"), ErrorReason::Conflict));
+        assert!(matches!(reason(410, "Expired"), ErrorReason::Gone));
+        assert!(matches!(reason(504, "ServerTimeout"), ErrorReason::Timeout));
+    }
+
+    #[test]
+    fn falls_back_to_status_codes() {
+        assert!(matches!(reason(403, ""), ErrorReason::Forbidden));
+        assert!(matches!(reason(422, "Unknown"), ErrorReason::Invalid));
+        assert!(matches!(reason(418, ""), ErrorReason::Other));
+    }
+
+    #[test]
+    fn keeps_the_status_of_api_errors() {
+        let response = kube::core::ErrorResponse {
+            status: "Failure".to_string(),
+            message: "pods \"web\" not found".to_string(),
+            reason: "NotFound".to_string(),
+            code: 404,
+        };
+        let error = anyhow::Error::new(kube::Error::Api(response)).context("Failed to get pod");
+        let error = K8sError::from_anyhow(error);
+        assert_eq!(error.status, 404);
+        assert!(matches!(error.reason, ErrorReason::NotFound));
+        assert!(error.message.starts_with("Failed to get pod: "));
+    }
+
+    #[test]
+    fn host_errors_have_no_status() {
+        let error = K8sError::from_anyhow(anyhow::anyhow!("Unknown kind"));
+        assert_eq!(error.status, 0);
+        assert!(matches!(error.reason, ErrorReason::Other));
+        assert!(matches!(
+            K8sError::read_only("").reason,
+            ErrorReason::ReadOnly
+        ));
+    }
+}
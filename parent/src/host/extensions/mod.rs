@@ -0,0 +1,70 @@
+//! # Host Extensions Module
+//!
+//! This module lets builds of the parent link additional WIT interfaces into operators,
+//! such as a client for a company-internal API, without changing how components are
+//! loaded. An extension adds its host functions to the linker, implementing the
+//! generated `Host` traits on `State`, and can keep data of its own in the state of each
+//! instance.
+//!
+//! Extensions are compiled in by adding them to `AVAILABLE`, and enabled by name with
+//! `extensions` in the runtime configuration. Operators that import an interface of an
+//! extension that is not enabled fail to load.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use wasmtime::component::Linker;
+
+use crate::config::metadata::WasmComponentMetadata;
+use crate::host::state::State;
+
+pub mod usage;
+
+/// An extension of the host API.
+pub trait HostExtension: Send + Sync {
+    /// Name the extension is enabled with in the runtime configuration.
+    fn name(&self) -> &'static str;
+
+    /// Adds the host functions of the extension to the linker.
+    fn add_to_linker(&self, linker: &mut Linker<State>) -> Result<()>;
+
+    /// Creates the data of the extension for a new instance of an operator. The data is
+    /// kept in `State::extensions`, keyed by its type.
+    fn instance_data(&self, _metadata: &WasmComponentMetadata) -> Option<Box<dyn Any + Send>> {
+        None
+    }
+}
+
+/// All extensions compiled into this build.
+const AVAILABLE: [&dyn HostExtension; 1] = [&usage::UsageExtension];
+
+/// Returns the extensions with the given names.
+pub fn enabled(names: &[String]) -> Result<Vec<&'static dyn HostExtension>> {
+    names
+        .iter()
+        .map(|name| {
+            AVAILABLE
+                .into_iter()
+                .find(|extension| extension.name() == name)
+                .ok_or_else(|| anyhow!("Unknown host extension '{}'", name))
+        })
+        .collect()
+}
+
+/// Data of the enabled extensions for one instance of an operator.
+#[derive(Default)]
+pub struct ExtensionData {
+    data: HashMap<TypeId, Box<dyn Any + Send>>,
+}
+
+impl ExtensionData {
+    pub fn insert(&mut self, data: Box<dyn Any + Send>) {
+        self.data.insert((*data).type_id(), data);
+    }
+
+    /// Returns the data of the given type, if an extension created it.
+    pub fn get_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.data.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+}
@@ -0,0 +1,65 @@
+//! # Usage Extension
+//!
+//! An example host extension implementing `local:usage/meter`, which lets operators
+//! report usage of billable resources. Usage is exported as the
+//! `wasm_operator_extension_usage_total` counter, and counted per instance so an operator
+//! can read back what it reported.
+
+use std::any::Any;
+use std::collections::HashMap;
+
+use anyhow::Result;
+use wasmtime::component::{HasSelf, Linker};
+
+use super::HostExtension;
+use crate::config::metadata::WasmComponentMetadata;
+use crate::host::state::State;
+use crate::metrics;
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        async: true,
+        path: "wit-extensions/usage.wit",
+        world: "usage-host",
+    });
+}
+
+pub struct UsageExtension;
+
+/// Usage recorded by one instance, by metric.
+#[derive(Default)]
+struct Usage(HashMap<String, u64>);
+
+impl HostExtension for UsageExtension {
+    fn name(&self) -> &'static str {
+        "usage"
+    }
+
+    fn add_to_linker(&self, linker: &mut Linker<State>) -> Result<()> {
+        bindings::local::usage::meter::add_to_linker::<_, HasSelf<_>>(linker, |state| state)
+    }
+
+    fn instance_data(&self, _metadata: &WasmComponentMetadata) -> Option<Box<dyn Any + Send>> {
+        Some(Box::new(Usage::default()))
+    }
+}
+
+impl bindings::local::usage::meter::Host for State {
+    async fn report(&mut self, metric: String, quantity: u64) {
+        metrics::increment_by(
+            "wasm_operator_extension_usage_total",
+            &[("operator", &self.metadata.name), ("metric", &metric)],
+            quantity as f64,
+        );
+        if let Some(usage) = self.extensions.get_mut::<Usage>() {
+            *usage.0.entry(metric).or_default() += quantity;
+        }
+    }
+
+    async fn total(&mut self, metric: String) -> u64 {
+        self.extensions
+            .get_mut::<Usage>()
+            .and_then(|usage| usage.0.get(&metric).copied())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,91 @@
+//! # Handlers Module
+//!
+//! This module calls the optional exports of a component. Only the exports of the
+//! `kube-operator` world are required; a component that serves HTTP requests, sets timers
+//! or subscribes to topics adds the `http-handler`, `timer-handler` or `message-handler`
+//! world to its own. The runtime looks the optional exports up when it
+//! instantiates a component and only calls the ones it found, so components that do not
+//! use a feature need not export a handler for it.
+
+use anyhow::{Context, Result};
+use wasmtime::component::{ComponentNamedList, Instance, Lift, Lower, TypedFunc};
+use wasmtime::Store;
+
+use crate::host::api::bindings::local::operator::types::{HttpRequest, HttpResponse};
+use crate::host::state::State;
+
+/// The optional exports of an instance.
+#[derive(Default, Clone, Copy)]
+pub struct Handlers {
+    handle_http: Option<TypedFunc<(HttpRequest,), (HttpResponse,)>>,
+    on_timer: Option<TypedFunc<(String,), ()>>,
+    on_message: Option<TypedFunc<(String, String), ()>>,
+}
+
+impl Handlers {
+    /// Looks up the optional exports of an instance. An export of the wrong type fails,
+    /// since the component was built against another version of the interface.
+    pub fn resolve(store: &mut Store<State>, instance: &Instance) -> Result<Self> {
+        Ok(Self {
+            handle_http: lookup(store, instance, "handle-http")?,
+            on_timer: lookup(store, instance, "on-timer")?,
+            on_message: lookup(store, instance, "on-message")?,
+        })
+    }
+
+    /// Returns whether the component exports `on-timer`.
+    pub fn has_on_timer(&self) -> bool {
+        self.on_timer.is_some()
+    }
+}
+
+fn lookup<Params, Results>(
+    store: &mut Store<State>,
+    instance: &Instance,
+    name: &str,
+) -> Result<Option<TypedFunc<Params, Results>>>
+where
+    Params: ComponentNamedList + Lower,
+    Results: ComponentNamedList + Lift,
+{
+    instance
+        .get_func(&mut *store, name)
+        .map(|func| {
+            func.typed(&*store)
+                .with_context(|| format!("The '{}' export has the wrong type", name))
+        })
+        .transpose()
+}
+
+/// Calls the `handle-http` export. Returns `None` if the component does not export it.
+pub async fn handle_http(
+    store: &mut Store<State>,
+    request: HttpRequest,
+) -> Result<Option<HttpResponse>> {
+    let Some(func) = store.data().handlers.handle_http else {
+        return Ok(None);
+    };
+    let (response,) = func.call_async(&mut *store, (request,)).await?;
+    func.post_return_async(&mut *store).await?;
+    Ok(Some(response))
+}
+
+/// Calls the `on-timer` export. Returns whether the component exports it.
+pub async fn on_timer(store: &mut Store<State>, token: String) -> Result<bool> {
+    let Some(func) = store.data().handlers.on_timer else {
+        return Ok(false);
+    };
+    func.call_async(&mut *store, (token,)).await?;
+    func.post_return_async(&mut *store).await?;
+    Ok(true)
+}
+
+/// Calls the `on-message` export. Returns whether the component exports it.
+pub async fn on_message(store: &mut Store<State>, topic: String, payload: String) -> Result<bool> {
+    let Some(func) = store.data().handlers.on_message else {
+        return Ok(false);
+    };
+    func.call_async(&mut *store, (topic, payload)).await?;
+    func.post_return_async(&mut *store).await?;
+    Ok(true)
+}
@@ -0,0 +1,78 @@
+//! # Key-Value Module
+//!
+//! This module implements the `kv-get`, `kv-set` and `kv-delete` host calls, which give an
+//! operator a small durable store for scratch state, such as the last version it rolled
+//! out. Unlike the memory snapshots taken on unload, the store survives restarts of the
+//! parent and does not depend on the guest serializing its state. Each operator gets a
+//! ConfigMap in the namespace of the parent, so the store is limited to what a ConfigMap
+//! holds (1 MiB in total).
+
+use anyhow::Result;
+
+use crate::kubernetes::KubernetesService;
+
+/// Label on the ConfigMaps of the store, set to the name of the operator.
+pub const KV_STORE_LABEL: &str = "operator.wasm/kv-store";
+
+/// Longest key a ConfigMap accepts.
+const MAX_KEY_LENGTH: usize = 253;
+
+/// Returns the name of the ConfigMap that holds the store of an operator.
+pub fn config_map_name(operator: &str) -> String {
+    format!("wasm-operator-kv-{}", operator)
+}
+
+/// Checks that a key is a valid ConfigMap key.
+pub fn check_key(key: &str) -> Result<(), String> {
+    let valid = !key.is_empty()
+        && key.len() <= MAX_KEY_LENGTH
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(format!(
+            "Invalid key '{}': keys are 1 to {} letters, digits, '-', '_' or '.'",
+            key, MAX_KEY_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the value of a key in the store of an operator.
+pub async fn get(
+    kubernetes_service: &KubernetesService,
+    operator: &str,
+    key: &str,
+) -> Result<Option<String>> {
+    kubernetes_service
+        .get_config_map_value(
+            &config_map_name(operator),
+            namespace(kubernetes_service),
+            key,
+        )
+        .await
+}
+
+/// Sets the value of a key in the store of an operator, or removes the key if the value is
+/// `None`.
+pub async fn set(
+    kubernetes_service: &KubernetesService,
+    operator: &str,
+    key: &str,
+    value: Option<&str>,
+) -> Result<()> {
+    kubernetes_service
+        .set_config_map_value(
+            &config_map_name(operator),
+            namespace(kubernetes_service),
+            key,
+            value,
+            (KV_STORE_LABEL, operator),
+        )
+        .await
+}
+
+/// The stores live in the namespace of the parent.
+fn namespace(kubernetes_service: &KubernetesService) -> &str {
+    &kubernetes_service.cluster_info().namespace
+}
@@ -0,0 +1,101 @@
+//! # Locks Module
+//!
+//! This module implements advisory locks shared by the operators running in one parent, so
+//! sibling operators can serialize access to external resources without building their
+//! own locking protocol on top of custom resources. The locks live in the parent's memory
+//! and are released when their holder is unloaded; use a lease to coordinate across parent
+//! replicas. The extra instances that run the parallel reconciles of an operator hold
+//! locks as `<operator>#<n>`, so two reconciles of one operator cannot hold the same lock
+//! at once; their locks are released along with those of the operator.
+
+use std::time::Duration;
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use tokio::sync::Notify;
+use tracing::debug;
+
+/// The advisory locks of all operators, by lock name.
+#[derive(Default)]
+pub struct LockTable {
+    /// The operator holding each lock.
+    holders: DashMap<String, String>,
+    /// Notified whenever a lock is released.
+    released: Notify,
+}
+
+impl LockTable {
+    /// Takes a lock if it is free or already held by the operator, and returns whether the
+    /// operator holds it now.
+    pub fn try_lock(&self, operator: &str, name: &str) -> bool {
+        match self.holders.entry(name.to_string()) {
+            Entry::Occupied(entry) => entry.get() == operator,
+            Entry::Vacant(entry) => {
+                entry.insert(operator.to_string());
+                debug!("Operator '{}' took lock '{}'", operator, name);
+                true
+            }
+        }
+    }
+
+    /// Waits up to `timeout` for a lock, and returns whether the operator holds it now.
+    pub async fn lock(&self, operator: &str, name: &str, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // Register for the release notification before checking, so a release in
+            // between is not missed.
+            let released = self.released.notified();
+            if self.try_lock(operator, name) {
+                return true;
+            }
+            if tokio::time::timeout_at(deadline, released).await.is_err() {
+                return false;
+            }
+        }
+    }
+
+    /// Releases a lock held by the operator, and returns whether it held it.
+    pub fn unlock(&self, operator: &str, name: &str) -> bool {
+        let unlocked = self
+            .holders
+            .remove_if(name, |_, holder| holder == operator)
+            .is_some();
+        if unlocked {
+            debug!("Operator '{}' released lock '{}'", operator, name);
+            self.released.notify_waiters();
+        }
+        unlocked
+    }
+
+    /// Releases all locks held by the operator and its extra instances, e.g. when it is
+    /// unloaded.
+    pub fn release_all(&self, operator: &str) {
+        let instances = format!("{}#", operator);
+        self.holders
+            .retain(|_, holder| holder != operator && !holder.starts_with(&instances));
+        self.released.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instances_of_an_operator_do_not_share_locks() {
+        let locks = LockTable::default();
+        assert!(locks.try_lock("operator", "a"));
+        assert!(!locks.try_lock("operator#1", "a"));
+        assert!(locks.try_lock("operator#1", "b"));
+        assert!(locks.try_lock("operator#10", "c"));
+        assert!(!locks.unlock("operator", "b"));
+
+        locks.release_all("operator#1");
+        assert!(locks.try_lock("other", "b"));
+        assert!(!locks.try_lock("other", "c"));
+
+        locks.release_all("operator");
+        assert!(locks.try_lock("other", "a"));
+        assert!(locks.try_lock("other", "c"));
+    }
+}
@@ -0,0 +1,66 @@
+//! # Message Bus Module
+//!
+//! This module keeps the messages operators send each other with `publish`, so operators
+//! running in the same parent can signal each other without writing an object to the API
+//! server and waiting for the watch event. The runtime delivers each message to the
+//! `on-message` export of the operators that subscribe to its topic in their metadata,
+//! waking them if they are unloaded. Messages live in the parent's memory: they are not
+//! seen by operators in other parents and are lost if the parent restarts.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use tokio::sync::Notify;
+
+/// Largest payload a message may carry.
+pub const MAX_PAYLOAD_BYTES: usize = 64 * 1024;
+
+/// The most messages waiting to be delivered at a time.
+pub const MAX_PENDING_MESSAGES: usize = 1024;
+
+/// A message published by an operator.
+pub struct Message {
+    pub publisher: String,
+    pub topic: String,
+    pub payload: String,
+}
+
+/// The messages waiting to be delivered.
+#[derive(Default)]
+pub struct MessageBus {
+    pending: Mutex<VecDeque<Message>>,
+    /// Notified whenever a message is published.
+    published: Notify,
+}
+
+impl MessageBus {
+    /// Queues a message for delivery to the subscribers of its topic.
+    pub fn publish(&self, message: Message) -> Result<(), String> {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.len() >= MAX_PENDING_MESSAGES {
+            return Err(format!(
+                "{} messages are waiting to be delivered",
+                MAX_PENDING_MESSAGES
+            ));
+        }
+        pending.push_back(message);
+        drop(pending);
+        self.published.notify_one();
+        Ok(())
+    }
+
+    /// Waits until at least one message is published, and removes and returns all pending
+    /// ones, oldest first.
+    pub async fn next(&self) -> Vec<Message> {
+        loop {
+            // Register for the notification before checking, so a message published in
+            // between is not missed.
+            let published = self.published.notified();
+            let messages: Vec<Message> = self.pending.lock().unwrap().drain(..).collect();
+            if !messages.is_empty() {
+                return messages;
+            }
+            published.await;
+        }
+    }
+}
@@ -6,4 +6,23 @@
 //! access and resource management.
 
 pub mod api;
+pub mod body_stream;
+pub mod budget;
+pub mod capabilities;
+pub mod decision_log;
+pub mod errors;
+pub mod extensions;
+pub mod handlers;
+pub mod kv;
+pub mod locks;
+pub mod message_bus;
+pub mod object;
+pub mod ownership;
+pub mod pager;
+pub mod reconcile_queue;
+pub mod requests;
+pub mod shadow;
 pub mod state;
+pub mod timers;
+pub mod transaction;
+pub mod watch_stream;
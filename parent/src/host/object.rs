@@ -0,0 +1,44 @@
+//! # Object Module
+//!
+//! This module implements the `k8s-object` resource, a handle to an object held by the
+//! host. Operators that set `lazy-objects` in their metadata get the object being
+//! reconciled through `reconcile-object` instead of as JSON in the reconcile request, so
+//! the host does not serialize the whole object for every reconcile and the guest only
+//! copies the fields it reads into its memory, e.g. the name and labels to decide whether
+//! there is anything to do.
+
+use std::sync::Arc;
+
+use kube::api::DynamicObject;
+use serde_json::Value;
+
+pub struct K8sObject {
+    object: Arc<DynamicObject>,
+}
+
+impl K8sObject {
+    pub fn new(object: Arc<DynamicObject>) -> Self {
+        Self { object }
+    }
+
+    pub fn get(&self) -> &DynamicObject {
+        &self.object
+    }
+
+    /// Returns the value at a JSON pointer into the object, as JSON.
+    pub fn field_json(&self, pointer: &str) -> Option<String> {
+        let rest = pointer.strip_prefix('/')?;
+        let (field, rest) = match rest.split_once('/') {
+            Some((field, rest)) => (field, format!("/{}", rest)),
+            None => (rest, String::new()),
+        };
+        let object = &self.object;
+        let value = match field {
+            "apiVersion" => Value::from(object.types.as_ref()?.api_version.clone()),
+            "kind" => Value::from(object.types.as_ref()?.kind.clone()),
+            "metadata" => serde_json::to_value(&object.metadata).ok()?,
+            _ => object.data.get(field)?.clone(),
+        };
+        value.pointer(&rest).map(Value::to_string)
+    }
+}
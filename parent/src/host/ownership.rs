@@ -0,0 +1,228 @@
+//! # Ownership Module
+//!
+//! This module keeps the graph of which operator manages which objects, so platform teams
+//! can tell which operator touched an object without searching through logs. Every write an
+//! operator makes through the host records the object, along with the object that owns it:
+//! the controller in its owner references, or else the object being reconciled when it was
+//! written. The graph is served by the admin API and counted in the
+//! `wasm_operator_managed_objects` gauge. Like the reconcile queue, it lives in the parent's
+//! memory and only covers the writes made since the parent started.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::host::api::bindings::local::operator::types::K8sError;
+use crate::metrics;
+use crate::runtime::introspection::now_ms;
+
+/// The most objects recorded for one operator. Writes to further objects are not recorded.
+pub const MAX_OBJECTS_PER_OPERATOR: usize = 10_000;
+
+/// The kind, namespace and name of an object.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct ObjectKey {
+    pub kind: String,
+    pub namespace: String,
+    pub name: String,
+}
+
+/// An object an operator wrote.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManagedObject {
+    pub operator: String,
+    #[serde(flatten)]
+    pub object: ObjectKey,
+    /// The object this one belongs to, if it is known.
+    pub owner: Option<ObjectKey>,
+    /// The host call of the last write, e.g. `create` or `patch`.
+    pub last_write: String,
+    pub last_write_ms: u64,
+}
+
+/// The objects written by all operators.
+#[derive(Default)]
+pub struct OwnershipGraph {
+    objects: Mutex<HashMap<(String, ObjectKey), ManagedObject>>,
+}
+
+impl OwnershipGraph {
+    /// Records a write of an operator to an object. The owner of an object is kept when a
+    /// later write does not name one.
+    pub fn record_write(
+        &self,
+        operator: &str,
+        object: ObjectKey,
+        owner: Option<ObjectKey>,
+        write: &str,
+    ) {
+        let mut objects = self.objects.lock().unwrap();
+        let key = (operator.to_string(), object);
+        if let Some(existing) = objects.get_mut(&key) {
+            if owner.is_some() {
+                existing.owner = owner;
+            }
+            existing.last_write = write.to_string();
+            existing.last_write_ms = now_ms();
+            return;
+        }
+        let count = managed_count(&objects, operator);
+        if count >= MAX_OBJECTS_PER_OPERATOR {
+            return;
+        }
+        let managed = ManagedObject {
+            operator: operator.to_string(),
+            object: key.1.clone(),
+            owner,
+            last_write: write.to_string(),
+            last_write_ms: now_ms(),
+        };
+        objects.insert(key, managed);
+        set_gauge(operator, count + 1);
+    }
+
+    /// Forgets an object that was deleted, for every operator that wrote it.
+    pub fn record_delete(&self, object: &ObjectKey) {
+        let mut objects = self.objects.lock().unwrap();
+        let operators: Vec<String> = objects
+            .keys()
+            .filter(|(_, key)| key == object)
+            .map(|(operator, _)| operator.clone())
+            .collect();
+        for operator in operators {
+            objects.remove(&(operator.clone(), object.clone()));
+            set_gauge(&operator, managed_count(&objects, &operator));
+        }
+    }
+
+    /// Returns the operators that wrote an object, with what they know about it.
+    pub fn managers_of(&self, object: &ObjectKey) -> Vec<ManagedObject> {
+        self.objects
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|managed| &managed.object == object)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the objects an operator wrote, sorted by kind, namespace and name.
+    pub fn managed_by(&self, operator: &str) -> Vec<ManagedObject> {
+        let mut managed: Vec<ManagedObject> = self
+            .objects
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|managed| managed.operator == operator)
+            .cloned()
+            .collect();
+        managed.sort_by(|a, b| {
+            (&a.object.kind, &a.object.namespace, &a.object.name).cmp(&(
+                &b.object.kind,
+                &b.object.namespace,
+                &b.object.name,
+            ))
+        });
+        managed
+    }
+}
+
+fn managed_count(objects: &HashMap<(String, ObjectKey), ManagedObject>, operator: &str) -> usize {
+    objects.keys().filter(|(o, _)| o == operator).count()
+}
+
+fn set_gauge(operator: &str, count: usize) {
+    metrics::set_gauge(
+        "wasm_operator_managed_objects",
+        &[("operator", operator)],
+        count as f64,
+    );
+}
+
+/// A write started with `start-request` or `batch`, recorded once it succeeded.
+pub struct RequestWrite {
+    pub graph: Arc<OwnershipGraph>,
+    pub operator: String,
+    /// The host call the write stands for, `create`, `update` or `delete`.
+    pub write: &'static str,
+    pub kind: String,
+    pub namespace: String,
+    /// Name of the object, or `None` for a create, whose result is the name.
+    pub name: Option<String>,
+    pub owner: Option<ObjectKey>,
+}
+
+impl RequestWrite {
+    /// Records the write if the request succeeded.
+    pub fn record(self, result: &Result<String, K8sError>) {
+        let Ok(output) = result else {
+            return;
+        };
+        let name = self.name.unwrap_or_else(|| output.clone());
+        if name.is_empty() {
+            return;
+        }
+        let object = ObjectKey {
+            kind: self.kind,
+            namespace: self.namespace,
+            name,
+        };
+        if self.write == "delete" {
+            self.graph.record_delete(&object);
+            return;
+        }
+        let owner = self.owner.filter(|owner| owner != &object);
+        self.graph
+            .record_write(&self.operator, object, owner, self.write);
+    }
+}
+
+/// Returns the controller, or else the first owner, in the owner references of an object.
+pub fn owner_of(resource: &Value, namespace: &str) -> Option<ObjectKey> {
+    let owners = resource.pointer("/metadata/ownerReferences")?.as_array()?;
+    let owner = owners
+        .iter()
+        .find(|owner| owner.get("controller").and_then(Value::as_bool) == Some(true))
+        .or_else(|| owners.first())?;
+    Some(ObjectKey {
+        kind: owner.get("kind")?.as_str()?.to_string(),
+        namespace: namespace.to_string(),
+        name: owner.get("name")?.as_str()?.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(graph: &Arc<OwnershipGraph>, write: &'static str, name: Option<&str>) -> RequestWrite {
+        RequestWrite {
+            graph: graph.clone(),
+            operator: "operator".to_string(),
+            write,
+            kind: "ConfigMap".to_string(),
+            namespace: "default".to_string(),
+            name: name.map(str::to_string),
+            owner: None,
+        }
+    }
+
+    #[test]
+    fn records_request_writes_that_succeeded() {
+        let graph = Arc::new(OwnershipGraph::default());
+        write(&graph, "create", None).record(&Err("conflict".to_string().into()));
+        assert!(graph.managed_by("operator").is_empty());
+
+        write(&graph, "create", None).record(&Ok("created".to_string()));
+        let managed = graph.managed_by("operator");
+        assert_eq!(managed.len(), 1);
+        assert_eq!(managed[0].object.name, "created");
+        assert_eq!(managed[0].last_write, "create");
+
+        write(&graph, "delete", Some("created")).record(&Ok(String::new()));
+        assert!(graph.managed_by("operator").is_empty());
+    }
+}
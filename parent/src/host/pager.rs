@@ -0,0 +1,58 @@
+//! # Pager Module
+//!
+//! This module implements the `list-pager` resource, which lets a guest page through a
+//! list with continue tokens instead of receiving all objects at once. Only one page is
+//! held at a time, by the host and by the guest, which keeps memory bounded when an
+//! operator reconciles namespaces with thousands of objects.
+
+use crate::kubernetes::KubernetesService;
+
+pub struct ListPager {
+    pub kind: String,
+    namespace: String,
+    label_selector: String,
+    field_selector: String,
+    /// The continue token of the next page, `None` before the first page.
+    continue_token: Option<String>,
+    done: bool,
+}
+
+impl ListPager {
+    pub fn new(
+        kind: String,
+        namespace: String,
+        label_selector: String,
+        field_selector: String,
+    ) -> Self {
+        Self {
+            kind,
+            namespace,
+            label_selector,
+            field_selector,
+            continue_token: None,
+            done: false,
+        }
+    }
+
+    /// Fetches the next page, or returns `None` once the list is exhausted.
+    pub async fn next_page(
+        &mut self,
+        kubernetes_service: &KubernetesService,
+    ) -> anyhow::Result<Option<Vec<String>>> {
+        if self.done {
+            return Ok(None);
+        }
+        let (objects, next) = kubernetes_service
+            .list_page(
+                &self.kind,
+                &self.namespace,
+                &self.label_selector,
+                &self.field_selector,
+                self.continue_token.as_deref(),
+            )
+            .await?;
+        self.done = next.is_none();
+        self.continue_token = next;
+        Ok(Some(objects))
+    }
+}
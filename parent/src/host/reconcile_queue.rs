@@ -0,0 +1,147 @@
+//! # Reconcile Queue Module
+//!
+//! This module keeps the reconciles operators request with `enqueue`, so the reconcile of
+//! one object can ask for another object to be reconciled later, e.g. a parent resource
+//! after one of its children changed, instead of writing a dummy update to it. The
+//! runtime reads the object when its reconcile is due and dispatches it like a watch
+//! event. Operators granted `may-trigger` can enqueue reconciles of other operators, which
+//! lets them coordinate without polling. Like timers, requested reconciles live in the parent's memory and do not
+//! survive a restart of the parent.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use crate::host::api::bindings::local::operator::types::ObjectReference;
+
+/// The most reconciles one operator may have pending at a time.
+pub const MAX_ENQUEUED_PER_OPERATOR: usize = 1024;
+
+/// A reconcile an operator asked for.
+pub struct EnqueuedReconcile {
+    pub operator: String,
+    pub object: ObjectReference,
+    /// The object whose reconcile asked for this one, if any.
+    pub triggered_by: Option<ObjectReference>,
+    /// The operator that asked for the reconcile, if it is not the one reconciling.
+    pub requested_by: Option<String>,
+}
+
+struct Pending {
+    deadline: Instant,
+    triggered_by: Option<ObjectReference>,
+    requested_by: Option<String>,
+}
+
+/// An operator and the kind, name and namespace of an object.
+type Key = (String, String, String, String);
+
+/// The pending reconciles of all operators.
+#[derive(Default)]
+pub struct ReconcileQueue {
+    pending: Mutex<HashMap<Key, Pending>>,
+    /// Notified whenever a reconcile is enqueued.
+    enqueued: Notify,
+}
+
+impl ReconcileQueue {
+    /// Enqueues a reconcile of an object. An object that is already pending is reconciled
+    /// once, at the earlier of the two deadlines.
+    pub fn enqueue(
+        &self,
+        operator: &str,
+        object: ObjectReference,
+        triggered_by: Option<ObjectReference>,
+        requested_by: Option<String>,
+        delay: Duration,
+    ) -> Result<(), String> {
+        let mut pending = self.pending.lock().unwrap();
+        let key = (
+            operator.to_string(),
+            object.kind,
+            object.name,
+            object.namespace,
+        );
+        let entry = Pending {
+            deadline: Instant::now() + delay,
+            triggered_by,
+            requested_by,
+        };
+        if let Some(existing) = pending.get_mut(&key) {
+            if entry.deadline < existing.deadline {
+                *existing = entry;
+            }
+        } else {
+            if pending.keys().filter(|(o, ..)| o == operator).count() >= MAX_ENQUEUED_PER_OPERATOR {
+                return Err(format!(
+                    "Operator '{}' already has {} pending reconciles",
+                    operator, MAX_ENQUEUED_PER_OPERATOR
+                ));
+            }
+            pending.insert(key, entry);
+        }
+        drop(pending);
+        self.enqueued.notify_one();
+        Ok(())
+    }
+
+    /// Waits until at least one reconcile is due, and removes and returns all due ones.
+    pub async fn next_due(&self) -> Vec<EnqueuedReconcile> {
+        loop {
+            // Register for the notification before checking, so a reconcile enqueued in
+            // between is not missed.
+            let enqueued = self.enqueued.notified();
+            let (due, next) = self.take_due();
+            if !due.is_empty() {
+                return due;
+            }
+            match next {
+                Some(deadline) => {
+                    let _ = tokio::time::timeout_at(deadline, enqueued).await;
+                }
+                None => enqueued.await,
+            }
+        }
+    }
+
+    /// Removes the due reconciles, and returns them with the deadline of the next pending
+    /// one.
+    fn take_due(&self) -> (Vec<EnqueuedReconcile>, Option<Instant>) {
+        let now = Instant::now();
+        let mut pending = self.pending.lock().unwrap();
+        let due_keys: Vec<Key> = pending
+            .iter()
+            .filter(|(_, entry)| entry.deadline <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        let due = due_keys
+            .into_iter()
+            .filter_map(|key| {
+                let entry = pending.remove(&key)?;
+                let (operator, kind, name, namespace) = key;
+                Some(EnqueuedReconcile {
+                    operator,
+                    object: ObjectReference {
+                        kind,
+                        name,
+                        namespace,
+                    },
+                    triggered_by: entry.triggered_by,
+                    requested_by: entry.requested_by,
+                })
+            })
+            .collect();
+        (due, pending.values().map(|entry| entry.deadline).min())
+    }
+
+    /// Cancels all pending reconciles of an operator.
+    pub fn cancel_all(&self, operator: &str) {
+        self.pending
+            .lock()
+            .unwrap()
+            .retain(|(o, ..), _| o != operator);
+    }
+}
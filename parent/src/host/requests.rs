@@ -0,0 +1,170 @@
+//! # Requests Module
+//!
+//! This module runs the API requests a guest starts with `start-request`. The guest-facing
+//! checks happen when the request is started; the request itself runs as a separate task
+//! that does not borrow the store, so the guest can start several requests and wait for
+//! them together instead of paying for each round trip in turn. A request can limit the
+//! time each attempt takes and retry attempts that failed for reasons that may pass, so a
+//! slow API server fails the request instead of holding up the reconcile that waits for it.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+use crate::host::api::bindings::local::operator::types::{
+    ApiRequest, ErrorReason, K8sError, RequestOptions,
+};
+use crate::host::ownership::RequestWrite;
+use crate::kubernetes::KubernetesService;
+
+/// Upper bound of the retries of a request, whatever the guest asks for.
+const MAX_RETRIES: u32 = 10;
+/// Delay before the first retry, doubled for each one after it.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+/// Upper bound of the delay between retries.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// A request running on the host, or its result once it finished or failed to start.
+pub enum PendingRequest {
+    Running(JoinHandle<Result<String, K8sError>>),
+    Finished(Result<String, K8sError>),
+}
+
+impl PendingRequest {
+    /// Starts a request that passed the guest-facing checks. A write is recorded in the
+    /// ownership graph once it succeeds, even if the guest no longer waits for it.
+    pub fn start(
+        kubernetes_service: Arc<KubernetesService>,
+        request: ApiRequest,
+        options: RequestOptions,
+        write: Option<RequestWrite>,
+    ) -> Self {
+        Self::Running(tokio::spawn(async move {
+            let result = execute_with_options(&kubernetes_service, request, options).await;
+            if let Some(write) = write {
+                write.record(&result);
+            }
+            result
+        }))
+    }
+
+    /// Waits for the request to finish and keeps its result, so it can be read again.
+    pub async fn wait(&mut self) -> Result<String, K8sError> {
+        let result = match self {
+            Self::Running(handle) => handle
+                .await
+                .unwrap_or_else(|e| Err(format!("Request failed: {}", e).into())),
+            Self::Finished(result) => return result.clone(),
+        };
+        *self = Self::Finished(result.clone());
+        result
+    }
+}
+
+/// Performs a request, retrying it and limiting each attempt as the options ask.
+async fn execute_with_options(
+    kubernetes_service: &KubernetesService,
+    request: ApiRequest,
+    options: RequestOptions,
+) -> Result<String, K8sError> {
+    let retries = options.retries.unwrap_or(0).min(MAX_RETRIES);
+    let mut attempt = 0;
+    loop {
+        let result = match options.timeout_ms {
+            Some(timeout_ms) => tokio::time::timeout(
+                Duration::from_millis(timeout_ms.into()),
+                execute(kubernetes_service, request.clone()),
+            )
+            .await
+            .unwrap_or_else(|_| {
+                Err(K8sError::new(
+                    0,
+                    ErrorReason::Timeout,
+                    format!("Request did not finish within {} ms", timeout_ms),
+                ))
+            }),
+            None => execute(kubernetes_service, request.clone()).await,
+        };
+        match result {
+            Err(error) if attempt < retries && is_retryable(&error) => {
+                tokio::time::sleep(retry_delay(attempt)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether a request that failed with an error may succeed when it is tried again.
+fn is_retryable(error: &K8sError) -> bool {
+    matches!(
+        error.reason,
+        ErrorReason::Timeout
+            | ErrorReason::TooManyRequests
+            | ErrorReason::InternalError
+            | ErrorReason::ServiceUnavailable
+    )
+}
+
+/// The delay before a retry, after `attempt` retries.
+fn retry_delay(attempt: u32) -> Duration {
+    RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_RETRY_DELAY)
+}
+
+/// Performs a single request against the API server.
+pub async fn execute(
+    kubernetes_service: &KubernetesService,
+    request: ApiRequest,
+) -> Result<String, K8sError> {
+    let result = match request {
+        ApiRequest::Get(target) => {
+            kubernetes_service
+                .get_resource(&target.kind, &target.name, &target.namespace)
+                .await
+        }
+        ApiRequest::Create(create) => {
+            kubernetes_service
+                .create_resource(&create.kind, &create.namespace, &create.resource_json)
+                .await
+        }
+        ApiRequest::Update(update) => kubernetes_service
+            .update_resource(
+                &update.target.kind,
+                &update.target.name,
+                &update.target.namespace,
+                &update.resource_json,
+            )
+            .await
+            .map(|_| String::new()),
+        ApiRequest::Delete(target) => kubernetes_service
+            .delete_resource(&target.kind, &target.name, &target.namespace)
+            .await
+            .map(|_| String::new()),
+    };
+    result.map_err(K8sError::from_anyhow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_only_errors_that_may_pass() {
+        let error = |reason| K8sError::new(0, reason, "");
+        assert!(is_retryable(&error(ErrorReason::Timeout)));
+        assert!(is_retryable(&error(ErrorReason::ServiceUnavailable)));
+        assert!(!is_retryable(&error(ErrorReason::Conflict)));
+        assert!(!is_retryable(&error(ErrorReason::NotFound)));
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_a_bound() {
+        assert_eq!(retry_delay(0), Duration::from_millis(100));
+        assert_eq!(retry_delay(3), Duration::from_millis(800));
+        assert_eq!(retry_delay(MAX_RETRIES), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(u32::MAX), MAX_RETRY_DELAY);
+    }
+}
@@ -0,0 +1,258 @@
+//! # Shadow Module
+//!
+//! This module implements shadow mode, which lets a new operator run next to the
+//! controller it is meant to replace. The writes of an operator in shadow mode are not
+//! applied; instead, each write is compared with the live object, which reflects what the
+//! incumbent controller did, and the differences are logged, counted and kept for the
+//! admin API. An operator that writes what the incumbent already wrote is ready to take
+//! over.
+
+use serde::Serialize;
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::host::api::bindings::local::operator::types::{ApiRequest, K8sError};
+use crate::host::state::State;
+use crate::metrics;
+use crate::runtime::introspection::now_ms;
+
+/// Fields of an object that the API server or the runtime maintain, as JSON pointers. The
+/// last one is the `APPLIED_BY_LABEL` label.
+const IGNORED_FIELDS: [&str; 6] = [
+    "/metadata/resourceVersion",
+    "/metadata/uid",
+    "/metadata/creationTimestamp",
+    "/metadata/generation",
+    "/metadata/managedFields",
+    "/metadata/labels/operator.wasm~1applied-by",
+];
+
+/// What an intercepted write would have done to its object.
+pub enum Intent {
+    /// The object would have the given fields, as JSON pointers to their values. `None`
+    /// means the field would be removed.
+    Fields(Vec<(String, Option<Value>)>),
+    /// The object would no longer exist.
+    Deleted,
+    /// The write does not target a single object, so it is recorded without a comparison.
+    Unchecked,
+}
+
+impl Intent {
+    /// The fields set by an object or a merge patch. Arrays are compared as a whole, and a
+    /// `null` in a merge patch removes the field.
+    pub fn from_object(json: &str) -> Result<Self, String> {
+        let value: Value =
+            serde_json::from_str(json).map_err(|e| format!("Invalid resource JSON: {}", e))?;
+        let mut fields = Vec::new();
+        collect_fields(String::new(), value, &mut fields);
+        Ok(Self::Fields(fields))
+    }
+
+    /// The fields set or removed by a JSON patch. `test`, `move` and `copy` operations are
+    /// not compared.
+    pub fn from_json_patch(json: &str) -> Result<Self, String> {
+        let operations: Vec<Value> =
+            serde_json::from_str(json).map_err(|e| format!("Invalid JSON patch: {}", e))?;
+        let fields = operations
+            .into_iter()
+            .filter_map(|mut operation| {
+                let path = operation.get("path")?.as_str()?.to_string();
+                match operation.get("op")?.as_str()? {
+                    "add" | "replace" => Some((path, Some(operation.get_mut("value")?.take()))),
+                    "remove" => Some((path, None)),
+                    _ => None,
+                }
+            })
+            .collect();
+        Ok(Self::Fields(fields))
+    }
+}
+
+fn collect_fields(path: String, value: Value, fields: &mut Vec<(String, Option<Value>)>) {
+    match value {
+        Value::Object(object) if !object.is_empty() => {
+            for (key, value) in object {
+                let key = key.replace('~', "~0").replace('/', "~1");
+                collect_fields(format!("{}/{}", path, key), value, fields);
+            }
+        }
+        Value::Null => fields.push((path, None)),
+        value => fields.push((path, Some(value))),
+    }
+}
+
+/// A field where the intercepted write differs from the live object.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldDifference {
+    /// JSON pointer to the field.
+    pub path: String,
+    /// The value the shadow operator would have written; absent if it would remove it.
+    pub intended: Option<Value>,
+    /// The value in the live object; absent if the field or the object does not exist.
+    pub actual: Option<Value>,
+}
+
+/// A write of an operator in shadow mode, compared with the live object.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowWrite {
+    pub timestamp_ms: u64,
+    pub verb: String,
+    pub kind: String,
+    pub namespace: String,
+    pub name: String,
+    /// Whether the live object matches the write. Absent for writes that were not compared.
+    pub matches: Option<bool>,
+    pub differences: Vec<FieldDifference>,
+}
+
+/// Compares the intent of a write with the live object.
+fn compare(intent: &Intent, live: Option<&Value>) -> (Option<bool>, Vec<FieldDifference>) {
+    match intent {
+        Intent::Fields(fields) => {
+            let differences: Vec<_> = fields
+                .iter()
+                .filter(|(path, _)| !IGNORED_FIELDS.iter().any(|f| path.starts_with(f)))
+                .filter_map(|(path, intended)| {
+                    let actual = live.and_then(|live| live.pointer(path)).cloned();
+                    (actual != *intended).then(|| FieldDifference {
+                        path: path.clone(),
+                        intended: intended.clone(),
+                        actual,
+                    })
+                })
+                .collect();
+            (Some(differences.is_empty()), differences)
+        }
+        Intent::Deleted => (Some(live.is_none()), Vec::new()),
+        Intent::Unchecked => (None, Vec::new()),
+    }
+}
+
+impl State {
+    /// Records a write of an operator in shadow mode instead of applying it.
+    pub async fn shadow_write(
+        &mut self,
+        verb: &str,
+        kind: &str,
+        name: &str,
+        namespace: &str,
+        intent: Intent,
+    ) -> Result<(), K8sError> {
+        let intent = if name.is_empty() {
+            // Objects named by the API server cannot be matched with a live object.
+            Intent::Unchecked
+        } else {
+            intent
+        };
+        let live = match intent {
+            Intent::Unchecked => None,
+            _ => self
+                .kubernetes_service
+                .find_resource(kind, name, namespace)
+                .await
+                .map_err(K8sError::from_anyhow)?
+                .map(|json| serde_json::from_str::<Value>(&json))
+                .transpose()
+                .map_err(|e| format!("Invalid live object: {}", e))?,
+        };
+        let (matches, differences) = compare(&intent, live.as_ref());
+
+        let outcome = match matches {
+            Some(true) => "match",
+            Some(false) => "diverged",
+            None => "unchecked",
+        };
+        if matches == Some(false) {
+            warn!(
+                "Shadow operator '{}' diverged on {} of {} '{}/{}'",
+                self.metadata.name, verb, kind, namespace, name
+            );
+        } else {
+            info!(
+                "Shadow operator '{}' intercepted {} of {} '{}/{}': {}",
+                self.metadata.name, verb, kind, namespace, name, outcome
+            );
+        }
+        metrics::increment(
+            "wasm_operator_shadow_writes_total",
+            &[("operator", &self.metadata.name), ("outcome", outcome)],
+        );
+
+        let timestamp_ms = now_ms();
+        self.introspection
+            .lock()
+            .unwrap()
+            .record_shadow_write(ShadowWrite {
+                timestamp_ms,
+                verb: verb.to_string(),
+                kind: kind.to_string(),
+                namespace: namespace.to_string(),
+                name: name.to_string(),
+                matches,
+                differences,
+            });
+        Ok(())
+    }
+
+    /// Records a write request started by an operator in shadow mode, and returns the
+    /// result the request would have had. Returns `None` for requests that are not writes.
+    pub async fn shadow_request(
+        &mut self,
+        request: &ApiRequest,
+    ) -> Option<Result<String, K8sError>> {
+        Some(match request {
+            ApiRequest::Get(_) => return None,
+            ApiRequest::Create(create) => {
+                let name = object_name(&create.resource_json);
+                let intent = match Intent::from_object(&create.resource_json) {
+                    Ok(intent) => intent,
+                    Err(e) => return Some(Err(e.into())),
+                };
+                self.shadow_write("create", &create.kind, &name, &create.namespace, intent)
+                    .await
+                    .map(|()| name)
+            }
+            ApiRequest::Update(update) => {
+                let intent = match Intent::from_object(&update.resource_json) {
+                    Ok(intent) => intent,
+                    Err(e) => return Some(Err(e.into())),
+                };
+                let target = &update.target;
+                self.shadow_write(
+                    "update",
+                    &target.kind,
+                    &target.name,
+                    &target.namespace,
+                    intent,
+                )
+                .await
+                .map(|()| String::new())
+            }
+            ApiRequest::Delete(target) => self
+                .shadow_write(
+                    "delete",
+                    &target.kind,
+                    &target.name,
+                    &target.namespace,
+                    Intent::Deleted,
+                )
+                .await
+                .map(|()| String::new()),
+        })
+    }
+}
+
+/// Returns the name in the metadata of an object, or an empty string if it has none.
+pub fn object_name(resource_json: &str) -> String {
+    serde_json::from_str::<Value>(resource_json)
+        .ok()
+        .and_then(|value| {
+            value
+                .pointer("/metadata/name")?
+                .as_str()
+                .map(str::to_string)
+        })
+        .unwrap_or_default()
+}
@@ -8,14 +8,481 @@
 
 use std::sync::Arc;
 
+use crate::config::metadata::{ReadGrant, WasmComponentMetadata};
+use crate::config::runtime::RuntimeConfig;
+use crate::host::api::bindings::local::operator::types::{ApiRequest, K8sError, ObjectReference};
+use crate::host::budget::Budget;
+use crate::host::extensions::ExtensionData;
+use crate::host::handlers::Handlers;
+use crate::host::locks::LockTable;
+use crate::host::message_bus::MessageBus;
+use crate::host::object::K8sObject;
+use crate::host::ownership::{self, ObjectKey, OwnershipGraph, RequestWrite};
+use crate::host::reconcile_queue::ReconcileQueue;
+use crate::host::timers::TimerQueue;
+use crate::kubernetes::lease::LeaseManager;
 use crate::kubernetes::KubernetesService;
-use wasmtime::component::{HasData, ResourceTable};
+use crate::metrics;
+use crate::runtime::introspection::SharedIntrospection;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::OwnerReference;
+use kube::api::DynamicObject;
+use serde_json::{json, Value};
+use tracing::{debug, info};
+use wasmtime::component::{HasData, Resource, ResourceTable, ResourceTableError};
+use wasmtime::StoreLimits;
 use wasmtime_wasi::p2::{IoView, WasiCtx, WasiView};
 
+/// Label marking the objects applied by an operator with applied-set tracking enabled.
+pub const APPLIED_BY_LABEL: &str = "operator.wasm/applied-by";
+
+/// The object a reconcile is running for.
+pub struct ReconcileTarget {
+    /// A controller reference to the object.
+    pub owner: OwnerReference,
+    /// Namespace of the object, empty for cluster-scoped objects.
+    pub namespace: String,
+}
+
+/// What the guest asked the host to do once the call it is handling returns.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LifecycleRequests {
+    pub checkpoint: bool,
+    pub unload: bool,
+}
+
+impl LifecycleRequests {
+    /// Adds the requests of another instance of the same operator.
+    pub fn merge(&mut self, other: LifecycleRequests) {
+        self.checkpoint |= other.checkpoint;
+        self.unload |= other.unload;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.checkpoint && !self.unload
+    }
+}
+
 pub struct State {
+    pub metadata: WasmComponentMetadata,
+    pub config: Arc<RuntimeConfig>,
     pub wasi_ctx: WasiCtx,
     pub kubernetes_service: Arc<KubernetesService>,
+    pub leases: Arc<LeaseManager>,
+    pub locks: Arc<LockTable>,
+    /// Who holds the locks this instance takes: the operator name, or `<operator>#<n>` for
+    /// an extra instance running parallel reconciles.
+    pub lock_holder: String,
+    pub timers: Arc<TimerQueue>,
+    pub reconcile_queue: Arc<ReconcileQueue>,
+    pub ownership: Arc<OwnershipGraph>,
+    pub message_bus: Arc<MessageBus>,
     pub resources: ResourceTable,
+    pub introspection: SharedIntrospection,
+    pub limits: StoreLimits,
+    pub budget: Budget,
+    /// The object being reconciled, while a reconcile runs.
+    pub reconciling: Option<ReconcileTarget>,
+    /// The object being reconciled, for `reconcile-object`, if the operator reads it lazily.
+    pub reconcile_object: Option<Arc<DynamicObject>>,
+    pub lifecycle_requests: LifecycleRequests,
+    pub extensions: ExtensionData,
+    /// The optional exports of the instance, resolved once it is instantiated.
+    pub handlers: Handlers,
+}
+
+impl State {
+    /// Rejects write calls when the runtime is read-only, and write calls into namespaces
+    /// excluded for this operator.
+    pub fn check_namespace_writable(&self, namespace: &str) -> Result<(), K8sError> {
+        if self.config.read_only {
+            info!(
+                "Read-only mode: dropped a write of operator '{}' to namespace '{}'",
+                self.metadata.name, namespace
+            );
+            metrics::increment(
+                "wasm_operator_read_only_writes_total",
+                &[("operator", &self.metadata.name)],
+            );
+            return Err(K8sError::read_only(format!(
+                "The runtime does not apply writes, nothing was changed in namespace '{}'",
+                namespace
+            )));
+        }
+        if self.config.is_namespace_excluded(namespace, &self.metadata) {
+            metrics::increment(
+                "wasm_operator_excluded_namespace_writes_total",
+                &[("operator", &self.metadata.name)],
+            );
+            return Err(K8sError::forbidden(format!(
+                "Namespace '{}' is excluded for operator '{}'",
+                namespace, self.metadata.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Rejects reads from namespaces excluded for this operator.
+    pub fn check_namespace_readable(&self, namespace: &str) -> Result<(), K8sError> {
+        if self.config.is_namespace_excluded(namespace, &self.metadata) {
+            return Err(K8sError::forbidden(format!(
+                "Namespace '{}' is excluded for operator '{}'",
+                namespace, self.metadata.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Rejects writes to the key-value store when the runtime is read-only. The store lives
+    /// in the namespace of the parent, so namespace exclusions do not apply to it.
+    pub fn check_kv_writable(&self) -> Result<(), K8sError> {
+        if self.config.read_only {
+            metrics::increment(
+                "wasm_operator_read_only_writes_total",
+                &[("operator", &self.metadata.name)],
+            );
+            return Err(K8sError::read_only(
+                "The runtime does not apply writes, the key-value store was not changed",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Rejects an object the operator creates or updates if it does not match the OpenAPI
+    /// schema of its kind, when `validate-writes` is set. Updates are apply patches, so
+    /// they may leave out required fields. A schema that cannot be fetched does not block
+    /// the write, which the API server validates anyway.
+    pub async fn check_schema(
+        &mut self,
+        kind: &str,
+        resource_json: &str,
+        update: bool,
+    ) -> Result<(), K8sError> {
+        if !self.config.validate_writes {
+            return Ok(());
+        }
+        // Invalid JSON is reported by the write itself.
+        let Ok(object) = serde_json::from_str::<Value>(resource_json) else {
+            return Ok(());
+        };
+        let errors = match self
+            .kubernetes_service
+            .validate_object(kind, &object, update)
+            .await
+        {
+            Ok(errors) => errors,
+            Err(e) => {
+                debug!("Skipped the schema check of a {}: {:#}", kind, e);
+                return Ok(());
+            }
+        };
+        if errors.is_empty() {
+            return Ok(());
+        }
+        metrics::increment(
+            "wasm_operator_schema_rejections_total",
+            &[("operator", &self.metadata.name), ("kind", kind)],
+        );
+        let fields: Vec<String> = errors
+            .iter()
+            .map(|error| format!("{} {}", error.field, error.message))
+            .collect();
+        Err(K8sError::invalid(format!(
+            "{} does not match its schema: {}",
+            kind,
+            fields.join("; ")
+        )))
+    }
+
+    /// Rejects reads of a Secret or ConfigMap that is not granted to this operator. `kind`
+    /// is only used in the error, and a `None` key stands for the whole object.
+    pub fn check_read_granted(
+        &self,
+        kind: &str,
+        grants: &[ReadGrant],
+        name: &str,
+        namespace: &str,
+        key: Option<&str>,
+    ) -> Result<(), K8sError> {
+        if ReadGrant::allows(grants, name, namespace, key) {
+            return Ok(());
+        }
+        metrics::increment(
+            "wasm_operator_denied_reads_total",
+            &[("operator", &self.metadata.name), ("kind", kind)],
+        );
+        Err(K8sError::forbidden(match key {
+            Some(key) => format!(
+                "Key '{}' of {} '{}/{}' is not granted to operator '{}'",
+                key, kind, namespace, name, self.metadata.name
+            ),
+            None => format!(
+                "{} '{}/{}' is not granted to operator '{}'",
+                kind, namespace, name, self.metadata.name
+            ),
+        }))
+    }
+
+    /// The object behind a `k8s-object` handle. Fails for a handle that is not in the
+    /// resource table, e.g. one the guest already dropped.
+    pub fn object(
+        &self,
+        object: &Resource<K8sObject>,
+    ) -> Result<&DynamicObject, ResourceTableError> {
+        self.resources.get(object).map(K8sObject::get)
+    }
+
+    /// A reference to the object being reconciled, if a reconcile runs.
+    pub fn reconciling_reference(&self) -> Option<ObjectReference> {
+        self.reconciling.as_ref().map(|target| ObjectReference {
+            kind: target.owner.kind.clone(),
+            name: target.owner.name.clone(),
+            namespace: target.namespace.clone(),
+        })
+    }
+
+    /// Whether a kind resolves to core Secrets, which can only be read when granted.
+    pub fn is_secret(&self, kind: &str) -> bool {
+        self.kubernetes_service.is_core_secret(kind)
+    }
+
+    /// Rejects reads of whole Secrets that are not granted to this operator in full.
+    pub fn check_readable(&self, kind: &str, name: &str, namespace: &str) -> Result<(), K8sError> {
+        if !self.is_secret(kind) {
+            return Ok(());
+        }
+        self.check_read_granted(
+            "Secret",
+            &self.metadata.readable_secrets,
+            name,
+            namespace,
+            None,
+        )
+    }
+
+    /// Rejects resource payloads from the guest that exceed the configured size limit.
+    pub fn check_guest_body_size(&self, resource_json: &str) -> Result<(), K8sError> {
+        let limit = self.config.size_limits.max_guest_body_bytes;
+        if resource_json.len() > limit {
+            metrics::increment(
+                "wasm_operator_oversized_payloads_total",
+                &[("operator", &self.metadata.name), ("direction", "outbound")],
+            );
+            return Err(K8sError::invalid(format!(
+                "Resource JSON of {} bytes exceeds the limit of {} bytes",
+                resource_json.len(),
+                limit
+            )));
+        }
+        Ok(())
+    }
+
+    /// Labels a resource submitted by the guest as applied by this operator, if applied-set
+    /// tracking is enabled.
+    pub fn label_applied(
+        &self,
+        kind: &str,
+        namespace: &str,
+        resource_json: String,
+    ) -> Result<String, String> {
+        if !self.metadata.track_applied {
+            return Ok(resource_json);
+        }
+        self.introspection
+            .lock()
+            .unwrap()
+            .record_applied(kind, namespace);
+        let mut resource: Value = serde_json::from_str(&resource_json)
+            .map_err(|e| format!("Invalid resource JSON: {}", e))?;
+        let metadata = resource
+            .as_object_mut()
+            .ok_or("Resource JSON is not an object")?
+            .entry("metadata")
+            .or_insert_with(|| json!({}));
+        let labels = metadata
+            .as_object_mut()
+            .ok_or("Resource metadata is not an object")?
+            .entry("labels")
+            .or_insert_with(|| json!({}));
+        labels
+            .as_object_mut()
+            .ok_or("Resource labels are not an object")?
+            .insert(APPLIED_BY_LABEL.to_string(), json!(self.metadata.name));
+        Ok(resource.to_string())
+    }
+
+    /// Adds an owner reference to the object being reconciled to a resource created by the
+    /// guest, if enabled for this operator. Owners can only be in the namespace of the
+    /// resource or cluster-scoped, and resources that already have owner references are
+    /// left as they are.
+    pub fn set_owner_reference(
+        &self,
+        namespace: &str,
+        resource_json: String,
+    ) -> Result<String, String> {
+        if !self.metadata.set_owner_references {
+            return Ok(resource_json);
+        }
+        let Some(target) = &self.reconciling else {
+            return Ok(resource_json);
+        };
+        if !target.namespace.is_empty() && target.namespace != namespace {
+            return Ok(resource_json);
+        }
+        let mut resource: Value = serde_json::from_str(&resource_json)
+            .map_err(|e| format!("Invalid resource JSON: {}", e))?;
+        let metadata = resource
+            .as_object_mut()
+            .ok_or("Resource JSON is not an object")?
+            .entry("metadata")
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .ok_or("Resource metadata is not an object")?;
+        if metadata.contains_key("ownerReferences") {
+            return Ok(resource_json);
+        }
+        metadata.insert("ownerReferences".to_string(), json!([target.owner]));
+        Ok(resource.to_string())
+    }
+
+    /// Records a write that succeeded in the ownership graph. The owner is the controller
+    /// in the owner references of the written object, or else the object being reconciled.
+    pub fn record_write(
+        &self,
+        write: &str,
+        kind: &str,
+        name: &str,
+        namespace: &str,
+        resource_json: &str,
+    ) {
+        if name.is_empty() {
+            return;
+        }
+        let object = ObjectKey {
+            kind: kind.to_string(),
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        };
+        let owner = self
+            .owner_of_write(namespace, resource_json)
+            .filter(|owner| owner != &object);
+        self.ownership
+            .record_write(&self.metadata.name, object, owner, write);
+    }
+
+    /// The owner of a written object: the controller in its owner references, or else the
+    /// object being reconciled.
+    fn owner_of_write(&self, namespace: &str, resource_json: &str) -> Option<ObjectKey> {
+        serde_json::from_str::<Value>(resource_json)
+            .ok()
+            .and_then(|resource| ownership::owner_of(&resource, namespace))
+            .or_else(|| {
+                self.reconciling.as_ref().map(|target| ObjectKey {
+                    kind: target.owner.kind.clone(),
+                    namespace: target.namespace.clone(),
+                    name: target.owner.name.clone(),
+                })
+            })
+    }
+
+    /// Returns what to record in the ownership graph once a checked request succeeds, if
+    /// it writes.
+    pub fn request_write(&self, request: &ApiRequest) -> Option<RequestWrite> {
+        let (write, kind, namespace, name, owner) = match request {
+            ApiRequest::Get(_) => return None,
+            ApiRequest::Create(create) => (
+                "create",
+                &create.kind,
+                &create.namespace,
+                None,
+                self.owner_of_write(&create.namespace, &create.resource_json),
+            ),
+            ApiRequest::Update(update) => (
+                "update",
+                &update.target.kind,
+                &update.target.namespace,
+                Some(update.target.name.clone()),
+                self.owner_of_write(&update.target.namespace, &update.resource_json),
+            ),
+            ApiRequest::Delete(target) => (
+                "delete",
+                &target.kind,
+                &target.namespace,
+                Some(target.name.clone()),
+                None,
+            ),
+        };
+        Some(RequestWrite {
+            graph: self.ownership.clone(),
+            operator: self.metadata.name.clone(),
+            write,
+            kind: kind.clone(),
+            namespace: namespace.clone(),
+            name,
+            owner,
+        })
+    }
+
+    /// Forgets a deleted object in the ownership graph.
+    pub fn record_delete(&self, kind: &str, name: &str, namespace: &str) {
+        self.ownership.record_delete(&ObjectKey {
+            kind: kind.to_string(),
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        });
+    }
+
+    /// Applies the checks of the corresponding host call to a request started by the guest.
+    pub async fn check_request(&mut self, request: ApiRequest) -> Result<ApiRequest, K8sError> {
+        Ok(match request {
+            ApiRequest::Get(target) => {
+                self.check_readable(&target.kind, &target.name, &target.namespace)?;
+                ApiRequest::Get(target)
+            }
+            ApiRequest::Create(mut create) => {
+                self.check_namespace_writable(&create.namespace)?;
+                self.check_guest_body_size(&create.resource_json)?;
+                self.check_schema(&create.kind, &create.resource_json, false)
+                    .await?;
+                create.resource_json =
+                    self.label_applied(&create.kind, &create.namespace, create.resource_json)?;
+                create.resource_json =
+                    self.set_owner_reference(&create.namespace, create.resource_json)?;
+                ApiRequest::Create(create)
+            }
+            ApiRequest::Update(mut update) => {
+                self.check_namespace_writable(&update.target.namespace)?;
+                self.check_guest_body_size(&update.resource_json)?;
+                self.check_schema(&update.target.kind, &update.resource_json, true)
+                    .await?;
+                update.resource_json = self.label_applied(
+                    &update.target.kind,
+                    &update.target.namespace,
+                    update.resource_json,
+                )?;
+                ApiRequest::Update(update)
+            }
+            ApiRequest::Delete(target) => {
+                self.check_namespace_writable(&target.namespace)?;
+                ApiRequest::Delete(target)
+            }
+        })
+    }
+
+    /// Restricts a label selector from the guest to the objects applied by this operator.
+    pub fn applied_set_selector(&self, selector: &str) -> Result<String, String> {
+        if !self.metadata.track_applied {
+            return Err(format!(
+                "Applied-set tracking is not enabled for operator '{}'",
+                self.metadata.name
+            ));
+        }
+        let applied = format!("{}={}", APPLIED_BY_LABEL, self.metadata.name);
+        if selector.trim().is_empty() {
+            Ok(applied)
+        } else {
+            Ok(format!("{},{}", selector, applied))
+        }
+    }
 }
 
 impl WasiView for State {
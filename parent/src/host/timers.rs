@@ -0,0 +1,90 @@
+//! # Timers Module
+//!
+//! This module keeps the timers operators set with `schedule`, so they can resync
+//! periodically, retry with backoff or clean up expired objects without busy-looping or
+//! waiting for a watch event. The runtime delivers due timers to the `on-timer` export of
+//! their operator, waking it if it is unloaded. Timers live in the parent's memory, so
+//! they do not survive a restart of the parent.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// The most timers one operator may have pending at a time.
+pub const MAX_TIMERS_PER_OPERATOR: usize = 1024;
+
+/// The pending timers of all operators.
+#[derive(Default)]
+pub struct TimerQueue {
+    /// Deadlines of the pending timers, by operator and token.
+    pending: Mutex<HashMap<(String, String), Instant>>,
+    /// Notified whenever a timer is scheduled.
+    scheduled: Notify,
+}
+
+impl TimerQueue {
+    /// Schedules a timer, replacing a pending timer of the operator with the same token.
+    pub fn schedule(&self, operator: &str, token: String, delay: Duration) -> Result<(), String> {
+        let mut pending = self.pending.lock().unwrap();
+        let key = (operator.to_string(), token);
+        if !pending.contains_key(&key)
+            && pending.keys().filter(|(o, _)| o == operator).count() >= MAX_TIMERS_PER_OPERATOR
+        {
+            return Err(format!(
+                "Operator '{}' already has {} pending timers",
+                operator, MAX_TIMERS_PER_OPERATOR
+            ));
+        }
+        pending.insert(key, Instant::now() + delay);
+        drop(pending);
+        self.scheduled.notify_one();
+        Ok(())
+    }
+
+    /// Waits until at least one timer is due, and removes and returns all due timers as
+    /// pairs of operator and token.
+    pub async fn next_due(&self) -> Vec<(String, String)> {
+        loop {
+            // Register for the notification before checking, so a timer scheduled in
+            // between is not missed.
+            let scheduled = self.scheduled.notified();
+            let (due, next) = self.take_due();
+            if !due.is_empty() {
+                return due;
+            }
+            match next {
+                Some(deadline) => {
+                    let _ = tokio::time::timeout_at(deadline, scheduled).await;
+                }
+                None => scheduled.await,
+            }
+        }
+    }
+
+    /// Removes the due timers, and returns them with the deadline of the next pending one.
+    fn take_due(&self) -> (Vec<(String, String)>, Option<Instant>) {
+        let now = Instant::now();
+        let mut pending = self.pending.lock().unwrap();
+        let mut due = Vec::new();
+        pending.retain(|key, deadline| {
+            if *deadline <= now {
+                due.push(key.clone());
+                false
+            } else {
+                true
+            }
+        });
+        (due, pending.values().min().copied())
+    }
+
+    /// Cancels all pending timers of an operator.
+    pub fn cancel_all(&self, operator: &str) {
+        self.pending
+            .lock()
+            .unwrap()
+            .retain(|(o, _), _| o != operator);
+    }
+}
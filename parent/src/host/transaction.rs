@@ -0,0 +1,308 @@
+//! # Transaction Module
+//!
+//! This module implements the `transaction` resource, which lets a guest stage several
+//! writes and apply them together. The writes are applied in order; if one of them fails,
+//! the writes applied before it are undone in reverse order. Kubernetes has no
+//! multi-object transactions, so the rollback is best-effort: objects that were created
+//! are deleted, updated objects are re-applied in their previous state, and deleted
+//! objects are created again.
+
+use serde_json::Value;
+use tracing::warn;
+
+use crate::host::api::bindings::local::operator::types::K8sError;
+use crate::host::shadow;
+use crate::kubernetes::KubernetesService;
+
+/// A write staged in a transaction.
+enum StagedWrite {
+    Create {
+        kind: String,
+        namespace: String,
+        resource_json: String,
+    },
+    Update {
+        kind: String,
+        name: String,
+        namespace: String,
+        resource_json: String,
+    },
+    Delete {
+        kind: String,
+        name: String,
+        namespace: String,
+    },
+}
+
+/// Reverts a write that was applied.
+enum Undo {
+    Delete {
+        kind: String,
+        name: String,
+        namespace: String,
+    },
+    Restore {
+        kind: String,
+        name: String,
+        namespace: String,
+        resource_json: String,
+    },
+    Recreate {
+        kind: String,
+        namespace: String,
+        resource_json: String,
+    },
+}
+
+/// An object a staged write changes.
+pub struct StagedObject {
+    /// The host call of the write: `create`, `update` or `delete`.
+    pub write: &'static str,
+    pub kind: String,
+    /// Name of the object, empty for objects created with a generated name.
+    pub name: String,
+    pub namespace: String,
+    /// The object written, empty for deletes.
+    pub resource_json: String,
+}
+
+#[derive(Default)]
+pub struct Transaction {
+    writes: Vec<StagedWrite>,
+}
+
+impl Transaction {
+    pub fn stage_create(&mut self, kind: String, namespace: String, resource_json: String) {
+        self.writes.push(StagedWrite::Create {
+            kind,
+            namespace,
+            resource_json,
+        });
+    }
+
+    pub fn stage_update(
+        &mut self,
+        kind: String,
+        name: String,
+        namespace: String,
+        resource_json: String,
+    ) {
+        self.writes.push(StagedWrite::Update {
+            kind,
+            name,
+            namespace,
+            resource_json,
+        });
+    }
+
+    pub fn stage_delete(&mut self, kind: String, name: String, namespace: String) {
+        self.writes.push(StagedWrite::Delete {
+            kind,
+            name,
+            namespace,
+        });
+    }
+
+    /// Returns the objects the staged writes change.
+    pub fn staged_objects(&self) -> Vec<StagedObject> {
+        self.writes
+            .iter()
+            .map(|write| match write {
+                StagedWrite::Create {
+                    kind,
+                    namespace,
+                    resource_json,
+                } => StagedObject {
+                    write: "create",
+                    kind: kind.clone(),
+                    name: shadow::object_name(resource_json),
+                    namespace: namespace.clone(),
+                    resource_json: resource_json.clone(),
+                },
+                StagedWrite::Update {
+                    kind,
+                    name,
+                    namespace,
+                    resource_json,
+                } => StagedObject {
+                    write: "update",
+                    kind: kind.clone(),
+                    name: name.clone(),
+                    namespace: namespace.clone(),
+                    resource_json: resource_json.clone(),
+                },
+                StagedWrite::Delete {
+                    kind,
+                    name,
+                    namespace,
+                } => StagedObject {
+                    write: "delete",
+                    kind: kind.clone(),
+                    name: name.clone(),
+                    namespace: namespace.clone(),
+                    resource_json: String::new(),
+                },
+            })
+            .collect()
+    }
+
+    /// Applies the staged writes, rolling back the applied ones if any of them fails.
+    ///
+    /// The staged writes are cleared, so the transaction can be reused. The error keeps the
+    /// status and reason of the write that failed.
+    pub async fn commit(&mut self, kubernetes_service: &KubernetesService) -> Result<(), K8sError> {
+        let writes = std::mem::take(&mut self.writes);
+        let total = writes.len();
+        let mut undo_log = Vec::new();
+
+        for (index, write) in writes.into_iter().enumerate() {
+            match apply(kubernetes_service, write).await {
+                Ok(undo) => undo_log.push(undo),
+                Err(e) => {
+                    let failed_undos = rollback(kubernetes_service, undo_log).await;
+                    let mut error = K8sError::from_anyhow(e);
+                    let mut message = format!(
+                        "Write {} of {} failed, rolled back the writes before it: {}",
+                        index + 1,
+                        total,
+                        error.message
+                    );
+                    if failed_undos > 0 {
+                        message.push_str(&format!(
+                            " ({} write(s) could not be rolled back)",
+                            failed_undos
+                        ));
+                    }
+                    error.message = message;
+                    return Err(error);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Applies a write and returns how to undo it.
+async fn apply(kubernetes_service: &KubernetesService, write: StagedWrite) -> anyhow::Result<Undo> {
+    match write {
+        StagedWrite::Create {
+            kind,
+            namespace,
+            resource_json,
+        } => {
+            let name = kubernetes_service
+                .create_resource(&kind, &namespace, &resource_json)
+                .await?;
+            Ok(Undo::Delete {
+                kind,
+                name,
+                namespace,
+            })
+        }
+        StagedWrite::Update {
+            kind,
+            name,
+            namespace,
+            resource_json,
+        } => {
+            let previous = kubernetes_service
+                .find_resource(&kind, &name, &namespace)
+                .await?;
+            kubernetes_service
+                .update_resource(&kind, &name, &namespace, &resource_json)
+                .await?;
+            // An update of an object that does not exist yet creates it.
+            Ok(match previous {
+                Some(previous) => Undo::Restore {
+                    kind,
+                    name,
+                    namespace,
+                    resource_json: without_server_fields(&previous)?,
+                },
+                None => Undo::Delete {
+                    kind,
+                    name,
+                    namespace,
+                },
+            })
+        }
+        StagedWrite::Delete {
+            kind,
+            name,
+            namespace,
+        } => {
+            let previous = kubernetes_service
+                .get_resource(&kind, &name, &namespace)
+                .await?;
+            kubernetes_service
+                .delete_resource(&kind, &name, &namespace)
+                .await?;
+            Ok(Undo::Recreate {
+                kind,
+                namespace,
+                resource_json: without_server_fields(&previous)?,
+            })
+        }
+    }
+}
+
+/// Undoes the applied writes in reverse order and returns the number of failed undos.
+async fn rollback(kubernetes_service: &KubernetesService, undo_log: Vec<Undo>) -> usize {
+    let mut failed = 0;
+    for undo in undo_log.into_iter().rev() {
+        let result = match &undo {
+            Undo::Delete {
+                kind,
+                name,
+                namespace,
+            } => {
+                kubernetes_service
+                    .delete_resource(kind, name, namespace)
+                    .await
+            }
+            Undo::Restore {
+                kind,
+                name,
+                namespace,
+                resource_json,
+            } => {
+                kubernetes_service
+                    .update_resource(kind, name, namespace, resource_json)
+                    .await
+            }
+            Undo::Recreate {
+                kind,
+                namespace,
+                resource_json,
+            } => kubernetes_service
+                .create_resource(kind, namespace, resource_json)
+                .await
+                .map(|_| ()),
+        };
+        if let Err(e) = result {
+            warn!("Failed to roll back transaction write: {}", e);
+            failed += 1;
+        }
+    }
+    failed
+}
+
+/// Removes the fields set by the API server, so an object can be written back.
+fn without_server_fields(resource_json: &str) -> anyhow::Result<String> {
+    let mut resource: Value = serde_json::from_str(resource_json)?;
+    if let Some(metadata) = resource.get_mut("metadata").and_then(Value::as_object_mut) {
+        for field in [
+            "resourceVersion",
+            "uid",
+            "creationTimestamp",
+            "generation",
+            "managedFields",
+        ] {
+            metadata.remove(field);
+        }
+    }
+    if let Some(resource) = resource.as_object_mut() {
+        resource.remove("status");
+    }
+    Ok(resource.to_string())
+}
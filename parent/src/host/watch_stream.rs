@@ -0,0 +1,87 @@
+//! # Watch Stream Module
+//!
+//! This module implements the `watch-stream` resource, which lets a guest consume the
+//! events of a watch itself instead of having the host dispatch them as reconciles. An
+//! operator can use it to follow secondary objects for as long as it needs them, e.g.
+//! while waiting for the Pods of a Job to finish. The stream is closed when the guest drops
+//! it or the operator is unloaded.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use kube::api::DynamicObject;
+use kube::runtime::watcher::{self, Event};
+
+use crate::host::api::bindings::local::operator::types::{EventType, WatchEvent};
+use crate::kubernetes::KubernetesService;
+use crate::runtime::events::SeenObjects;
+
+/// Longest a single `next-event` call waits for an event.
+pub const MAX_WAIT: Duration = Duration::from_secs(10);
+
+pub struct WatchStream {
+    kind: String,
+    stream: BoxStream<'static, Result<Event<DynamicObject>, watcher::Error>>,
+    seen: SeenObjects,
+}
+
+impl WatchStream {
+    /// Starts a watch on the objects of a kind that match the label selector. An empty
+    /// namespace watches all namespaces, or a cluster-scoped kind.
+    pub async fn new(
+        kubernetes_service: &KubernetesService,
+        kind: String,
+        namespace: &str,
+        label_selector: &str,
+    ) -> Result<Self> {
+        let (resource, _) = kubernetes_service.resolve_kind(&kind).await?;
+        let stream = watcher::watcher(
+            kubernetes_service.dynamic_api(resource, namespace),
+            watcher::Config::default().labels(label_selector),
+        )
+        .boxed();
+        Ok(Self {
+            kind,
+            stream,
+            seen: SeenObjects::default(),
+        })
+    }
+
+    /// Waits until `deadline` for the next event of an object whose namespace is not
+    /// `excluded`, and returns `None` if there is none by then. The objects that already
+    /// exist when the watch starts are returned as `added` events, and those listed again
+    /// unchanged after the watch is re-established as `resynced`. Errors do not end the
+    /// watch; the next call retries it.
+    pub async fn next_event(
+        &mut self,
+        deadline: Instant,
+        excluded: impl Fn(&str) -> bool,
+    ) -> Result<Option<WatchEvent>> {
+        loop {
+            let next = tokio::time::timeout_at(deadline.into(), self.stream.next()).await;
+            let (event_type, object) = match next {
+                Err(_) => return Ok(None),
+                Ok(None) => return Err(anyhow!("The watch on kind '{}' ended", self.kind)),
+                Ok(Some(event)) => match event? {
+                    Event::Apply(object) | Event::InitApply(object) => {
+                        (self.seen.apply(&object), object)
+                    }
+                    Event::Delete(object) => {
+                        self.seen.delete(&object);
+                        (EventType::Deleted, object)
+                    }
+                    Event::Init | Event::InitDone => continue,
+                },
+            };
+            if excluded(object.metadata.namespace.as_deref().unwrap_or_default()) {
+                continue;
+            }
+            return Ok(Some(WatchEvent {
+                event_type,
+                resource_json: serde_json::to_string(&object)?,
+            }));
+        }
+    }
+}
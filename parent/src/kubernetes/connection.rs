@@ -0,0 +1,60 @@
+//! # Connection Module
+//!
+//! This module applies the connection settings from the `kubernetes` section of the
+//! runtime configuration to the inferred kube config, for environments where the
+//! inferred config alone cannot reach the API server (proxies, private CAs, custom
+//! endpoints or slow links).
+
+use std::fs;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use kube::Config;
+
+use crate::config::runtime::KubernetesConfig;
+
+/// Applies the configured connection overrides to the kube config.
+pub fn apply(settings: &KubernetesConfig, config: &mut Config) -> Result<()> {
+    if let Some(cluster_url) = &settings.cluster_url {
+        config.cluster_url = cluster_url
+            .parse()
+            .with_context(|| format!("Invalid cluster URL '{}'", cluster_url))?;
+    }
+    if let Some(tls_server_name) = &settings.tls_server_name {
+        config.tls_server_name = Some(tls_server_name.clone());
+    }
+    if let Some(secs) = settings.connect_timeout_secs {
+        config.connect_timeout = Some(Duration::from_secs(secs));
+    }
+    if let Some(secs) = settings.read_timeout_secs {
+        config.read_timeout = Some(Duration::from_secs(secs));
+    }
+    if let Some(secs) = settings.write_timeout_secs {
+        config.write_timeout = Some(Duration::from_secs(secs));
+    }
+    if settings.disable_compression {
+        config.disable_compression = true;
+    }
+
+    if let Some(proxy_url) = &settings.proxy_url {
+        config.proxy_url = Some(
+            proxy_url
+                .parse()
+                .with_context(|| format!("Invalid proxy URL '{}'", proxy_url))?,
+        );
+    }
+
+    for path in &settings.ca_bundles {
+        let contents = fs::read(path)
+            .with_context(|| format!("Failed to read CA bundle {}", path.display()))?;
+        let certs = pem::parse_many(contents)
+            .with_context(|| format!("Invalid PEM in CA bundle {}", path.display()))?;
+        // Once root certificates are set, the client trusts only those, so the bundles
+        // are added next to the cluster CA rather than replacing it.
+        config
+            .root_cert
+            .get_or_insert_with(Vec::new)
+            .extend(certs.into_iter().map(|cert| cert.into_contents()));
+    }
+    Ok(())
+}
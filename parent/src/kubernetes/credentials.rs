@@ -0,0 +1,186 @@
+//! # Credentials Module
+//!
+//! This module implements the pluggable credential providers that can replace the
+//! credentials found by `Config::infer`. Exec-based providers (AWS EKS, GCP workload
+//! identity, or any custom plugin) and token files are refreshed by the kube client itself;
+//! tokens fetched from Vault are written to a token file that is refreshed periodically.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty};
+use hyper::Request;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use kube::config::{AuthInfo, ExecConfig};
+use kube::Config;
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::config::runtime::CredentialProvider;
+use crate::platform;
+
+const EXEC_API_VERSION: &str = "client.authentication.k8s.io/v1beta1";
+
+/// Set once the Vault refresh loop runs, so re-applying the provider when the client is
+/// rebuilt does not start a second one.
+static VAULT_REFRESH_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Replaces the credentials in the kube config with those of the configured provider.
+pub async fn apply(provider: &CredentialProvider, config: &mut Config) -> Result<()> {
+    config.auth_info = match provider {
+        CredentialProvider::TokenFile { path } => token_file_auth(path),
+        CredentialProvider::Exec { command, args, env } => {
+            exec_auth(command, args.clone(), env.clone())
+        }
+        CredentialProvider::AwsEks {
+            cluster_name,
+            region,
+        } => {
+            let mut args = vec![
+                "eks".to_string(),
+                "get-token".to_string(),
+                "--cluster-name".to_string(),
+                cluster_name.clone(),
+            ];
+            if let Some(region) = region {
+                args.extend(["--region".to_string(), region.clone()]);
+            }
+            exec_auth("aws", args, Default::default())
+        }
+        CredentialProvider::GcpWorkloadIdentity => {
+            exec_auth("gke-gcloud-auth-plugin", Vec::new(), Default::default())
+        }
+        CredentialProvider::Vault {
+            address,
+            secret_path,
+            field,
+            vault_token_file,
+            refresh_interval_secs,
+        } => {
+            let vault = VaultSource {
+                address: address.clone(),
+                secret_path: secret_path.clone(),
+                field: field.clone(),
+                vault_token_file: vault_token_file.clone(),
+            };
+            let token_path = std::env::temp_dir()
+                .join("wasm-operator")
+                .join("kube-token");
+            vault.refresh(&token_path).await?;
+            info!("Using Kubernetes credentials from Vault at {}", address);
+
+            if !VAULT_REFRESH_STARTED.swap(true, Ordering::SeqCst) {
+                let interval = Duration::from_secs(*refresh_interval_secs);
+                let refresh_path = token_path.clone();
+                tokio::spawn(async move {
+                    loop {
+                        tokio::time::sleep(interval).await;
+                        if let Err(e) = vault.refresh(&refresh_path).await {
+                            warn!("Failed to refresh Kubernetes token from Vault: {}", e);
+                        }
+                    }
+                });
+            }
+
+            token_file_auth(&token_path)
+        }
+    };
+    Ok(())
+}
+
+fn token_file_auth(path: &Path) -> AuthInfo {
+    AuthInfo {
+        token_file: Some(path.display().to_string()),
+        ..Default::default()
+    }
+}
+
+fn exec_auth(command: &str, args: Vec<String>, env: HashMap<String, String>) -> AuthInfo {
+    let env = env
+        .into_iter()
+        .map(|(name, value)| {
+            HashMap::from([("name".to_string(), name), ("value".to_string(), value)])
+        })
+        .collect();
+    AuthInfo {
+        exec: Some(ExecConfig {
+            api_version: Some(EXEC_API_VERSION.to_string()),
+            command: Some(command.to_string()),
+            args: Some(args),
+            env: Some(env),
+            drop_env: None,
+            interactive_mode: None,
+            provide_cluster_info: false,
+            cluster: None,
+        }),
+        ..Default::default()
+    }
+}
+
+/// A Kubernetes token stored in a Vault KV secret.
+struct VaultSource {
+    address: String,
+    secret_path: String,
+    field: String,
+    vault_token_file: PathBuf,
+}
+
+impl VaultSource {
+    /// Fetches the token from Vault and writes it to the token file read by the kube client.
+    async fn refresh(&self, token_path: &Path) -> Result<()> {
+        let token = self.fetch().await?;
+        platform::write_private(token_path, token)
+            .await
+            .with_context(|| format!("Failed to write token file {}", token_path.display()))
+    }
+
+    async fn fetch(&self) -> Result<String> {
+        let vault_token = tokio::fs::read_to_string(&self.vault_token_file)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to read Vault token from {}",
+                    self.vault_token_file.display()
+                )
+            })?;
+
+        let connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_native_roots()?
+            .https_or_http()
+            .enable_http1()
+            .build();
+        let client: Client<_, Empty<Bytes>> =
+            Client::builder(TokioExecutor::new()).build(connector);
+
+        let uri = format!(
+            "{}/v1/{}",
+            self.address.trim_end_matches('/'),
+            self.secret_path.trim_start_matches('/')
+        );
+        let request = Request::get(uri)
+            .header("X-Vault-Token", vault_token.trim())
+            .body(Empty::new())?;
+        let response = client
+            .request(request)
+            .await
+            .context("Failed to query Vault")?;
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+        if !status.is_success() {
+            return Err(anyhow!("Vault returned status {}", status));
+        }
+
+        let secret: Value = serde_json::from_slice(&body).context("Invalid Vault response")?;
+        // KV version 2 nests the secret data one level deeper than version 1.
+        secret["data"]["data"][&self.field]
+            .as_str()
+            .or_else(|| secret["data"][&self.field].as_str())
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Vault secret has no field '{}'", self.field))
+    }
+}
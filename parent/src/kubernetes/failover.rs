@@ -0,0 +1,183 @@
+//! # Failover Module
+//!
+//! This module lets the parent fail over between several API server endpoints, for highly
+//! available control planes without a load balancer in front of them and for edge clusters
+//! with flaky links. Each request goes to the active endpoint; if it cannot be reached,
+//! the request is retried on the other endpoints in order and the first one that answers
+//! becomes active. A background check probes `/readyz` of the active endpoint and moves
+//! off it when it stops answering or is not ready. Watches keep their resource version:
+//! once their connection to the old endpoint fails, they resume on the new one like after
+//! any other watch error.
+//!
+//! A request is retried when no response was received for it, so a write whose response
+//! was lost can reach the API server twice; creates then fail with `already-exists`.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use anyhow::{Context as _, Result};
+use futures::future::BoxFuture;
+use http::{Request, Response};
+use kube::client::Body;
+use kube::{Client, Config};
+use tower::Service;
+use tracing::{info, warn};
+
+use crate::metrics;
+
+/// How often the active endpoint is checked, unless configured otherwise.
+pub const DEFAULT_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long a health check waits for an endpoint to answer.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The API server endpoints of a client, and which one is active.
+struct Endpoints {
+    /// The URL of each endpoint, with a client that connects to it.
+    endpoints: Vec<(String, Client)>,
+    active: AtomicUsize,
+}
+
+/// Builds a client that fails over from the cluster URL of the config to the given URLs.
+pub fn client(config: Config, failover_urls: &[String], interval: Duration) -> Result<Client> {
+    let default_namespace = config.default_namespace.clone();
+    let mut endpoints = Vec::new();
+    let urls = std::iter::once(config.cluster_url.to_string()).chain(failover_urls.iter().cloned());
+    for url in urls {
+        let mut endpoint_config = config.clone();
+        endpoint_config.cluster_url = url
+            .parse()
+            .with_context(|| format!("Invalid failover URL '{}'", url))?;
+        let client = Client::try_from(endpoint_config)
+            .with_context(|| format!("Failed to create a client for {}", url))?;
+        endpoints.push((url, client));
+    }
+    info!(
+        "API server endpoints: {}",
+        endpoints
+            .iter()
+            .map(|(url, _)| url.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let endpoints = Arc::new(Endpoints {
+        endpoints,
+        active: AtomicUsize::new(0),
+    });
+    tokio::spawn(health_check_loop(Arc::downgrade(&endpoints), interval));
+    Ok(Client::new(
+        FailoverService { endpoints },
+        default_namespace,
+    ))
+}
+
+/// Checks the active endpoint until the client is dropped.
+async fn health_check_loop(endpoints: Weak<Endpoints>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let Some(endpoints) = endpoints.upgrade() else {
+            return;
+        };
+        endpoints.check_health().await;
+    }
+}
+
+impl Endpoints {
+    /// Sends a request to the active endpoint, or to the next one that answers.
+    async fn send(&self, request: Request<Body>) -> kube::Result<Response<Body>> {
+        // Requests are replayed on another endpoint, so their body is read up front.
+        let (parts, body) = request.into_parts();
+        let body = body.collect_bytes().await?;
+        let active = self.active.load(Ordering::Relaxed);
+        let mut last_error = None;
+        for offset in 0..self.endpoints.len() {
+            let index = (active + offset) % self.endpoints.len();
+            let (url, client) = &self.endpoints[index];
+            let request = Request::from_parts(parts.clone(), Body::from(body.clone()));
+            match client.send(request).await {
+                Ok(response) => {
+                    self.switch(active, index);
+                    return Ok(response);
+                }
+                Err(e) => {
+                    warn!("API server {} cannot be reached: {}", url, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.expect("a client has at least one endpoint"))
+    }
+
+    /// Moves off the active endpoint if it is not healthy and another one is.
+    async fn check_health(&self) {
+        let active = self.active.load(Ordering::Relaxed);
+        if self.is_healthy(active).await {
+            return;
+        }
+        warn!(
+            "API server {} failed its health check",
+            self.endpoints[active].0
+        );
+        for offset in 1..self.endpoints.len() {
+            let index = (active + offset) % self.endpoints.len();
+            if self.is_healthy(index).await {
+                self.switch(active, index);
+                return;
+            }
+        }
+    }
+
+    /// Whether an endpoint answers and is ready. A refused probe, e.g. for lack of
+    /// permission, still shows the API server is up.
+    async fn is_healthy(&self, index: usize) -> bool {
+        let request = Request::get("/readyz")
+            .body(Body::empty())
+            .expect("the probe request is valid");
+        let probe = self.endpoints[index].1.send(request);
+        match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, probe).await {
+            Ok(Ok(response)) => !response.status().is_server_error(),
+            _ => false,
+        }
+    }
+
+    /// Makes another endpoint active, unless another request switched already.
+    fn switch(&self, from: usize, to: usize) {
+        if from == to {
+            return;
+        }
+        if self
+            .active
+            .compare_exchange(from, to, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+        {
+            warn!(
+                "Failed over from API server {} to {}",
+                self.endpoints[from].0, self.endpoints[to].0
+            );
+            metrics::increment("wasm_operator_api_failovers_total", &[]);
+        }
+    }
+}
+
+/// Sends the requests of a client to its endpoints.
+#[derive(Clone)]
+struct FailoverService {
+    endpoints: Arc<Endpoints>,
+}
+
+impl Service<Request<Body>> for FailoverService {
+    type Response = Response<Body>;
+    type Error = kube::Error;
+    type Future = BoxFuture<'static, kube::Result<Response<Body>>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let endpoints = self.endpoints.clone();
+        Box::pin(async move { endpoints.send(request).await })
+    }
+}
@@ -0,0 +1,336 @@
+//! # Lease Module
+//!
+//! This module implements Lease-based leader election for operators that coordinate with
+//! controllers outside of this runtime. A lease acquired by an operator is renewed in the
+//! background until the operator releases it or is unloaded, at which point the lease is
+//! surrendered so another holder can take over without waiting for it to expire. A lease
+//! that another holder took over is no longer renewed, and `renew-lease` returns false for
+//! it, so the operator can tell it lost the lease.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use dashmap::DashMap;
+use k8s_openapi::api::coordination::v1::{Lease, LeaseSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta};
+use k8s_openapi::chrono::Utc;
+use kube::api::{Api, PostParams};
+use tokio::task::AbortHandle;
+use tracing::{info, warn};
+
+use super::{KubernetesService, FIELD_MANAGER};
+
+/// Environment variable holding the name of the pod the parent runs in.
+///
+/// Populate it through the downward API (`metadata.name`).
+const POD_NAME_ENV: &str = "POD_NAME";
+
+/// Identifies a lease held by an operator.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct LeaseKey {
+    operator: String,
+    namespace: String,
+    name: String,
+}
+
+/// Acquires, renews and releases the leases of all operators.
+pub struct LeaseManager {
+    kubernetes_service: Arc<KubernetesService>,
+    /// Renewal tasks of the held leases. A task removes its lease when it is lost.
+    held: Arc<DashMap<LeaseKey, AbortHandle>>,
+}
+
+impl LeaseManager {
+    pub fn new(kubernetes_service: Arc<KubernetesService>) -> Self {
+        Self {
+            kubernetes_service,
+            held: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// Tries to acquire a lease for an operator, and returns whether it holds it now.
+    ///
+    /// An acquired lease is renewed in the background at a third of its duration, until it
+    /// is released or lost.
+    pub async fn acquire(
+        &self,
+        operator: &str,
+        name: &str,
+        namespace: &str,
+        ttl_seconds: i32,
+    ) -> Result<bool> {
+        let identity = holder_identity(operator);
+        if !try_acquire(
+            &self.kubernetes_service,
+            name,
+            namespace,
+            &identity,
+            ttl_seconds,
+        )
+        .await?
+        {
+            return Ok(false);
+        }
+
+        let key = LeaseKey {
+            operator: operator.to_string(),
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        };
+        if self.held.contains_key(&key) {
+            return Ok(true);
+        }
+        info!(
+            "Operator '{}' acquired lease '{}/{}'",
+            operator, namespace, name
+        );
+
+        let kubernetes_service = self.kubernetes_service.clone();
+        let held = self.held.clone();
+        let renewed = key.clone();
+        let interval = Duration::from_secs(ttl_seconds.max(3).unsigned_abs().into()) / 3;
+        let renewal = tokio::spawn(async move {
+            let LeaseKey {
+                operator,
+                namespace,
+                name,
+            } = &renewed;
+            loop {
+                tokio::time::sleep(interval).await;
+                match renew(&kubernetes_service, name, namespace, &identity).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        warn!(
+                            "Operator '{}' lost lease '{}/{}' to another holder",
+                            operator, namespace, name
+                        );
+                        held.remove(&renewed);
+                        return;
+                    }
+                    Err(e) => warn!("Failed to renew lease '{}/{}': {}", namespace, name, e),
+                }
+            }
+        });
+        self.held.insert(key, renewal.abort_handle());
+        Ok(true)
+    }
+
+    /// Renews a lease held by an operator, and returns whether it still holds it. A lease
+    /// that was lost is no longer renewed in the background.
+    pub async fn renew(&self, operator: &str, name: &str, namespace: &str) -> Result<bool> {
+        let held = renew(
+            &self.kubernetes_service,
+            name,
+            namespace,
+            &holder_identity(operator),
+        )
+        .await?;
+        if !held {
+            let key = LeaseKey {
+                operator: operator.to_string(),
+                namespace: namespace.to_string(),
+                name: name.to_string(),
+            };
+            if let Some((_, renewal)) = self.held.remove(&key) {
+                renewal.abort();
+            }
+        }
+        Ok(held)
+    }
+
+    /// Releases a lease held by an operator and stops renewing it.
+    pub async fn release(&self, operator: &str, name: &str, namespace: &str) -> Result<()> {
+        let key = LeaseKey {
+            operator: operator.to_string(),
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        };
+        if let Some((_, renewal)) = self.held.remove(&key) {
+            renewal.abort();
+        }
+        release(
+            &self.kubernetes_service,
+            name,
+            namespace,
+            &holder_identity(operator),
+        )
+        .await
+    }
+
+    /// Releases all leases held by an operator, e.g. when it is unloaded.
+    pub async fn release_all(&self, operator: &str) {
+        let keys: Vec<LeaseKey> = self
+            .held
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|key| key.operator == operator)
+            .collect();
+        for key in keys {
+            if let Err(e) = self.release(operator, &key.name, &key.namespace).await {
+                warn!(
+                    "Failed to release lease '{}/{}' of operator '{}': {}",
+                    key.namespace, key.name, operator, e
+                );
+            }
+        }
+    }
+}
+
+/// Returns the holder identity of an operator, unique across parent replicas.
+fn holder_identity(operator: &str) -> String {
+    let pod = std::env::var(POD_NAME_ENV)
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "parent".to_string());
+    format!("{}_{}", pod, operator)
+}
+
+/// Takes the lease if it is free, expired, or already held by `identity`.
+async fn try_acquire(
+    kubernetes_service: &KubernetesService,
+    name: &str,
+    namespace: &str,
+    identity: &str,
+    ttl_seconds: i32,
+) -> Result<bool> {
+    let api: Api<Lease> = Api::namespaced(kubernetes_service.client(), namespace);
+    let now = MicroTime(Utc::now());
+    let post_params = post_params();
+
+    let Some(mut lease) = api.get_opt(name).await.context("Failed to get lease")? else {
+        let lease = Lease {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                namespace: Some(namespace.to_string()),
+                ..Default::default()
+            },
+            spec: Some(LeaseSpec {
+                holder_identity: Some(identity.to_string()),
+                lease_duration_seconds: Some(ttl_seconds),
+                acquire_time: Some(now.clone()),
+                renew_time: Some(now),
+                lease_transitions: Some(0),
+                ..Default::default()
+            }),
+        };
+        return match api.create(&post_params, &lease).await {
+            Ok(_) => Ok(true),
+            // Someone else created it first.
+            Err(kube::Error::Api(response)) if response.code == 409 => Ok(false),
+            Err(e) => Err(e).context("Failed to create lease"),
+        };
+    };
+
+    let spec = lease.spec.get_or_insert_with(Default::default);
+    let holder = spec.holder_identity.as_deref().unwrap_or_default();
+    if !holder.is_empty() && holder != identity && !is_expired(spec) {
+        return Ok(false);
+    }
+    if holder != identity {
+        spec.lease_transitions = Some(spec.lease_transitions.unwrap_or(0) + 1);
+        spec.acquire_time = Some(now.clone());
+    }
+    spec.holder_identity = Some(identity.to_string());
+    spec.lease_duration_seconds = Some(ttl_seconds);
+    spec.renew_time = Some(now);
+
+    // The replace carries the resource version that was read, so it fails if another
+    // holder took the lease in the meantime.
+    match api.replace(name, &post_params, &lease).await {
+        Ok(_) => Ok(true),
+        Err(kube::Error::Api(response)) if response.code == 409 => Ok(false),
+        Err(e) => Err(e).context("Failed to update lease"),
+    }
+}
+
+/// Renews the lease if `identity` still holds it, and returns whether it does.
+async fn renew(
+    kubernetes_service: &KubernetesService,
+    name: &str,
+    namespace: &str,
+    identity: &str,
+) -> Result<bool> {
+    let api: Api<Lease> = Api::namespaced(kubernetes_service.client(), namespace);
+    let Some(mut lease) = api.get_opt(name).await.context("Failed to get lease")? else {
+        return Ok(false);
+    };
+    let spec = lease.spec.get_or_insert_with(Default::default);
+    if spec.holder_identity.as_deref() != Some(identity) {
+        return Ok(false);
+    }
+    spec.renew_time = Some(MicroTime(Utc::now()));
+    match api.replace(name, &post_params(), &lease).await {
+        Ok(_) => Ok(true),
+        // Another holder took the lease since it was read.
+        Err(kube::Error::Api(response)) if response.code == 409 => Ok(false),
+        Err(e) => Err(e).context("Failed to renew lease"),
+    }
+}
+
+/// Surrenders the lease by clearing its holder, if `identity` still holds it.
+async fn release(
+    kubernetes_service: &KubernetesService,
+    name: &str,
+    namespace: &str,
+    identity: &str,
+) -> Result<()> {
+    let api: Api<Lease> = Api::namespaced(kubernetes_service.client(), namespace);
+    let Some(mut lease) = api.get_opt(name).await.context("Failed to get lease")? else {
+        return Ok(());
+    };
+    let spec = lease.spec.get_or_insert_with(Default::default);
+    if spec.holder_identity.as_deref() != Some(identity) {
+        return Ok(());
+    }
+    spec.holder_identity = None;
+    spec.acquire_time = None;
+    spec.renew_time = None;
+    api.replace(name, &post_params(), &lease)
+        .await
+        .context("Failed to release lease")?;
+    Ok(())
+}
+
+fn post_params() -> PostParams {
+    PostParams {
+        field_manager: Some(FIELD_MANAGER.to_string()),
+        ..Default::default()
+    }
+}
+
+fn is_expired(spec: &LeaseSpec) -> bool {
+    let (Some(renew_time), Some(duration)) = (&spec.renew_time, spec.lease_duration_seconds) else {
+        return true;
+    };
+    renew_time.0 + k8s_openapi::chrono::Duration::seconds(duration.into()) < Utc::now()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(renewed_seconds_ago: i64, duration: i32) -> LeaseSpec {
+        LeaseSpec {
+            renew_time: Some(MicroTime(
+                Utc::now() - k8s_openapi::chrono::Duration::seconds(renewed_seconds_ago),
+            )),
+            lease_duration_seconds: Some(duration),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lease_expires_after_its_duration() {
+        assert!(!is_expired(&spec(5, 15)));
+        assert!(is_expired(&spec(20, 15)));
+    }
+
+    #[test]
+    fn lease_without_renew_time_is_expired() {
+        let spec = LeaseSpec {
+            lease_duration_seconds: Some(15),
+            ..Default::default()
+        };
+        assert!(is_expired(&spec));
+    }
+}
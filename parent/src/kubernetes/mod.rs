@@ -4,11 +4,69 @@
 //! the creation of a Kubernetes client, execution of HTTP requests against the API,
 //! and serialization/deserialization of Kubernetes API responses.
 
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
 use anyhow::{anyhow, Context, Result};
-use kube::api::{Api, DeleteParams, DynamicObject, Patch, PatchParams, PostParams};
-use kube::discovery::{ApiGroup, ApiResource};
-use kube::{Client, Config, Discovery};
+use futures::stream::{self, BoxStream};
+use futures::{AsyncBufRead, StreamExt};
+use k8s_openapi::api::core::v1::{ConfigMap, Namespace, Pod, Secret};
+use kube::api::{
+    Api, DeleteParams, DynamicObject, ListParams, LogParams, ObjectList, Patch, PatchParams,
+    PostParams, WatchEvent, WatchParams,
+};
+use kube::config::{KubeConfigOptions, Kubeconfig};
+use kube::discovery::{ApiCapabilities, ApiResource};
+use kube::runtime::watcher::{self, Event};
+use kube::{Client, Config, Discovery, Resource};
 use serde_json::Value;
+use tokio::sync::{Mutex, OnceCell};
+use tracing::{info, warn};
+
+use crate::config::runtime::KubernetesConfig;
+use crate::metrics;
+
+use self::openapi::SchemaCache;
+use self::topology::Topology;
+
+pub mod connection;
+pub mod credentials;
+pub mod failover;
+pub mod lease;
+pub mod openapi;
+pub mod quantity;
+pub mod resource_metrics;
+pub mod topology;
+
+/// Environment variable holding the name of the service account the parent runs as.
+///
+/// Populate it through the downward API (`spec.serviceAccountName`).
+const SERVICE_ACCOUNT_ENV: &str = "SERVICE_ACCOUNT_NAME";
+
+/// Field manager of all writes made by the parent and its operators.
+pub const FIELD_MANAGER: &str = "wasm-operator";
+
+/// Minimum time between two credential refreshes, so a burst of rejected requests
+/// triggers a single refresh.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Minimum time between two API discoveries triggered by a kind that was not found, so
+/// guests probing for a missing CRD do not run one on every call.
+const MIN_DISCOVERY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Number of objects per page of `list_page`, unless configured otherwise.
+const DEFAULT_LIST_PAGE_SIZE: u32 = 500;
+
+/// Information about the cluster the parent is connected to.
+#[derive(Debug, Clone)]
+pub struct ClusterInfo {
+    pub server_version: String,
+    pub namespace: String,
+    pub service_account: String,
+}
 
 /// A service for interacting with the Kubernetes API dynamically.
 ///
@@ -16,40 +74,312 @@ use serde_json::Value;
 /// methods to interact with them using dynamic objects, allowing it to work
 /// with any Kubernetes resource kind, including Custom Resources.
 pub struct KubernetesService {
-    client: Client,
-    discovery: Discovery,
+    settings: KubernetesConfig,
+    client: RwLock<Client>,
+    last_refresh: Mutex<Option<Instant>>,
+    discovery: RwLock<Discovery>,
+    last_discovery: Mutex<Instant>,
+    /// Kinds discovered one by one when the discovery is not cached, by the kind they
+    /// were asked for.
+    resolved: RwLock<HashMap<String, (ApiResource, ApiCapabilities)>>,
+    /// When kinds that were not found were last discovered, when the discovery is not
+    /// cached.
+    missing: RwLock<HashMap<String, Instant>>,
+    cluster_info: ClusterInfo,
+    /// Node and pod informers, started on the first topology query.
+    topology: OnceCell<Topology>,
+    /// OpenAPI schemas fetched to validate objects.
+    schemas: SchemaCache,
+}
+
+/// Returns whether a client error means the credentials were rejected or expired.
+pub fn is_unauthorized(error: &kube::Error) -> bool {
+    match error {
+        kube::Error::Api(response) => response.code == 401,
+        kube::Error::Auth(_) => true,
+        _ => false,
+    }
+}
+
+/// Returns whether a watcher error means the credentials were rejected or expired.
+pub fn is_unauthorized_watch_error(error: &watcher::Error) -> bool {
+    match error {
+        watcher::Error::InitialListFailed(e)
+        | watcher::Error::WatchStartFailed(e)
+        | watcher::Error::WatchFailed(e) => is_unauthorized(e),
+        watcher::Error::WatchError(response) => response.code == 401,
+        watcher::Error::NoResourceVersion => false,
+    }
+}
+
+/// The state of a watch resumed with `resume_watcher`.
+enum Resumed {
+    Starting(Api<DynamicObject>, String),
+    Watching(
+        Api<DynamicObject>,
+        BoxStream<'static, kube::Result<WatchEvent<DynamicObject>>>,
+    ),
+    Relisting(BoxStream<'static, watcher::Result<Event<DynamicObject>>>),
+    Ended,
+}
+
+/// Watches objects from a resource version on, without listing them again, and falls back
+/// to a watcher that lists them again only if the API server no longer has that version
+/// (410 Gone). The stream ends when the API server ends the watch, so it can be resumed
+/// again from the last resource version seen.
+pub fn resume_watcher(
+    api: Api<DynamicObject>,
+    resource_version: String,
+) -> BoxStream<'static, watcher::Result<Event<DynamicObject>>> {
+    stream::unfold(
+        Resumed::Starting(api, resource_version),
+        |state| async move {
+            let (api, mut events) = match state {
+                Resumed::Starting(api, resource_version) => {
+                    match api.watch(&WatchParams::default(), &resource_version).await {
+                        Ok(events) => (api, events.boxed()),
+                        Err(kube::Error::Api(response)) if response.code == 410 => {
+                            return relist(api).await;
+                        }
+                        Err(e) => {
+                            return Some((Err(watcher::Error::WatchStartFailed(e)), Resumed::Ended))
+                        }
+                    }
+                }
+                Resumed::Watching(api, events) => (api, events),
+                Resumed::Relisting(mut relisted) => {
+                    let event = relisted.next().await?;
+                    return Some((event, Resumed::Relisting(relisted)));
+                }
+                Resumed::Ended => return None,
+            };
+            loop {
+                let event = match events.next().await? {
+                    Ok(WatchEvent::Added(object) | WatchEvent::Modified(object)) => {
+                        Ok(Event::Apply(object))
+                    }
+                    Ok(WatchEvent::Deleted(object)) => Ok(Event::Delete(object)),
+                    Ok(WatchEvent::Bookmark(_)) => continue,
+                    Ok(WatchEvent::Error(response)) if response.code == 410 => {
+                        return relist(api).await;
+                    }
+                    Ok(WatchEvent::Error(response)) => Err(watcher::Error::WatchError(response)),
+                    Err(e) => Err(watcher::Error::WatchFailed(e)),
+                };
+                return Some((event, Resumed::Watching(api, events)));
+            }
+        },
+    )
+    .boxed()
+}
+
+async fn relist(
+    api: Api<DynamicObject>,
+) -> Option<(watcher::Result<Event<DynamicObject>>, Resumed)> {
+    let mut relisted = watcher::watcher(api, Default::default()).boxed();
+    let event = relisted.next().await?;
+    Some((event, Resumed::Relisting(relisted)))
+}
+
+/// Loads the configuration from the configured kubeconfig file and context, or infers it
+/// from the environment if neither is set.
+async fn load_config(settings: &KubernetesConfig) -> Result<Config> {
+    let options = KubeConfigOptions {
+        context: settings.context.clone(),
+        ..Default::default()
+    };
+    let mut config = match (&settings.kubeconfig, &settings.context) {
+        (Some(path), _) => {
+            let kubeconfig = Kubeconfig::read_from(path)
+                .with_context(|| format!("Failed to read kubeconfig {}", path.display()))?;
+            Config::from_custom_kubeconfig(kubeconfig, &options)
+                .await
+                .with_context(|| format!("Failed to load kubeconfig {}", path.display()))?
+        }
+        (None, Some(context)) => Config::from_kubeconfig(&options)
+            .await
+            .with_context(|| format!("Failed to load kubeconfig context '{}'", context))?,
+        (None, None) => Config::infer()
+            .await
+            .context("Failed to infer Kubernetes config")?,
+    };
+    if let Some(namespace) = &settings.namespace {
+        config.default_namespace = namespace.clone();
+    }
+    Ok(config)
+}
+
+/// Builds a client from the loaded configuration and the configured overrides.
+async fn build_client(settings: &KubernetesConfig) -> Result<Client> {
+    let mut config = load_config(settings).await?;
+    connection::apply(settings, &mut config)?;
+    if let Some(provider) = &settings.credentials {
+        credentials::apply(provider, &mut config)
+            .await
+            .context("Failed to apply Kubernetes credential provider")?;
+    }
+    if !settings.failover_urls.is_empty() {
+        let interval = settings
+            .health_check_interval_secs
+            .map(Duration::from_secs)
+            .unwrap_or(failover::DEFAULT_HEALTH_CHECK_INTERVAL);
+        return failover::client(config, &settings.failover_urls, interval);
+    }
+    Client::try_from(config).context("Failed to create Kubernetes client")
+}
+
+async fn run_discovery(client: Client) -> Result<Discovery> {
+    Discovery::new(client)
+        .run()
+        .await
+        .context("Failed to run Kubernetes API discovery")
+}
+
+/// Finds a kind, possibly qualified, in a discovery.
+fn search(discovery: &Discovery, kind: &str) -> Option<(ApiResource, ApiCapabilities)> {
+    let (qualifier, name) = split_kind(kind);
+    for group in discovery.groups() {
+        for version in group.versions() {
+            for (ar, caps) in group.versioned_resources(version) {
+                if ar.kind.eq_ignore_ascii_case(name)
+                    && qualifier.as_ref().is_none_or(|q| q.matches(&ar))
+                {
+                    return Some((ar, caps));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Splits a kind into its qualifier, if it has one, and its name.
+fn split_kind(kind: &str) -> (Option<ApiQualifier<'_>>, &str) {
+    match kind.rsplit_once('/') {
+        Some((qualifier, name)) => (Some(ApiQualifier::parse(qualifier)), name),
+        None => (None, kind),
+    }
+}
+
+/// Returns whether a kind names the core `Secret`, going by the name alone, for kinds the
+/// API discovery does not know.
+fn names_core_secret(kind: &str) -> bool {
+    let (qualifier, name) = split_kind(kind);
+    name.eq_ignore_ascii_case("secret")
+        && match qualifier {
+            None => true,
+            Some(ApiQualifier::Version { group, .. }) => group.is_empty(),
+            Some(ApiQualifier::Group(_)) => false,
+        }
 }
 
 impl KubernetesService {
     /// Creates a new `KubernetesService`.
     ///
     /// This function infers the Kubernetes configuration from the environment,
-    /// creates a Kubernetes client, and performs API discovery.
-    pub async fn new() -> Result<Self> {
-        let config = Config::infer()
+    /// applies the configured overrides, creates a Kubernetes client, and performs
+    /// API discovery.
+    pub async fn new(settings: &KubernetesConfig) -> Result<Self> {
+        let client = build_client(settings).await?;
+        let discovery = if settings.disable_discovery_cache {
+            Discovery::new(client.clone())
+        } else {
+            run_discovery(client.clone()).await?
+        };
+        let version = client
+            .apiserver_version()
             .await
-            .context("Failed to infer Kubernetes config")?;
-        let client = Client::try_from(config).context("Failed to create Kubernetes client")?;
-        let discovery = Discovery::new(client.clone())
-            .run()
-            .await
-            .context("Failed to run Kubernetes API discovery")?;
-        Ok(KubernetesService { client, discovery })
+            .context("Failed to query Kubernetes API server version")?;
+        let cluster_info = ClusterInfo {
+            server_version: version.git_version,
+            namespace: client.default_namespace().to_string(),
+            service_account: std::env::var(SERVICE_ACCOUNT_ENV).unwrap_or_default(),
+        };
+        Ok(KubernetesService {
+            settings: settings.clone(),
+            client: RwLock::new(client),
+            last_refresh: Mutex::new(None),
+            discovery: RwLock::new(discovery),
+            last_discovery: Mutex::new(Instant::now()),
+            resolved: RwLock::default(),
+            missing: RwLock::default(),
+            cluster_info,
+            topology: OnceCell::new(),
+            schemas: SchemaCache::default(),
+        })
     }
 
-    /// Finds the `ApiResource` and (optional) `ApiGroup` for a given kind.
+    /// Returns the current client.
+    pub fn client(&self) -> Client {
+        self.client.read().unwrap().clone()
+    }
+
+    /// Re-acquires credentials and swaps in a new client.
     ///
-    /// This function searches the discovered API resources for a kind matching
-    /// the provided name (case-insensitive).
-    pub fn find_api_resource(&self, kind: &str) -> Result<(ApiResource, Option<&ApiGroup>)> {
-        for group in self.discovery.groups() {
-            for version in group.versions() {
-                for (ar, _caps) in group.versioned_resources(version) {
-                    if ar.kind.eq_ignore_ascii_case(kind) {
-                        return Ok((ar.clone(), Some(group)));
-                    }
+    /// Existing watches keep running on the old client until they fail and are
+    /// restarted; guest state is unaffected.
+    pub async fn refresh_credentials(&self) -> Result<()> {
+        let mut last_refresh = self.last_refresh.lock().await;
+        if last_refresh.is_some_and(|at| at.elapsed() < MIN_REFRESH_INTERVAL) {
+            return Ok(());
+        }
+
+        info!("Re-acquiring Kubernetes credentials");
+        let client = build_client(&self.settings).await?;
+        *self.client.write().unwrap() = client;
+        *last_refresh = Some(Instant::now());
+        metrics::increment("wasm_operator_credential_refreshes_total", &[]);
+        Ok(())
+    }
+
+    /// Runs a request, and retries it once with refreshed credentials if they were rejected.
+    async fn with_reauth<T, F, Fut>(&self, op: F) -> kube::Result<T>
+    where
+        F: Fn(Client) -> Fut,
+        Fut: Future<Output = kube::Result<T>>,
+    {
+        match op(self.client()).await {
+            Err(e) if is_unauthorized(&e) => {
+                warn!("Kubernetes API rejected the credentials: {}", e);
+                if let Err(refresh_error) = self.refresh_credentials().await {
+                    warn!(
+                        "Failed to refresh Kubernetes credentials: {}",
+                        refresh_error
+                    );
+                    return Err(e);
                 }
+                op(self.client()).await
             }
+            result => result,
+        }
+    }
+
+    /// Returns information about the connected cluster, gathered at startup.
+    pub fn cluster_info(&self) -> &ClusterInfo {
+        &self.cluster_info
+    }
+
+    /// Returns the cached node and pod informers, starting them on the first call.
+    pub async fn topology(&self) -> Result<&Topology> {
+        self.topology
+            .get_or_try_init(|| Topology::start(self.client()))
+            .await
+    }
+
+    /// Finds the `ApiResource` and `ApiCapabilities` for a given kind.
+    ///
+    /// This function searches the discovered API resources for a kind matching
+    /// the provided name (case-insensitive). The kind can be qualified with an API version
+    /// or a group, as in `networking.k8s.io/v1/Ingress` or `networking.k8s.io/Ingress`, for
+    /// kinds that more than one group defines. Without a qualifier the first match wins.
+    ///
+    /// When the discovery is not cached, only the kinds that were resolved before are
+    /// found; use `resolve_kind` to discover the others.
+    pub fn find_api_resource(&self, kind: &str) -> Result<(ApiResource, ApiCapabilities)> {
+        if let Some(found) = search(&self.discovery.read().unwrap(), kind) {
+            return Ok(found);
+        }
+        if let Some(found) = self.resolved.read().unwrap().get(kind) {
+            return Ok(found.clone());
         }
         Err(anyhow!(
             "Kind '{}' not found in discovered API resources",
@@ -57,32 +387,477 @@ impl KubernetesService {
         ))
     }
 
+    /// Like `find_api_resource`, but discovers the kind first if the discovery is not
+    /// cached and the kind was not resolved yet.
+    pub async fn resolve_kind(&self, kind: &str) -> Result<(ApiResource, ApiCapabilities)> {
+        if !self.settings.disable_discovery_cache {
+            return self.find_api_resource(kind);
+        }
+        self.discover(kind)
+            .await?
+            .ok_or_else(|| anyhow!("Kind '{}' not found in discovered API resources", kind))
+    }
+
+    /// Whether a kind resolves to core Secrets. A kind that was not discovered yet is
+    /// taken for core Secrets if it would resolve to them, so unresolved kinds do not
+    /// bypass the checks on reading Secrets.
+    pub fn is_core_secret(&self, kind: &str) -> bool {
+        if let Ok((resource, _)) = self.find_api_resource(kind) {
+            return resource.group.is_empty() && resource.kind == "Secret";
+        }
+        names_core_secret(kind)
+    }
+
+    /// Like `find_api_resource`, but runs the API discovery again if the kind is not found,
+    /// so kinds added since, such as a CRD installed after the parent started, are found
+    /// too. Returns `None` if the API server does not serve the kind.
+    pub async fn discover(&self, kind: &str) -> Result<Option<(ApiResource, ApiCapabilities)>> {
+        if let Ok(found) = self.find_api_resource(kind) {
+            return Ok(Some(found));
+        }
+        if self.settings.disable_discovery_cache {
+            return self.discover_uncached(kind).await;
+        }
+        self.refresh_discovery().await?;
+        Ok(self.find_api_resource(kind).ok())
+    }
+
+    /// Discovers a single kind without keeping the rest of the discovery, unless the kind
+    /// was not found less than `MIN_DISCOVERY_INTERVAL` ago. Kinds qualified with a group
+    /// only discover that group.
+    async fn discover_uncached(
+        &self,
+        kind: &str,
+    ) -> Result<Option<(ApiResource, ApiCapabilities)>> {
+        if self
+            .missing
+            .read()
+            .unwrap()
+            .get(kind)
+            .is_some_and(|at| at.elapsed() < MIN_DISCOVERY_INTERVAL)
+        {
+            return Ok(None);
+        }
+        let mut discovery = Discovery::new(self.client());
+        let group = match split_kind(kind).0 {
+            Some(ApiQualifier::Version { group, .. } | ApiQualifier::Group(group)) => Some(group),
+            None => None,
+        };
+        if let Some(group) = group {
+            discovery = discovery.filter(&[group]);
+        }
+        let discovery = discovery
+            .run()
+            .await
+            .context("Failed to run Kubernetes API discovery")?;
+        match search(&discovery, kind) {
+            Some(found) => {
+                self.resolved
+                    .write()
+                    .unwrap()
+                    .insert(kind.to_string(), found.clone());
+                Ok(Some(found))
+            }
+            None => {
+                self.missing
+                    .write()
+                    .unwrap()
+                    .insert(kind.to_string(), Instant::now());
+                Ok(None)
+            }
+        }
+    }
+
+    /// Runs the API discovery again, unless it ran less than `MIN_DISCOVERY_INTERVAL` ago.
+    async fn refresh_discovery(&self) -> Result<()> {
+        let mut last_discovery = self.last_discovery.lock().await;
+        if last_discovery.elapsed() < MIN_DISCOVERY_INTERVAL {
+            return Ok(());
+        }
+        let discovery = run_discovery(self.client()).await?;
+        *self.discovery.write().unwrap() = discovery;
+        *last_discovery = Instant::now();
+        Ok(())
+    }
+
     /// Returns a dynamic, namespaced API client for a given `ApiResource`.
     pub fn dynamic_api(&self, ar: ApiResource, namespace: &str) -> Api<DynamicObject> {
-        Api::namespaced_with(self.client.clone(), namespace, &ar)
+        Api::namespaced_with(self.client(), namespace, &ar)
+    }
+
+    /// Returns the API resource, namespace and name of an object, based on its metadata.
+    async fn locate_object(&self, object: &DynamicObject) -> Result<(ApiResource, String, String)> {
+        let kind = object
+            .types
+            .as_ref()
+            .map(|types| qualified_kind(&types.kind, Some(&types.api_version)))
+            .ok_or_else(|| anyhow!("Object has no type metadata"))?;
+        let name = object
+            .metadata
+            .name
+            .clone()
+            .ok_or_else(|| anyhow!("Object has no name"))?;
+        let namespace = object.metadata.namespace.clone().unwrap_or_default();
+        let (ar, _) = self.resolve_kind(&kind).await?;
+        Ok((ar, namespace, name))
+    }
+
+    /// Applies a JSON merge patch to an object.
+    pub async fn merge_patch_object(&self, object: &DynamicObject, patch: &Value) -> Result<()> {
+        let (ar, namespace, name) = self.locate_object(object).await?;
+        let patch_params = PatchParams {
+            field_manager: Some(FIELD_MANAGER.to_string()),
+            ..Default::default()
+        };
+        self.with_reauth(|client| {
+            let api: Api<DynamicObject> = Api::namespaced_with(client, &namespace, &ar);
+            let name = name.clone();
+            let patch_params = &patch_params;
+            async move { api.patch(&name, patch_params, &Patch::Merge(patch)).await }
+        })
+        .await
+        .context("Failed to patch resource")?;
+        Ok(())
+    }
+
+    /// Applies a JSON merge patch to the status subresource of an object.
+    pub async fn merge_patch_object_status(
+        &self,
+        object: &DynamicObject,
+        patch: &Value,
+    ) -> Result<()> {
+        let (ar, namespace, name) = self.locate_object(object).await?;
+        let patch_params = PatchParams {
+            field_manager: Some(FIELD_MANAGER.to_string()),
+            ..Default::default()
+        };
+        self.with_reauth(|client| {
+            let api: Api<DynamicObject> = Api::namespaced_with(client, &namespace, &ar);
+            let name = name.clone();
+            let patch_params = &patch_params;
+            async move {
+                api.patch_status(&name, patch_params, &Patch::Merge(patch))
+                    .await
+            }
+        })
+        .await
+        .context("Failed to patch resource status")?;
+        Ok(())
+    }
+
+    /// Returns the value of a key of a Secret, or `None` if the Secret or the key does not
+    /// exist.
+    pub async fn get_secret_value(
+        &self,
+        name: &str,
+        namespace: &str,
+        key: &str,
+    ) -> Result<Option<Vec<u8>>> {
+        let secret = self
+            .with_reauth(|client| {
+                let api: Api<Secret> = Api::namespaced(client, namespace);
+                async move { api.get_opt(name).await }
+            })
+            .await
+            .context("Failed to get secret")?;
+        Ok(secret
+            .and_then(|secret| secret.data)
+            .and_then(|mut data| data.remove(key))
+            .map(|value| value.0))
+    }
+
+    /// Returns the value of a key of a ConfigMap, or `None` if the ConfigMap or the key
+    /// does not exist.
+    pub async fn get_config_map_value(
+        &self,
+        name: &str,
+        namespace: &str,
+        key: &str,
+    ) -> Result<Option<String>> {
+        let config_map = self
+            .with_reauth(|client| {
+                let api: Api<ConfigMap> = Api::namespaced(client, namespace);
+                async move { api.get_opt(name).await }
+            })
+            .await
+            .context("Failed to get config map")?;
+        Ok(config_map
+            .and_then(|config_map| config_map.data)
+            .and_then(|mut data| data.remove(key)))
+    }
+
+    /// Sets the value of a key of a ConfigMap, or removes the key if the value is `None`.
+    /// A missing ConfigMap is created with the given label when a value is set.
+    pub async fn set_config_map_value(
+        &self,
+        name: &str,
+        namespace: &str,
+        key: &str,
+        value: Option<&str>,
+        label: (&str, &str),
+    ) -> Result<()> {
+        let patch = serde_json::json!({ "data": { key: value } });
+        let patch_params = PatchParams {
+            field_manager: Some(FIELD_MANAGER.to_string()),
+            ..Default::default()
+        };
+        let patch_config_map = || {
+            self.with_reauth(|client| {
+                let api: Api<ConfigMap> = Api::namespaced(client, namespace);
+                let patch_params = &patch_params;
+                let patch = &patch;
+                async move { api.patch(name, patch_params, &Patch::Merge(patch)).await }
+            })
+        };
+        match patch_config_map().await {
+            Err(kube::Error::Api(response)) if response.code == 404 => {}
+            result => return result.map(|_| ()).context("Failed to patch config map"),
+        }
+        let Some(value) = value else {
+            return Ok(());
+        };
+        let config_map: ConfigMap = serde_json::from_value(serde_json::json!({
+            "metadata": {
+                "name": name,
+                "namespace": namespace,
+                "labels": { label.0: label.1 },
+            },
+            "data": { key: value },
+        }))?;
+        let post_params = PostParams {
+            field_manager: Some(FIELD_MANAGER.to_string()),
+            ..Default::default()
+        };
+        let created = self
+            .with_reauth(|client| {
+                let api: Api<ConfigMap> = Api::namespaced(client, namespace);
+                let config_map = &config_map;
+                let post_params = &post_params;
+                async move { api.create(post_params, config_map).await }
+            })
+            .await;
+        match created {
+            // Created by another writer in the meantime.
+            Err(kube::Error::Api(response)) if response.code == 409 => patch_config_map()
+                .await
+                .map(|_| ())
+                .context("Failed to patch config map"),
+            result => result.map(|_| ()).context("Failed to create config map"),
+        }
+    }
+
+    /// Returns the logs of a container of a Pod, the default container if none is given,
+    /// cut off after `limit_bytes`.
+    pub async fn pod_logs(
+        &self,
+        namespace: &str,
+        pod: &str,
+        container: Option<&str>,
+        tail_lines: Option<u32>,
+        limit_bytes: usize,
+    ) -> Result<String> {
+        let log_params = LogParams {
+            container: container.map(str::to_string),
+            tail_lines: tail_lines.map(i64::from),
+            limit_bytes: Some(limit_bytes as i64),
+            ..Default::default()
+        };
+        self.with_reauth(|client| {
+            let api: Api<Pod> = Api::namespaced(client, namespace);
+            let log_params = &log_params;
+            async move { api.logs(pod, log_params).await }
+        })
+        .await
+        .context("Failed to get pod logs")
     }
 
     pub async fn get_resource(&self, kind: &str, name: &str, namespace: &str) -> Result<String> {
-        let (ar, _) = self.find_api_resource(kind)?;
-        let api = self.dynamic_api(ar, namespace);
-        let resource = api.get(name).await.context("Failed to get resource")?;
+        let (ar, _) = self.resolve_kind(kind).await?;
+        let resource = self
+            .with_reauth(|client| {
+                let api: Api<DynamicObject> = Api::namespaced_with(client, namespace, &ar);
+                async move { api.get(name).await }
+            })
+            .await
+            .context("Failed to get resource")?;
         serde_json::to_string(&resource).context("Failed to serialize resource to JSON")
     }
 
+    /// Returns the object, or `None` if it does not exist.
+    pub async fn find_object(
+        &self,
+        kind: &str,
+        name: &str,
+        namespace: &str,
+    ) -> Result<Option<DynamicObject>> {
+        let (ar, _) = self.resolve_kind(kind).await?;
+        self.with_reauth(|client| {
+            let api: Api<DynamicObject> = Api::namespaced_with(client, namespace, &ar);
+            async move { api.get_opt(name).await }
+        })
+        .await
+        .context("Failed to get resource")
+    }
+
+    /// Returns the object as JSON, or `None` if it does not exist.
+    pub async fn find_resource(
+        &self,
+        kind: &str,
+        name: &str,
+        namespace: &str,
+    ) -> Result<Option<String>> {
+        self.find_object(kind, name, namespace)
+            .await?
+            .map(|resource| serde_json::to_string(&resource))
+            .transpose()
+            .context("Failed to serialize resource to JSON")
+    }
+
+    /// Returns the objects of a kind in a namespace that match the label and field
+    /// selectors, as JSON. Empty selectors match all objects, and an empty namespace
+    /// lists the objects in all namespaces or of a cluster-scoped kind.
+    pub async fn list_resources(
+        &self,
+        kind: &str,
+        namespace: &str,
+        label_selector: &str,
+        field_selector: &str,
+    ) -> Result<Vec<String>> {
+        let list_params = ListParams::default()
+            .labels(label_selector)
+            .fields(field_selector);
+        let objects = self.list(kind, namespace, list_params).await?;
+        to_json(&objects.items)
+    }
+
+    /// Starts listing the objects `list_resources` would return, and returns the body of
+    /// the response as it arrives: a list object as JSON.
+    pub async fn list_body(
+        &self,
+        kind: &str,
+        namespace: &str,
+        label_selector: &str,
+        field_selector: &str,
+    ) -> Result<Pin<Box<dyn AsyncBufRead + Send>>> {
+        let (ar, _) = self.resolve_kind(kind).await?;
+        let namespace = (!namespace.is_empty()).then_some(namespace);
+        let request = kube::core::Request::new(DynamicObject::url_path(&ar, namespace));
+        let list_params = ListParams::default()
+            .labels(label_selector)
+            .fields(field_selector);
+        let body = self
+            .with_reauth(|client| {
+                let request = request.list(&list_params);
+                async move {
+                    client
+                        .request_stream(request.map_err(kube::Error::BuildRequest)?)
+                        .await
+                }
+            })
+            .await
+            .context("Failed to list resources")?;
+        Ok(Box::pin(body))
+    }
+
+    /// Lists one page of the objects `list_resources` would return, continuing after the
+    /// page that returned `continue_token`. Returns the objects as JSON and the token of
+    /// the next page, if there is one. Tokens expire after a few minutes, after which the
+    /// API server answers with 410 Gone.
+    pub async fn list_page(
+        &self,
+        kind: &str,
+        namespace: &str,
+        label_selector: &str,
+        field_selector: &str,
+        continue_token: Option<&str>,
+    ) -> Result<(Vec<String>, Option<String>)> {
+        let mut list_params = ListParams::default()
+            .labels(label_selector)
+            .fields(field_selector)
+            .limit(
+                self.settings
+                    .list_page_size
+                    .unwrap_or(DEFAULT_LIST_PAGE_SIZE),
+            );
+        if let Some(token) = continue_token {
+            list_params = list_params.continue_token(token);
+        }
+        let objects = self.list(kind, namespace, list_params).await?;
+        let next = objects.metadata.continue_.filter(|token| !token.is_empty());
+        Ok((to_json(&objects.items)?, next))
+    }
+
+    /// Returns the names of the namespaces that match the label selector.
+    pub async fn list_namespaces(&self, label_selector: &str) -> Result<Vec<String>> {
+        let list_params = ListParams::default().labels(label_selector);
+        let namespaces = self
+            .with_reauth(|client| {
+                let api: Api<Namespace> = Api::all(client);
+                let list_params = &list_params;
+                async move { api.list(list_params).await }
+            })
+            .await
+            .context("Failed to list namespaces")?;
+        Ok(namespaces
+            .items
+            .into_iter()
+            .filter_map(|namespace| namespace.metadata.name)
+            .collect())
+    }
+
+    /// Returns the objects of a kind in a namespace, or in all namespaces if it is empty.
+    pub async fn list_objects(&self, kind: &str, namespace: &str) -> Result<Vec<DynamicObject>> {
+        Ok(self
+            .list(kind, namespace, ListParams::default())
+            .await?
+            .items)
+    }
+
+    async fn list(
+        &self,
+        kind: &str,
+        namespace: &str,
+        list_params: ListParams,
+    ) -> Result<ObjectList<DynamicObject>> {
+        let (ar, _) = self.resolve_kind(kind).await?;
+        self.with_reauth(|client| {
+            let api: Api<DynamicObject> = if namespace.is_empty() {
+                Api::all_with(client, &ar)
+            } else {
+                Api::namespaced_with(client, namespace, &ar)
+            };
+            let list_params = &list_params;
+            async move { api.list(list_params).await }
+        })
+        .await
+        .context("Failed to list resources")
+    }
+
+    /// Creates an object and returns its name, which is generated by the API server when
+    /// the object only sets `generateName`.
     pub async fn create_resource(
         &self,
         kind: &str,
         namespace: &str,
         resource_json: &str,
-    ) -> Result<()> {
-        let (ar, _) = self.find_api_resource(kind)?;
-        let api = self.dynamic_api(ar, namespace);
+    ) -> Result<String> {
+        let (ar, _) = self.resolve_kind(kind).await?;
         let resource: DynamicObject = serde_json::from_str(resource_json)
             .context("Failed to deserialize resource from JSON")?;
-        api.create(&PostParams::default(), &resource)
+        let post_params = PostParams {
+            field_manager: Some(FIELD_MANAGER.to_string()),
+            ..Default::default()
+        };
+        let created = self
+            .with_reauth(|client| {
+                let api: Api<DynamicObject> = Api::namespaced_with(client, namespace, &ar);
+                let resource = &resource;
+                let post_params = &post_params;
+                async move { api.create(post_params, resource).await }
+            })
             .await
             .context("Failed to create resource")?;
-        Ok(())
+        Ok(created.metadata.name.unwrap_or_default())
     }
 
     pub async fn update_resource(
@@ -92,22 +867,260 @@ impl KubernetesService {
         namespace: &str,
         resource_json: &str,
     ) -> Result<()> {
-        let (ar, _) = self.find_api_resource(kind)?;
-        let api = self.dynamic_api(ar, namespace);
+        let (ar, _) = self.resolve_kind(kind).await?;
         let resource: Value = serde_json::from_str(resource_json)
             .context("Failed to deserialize resource from JSON for update")?;
-        api.patch(name, &PatchParams::apply(kind), &Patch::Apply(&resource))
-            .await
-            .context("Failed to update resource")?;
+        self.with_reauth(|client| {
+            let api: Api<DynamicObject> = Api::namespaced_with(client, namespace, &ar);
+            let resource = &resource;
+            async move {
+                api.patch(
+                    name,
+                    &PatchParams::apply(FIELD_MANAGER),
+                    &Patch::Apply(resource),
+                )
+                .await
+            }
+        })
+        .await
+        .context("Failed to update resource")?;
+        Ok(())
+    }
+
+    /// Applies a patch to an object. Apply patches are made with the field manager of the
+    /// runtime.
+    pub async fn patch_resource(
+        &self,
+        kind: &str,
+        name: &str,
+        namespace: &str,
+        patch: &Patch<Value>,
+    ) -> Result<()> {
+        let (ar, _) = self.resolve_kind(kind).await?;
+        let patch_params = match patch {
+            Patch::Apply(_) => PatchParams::apply(FIELD_MANAGER),
+            _ => PatchParams {
+                field_manager: Some(FIELD_MANAGER.to_string()),
+                ..Default::default()
+            },
+        };
+        self.with_reauth(|client| {
+            let api: Api<DynamicObject> = Api::namespaced_with(client, namespace, &ar);
+            let patch_params = &patch_params;
+            async move { api.patch(name, patch_params, patch).await }
+        })
+        .await
+        .context("Failed to patch resource")?;
+        Ok(())
+    }
+
+    /// Sets the status of an object through its status subresource with a server-side
+    /// apply, leaving the status fields owned by other managers alone.
+    pub async fn update_status(
+        &self,
+        kind: &str,
+        name: &str,
+        namespace: &str,
+        status_json: &str,
+    ) -> Result<()> {
+        let (ar, _) = self.resolve_kind(kind).await?;
+        let status: Value =
+            serde_json::from_str(status_json).context("Failed to deserialize status from JSON")?;
+        let resource = serde_json::json!({
+            "apiVersion": ar.api_version,
+            "kind": ar.kind,
+            "metadata": { "name": name },
+            "status": status,
+        });
+        self.with_reauth(|client| {
+            let api: Api<DynamicObject> = Api::namespaced_with(client, namespace, &ar);
+            let resource = &resource;
+            async move {
+                api.patch_status(
+                    name,
+                    &PatchParams::apply(FIELD_MANAGER),
+                    &Patch::Apply(resource),
+                )
+                .await
+            }
+        })
+        .await
+        .context("Failed to update resource status")?;
         Ok(())
     }
 
     pub async fn delete_resource(&self, kind: &str, name: &str, namespace: &str) -> Result<()> {
-        let (ar, _) = self.find_api_resource(kind)?;
-        let api = self.dynamic_api(ar, namespace);
-        api.delete(name, &DeleteParams::default())
-            .await
-            .context("Failed to delete resource")?;
+        let (ar, _) = self.resolve_kind(kind).await?;
+        self.with_reauth(|client| {
+            let api: Api<DynamicObject> = Api::namespaced_with(client, namespace, &ar);
+            async move { api.delete(name, &DeleteParams::default()).await }
+        })
+        .await
+        .context("Failed to delete resource")?;
         Ok(())
     }
+
+    /// Deletes all objects of a kind in a namespace that match the label selector.
+    pub async fn delete_collection(
+        &self,
+        kind: &str,
+        namespace: &str,
+        selector: &str,
+    ) -> Result<()> {
+        let (ar, _) = self.resolve_kind(kind).await?;
+        self.with_reauth(|client| {
+            let api: Api<DynamicObject> = Api::namespaced_with(client, namespace, &ar);
+            async move {
+                api.delete_collection(
+                    &DeleteParams::default(),
+                    &ListParams::default().labels(selector),
+                )
+                .await
+            }
+        })
+        .await
+        .context("Failed to delete resources")?;
+        Ok(())
+    }
+
+    /// Deletes the objects of a kind that match the label selector, except those named in
+    /// `keep`, and returns the names of the deleted objects.
+    pub async fn prune(
+        &self,
+        kind: &str,
+        namespace: &str,
+        selector: &str,
+        keep: &[String],
+    ) -> Result<Vec<String>> {
+        let (ar, _) = self.resolve_kind(kind).await?;
+        let list_params = ListParams::default().labels(selector);
+        let objects = self
+            .with_reauth(|client| {
+                let api: Api<DynamicObject> = Api::namespaced_with(client, namespace, &ar);
+                let list_params = &list_params;
+                async move { api.list(list_params).await }
+            })
+            .await
+            .context("Failed to list resources to prune")?;
+
+        let mut pruned = Vec::new();
+        for object in objects {
+            let Some(name) = object.metadata.name else {
+                continue;
+            };
+            if keep.contains(&name) {
+                continue;
+            }
+            self.delete_resource(kind, &name, namespace).await?;
+            pruned.push(name);
+        }
+        Ok(pruned)
+    }
+}
+
+/// Qualifies a kind with an API version, if one is given, for `find_api_resource`.
+pub fn qualified_kind(kind: &str, api_version: Option<&str>) -> String {
+    match api_version {
+        Some(api_version) if !api_version.is_empty() => format!("{}/{}", api_version, kind),
+        _ => kind.to_string(),
+    }
+}
+
+/// The group, and possibly version, a kind is qualified with.
+#[derive(Debug, PartialEq)]
+enum ApiQualifier<'a> {
+    /// An API version such as `apps/v1`, or `v1` for the core group.
+    Version { group: &'a str, version: &'a str },
+    /// A group such as `networking.k8s.io`, in any version.
+    Group(&'a str),
+}
+
+impl<'a> ApiQualifier<'a> {
+    fn parse(qualifier: &'a str) -> Self {
+        match qualifier.split_once('/') {
+            Some((group, version)) => Self::Version { group, version },
+            // Group names contain a dot, core versions such as `v1` do not.
+            None if qualifier.contains('.') => Self::Group(qualifier),
+            None => Self::Version {
+                group: "",
+                version: qualifier,
+            },
+        }
+    }
+
+    fn matches(&self, ar: &ApiResource) -> bool {
+        match self {
+            Self::Version { group, version } => ar.group == *group && ar.version == *version,
+            Self::Group(group) => ar.group == *group,
+        }
+    }
+}
+
+fn to_json(objects: &[DynamicObject]) -> Result<Vec<String>> {
+    objects
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<_, _>>()
+        .context("Failed to serialize resource to JSON")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_resource(group: &str, version: &str) -> ApiResource {
+        ApiResource {
+            group: group.to_string(),
+            version: version.to_string(),
+            api_version: if group.is_empty() {
+                version.to_string()
+            } else {
+                format!("{}/{}", group, version)
+            },
+            kind: "Ingress".to_string(),
+            plural: "ingresses".to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_api_qualifiers() {
+        assert_eq!(
+            ApiQualifier::parse("apps/v1"),
+            ApiQualifier::Version {
+                group: "apps",
+                version: "v1"
+            }
+        );
+        assert_eq!(
+            ApiQualifier::parse("v1"),
+            ApiQualifier::Version {
+                group: "",
+                version: "v1"
+            }
+        );
+        assert_eq!(
+            ApiQualifier::parse("networking.k8s.io"),
+            ApiQualifier::Group("networking.k8s.io")
+        );
+    }
+
+    #[test]
+    fn qualifiers_match_their_group_and_version() {
+        let ingress = api_resource("networking.k8s.io", "v1");
+        assert!(ApiQualifier::parse("networking.k8s.io").matches(&ingress));
+        assert!(ApiQualifier::parse("networking.k8s.io/v1").matches(&ingress));
+        assert!(!ApiQualifier::parse("networking.k8s.io/v1beta1").matches(&ingress));
+        assert!(!ApiQualifier::parse("v1").matches(&ingress));
+        assert!(ApiQualifier::parse("v1").matches(&api_resource("", "v1")));
+    }
+
+    #[test]
+    fn recognizes_core_secrets_by_name() {
+        assert!(names_core_secret("Secret"));
+        assert!(names_core_secret("secret"));
+        assert!(names_core_secret("v1/Secret"));
+        assert!(!names_core_secret("example.com/v1/Secret"));
+        assert!(!names_core_secret("example.com/Secret"));
+        assert!(!names_core_secret("ConfigMap"));
+    }
 }
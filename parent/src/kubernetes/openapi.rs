@@ -0,0 +1,403 @@
+//! # OpenAPI Module
+//!
+//! This module checks objects against the OpenAPI v3 schema the API server publishes for
+//! their kind, so an operator learns which fields of a create or update are wrong before
+//! sending it, with one error per field instead of the single message of a 422 response.
+//! Schemas are fetched per group version from `/openapi/v3` and kept for a few minutes, so
+//! CRDs installed or changed since are picked up. The check covers types, required fields,
+//! unknown fields and enums; formats, patterns, bounds and CEL rules are left to the API
+//! server.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use kube::discovery::ApiResource;
+use serde_json::Value;
+
+use super::KubernetesService;
+use crate::host::api::bindings::local::operator::types::FieldError;
+
+/// How long a fetched schema document is used before it is fetched again.
+const SCHEMA_TTL: Duration = Duration::from_secs(300);
+
+/// How deep objects are checked, against schemas that refer to themselves.
+const MAX_DEPTH: usize = 64;
+
+/// The schema documents fetched so far, by path.
+#[derive(Default)]
+pub struct SchemaCache {
+    documents: Mutex<HashMap<String, (Instant, Arc<Value>)>>,
+}
+
+impl KubernetesService {
+    /// Checks an object of a kind against its OpenAPI schema and returns the fields that
+    /// are wrong. With `partial`, missing required fields are not reported, for apply
+    /// patches that only hold the fields their manager sets.
+    pub async fn validate_object(
+        &self,
+        kind: &str,
+        object: &Value,
+        partial: bool,
+    ) -> Result<Vec<FieldError>> {
+        let (ar, _) = self.resolve_kind(kind).await?;
+        let document = self.schema_document(&ar).await?;
+        let schema = find_schema(&document, &ar)
+            .ok_or_else(|| anyhow!("The API server publishes no schema for kind '{}'", ar.kind))?;
+        let mut validator = Validator::new(&document, partial);
+        validator.validate(schema, object, "", 0);
+        Ok(validator.errors)
+    }
+
+    async fn schema_document(&self, ar: &ApiResource) -> Result<Arc<Value>> {
+        let path = if ar.group.is_empty() {
+            format!("/openapi/v3/api/{}", ar.version)
+        } else {
+            format!("/openapi/v3/apis/{}/{}", ar.group, ar.version)
+        };
+        if let Some((fetched, document)) = self.schemas.documents.lock().unwrap().get(&path)
+            && fetched.elapsed() < SCHEMA_TTL
+        {
+            return Ok(document.clone());
+        }
+        let document: Arc<Value> = Arc::new(self.get_json(&path).await?);
+        self.schemas
+            .documents
+            .lock()
+            .unwrap()
+            .insert(path, (Instant::now(), document.clone()));
+        Ok(document)
+    }
+}
+
+/// Finds the schema of a kind among the schemas of its group version.
+fn find_schema<'a>(document: &'a Value, ar: &ApiResource) -> Option<&'a Value> {
+    document
+        .pointer("/components/schemas")?
+        .as_object()?
+        .values()
+        .find(|schema| {
+            schema
+                .get("x-kubernetes-group-version-kind")
+                .and_then(Value::as_array)
+                .is_some_and(|gvks| {
+                    gvks.iter().any(|gvk| {
+                        gvk["group"] == ar.group.as_str()
+                            && gvk["version"] == ar.version.as_str()
+                            && gvk["kind"] == ar.kind.as_str()
+                    })
+                })
+        })
+}
+
+struct Validator<'a> {
+    document: &'a Value,
+    partial: bool,
+    errors: Vec<FieldError>,
+}
+
+impl<'a> Validator<'a> {
+    fn new(document: &'a Value, partial: bool) -> Self {
+        Self {
+            document,
+            partial,
+            errors: Vec::new(),
+        }
+    }
+
+    fn validate(&mut self, schema: &'a Value, value: &Value, path: &str, depth: usize) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        let schema = self.resolve(schema);
+        // Built-in kinds wrap references in `allOf` to give them a description.
+        for part in schema
+            .get("allOf")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            self.validate(part, value, path, depth + 1);
+        }
+        // A null clears a field, which the API server accepts for any optional field.
+        if value.is_null() {
+            return;
+        }
+        for keyword in ["oneOf", "anyOf"] {
+            if let Some(alternatives) = schema.get(keyword).and_then(Value::as_array)
+                && !alternatives.iter().any(|alternative| {
+                    let mut validator = Validator::new(self.document, self.partial);
+                    validator.validate(alternative, value, path, depth + 1);
+                    validator.errors.is_empty()
+                })
+            {
+                self.error(
+                    path,
+                    "does not match any of the allowed schemas".to_string(),
+                );
+            }
+        }
+        if flag(schema, "x-kubernetes-int-or-string") {
+            if !(is_integer(value) || value.is_string()) {
+                self.error(path, "must be an integer or a string".to_string());
+            }
+            return;
+        }
+
+        let valid = match schema.get("type").and_then(Value::as_str) {
+            Some("object") => {
+                self.validate_object(schema, value, path, depth);
+                true
+            }
+            Some("array") => {
+                self.validate_array(schema, value, path, depth);
+                true
+            }
+            Some("string") => value.is_string(),
+            Some("integer") => is_integer(value),
+            Some("number") => value.is_number(),
+            Some("boolean") => value.is_boolean(),
+            _ => {
+                if schema.get("properties").is_some() {
+                    self.validate_object(schema, value, path, depth);
+                }
+                true
+            }
+        };
+        if !valid {
+            let expected = schema["type"].as_str().unwrap_or_default();
+            self.error(path, format!("must be of type {}", expected));
+            return;
+        }
+
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+            && !allowed.contains(value)
+        {
+            let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            self.error(path, format!("must be one of {}", allowed.join(", ")));
+        }
+    }
+
+    fn validate_object(&mut self, schema: &'a Value, value: &Value, path: &str, depth: usize) {
+        let Some(object) = value.as_object() else {
+            self.error(path, "must be of type object".to_string());
+            return;
+        };
+        if !self.partial {
+            let required = schema.get("required").and_then(Value::as_array);
+            for field in required.into_iter().flatten().filter_map(Value::as_str) {
+                if !object.contains_key(field) {
+                    self.error(&join(path, field), "is required".to_string());
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let additional = schema.get("additionalProperties");
+        let open = flag(schema, "x-kubernetes-preserve-unknown-fields")
+            || additional == Some(&Value::Bool(true));
+        for (key, field) in object {
+            let field_path = join(path, key);
+            if let Some(property) = properties.and_then(|properties| properties.get(key)) {
+                self.validate(property, field, &field_path, depth + 1);
+            } else if let Some(additional) = additional.filter(|additional| additional.is_object())
+            {
+                self.validate(additional, field, &field_path, depth + 1);
+            } else if properties.is_some() && !open {
+                self.error(&field_path, "is not a known field".to_string());
+            }
+        }
+    }
+
+    fn validate_array(&mut self, schema: &'a Value, value: &Value, path: &str, depth: usize) {
+        let Some(items) = value.as_array() else {
+            self.error(path, "must be of type array".to_string());
+            return;
+        };
+        if let Some(item_schema) = schema.get("items") {
+            for (index, item) in items.iter().enumerate() {
+                let item_path = format!("{}[{}]", path, index);
+                self.validate(item_schema, item, &item_path, depth + 1);
+            }
+        }
+    }
+
+    /// Follows a `$ref` to the schema it names, within the same document.
+    fn resolve(&self, schema: &'a Value) -> &'a Value {
+        schema
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|reference| reference.strip_prefix('#'))
+            .and_then(|pointer| self.document.pointer(pointer))
+            .unwrap_or(schema)
+    }
+
+    fn error(&mut self, path: &str, message: String) {
+        let field = if path.is_empty() { "<root>" } else { path };
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            message,
+        });
+    }
+}
+
+fn flag(schema: &Value, name: &str) -> bool {
+    schema.get(name).and_then(Value::as_bool) == Some(true)
+}
+
+fn is_integer(value: &Value) -> bool {
+    value.is_i64() || value.is_u64()
+}
+
+fn join(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_string()
+    } else {
+        format!("{}.{}", path, field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kube::api::GroupVersionKind;
+    use serde_json::json;
+
+    fn document() -> Value {
+        json!({
+            "components": {"schemas": {
+                "Widget": {
+                    "type": "object",
+                    "required": ["spec"],
+                    "properties": {
+                        "apiVersion": {"type": "string"},
+                        "kind": {"type": "string"},
+                        "metadata": {
+                            "type": "object",
+                            "x-kubernetes-preserve-unknown-fields": true
+                        },
+                        "spec": {"allOf": [{"$ref": "#/components/schemas/WidgetSpec"}]}
+                    },
+                    "x-kubernetes-group-version-kind": [
+                        {"group": "example.io", "version": "v1", "kind": "Widget"}
+                    ]
+                },
+                "WidgetSpec": {
+                    "type": "object",
+                    "required": ["size"],
+                    "properties": {
+                        "size": {"type": "integer"},
+                        "mode": {"type": "string", "enum": ["fast", "safe"]},
+                        "port": {"x-kubernetes-int-or-string": true},
+                        "ratio": {"type": "number"},
+                        "tags": {"type": "array", "items": {"type": "string"}},
+                        "labels": {"type": "object", "additionalProperties": {"type": "string"}},
+                        "source": {"oneOf": [
+                            {"required": ["url"], "properties": {"url": {"type": "string"}}},
+                            {"required": ["path"], "properties": {"path": {"type": "string"}}}
+                        ]},
+                        "child": {"$ref": "#/components/schemas/WidgetSpec"}
+                    }
+                }
+            }}
+        })
+    }
+
+    fn errors(object: Value, partial: bool) -> Vec<(String, String)> {
+        let document = document();
+        let ar = ApiResource::from_gvk(&GroupVersionKind::gvk("example.io", "v1", "Widget"));
+        let schema = find_schema(&document, &ar).unwrap();
+        let mut validator = Validator::new(&document, partial);
+        validator.validate(schema, &object, "", 0);
+        validator
+            .errors
+            .into_iter()
+            .map(|error| (error.field, error.message))
+            .collect()
+    }
+
+    fn error(field: &str, message: &str) -> (String, String) {
+        (field.to_string(), message.to_string())
+    }
+
+    #[test]
+    fn finds_the_schema_of_a_kind() {
+        let document = document();
+        let widget = ApiResource::from_gvk(&GroupVersionKind::gvk("example.io", "v1", "Widget"));
+        assert!(find_schema(&document, &widget).is_some());
+        let other = ApiResource::from_gvk(&GroupVersionKind::gvk("example.io", "v2", "Widget"));
+        assert!(find_schema(&document, &other).is_none());
+    }
+
+    #[test]
+    fn accepts_valid_objects() {
+        let object = json!({
+            "apiVersion": "example.io/v1",
+            "kind": "Widget",
+            "metadata": {"name": "w", "annotations": {"a": "b"}},
+            "spec": {
+                "size": 3,
+                "mode": "safe",
+                "port": "http",
+                "ratio": 1,
+                "tags": ["a", "b"],
+                "labels": {"team": "core"},
+                "source": {"path": "/data"},
+                "child": {"size": 1, "port": 8080, "mode": null}
+            }
+        });
+        assert_eq!(errors(object, false), []);
+    }
+
+    #[test]
+    fn reports_each_wrong_field() {
+        let object = json!({
+            "spec": {
+                "size": 1.5,
+                "mode": "slow",
+                "port": true,
+                "tags": ["a", 1],
+                "labels": {"team": 1},
+                "source": {"url": "https://example.io", "path": "/data"},
+                "child": {"colour": "red"},
+                "extra": 1
+            }
+        });
+        assert_eq!(
+            errors(object, false),
+            [
+                error("spec.child.size", "is required"),
+                error("spec.child.colour", "is not a known field"),
+                error("spec.extra", "is not a known field"),
+                error("spec.labels.team", "must be of type string"),
+                error("spec.mode", "must be one of \"fast\", \"safe\""),
+                error("spec.port", "must be an integer or a string"),
+                error("spec.size", "must be of type integer"),
+                error("spec.source", "does not match any of the allowed schemas"),
+                error("spec.tags[1]", "must be of type string"),
+            ]
+        );
+    }
+
+    #[test]
+    fn partial_objects_may_omit_required_fields() {
+        assert_eq!(
+            errors(json!({"metadata": {}}), false),
+            [error("spec", "is required")]
+        );
+        assert_eq!(errors(json!({"metadata": {}}), true), []);
+        assert_eq!(
+            errors(json!({"spec": {"size": "3"}}), true),
+            [error("spec.size", "must be of type integer")]
+        );
+    }
+
+    #[test]
+    fn reports_the_root_of_wrong_objects() {
+        assert_eq!(
+            errors(json!([]), false),
+            [error("<root>", "must be of type object")]
+        );
+    }
+}
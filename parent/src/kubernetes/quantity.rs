@@ -0,0 +1,34 @@
+//! # Quantity Module
+//!
+//! This module parses Kubernetes resource quantities, as found in resource requests,
+//! node capacity and the metrics APIs. It has no dependencies on the rest of the parent,
+//! so the fuzz targets can include it directly.
+
+/// Parses a Kubernetes quantity such as `250m`, `1.5`, `128Mi` or `1e3`.
+pub fn parse(quantity: &str) -> Option<f64> {
+    const SUFFIXES: [(&str, f64); 15] = [
+        ("Ki", 1024.0),
+        ("Mi", 1024.0 * 1024.0),
+        ("Gi", 1024.0 * 1024.0 * 1024.0),
+        ("Ti", 1024.0 * 1024.0 * 1024.0 * 1024.0),
+        ("Pi", 1024.0 * 1024.0 * 1024.0 * 1024.0 * 1024.0),
+        ("Ei", 1024.0 * 1024.0 * 1024.0 * 1024.0 * 1024.0 * 1024.0),
+        ("n", 1e-9),
+        ("u", 1e-6),
+        ("m", 1e-3),
+        ("k", 1e3),
+        ("M", 1e6),
+        ("G", 1e9),
+        ("T", 1e12),
+        ("P", 1e15),
+        ("E", 1e18),
+    ];
+    let quantity = quantity.trim();
+    for (suffix, factor) in SUFFIXES {
+        if let Some(number) = quantity.strip_suffix(suffix) {
+            return number.parse::<f64>().ok().map(|n| n * factor);
+        }
+    }
+    // Plain numbers, including decimal exponents such as `1e3`.
+    quantity.parse().ok()
+}
@@ -0,0 +1,199 @@
+//! # Resource Metrics Module
+//!
+//! This module queries the resource metrics API (`metrics.k8s.io`) and the custom metrics
+//! API (`custom.metrics.k8s.io`), which are served by aggregated API servers such as
+//! metrics-server or the Prometheus adapter. Guests get the parsed values instead of
+//! building the request paths and parsing quantities themselves, which is all an
+//! autoscaler needs.
+
+use anyhow::{Context, Result};
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+use serde::Deserialize;
+
+use super::quantity;
+use super::KubernetesService;
+use crate::host::api::bindings::local::operator::types::{
+    ContainerUsage, MetricValue, NodeUsage, ObjectReference, PodUsage,
+};
+
+const RESOURCE_METRICS_PATH: &str = "/apis/metrics.k8s.io/v1beta1";
+const CUSTOM_METRICS_PATH: &str = "/apis/custom.metrics.k8s.io/v1beta2";
+
+#[derive(Deserialize)]
+struct List<T> {
+    items: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    namespace: String,
+}
+
+#[derive(Deserialize)]
+struct Usage {
+    #[serde(default)]
+    cpu: Option<Quantity>,
+    #[serde(default)]
+    memory: Option<Quantity>,
+}
+
+#[derive(Deserialize)]
+struct PodMetrics {
+    metadata: Metadata,
+    containers: Vec<ContainerMetrics>,
+}
+
+#[derive(Deserialize)]
+struct ContainerMetrics {
+    name: String,
+    usage: Usage,
+}
+
+#[derive(Deserialize)]
+struct NodeMetrics {
+    metadata: Metadata,
+    usage: Usage,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CustomMetricValue {
+    described_object: DescribedObject,
+    value: Quantity,
+}
+
+#[derive(Deserialize)]
+struct DescribedObject {
+    #[serde(default)]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    namespace: String,
+}
+
+impl KubernetesService {
+    /// Returns the current CPU and memory usage of the pods in a namespace that match the
+    /// label selector.
+    pub async fn pod_metrics(&self, namespace: &str, selector: &str) -> Result<Vec<PodUsage>> {
+        let path = format!(
+            "{}/namespaces/{}/pods?labelSelector={}",
+            RESOURCE_METRICS_PATH,
+            namespace,
+            encode(selector)
+        );
+        let list: List<PodMetrics> = self.get_json(&path).await?;
+        Ok(list
+            .items
+            .into_iter()
+            .map(|pod| PodUsage {
+                name: pod.metadata.name,
+                namespace: pod.metadata.namespace,
+                containers: pod
+                    .containers
+                    .into_iter()
+                    .map(|container| {
+                        let (cpu_millis, memory_bytes) = usage(&container.usage);
+                        ContainerUsage {
+                            name: container.name,
+                            cpu_millis,
+                            memory_bytes,
+                        }
+                    })
+                    .collect(),
+            })
+            .collect())
+    }
+
+    /// Returns the current CPU and memory usage of all nodes.
+    pub async fn node_metrics(&self) -> Result<Vec<NodeUsage>> {
+        let path = format!("{}/nodes", RESOURCE_METRICS_PATH);
+        let list: List<NodeMetrics> = self.get_json(&path).await?;
+        Ok(list
+            .items
+            .into_iter()
+            .map(|node| {
+                let (cpu_millis, memory_bytes) = usage(&node.usage);
+                NodeUsage {
+                    name: node.metadata.name,
+                    cpu_millis,
+                    memory_bytes,
+                }
+            })
+            .collect())
+    }
+
+    /// Returns the values of a custom metric for an object, or for all objects of the kind
+    /// when `name` is `*`.
+    pub async fn custom_metric(
+        &self,
+        kind: &str,
+        name: &str,
+        namespace: &str,
+        metric: &str,
+    ) -> Result<Vec<MetricValue>> {
+        let (ar, _) = self.resolve_kind(kind).await?;
+        let path = if namespace.is_empty() {
+            format!("{}/{}/{}/{}", CUSTOM_METRICS_PATH, ar.plural, name, metric)
+        } else {
+            format!(
+                "{}/namespaces/{}/{}/{}/{}",
+                CUSTOM_METRICS_PATH, namespace, ar.plural, name, metric
+            )
+        };
+        let list: List<CustomMetricValue> = self.get_json(&path).await?;
+        Ok(list
+            .items
+            .into_iter()
+            .map(|item| MetricValue {
+                object: ObjectReference {
+                    kind: item.described_object.kind,
+                    name: item.described_object.name,
+                    namespace: item.described_object.namespace,
+                },
+                value: quantity::parse(&item.value.0).unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    pub(super) async fn get_json<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T> {
+        self.with_reauth(|client| {
+            let request = http::Request::get(path)
+                .body(Vec::new())
+                .expect("metrics request is valid");
+            async move { client.request(request).await }
+        })
+        .await
+        .with_context(|| format!("Failed to query {}", path))
+    }
+}
+
+/// Returns the CPU usage in millicores and the memory usage in bytes.
+fn usage(usage: &Usage) -> (u64, u64) {
+    let parse = |value: &Option<Quantity>| {
+        value
+            .as_ref()
+            .and_then(|value| quantity::parse(&value.0))
+            .unwrap_or_default()
+    };
+    (
+        (parse(&usage.cpu) * 1000.0).ceil() as u64,
+        parse(&usage.memory).ceil() as u64,
+    )
+}
+
+/// Percent-encodes a label selector for use in a query string.
+fn encode(selector: &str) -> String {
+    selector
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
@@ -0,0 +1,182 @@
+//! # Topology Module
+//!
+//! This module keeps cached informers of the nodes and pods of the cluster, so operators
+//! that place or rebalance workloads can query node capacity and node occupancy without
+//! listing and parsing the raw objects inside the guest. The informers are started on the
+//! first query and kept up to date by a watch.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use futures::{future, StreamExt};
+use k8s_openapi::api::core::v1::{Node, Pod};
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+use kube::runtime::reflector::{self, Store};
+use kube::runtime::{watcher, WatchStreamExt};
+use kube::{Api, Client, ResourceExt};
+use tracing::warn;
+
+use super::quantity;
+use crate::host::api::bindings::local::operator::types::{NodeCapacity, NodeInfo, PodInfo};
+
+/// Cached views of the nodes and pods of the cluster.
+pub struct Topology {
+    nodes: Store<Node>,
+    pods: Store<Pod>,
+}
+
+impl Topology {
+    /// Starts the node and pod informers and waits for their initial lists.
+    pub async fn start(client: Client) -> Result<Self> {
+        let nodes = reflect(Api::all(client.clone()), "nodes");
+        let pods = reflect(Api::all(client), "pods");
+        nodes
+            .wait_until_ready()
+            .await
+            .context("Node informer stopped")?;
+        pods.wait_until_ready()
+            .await
+            .context("Pod informer stopped")?;
+        Ok(Self { nodes, pods })
+    }
+
+    pub fn list_nodes(&self) -> Vec<NodeInfo> {
+        let mut nodes: Vec<NodeInfo> = self
+            .nodes
+            .state()
+            .iter()
+            .map(|node| {
+                let status = node.status.as_ref();
+                let ready = status
+                    .and_then(|status| status.conditions.as_ref())
+                    .into_iter()
+                    .flatten()
+                    .any(|c| c.type_ == "Ready" && c.status == "True");
+                NodeInfo {
+                    name: node.name_any(),
+                    labels: node.labels().clone().into_iter().collect(),
+                    ready,
+                    unschedulable: node
+                        .spec
+                        .as_ref()
+                        .and_then(|spec| spec.unschedulable)
+                        .unwrap_or(false),
+                }
+            })
+            .collect();
+        nodes.sort_by(|a, b| a.name.cmp(&b.name));
+        nodes
+    }
+
+    pub fn node_capacity(&self, name: &str) -> Option<NodeCapacity> {
+        let node = self.nodes.find(|node| node.name_any() == name)?;
+        let status = node.status.as_ref();
+        let capacity = status.and_then(|status| status.capacity.as_ref());
+        let allocatable = status.and_then(|status| status.allocatable.as_ref());
+        Some(NodeCapacity {
+            cpu_millis: millis(capacity, "cpu"),
+            memory_bytes: units(capacity, "memory"),
+            pods: units(capacity, "pods"),
+            allocatable_cpu_millis: millis(allocatable, "cpu"),
+            allocatable_memory_bytes: units(allocatable, "memory"),
+            allocatable_pods: units(allocatable, "pods"),
+        })
+    }
+
+    pub fn list_pods_on_node(&self, node: &str) -> Vec<PodInfo> {
+        let mut pods: Vec<PodInfo> = self
+            .pods
+            .state()
+            .iter()
+            .filter(|pod| {
+                pod.spec.as_ref().and_then(|spec| spec.node_name.as_deref()) == Some(node)
+            })
+            .map(|pod| {
+                let (cpu_request_millis, memory_request_bytes) = requests(pod);
+                PodInfo {
+                    name: pod.name_any(),
+                    namespace: pod.namespace().unwrap_or_default(),
+                    phase: pod
+                        .status
+                        .as_ref()
+                        .and_then(|status| status.phase.clone())
+                        .unwrap_or_default(),
+                    cpu_request_millis,
+                    memory_request_bytes,
+                }
+            })
+            .collect();
+        pods.sort_by(|a, b| (&a.namespace, &a.name).cmp(&(&b.namespace, &b.name)));
+        pods
+    }
+}
+
+/// Starts an informer that mirrors all objects of the API into a store.
+fn reflect<K>(api: Api<K>, name: &'static str) -> Store<K>
+where
+    K: kube::Resource<DynamicType = ()>
+        + Clone
+        + std::fmt::Debug
+        + serde::de::DeserializeOwned
+        + Send
+        + Sync
+        + 'static,
+{
+    let (reader, writer) = reflector::store();
+    let stream = watcher(api, watcher::Config::default())
+        .default_backoff()
+        // Managed fields are large and never needed here.
+        .modify(|object| object.managed_fields_mut().clear())
+        .reflect(writer)
+        .applied_objects();
+    tokio::spawn(stream.for_each(move |result| {
+        if let Err(e) = result {
+            warn!("Topology informer for {} failed: {}", name, e);
+        }
+        future::ready(())
+    }));
+    reader
+}
+
+/// Returns the CPU and memory requested by a pod, as the scheduler accounts them: the sum
+/// over its containers, or the largest init container if that is higher.
+fn requests(pod: &Pod) -> (u64, u64) {
+    let Some(spec) = &pod.spec else {
+        return (0, 0);
+    };
+    let container_requests = |container: &k8s_openapi::api::core::v1::Container| {
+        let requests = container
+            .resources
+            .as_ref()
+            .and_then(|resources| resources.requests.as_ref());
+        (millis(requests, "cpu"), units(requests, "memory"))
+    };
+    let (cpu, memory) = spec
+        .containers
+        .iter()
+        .map(container_requests)
+        .fold((0, 0), |(cpu, memory), (c, m)| (cpu + c, memory + m));
+    spec.init_containers
+        .iter()
+        .flatten()
+        .map(container_requests)
+        .fold((cpu, memory), |(cpu, memory), (c, m)| {
+            (cpu.max(c), memory.max(m))
+        })
+}
+
+fn millis(quantities: Option<&BTreeMap<String, Quantity>>, name: &str) -> u64 {
+    amount(quantities, name)
+        .map(|value| (value * 1000.0).ceil() as u64)
+        .unwrap_or(0)
+}
+
+fn units(quantities: Option<&BTreeMap<String, Quantity>>, name: &str) -> u64 {
+    amount(quantities, name)
+        .map(|value| value.ceil() as u64)
+        .unwrap_or(0)
+}
+
+fn amount(quantities: Option<&BTreeMap<String, Quantity>>, name: &str) -> Option<f64> {
+    quantity::parse(&quantities?.get(name)?.0)
+}
@@ -0,0 +1,50 @@
+//! # WASM Operator Runtime
+//!
+//! This crate runs Kubernetes operators compiled to WebAssembly components. The `parent`
+//! binary is a thin wrapper around it, and other projects can embed the runtime in their
+//! own processes:
+//!
+//! ```no_run
+//! use std::path::PathBuf;
+//!
+//! use wasm_operator_runtime::{RuntimeConfig, WasmComponentMetadata, WasmRuntime};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let metadata = WasmComponentMetadata::load_from_yaml(&PathBuf::from("operators.yaml"))?;
+//! let runtime = WasmRuntime::builder()
+//!     .config(RuntimeConfig::default())
+//!     .build()
+//!     .await?;
+//! runtime.run_components(metadata).await
+//! # }
+//! ```
+//!
+//! The runtime spawns tasks with `tokio::task::spawn_local`, so it must run inside a
+//! `tokio::task::LocalSet` on a tokio runtime with all drivers enabled.
+
+#[cfg(feature = "admin-api")]
+pub mod admin;
+pub mod bundle;
+pub mod cache;
+#[cfg(feature = "oci")]
+pub mod catalog;
+pub mod config;
+pub mod conformance;
+#[cfg(feature = "admin-api")]
+pub mod debug_bundle;
+pub mod dependencies;
+mod host;
+pub mod kubernetes;
+mod metrics;
+pub mod package;
+mod platform;
+pub mod preflight;
+pub mod runtime;
+mod tarball;
+pub mod verify_build;
+
+pub use config::metadata::WasmComponentMetadata;
+pub use config::profile::Profile;
+pub use config::runtime::RuntimeConfig;
+pub use kubernetes::KubernetesService;
+pub use runtime::{WasmRuntime, WasmRuntimeBuilder};
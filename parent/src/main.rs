@@ -1,29 +1,125 @@
 //! # Main Module
 //!
-//! This module serves as the entry point for the Wasm Operator. It is responsible for
-//! parsing command-line arguments, setting up logging, loading the WASM component
-//! configuration, and orchestrating the Kubernetes service and the WASM runtime
-//! to execute the Wasm modules.
+//! This module is the entry point of the `parent` binary, a thin wrapper around the
+//! `wasm_operator_runtime` library. It is responsible for parsing command-line arguments,
+//! setting up logging, loading the WASM component configuration, and running the
+//! subcommands and the WASM runtime.
 
-mod config;
-mod host;
-mod kubernetes;
-mod runtime;
-
-use std::sync::Arc;
+use std::net::SocketAddr;
 use std::{env, path::PathBuf};
 
-use config::metadata::WasmComponentMetadata;
-use kubernetes::KubernetesService;
-use runtime::WasmRuntime;
+#[cfg(feature = "admin-api")]
+use tracing::error;
 use tracing::{debug, info};
 use tracing_subscriber::FmtSubscriber;
+use wasm_operator_runtime::config::runtime::LogFormat;
+#[cfg(feature = "admin-api")]
+use wasm_operator_runtime::runtime::watchdog::Probe;
+#[cfg(feature = "admin-api")]
+use wasm_operator_runtime::{admin, debug_bundle};
+use wasm_operator_runtime::{
+    cache, conformance, package, preflight, verify_build, Profile, RuntimeConfig,
+    WasmComponentMetadata, WasmRuntime,
+};
+
+/// Command-line arguments of the parent.
+struct Args {
+    config_path: PathBuf,
+    debug: bool,
+    runtime_config_path: Option<PathBuf>,
+    admin_addr: Option<SocketAddr>,
+    state_dir: Option<PathBuf>,
+    profile: Profile,
+    restore_from: Option<PathBuf>,
+    read_only: bool,
+    kubeconfig: Option<PathBuf>,
+    context: Option<String>,
+    namespace: Option<String>,
+}
 
 fn main() -> anyhow::Result<()> {
-    let (config_path, debug) = parse_args()?;
+    let raw_args: Vec<String> = env::args().collect();
+    if raw_args.get(1).map(String::as_str) == Some("conformance") {
+        setup_logging(false, LogFormat::Full);
+        if !conformance::run(&raw_args[2..])? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    #[cfg(feature = "admin-api")]
+    if raw_args.get(1).map(String::as_str) == Some("debug-bundle") {
+        return debug_bundle::run(&raw_args[2..]);
+    }
+    if raw_args.get(1).map(String::as_str) == Some("package") {
+        return package::run_package(&raw_args[2..]);
+    }
+    if raw_args.get(1).map(String::as_str) == Some("install") {
+        return package::run_install(&raw_args[2..]);
+    }
+    #[cfg(feature = "oci")]
+    if raw_args.get(1).map(String::as_str) == Some("available") {
+        return package::run_available(&raw_args[2..]);
+    }
+    #[cfg(feature = "oci")]
+    if raw_args.get(1).map(String::as_str) == Some("upgrade") {
+        return package::run_upgrade(&raw_args[2..]);
+    }
+    if raw_args.get(1).map(String::as_str) == Some("export-bundles") {
+        return package::run_export(&raw_args[2..]);
+    }
+    if raw_args.get(1).map(String::as_str) == Some("import-bundles") {
+        return package::run_import(&raw_args[2..]);
+    }
+    if raw_args.get(1).map(String::as_str) == Some("cache") {
+        return cache::run(&raw_args[2..]);
+    }
+    if raw_args.get(1).map(String::as_str) == Some("verify-build") {
+        if !verify_build::run(&raw_args[2..])? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if raw_args.get(1).map(String::as_str) == Some("preflight") {
+        setup_logging(false, LogFormat::Full);
+        if !preflight::run(&raw_args[2..])? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let args = parse_args()?;
+
+    let mut runtime_config = match &args.runtime_config_path {
+        Some(path) => RuntimeConfig::load_from_yaml(path, args.profile.defaults())?,
+        None => args.profile.defaults(),
+    };
+    setup_logging(args.debug, runtime_config.log_format);
+    let components_metadata = WasmComponentMetadata::load_from_yaml(&args.config_path)?;
 
-    setup_logging(debug);
-    let components_metadata = WasmComponentMetadata::load_from_yaml(&config_path)?;
+    if let Some(admin_addr) = args.admin_addr {
+        runtime_config.admin_addr = admin_addr;
+    }
+    if let Some(state_dir) = args.state_dir {
+        runtime_config.state_dir = state_dir;
+    }
+    if let Some(restore_from) = args.restore_from {
+        runtime_config.restore_from = Some(restore_from);
+    }
+    if args.read_only {
+        runtime_config.read_only = true;
+    }
+    if let Some(kubeconfig) = args.kubeconfig {
+        runtime_config.kubernetes.kubeconfig = Some(kubeconfig);
+    }
+    if let Some(context) = args.context {
+        runtime_config.kubernetes.context = Some(context);
+    }
+    if let Some(namespace) = args.namespace {
+        runtime_config.kubernetes.namespace = Some(namespace);
+    }
+    if runtime_config.read_only {
+        info!("Running in read-only mode, writes of operators are not applied.");
+    }
 
     info!("Loaded {} WASM component(s):", components_metadata.len());
     for metadata in &components_metadata {
@@ -36,8 +132,30 @@ fn main() -> anyhow::Result<()> {
         .build()?;
     let local = tokio::task::LocalSet::new();
     local.block_on(&tokio_runtime, async {
-        let k8s_service = Arc::new(KubernetesService::new().await?);
-        let wasm_runtime = Arc::new(WasmRuntime::new(k8s_service.clone())?);
+        #[cfg(feature = "admin-api")]
+        let admin_addr = runtime_config.admin_addr;
+        let wasm_runtime = WasmRuntime::builder()
+            .config(runtime_config)
+            .build()
+            .await?;
+
+        #[cfg(feature = "admin-api")]
+        {
+            let admin_runtime = wasm_runtime.clone();
+            wasm_runtime.supervise(
+                "admin-api",
+                Probe::Connect(admin_addr),
+                Box::new(move || {
+                    let admin_runtime = admin_runtime.clone();
+                    Some(Box::pin(async move {
+                        if let Err(e) = admin::serve(admin_addr, admin_runtime).await {
+                            error!("Admin API stopped: {}", e);
+                        }
+                    }))
+                }),
+            );
+        }
+
         // The future inside block_on needs to return a Result.
         // After run_components (which returns a Result) is awaited, we wrap the
         // successful `()` value in an `Ok` to match the expected return type.
@@ -51,17 +169,20 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn setup_logging(debug: bool) {
+fn setup_logging(debug: bool, format: LogFormat) {
     let level = if debug {
         tracing::Level::DEBUG
     } else {
         tracing::Level::INFO
     };
 
-    tracing::subscriber::set_global_default(
-        FmtSubscriber::builder().with_max_level(level).finish(),
-    )
-        .expect("setting default subscriber failed");
+    let builder = FmtSubscriber::builder().with_max_level(level);
+    let result = match format {
+        LogFormat::Full => tracing::subscriber::set_global_default(builder.finish()),
+        LogFormat::Pretty => tracing::subscriber::set_global_default(builder.pretty().finish()),
+        LogFormat::Json => tracing::subscriber::set_global_default(builder.json().finish()),
+    };
+    result.expect("setting default subscriber failed");
 
     if debug {
         debug!("Debug logging enabled.");
@@ -70,14 +191,70 @@ fn setup_logging(debug: bool) {
     }
 }
 
-fn parse_args() -> anyhow::Result<(PathBuf, bool)> {
+fn parse_args() -> anyhow::Result<Args> {
     let args: Vec<String> = env::args().collect();
     let mut debug = false;
+    let mut read_only = false;
+    let mut runtime_config_path: Option<PathBuf> = None;
+    let mut admin_addr: Option<SocketAddr> = None;
+    let mut state_dir: Option<PathBuf> = None;
+    let mut profile = Profile::default();
+    let mut restore_from: Option<PathBuf> = None;
+    let mut kubeconfig: Option<PathBuf> = None;
+    let mut context: Option<String> = None;
+    let mut namespace: Option<String> = None;
     let mut config_path: Option<PathBuf> = None;
 
-    for arg in &args[1..] {
+    let mut iter = args[1..].iter();
+    while let Some(arg) = iter.next() {
         if arg == "--debug" {
             debug = true;
+        } else if arg == "--read-only" {
+            read_only = true;
+        } else if arg == "--admin-addr" {
+            let value = iter
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("--admin-addr requires a value"))?;
+            admin_addr = Some(
+                value
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Invalid --admin-addr '{}': {}", value, e))?,
+            );
+        } else if arg == "--runtime-config" {
+            let value = iter
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("--runtime-config requires a value"))?;
+            runtime_config_path = Some(PathBuf::from(value));
+        } else if arg == "--profile" {
+            let value = iter
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("--profile requires a value"))?;
+            profile = value.parse()?;
+        } else if arg == "--restore-from" {
+            let value = iter
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("--restore-from requires a value"))?;
+            restore_from = Some(PathBuf::from(value));
+        } else if arg == "--state-dir" {
+            let value = iter
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("--state-dir requires a value"))?;
+            state_dir = Some(PathBuf::from(value));
+        } else if arg == "--kubeconfig" {
+            let value = iter
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("--kubeconfig requires a value"))?;
+            kubeconfig = Some(PathBuf::from(value));
+        } else if arg == "--context" {
+            let value = iter
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("--context requires a value"))?;
+            context = Some(value.clone());
+        } else if arg == "--namespace" || arg == "-n" {
+            let value = iter
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("--namespace requires a value"))?;
+            namespace = Some(value.clone());
         } else if config_path.is_none() {
             config_path = Some(PathBuf::from(arg));
         } else {
@@ -86,8 +263,23 @@ fn parse_args() -> anyhow::Result<(PathBuf, bool)> {
     }
 
     let config_path = config_path.ok_or_else(|| {
-        anyhow::anyhow!("Usage: {} [--debug] <path_to_wasm_config.yaml>", args[0])
+        anyhow::anyhow!(
+            "Usage: {} [--debug] [--read-only] [--profile dev|bench|prod|edge] [--runtime-config <path>] [--admin-addr <addr>] [--state-dir <path>] [--restore-from <dir>] [--kubeconfig <path>] [--context <name>] [--namespace <name>] <path_to_wasm_config.yaml>",
+            args[0]
+        )
     })?;
 
-    Ok((config_path, debug))
+    Ok(Args {
+        config_path,
+        debug,
+        runtime_config_path,
+        admin_addr,
+        state_dir,
+        profile,
+        restore_from,
+        read_only,
+        kubeconfig,
+        context,
+        namespace,
+    })
 }
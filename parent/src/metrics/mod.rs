@@ -0,0 +1,190 @@
+//! # Metrics Module
+//!
+//! This module provides a minimal, process-wide registry of counters and gauges. The
+//! registry is rendered in the Prometheus text exposition format by the admin API, so
+//! the runtime can be scraped without pulling in a full metrics stack. Builds without the
+//! `metrics` feature never record anything.
+
+#[cfg(feature = "admin-api")]
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use dashmap::DashMap;
+
+type Labels = Vec<(String, String)>;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum MetricType {
+    Counter,
+    Gauge,
+}
+
+impl MetricType {
+    #[cfg(feature = "admin-api")]
+    fn as_str(&self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    types: DashMap<&'static str, MetricType>,
+    values: DashMap<(&'static str, Labels), f64>,
+}
+
+/// Returns the process-wide metrics registry.
+pub fn global() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::default)
+}
+
+fn enabled() -> bool {
+    cfg!(feature = "metrics") && ENABLED.load(Ordering::Relaxed)
+}
+
+/// Turns recording metrics on or off. Metrics are recorded by default.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Increments a counter by one.
+pub fn increment(name: &'static str, labels: &[(&str, &str)]) {
+    if enabled() {
+        global().add(name, MetricType::Counter, labels, 1.0);
+    }
+}
+
+/// Increments a counter by the given amount.
+pub fn increment_by(name: &'static str, labels: &[(&str, &str)], value: f64) {
+    if enabled() {
+        global().add(name, MetricType::Counter, labels, value);
+    }
+}
+
+/// Sets a gauge to the given value.
+pub fn set_gauge(name: &'static str, labels: &[(&str, &str)], value: f64) {
+    if enabled() {
+        global().set(name, MetricType::Gauge, labels, value);
+    }
+}
+
+impl Metrics {
+    fn key(name: &'static str, labels: &[(&str, &str)]) -> (&'static str, Labels) {
+        let labels = labels
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        (name, labels)
+    }
+
+    fn add(&self, name: &'static str, ty: MetricType, labels: &[(&str, &str)], value: f64) {
+        self.types.entry(name).or_insert(ty);
+        *self.values.entry(Self::key(name, labels)).or_insert(0.0) += value;
+    }
+
+    fn set(&self, name: &'static str, ty: MetricType, labels: &[(&str, &str)], value: f64) {
+        self.types.entry(name).or_insert(ty);
+        self.values.insert(Self::key(name, labels), value);
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    #[cfg(feature = "admin-api")]
+    pub fn render(&self) -> String {
+        let mut names: Vec<&'static str> = self.types.iter().map(|e| *e.key()).collect();
+        names.sort_unstable();
+
+        let mut output = String::new();
+        for name in names {
+            let ty = self
+                .types
+                .get(name)
+                .map(|t| *t)
+                .unwrap_or(MetricType::Counter);
+            let _ = writeln!(output, "# TYPE {} {}", name, ty.as_str());
+
+            let mut samples: Vec<(Labels, f64)> = self
+                .values
+                .iter()
+                .filter(|e| e.key().0 == name)
+                .map(|e| (e.key().1.clone(), *e.value()))
+                .collect();
+            samples.sort_by(|a, b| a.0.cmp(&b.0));
+
+            for (labels, value) in samples {
+                if labels.is_empty() {
+                    let _ = writeln!(output, "{} {}", name, value);
+                } else {
+                    let labels = labels
+                        .iter()
+                        .map(|(k, v)| format!("{}=\"{}\"", k, escape_label_value(v)))
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    let _ = writeln!(output, "{}{{{}}} {}", name, labels, value);
+                }
+            }
+        }
+        output
+    }
+}
+
+/// Escapes a label value for the text exposition format. Operator and kind names come from
+/// user metadata, so they may contain any of the characters that must be escaped.
+#[cfg(feature = "admin-api")]
+fn escape_label_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(all(test, feature = "admin-api"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(escape_label_value("plain"), "plain");
+        assert_eq!(escape_label_value(r#"a"b"#), r#"a\"b"#);
+        assert_eq!(escape_label_value(r"a\b"), r"a\\b");
+        assert_eq!(escape_label_value("a\nb"), r"a\nb");
+    }
+
+    #[test]
+    fn renders_sorted_samples() {
+        let metrics = Metrics::default();
+        metrics.add("b_total", MetricType::Counter, &[("operator", "y")], 1.0);
+        metrics.add("b_total", MetricType::Counter, &[("operator", "x")], 2.0);
+        metrics.add("b_total", MetricType::Counter, &[("operator", "x")], 1.0);
+        metrics.set("a", MetricType::Gauge, &[], 0.5);
+        metrics.set("a", MetricType::Gauge, &[], 4.0);
+        metrics.add(
+            "c_total",
+            MetricType::Counter,
+            &[("operator", "q\"\n")],
+            1.0,
+        );
+
+        assert_eq!(
+            metrics.render(),
+            "# TYPE a gauge\n\
+             a 4\n\
+             # TYPE b_total counter\n\
+             b_total{operator=\"x\"} 3\n\
+             b_total{operator=\"y\"} 1\n\
+             # TYPE c_total counter\n\
+             c_total{operator=\"q\\\"\\n\"} 1\n"
+        );
+    }
+}
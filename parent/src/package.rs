@@ -0,0 +1,617 @@
+//! # Package Module
+//!
+//! This module implements the commands that distribute operators as `.wopr` bundles:
+//! `parent package` packs an operator into a bundle, `parent install` checks a bundle and
+//! sets it up to be run, `parent available` lists the operators in a catalog, and
+//! `parent upgrade` installs newer versions of installed operators from a catalog.
+//! `parent export-bundles` and `parent import-bundles` mirror the operators of a config
+//! into a directory and install them from it, e.g. in a disconnected cluster. Install
+//! and upgrade resolve the dependencies of bundles first, and take missing ones from a
+//! catalog. See the bundle and catalog modules for the formats.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::bundle::{self, Bundle};
+#[cfg(feature = "oci")]
+use crate::cache::ComponentCache;
+#[cfg(feature = "oci")]
+use crate::catalog::{CatalogClient, CatalogEntry, CatalogIndex, Reference};
+use crate::config::metadata::WasmComponentMetadata;
+#[cfg(feature = "oci")]
+use crate::dependencies::Step;
+use crate::dependencies::{Action, Package, Plan, Requirement, Resolver};
+
+const PACKAGE_USAGE: &str = "Usage: parent package --component <path> --config <path> [--crd <path>]... [--version <version>] [--requires <name>@<version>]... [--signing-key <path>] --output <path>";
+const INSTALL_USAGE: &str = "Usage: parent install [--catalog <reference>] [--dir <path>] [--public-key <path>] [--dry-run] <bundle.wopr>";
+#[cfg(feature = "oci")]
+const AVAILABLE_USAGE: &str = "Usage: parent available --catalog <reference> [--dir <path>]";
+#[cfg(feature = "oci")]
+const UPGRADE_USAGE: &str = "Usage: parent upgrade --catalog <reference> [--dir <path>] [--public-key <path>] [--dry-run] [<name>...]";
+const EXPORT_USAGE: &str = "Usage: parent export-bundles <config.yaml> --out <dir>";
+const IMPORT_USAGE: &str =
+    "Usage: parent import-bundles [--dir <path>] [--public-key <path>] [--dry-run] <export dir>";
+const DEFAULT_DIR: &str = "operators";
+/// The index of an export, next to its bundles.
+const EXPORT_INDEX: &str = "index.json";
+
+/// The bundles of an export, with the hex-encoded SHA-256 digests of their files.
+#[derive(Serialize, Deserialize)]
+struct ExportIndex {
+    bundles: Vec<ExportedBundle>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExportedBundle {
+    name: String,
+    file: String,
+    digest: String,
+}
+
+/// Runs the package subcommand with the arguments that follow it.
+pub fn run_package(args: &[String]) -> Result<()> {
+    let mut component: Option<PathBuf> = None;
+    let mut config: Option<PathBuf> = None;
+    let mut crds: Vec<PathBuf> = Vec::new();
+    let mut version: Option<String> = None;
+    let mut requires: Vec<Requirement> = Vec::new();
+    let mut signing_key: Option<PathBuf> = None;
+    let mut output: Option<PathBuf> = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .map(PathBuf::from)
+                .ok_or_else(|| anyhow!("{} requires a value", arg))
+        };
+        match arg.as_str() {
+            "--component" => component = Some(value()?),
+            "--config" => config = Some(value()?),
+            "--crd" => crds.push(value()?),
+            "--version" => version = Some(value()?.display().to_string()),
+            "--requires" => requires.push(value()?.display().to_string().parse()?),
+            "--signing-key" => signing_key = Some(value()?),
+            "--output" => output = Some(value()?),
+            _ => bail!("Unexpected argument: {}\n{}", arg, PACKAGE_USAGE),
+        }
+    }
+    let (Some(component), Some(config), Some(output)) = (component, config, output) else {
+        bail!(PACKAGE_USAGE);
+    };
+    if !bundle::is_bundle(&output) {
+        bail!("The output must end in .{}", bundle::EXTENSION);
+    }
+
+    let component = std::fs::read(&component)
+        .with_context(|| format!("Failed to read {}", component.display()))?;
+    let config = std::fs::read_to_string(&config)
+        .with_context(|| format!("Failed to read {}", config.display()))?;
+    let crds = crds
+        .iter()
+        .map(|path| {
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| anyhow!("Invalid CRD path {}", path.display()))?;
+            let contents = std::fs::read(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            Ok((name.to_string(), contents))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let signing_key = signing_key
+        .as_deref()
+        .map(bundle::load_signing_key)
+        .transpose()?;
+
+    let packed = Bundle::pack(
+        &component,
+        &config,
+        &crds,
+        version,
+        requires,
+        signing_key.as_ref(),
+    )?;
+    std::fs::write(&output, &packed)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    println!(
+        "Wrote {}bundle of {} bytes to {}",
+        if signing_key.is_some() { "signed " } else { "" },
+        packed.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// Runs the install subcommand with the arguments that follow it. Missing dependencies of
+/// the bundle are installed from `--catalog` first, in builds with the `oci` feature.
+pub fn run_install(args: &[String]) -> Result<()> {
+    let args = CommandArgs::parse(args, INSTALL_USAGE)?;
+    let [bundle_path] = args.rest.as_slice() else {
+        bail!(INSTALL_USAGE);
+    };
+    let bundle_path = PathBuf::from(bundle_path);
+
+    let data = std::fs::read(&bundle_path)
+        .with_context(|| format!("Failed to read bundle {}", bundle_path.display()))?;
+    let bundle = Bundle::from_bytes(&data)
+        .with_context(|| format!("Invalid bundle {}", bundle_path.display()))?;
+    let public_key = args
+        .public_key
+        .as_deref()
+        .map(bundle::load_public_key)
+        .transpose()?;
+    block_on(async {
+        #[cfg(feature = "oci")]
+        let mut catalog = match &args.catalog {
+            Some(reference) => {
+                let mut client = client()?;
+                let index = client.index(reference).await?;
+                Some((client, index))
+            }
+            None => None,
+        };
+        let installed = installed_packages(&args.dir);
+        #[cfg(feature = "oci")]
+        let resolver = match &catalog {
+            Some((_, index)) => Resolver::with_catalog(installed, index),
+            None => Resolver::new(installed, None),
+        };
+        #[cfg(not(feature = "oci"))]
+        let resolver = Resolver::new(installed, None);
+        let plan = resolver.plan(vec![Package::of_bundle(&bundle)?])?;
+        print_plan(&plan);
+        if args.dry_run {
+            return Ok(());
+        }
+        for step in &plan.steps {
+            if step.package.name == bundle.manifest.name {
+                install(&bundle, &data, &args.dir, public_key.as_deref(), false)?;
+                continue;
+            }
+            // Dependencies only come from the catalog.
+            #[cfg(feature = "oci")]
+            {
+                let (client, index) = catalog
+                    .as_mut()
+                    .ok_or_else(|| anyhow!("No catalog to install {} from", step.package.name))?;
+                install_from_catalog(client, index, step, &args.dir, public_key.as_deref()).await?;
+            }
+            #[cfg(not(feature = "oci"))]
+            bail!("No catalog to install {} from", step.package.name);
+        }
+        Ok(())
+    })
+}
+
+/// Runs the export-bundles subcommand with the arguments that follow it. Every operator in
+/// the config is written to the output directory as a bundle, along with an index of their
+/// digests: bundles are copied as they are, signatures included, and plain components
+/// are packed into unsigned bundles with their metadata.
+pub fn run_export(args: &[String]) -> Result<()> {
+    let mut config: Option<PathBuf> = None;
+    let mut out: Option<PathBuf> = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--out" {
+            out = Some(PathBuf::from(
+                iter.next()
+                    .ok_or_else(|| anyhow!("--out requires a value"))?,
+            ));
+        } else if config.is_none() && !arg.starts_with("--") {
+            config = Some(PathBuf::from(arg));
+        } else {
+            bail!("Unexpected argument: {}\n{}", arg, EXPORT_USAGE);
+        }
+    }
+    let (Some(config), Some(out)) = (config, out) else {
+        bail!(EXPORT_USAGE);
+    };
+
+    let operators = WasmComponentMetadata::load_from_yaml(&config)?;
+    std::fs::create_dir_all(&out).with_context(|| format!("Failed to create {}", out.display()))?;
+    let mut index = ExportIndex {
+        bundles: Vec::new(),
+    };
+    for metadata in &operators {
+        let (name, data) = if bundle::is_bundle(&metadata.wasm) {
+            let data = std::fs::read(&metadata.wasm)
+                .with_context(|| format!("Failed to read bundle {}", metadata.wasm.display()))?;
+            let bundle = Bundle::from_bytes(&data)
+                .with_context(|| format!("Invalid bundle {}", metadata.wasm.display()))?;
+            (bundle.manifest.name, data)
+        } else {
+            let component = std::fs::read(&metadata.wasm)
+                .with_context(|| format!("Failed to read {}", metadata.wasm.display()))?;
+            let config = serde_yml::to_string(metadata)?;
+            let data = Bundle::pack(&component, &config, &[], None, Vec::new(), None)?;
+            (metadata.name.clone(), data)
+        };
+        check_name(&name)?;
+        if index.bundles.iter().any(|exported| exported.name == name) {
+            bail!("Operator '{}' is exported twice", name);
+        }
+        let file = format!("{}.{}", name, bundle::EXTENSION);
+        write(&out.join(&file), &data)?;
+        println!("Exported '{}' ({} bytes)", name, data.len());
+        index.bundles.push(ExportedBundle {
+            name,
+            digest: bundle::sha256(&data),
+            file,
+        });
+    }
+    write(&out.join(EXPORT_INDEX), &serde_json::to_vec_pretty(&index)?)?;
+    println!(
+        "Exported {} operator(s) to {}",
+        index.bundles.len(),
+        out.display()
+    );
+    Ok(())
+}
+
+/// Runs the import-bundles subcommand with the arguments that follow it. The bundles of an
+/// export are checked against the digests in its index and installed, dependencies first.
+pub fn run_import(args: &[String]) -> Result<()> {
+    let args = CommandArgs::parse(args, IMPORT_USAGE)?;
+    let [export] = args.rest.as_slice() else {
+        bail!(IMPORT_USAGE);
+    };
+    #[cfg(feature = "oci")]
+    if args.catalog.is_some() {
+        bail!(IMPORT_USAGE);
+    }
+    let export = PathBuf::from(export);
+    let index_path = export.join(EXPORT_INDEX);
+    let index: ExportIndex = serde_json::from_slice(
+        &std::fs::read(&index_path)
+            .with_context(|| format!("Failed to read {}", index_path.display()))?,
+    )
+    .with_context(|| format!("Invalid export index {}", index_path.display()))?;
+    let public_key = args
+        .public_key
+        .as_deref()
+        .map(bundle::load_public_key)
+        .transpose()?;
+
+    let mut bundles = Vec::new();
+    for exported in &index.bundles {
+        if exported.file.contains(['/', '\\']) {
+            bail!("Invalid file name in export index: '{}'", exported.file);
+        }
+        let path = export.join(&exported.file);
+        let data =
+            std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        if bundle::sha256(&data) != exported.digest {
+            bail!(
+                "Digest of {} does not match the export index",
+                path.display()
+            );
+        }
+        let bundle = Bundle::from_bytes(&data)
+            .with_context(|| format!("Invalid bundle {}", path.display()))?;
+        if bundle.manifest.name != exported.name {
+            bail!(
+                "{} holds '{}' instead of '{}'",
+                path.display(),
+                bundle.manifest.name,
+                exported.name
+            );
+        }
+        bundles.push((bundle, data));
+    }
+
+    let packages = bundles
+        .iter()
+        .map(|(bundle, _)| Package::of_bundle(bundle))
+        .collect::<Result<Vec<_>>>()?;
+    let plan =
+        Resolver::new(installed_packages(&args.dir), Some(packages.clone())).plan(packages)?;
+    print_plan(&plan);
+    if args.dry_run {
+        return Ok(());
+    }
+    for step in &plan.steps {
+        let (bundle, data) = bundles
+            .iter()
+            .find(|(bundle, _)| bundle.manifest.name == step.package.name)
+            .expect("plans only hold exported bundles");
+        let keep_config = matches!(step.action, Action::Upgrade { .. });
+        install(bundle, data, &args.dir, public_key.as_deref(), keep_config)?;
+    }
+    Ok(())
+}
+
+/// Runs the available subcommand with the arguments that follow it.
+#[cfg(feature = "oci")]
+pub fn run_available(args: &[String]) -> Result<()> {
+    let args = CommandArgs::parse(args, AVAILABLE_USAGE)?;
+    let Some(catalog) = &args.catalog else {
+        bail!(AVAILABLE_USAGE);
+    };
+    if args.public_key.is_some() || args.dry_run || !args.rest.is_empty() {
+        bail!(AVAILABLE_USAGE);
+    }
+    let index = block_on(async { client()?.index(catalog).await })?;
+    for entry in &index.operators {
+        let installed = match installed_version(&args.dir, &entry.name) {
+            Some(version) => format!(" (installed: {})", version),
+            None => String::new(),
+        };
+        println!("{} {}{}", entry.name, entry.version, installed);
+        if !entry.description.is_empty() {
+            println!("  {}", entry.description);
+        }
+        if let Some(capabilities) = &entry.capabilities {
+            println!("  imports: {}", capabilities.imports.join(", "));
+        }
+        if !entry.requires.is_empty() {
+            let requires: Vec<String> = entry.requires.iter().map(ToString::to_string).collect();
+            println!("  requires: {}", requires.join(", "));
+        }
+    }
+    Ok(())
+}
+
+/// Runs the upgrade subcommand with the arguments that follow it. Operators are upgraded
+/// to the highest version in the catalog when it differs from the installed one, after
+/// the dependencies that version needs, and keep their `operator.yaml`.
+#[cfg(feature = "oci")]
+pub fn run_upgrade(args: &[String]) -> Result<()> {
+    let args = CommandArgs::parse(args, UPGRADE_USAGE)?;
+    let Some(catalog) = &args.catalog else {
+        bail!(UPGRADE_USAGE);
+    };
+    let public_key = args
+        .public_key
+        .as_deref()
+        .map(bundle::load_public_key)
+        .transpose()?;
+    block_on(async {
+        let mut client = client()?;
+        let index = client.index(catalog).await?;
+        let installed = installed_packages(&args.dir);
+        let mut targets = Vec::new();
+        for package in &installed {
+            if !args.rest.is_empty() && !args.rest.contains(&package.name) {
+                continue;
+            }
+            let Some(latest) = latest_entry(&index, &package.name) else {
+                continue;
+            };
+            if package.version.as_deref() == Some(latest.version.as_str()) {
+                println!("{} {} is up to date", package.name, latest.version);
+                continue;
+            }
+            targets.push(Package::of_entry(latest));
+        }
+        let plan = Resolver::with_catalog(installed, &index).plan(targets)?;
+        print_plan(&plan);
+        if args.dry_run {
+            return Ok(());
+        }
+        for step in &plan.steps {
+            install_from_catalog(&mut client, &index, step, &args.dir, public_key.as_deref())
+                .await?;
+        }
+        println!("Applied {} step(s)", plan.steps.len());
+        Ok(())
+    })
+}
+
+/// Fetches the bundle of a step of a plan from the catalog and installs it. Upgrades keep
+/// the `operator.yaml` of the operator.
+#[cfg(feature = "oci")]
+async fn install_from_catalog(
+    client: &mut CatalogClient,
+    index: &CatalogIndex,
+    step: &Step,
+    dir: &Path,
+    public_key: Option<&[u8]>,
+) -> Result<()> {
+    let package = &step.package;
+    let entry = index
+        .operators
+        .iter()
+        .find(|entry| {
+            entry.name == package.name && Some(&entry.version) == package.version.as_ref()
+        })
+        .ok_or_else(|| anyhow!("The catalog has no entry for {}", step))?;
+    let data = client.bundle(entry).await?;
+    let bundle = Bundle::from_bytes(&data)
+        .with_context(|| format!("Invalid bundle for '{}'", entry.name))?;
+    if bundle.manifest.name != entry.name
+        || bundle.manifest.version.as_deref() != Some(entry.version.as_str())
+    {
+        bail!(
+            "The bundle of '{}' {} holds '{}' {}",
+            entry.name,
+            entry.version,
+            bundle.manifest.name,
+            bundle
+                .manifest
+                .version
+                .as_deref()
+                .unwrap_or("without a version")
+        );
+    }
+    if bundle.manifest.requires != entry.requires {
+        bail!(
+            "The requirements of the bundle of '{}' {} differ from the catalog's",
+            entry.name,
+            entry.version
+        );
+    }
+    println!("Running: {}", step);
+    let keep_config = matches!(step.action, Action::Upgrade { .. });
+    install(&bundle, &data, dir, public_key, keep_config)
+}
+
+/// Checks a bundle and installs it to `<dir>/<name>/`. An existing `operator.yaml` is kept
+/// if `keep_config` is set.
+fn install(
+    bundle: &Bundle,
+    data: &[u8],
+    dir: &Path,
+    public_key: Option<&[u8]>,
+    keep_config: bool,
+) -> Result<()> {
+    match public_key {
+        Some(public_key) => bundle.verify_signature(public_key)?,
+        None if bundle.is_signed() => {
+            println!("WARNING the bundle is signed, pass --public-key to verify it")
+        }
+        None => println!("WARNING the bundle is not signed"),
+    }
+    let mut metadata = bundle.metadata()?;
+    let name = metadata.name.clone();
+    check_name(&name)?;
+
+    let target = dir.join(&name);
+    std::fs::create_dir_all(target.join("crds"))
+        .with_context(|| format!("Failed to create {}", target.display()))?;
+    let installed_bundle = bundle_path(dir, &name);
+    write(&installed_bundle, data)?;
+    for (file_name, contents) in bundle.crds() {
+        write(&target.join("crds").join(file_name), contents)?;
+    }
+    let config_path = target.join("operator.yaml");
+    if !(keep_config && config_path.exists()) {
+        metadata.wasm = std::path::absolute(&installed_bundle)?;
+        write(&config_path, serde_yml::to_string(&metadata)?.as_bytes())?;
+    }
+
+    println!("Installed operator '{}' to {}", name, target.display());
+    if bundle.crds().next().is_some() {
+        println!(
+            "Apply its CRDs with: kubectl apply -f {}",
+            target.join("crds").display()
+        );
+    }
+    println!("Run it with: parent {}", config_path.display());
+    Ok(())
+}
+
+/// The options shared by the install, available and upgrade subcommands.
+struct CommandArgs {
+    #[cfg(feature = "oci")]
+    catalog: Option<Reference>,
+    dir: PathBuf,
+    public_key: Option<PathBuf>,
+    /// Print the plan without installing anything.
+    dry_run: bool,
+    /// The arguments that are not options.
+    rest: Vec<String>,
+}
+
+impl CommandArgs {
+    fn parse(args: &[String], usage: &str) -> Result<Self> {
+        let mut parsed = Self {
+            #[cfg(feature = "oci")]
+            catalog: None,
+            dir: PathBuf::from(DEFAULT_DIR),
+            public_key: None,
+            dry_run: false,
+            rest: Vec::new(),
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let mut value = || {
+                iter.next()
+                    .ok_or_else(|| anyhow!("{} requires a value", arg))
+            };
+            match arg.as_str() {
+                #[cfg(feature = "oci")]
+                "--catalog" => parsed.catalog = Some(value()?.parse()?),
+                "--dir" => parsed.dir = PathBuf::from(value()?),
+                "--public-key" => parsed.public_key = Some(PathBuf::from(value()?)),
+                "--dry-run" => parsed.dry_run = true,
+                _ if arg.starts_with("--") => bail!("Unexpected argument: {}\n{}", arg, usage),
+                _ => parsed.rest.push(arg.clone()),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn print_plan(plan: &Plan) {
+    if plan.steps.is_empty() {
+        println!("Nothing to do");
+        return;
+    }
+    println!("Plan:");
+    for step in &plan.steps {
+        println!("  {}", step);
+    }
+}
+
+/// The operators installed in a directory, skipping those without a readable bundle.
+fn installed_packages(dir: &Path) -> Vec<Package> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            let bundle = Bundle::read(&bundle_path(dir, &name)).ok()?;
+            Package::of_bundle(&bundle).ok()
+        })
+        .collect()
+}
+
+/// The entry of an operator with the highest version in a catalog. Versions that are not
+/// semver are only picked if there is no other.
+#[cfg(feature = "oci")]
+fn latest_entry<'a>(index: &'a CatalogIndex, name: &str) -> Option<&'a CatalogEntry> {
+    index
+        .operators
+        .iter()
+        .filter(|entry| entry.name == name)
+        .max_by_key(|entry| semver::Version::parse(&entry.version).ok())
+}
+
+/// Rejects operator names that would place files outside their directory.
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+        bail!("Invalid operator name: '{}'", name);
+    }
+    Ok(())
+}
+
+fn bundle_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(name)
+        .join(format!("{}.{}", name, bundle::EXTENSION))
+}
+
+/// Returns the version of an installed operator, `unknown` if its bundle has none.
+#[cfg(feature = "oci")]
+fn installed_version(dir: &Path, name: &str) -> Option<String> {
+    let bundle = Bundle::read(&bundle_path(dir, name)).ok()?;
+    Some(
+        bundle
+            .manifest
+            .version
+            .unwrap_or_else(|| "unknown".to_string()),
+    )
+}
+
+/// A catalog client that caches bundles, if there is a cache directory.
+#[cfg(feature = "oci")]
+fn client() -> Result<CatalogClient> {
+    let client = CatalogClient::new()?;
+    Ok(match ComponentCache::open_default() {
+        Ok(cache) => client.with_cache(cache),
+        Err(_) => client,
+    })
+}
+
+fn block_on<T>(future: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(future)
+}
+
+fn write(path: &Path, contents: &[u8]) -> Result<()> {
+    std::fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
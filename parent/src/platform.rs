@@ -0,0 +1,62 @@
+//! # Platform Module
+//!
+//! This module hides the differences between the platforms the parent runs on. In a
+//! cluster that is Linux, but developers also run the parent locally on macOS or Windows
+//! against a kind cluster.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Returns the default directory for the snapshots of unloaded operators.
+pub fn default_state_dir() -> PathBuf {
+    std::env::temp_dir().join("wasm-state")
+}
+
+/// Writes a file that only the current user may read, creating its parent directories.
+///
+/// Used for files that may hold secrets, such as guest memory snapshots and tokens. On
+/// Windows the file inherits the permissions of its directory, which for the temp
+/// directory is already limited to the current user.
+pub async fn write_private(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, contents).await?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).await?;
+    }
+    Ok(())
+}
+
+/// Waits for a shutdown request: Ctrl-C everywhere, and SIGTERM on Unix.
+pub async fn shutdown_signal() -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
+        }
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
+
+/// Returns the user and group that own a path, or None on platforms without Unix owners.
+pub fn owner(path: &Path) -> io::Result<Option<(u32, u32)>> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = std::fs::metadata(path)?;
+        Ok(Some((metadata.uid(), metadata.gid())))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        Ok(None)
+    }
+}
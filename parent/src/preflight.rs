@@ -0,0 +1,286 @@
+//! # Preflight Module
+//!
+//! This module implements `parent preflight`, which checks that the service account of
+//! the parent may do what the configured operators need before they are started. Missing
+//! permissions otherwise only show up as 403 errors in the logs of each failed watch or
+//! write. The required permissions are derived from the watch requests of each operator
+//! and from the features enabled in its configuration, and each one is checked with a
+//! SelfSubjectAccessReview.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use k8s_openapi::api::authorization::v1::{
+    ResourceAttributes, SelfSubjectAccessReview, SelfSubjectAccessReviewSpec,
+};
+use kube::api::PostParams;
+use kube::Api;
+use wasmtime::Engine;
+
+use crate::config::metadata::{DecisionLogTarget, ErrorReporting, WasmComponentMetadata};
+use crate::config::profile::Profile;
+use crate::config::runtime::RuntimeConfig;
+use crate::host::locks::LockTable;
+use crate::kubernetes::lease::LeaseManager;
+use crate::kubernetes::{qualified_kind, KubernetesService};
+use crate::runtime::instance::WasmInstance;
+use crate::runtime::introspection::OperatorIntrospection;
+
+const USAGE: &str = "Usage: parent preflight [--profile dev|bench|prod|edge] [--runtime-config <path>] <path_to_wasm_config.yaml>";
+
+/// A permission an operator needs, as checked by a SelfSubjectAccessReview.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Permission {
+    verb: &'static str,
+    group: String,
+    resource: String,
+    subresource: Option<&'static str>,
+    /// Empty for all namespaces.
+    namespace: String,
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let group = if self.group.is_empty() {
+            "core"
+        } else {
+            &self.group
+        };
+        write!(f, "{} {}/{}", self.verb, group, self.resource)?;
+        if let Some(subresource) = self.subresource {
+            write!(f, "/{}", subresource)?;
+        }
+        if self.namespace.is_empty() {
+            write!(f, " in all namespaces")
+        } else {
+            write!(f, " in namespace '{}'", self.namespace)
+        }
+    }
+}
+
+/// Checks the permissions of the operators in the configuration named in the arguments,
+/// prints a report, and returns whether all of them are granted.
+pub fn run(args: &[String]) -> Result<bool> {
+    let mut profile = Profile::default();
+    let mut runtime_config_path: Option<PathBuf> = None;
+    let mut config_path: Option<PathBuf> = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--profile" {
+            profile = iter
+                .next()
+                .ok_or_else(|| anyhow!("--profile requires a value"))?
+                .parse()?;
+        } else if arg == "--runtime-config" {
+            runtime_config_path =
+                Some(PathBuf::from(iter.next().ok_or_else(|| {
+                    anyhow!("--runtime-config requires a value")
+                })?));
+        } else if config_path.is_none() {
+            config_path = Some(PathBuf::from(arg));
+        } else {
+            bail!("Unexpected argument: {}\n{}", arg, USAGE);
+        }
+    }
+    let config_path = config_path.ok_or_else(|| anyhow!(USAGE))?;
+
+    let config = Arc::new(match &runtime_config_path {
+        Some(path) => RuntimeConfig::load_from_yaml(path, profile.defaults())?,
+        None => profile.defaults(),
+    });
+    let components_metadata = WasmComponentMetadata::load_from_yaml(&config_path)?;
+
+    let mut engine_config = wasmtime::Config::new();
+    engine_config.async_support(true);
+    let engine = Engine::new(&engine_config)?;
+
+    let tokio_runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    let local = tokio::task::LocalSet::new();
+    local.block_on(&tokio_runtime, async {
+        let kubernetes_service = Arc::new(KubernetesService::new(&config.kubernetes).await?);
+        let mut passed = true;
+        for metadata in components_metadata {
+            println!("Operator {}", metadata.name);
+            match required_permissions(&engine, &kubernetes_service, &config, &metadata).await {
+                Ok(permissions) => {
+                    for permission in permissions {
+                        passed &= check(&kubernetes_service, &permission).await;
+                    }
+                }
+                Err(e) => {
+                    println!("FAIL {:#}", e);
+                    passed = false;
+                }
+            }
+        }
+        if passed {
+            println!("PASSED all checks");
+        } else {
+            println!("FAILED, grant the missing permissions to the service account of the parent");
+        }
+        Ok(passed)
+    })
+}
+
+/// Derives the permissions an operator needs from its watch requests and configuration.
+///
+/// The writes a guest makes are not declared up front, so only the permissions of the
+/// host features enabled for it are included.
+async fn required_permissions(
+    engine: &Engine,
+    kubernetes_service: &Arc<KubernetesService>,
+    config: &Arc<RuntimeConfig>,
+    metadata: &WasmComponentMetadata,
+) -> Result<Vec<Permission>> {
+    let (operator, mut store) = WasmInstance::new(
+        engine.clone(),
+        kubernetes_service.clone(),
+        Arc::new(LeaseManager::new(kubernetes_service.clone())),
+        Arc::new(LockTable::default()),
+        config.clone(),
+        metadata.clone(),
+        OperatorIntrospection::new(metadata.clone()),
+    )
+    .load()
+    .await?;
+    let watches = operator
+        .call_get_watch_requests(&mut store)
+        .await
+        .context("Failed to get the watch requests")?;
+
+    let mut permissions = Vec::new();
+    for watch in watches {
+        if config.is_namespace_excluded(&watch.namespace, metadata) {
+            continue;
+        }
+        let (ar, _) = kubernetes_service
+            .resolve_kind(&qualified_kind(&watch.kind, watch.api_version.as_deref()))
+            .await
+            .with_context(|| format!("Watch for kind '{}'", watch.kind))?;
+        let permission = |verb, subresource| Permission {
+            verb,
+            group: ar.group.clone(),
+            resource: ar.plural.clone(),
+            subresource,
+            namespace: watch.namespace.clone(),
+        };
+        permissions.extend(["get", "list", "watch"].map(|verb| permission(verb, None)));
+        if watch.finalizer.is_some() {
+            permissions.push(permission("patch", None));
+        }
+        if metadata.set_owner_references {
+            // Needed to set blockOwnerDeletion on owner references to the watched objects.
+            permissions.push(permission("update", Some("finalizers")));
+        }
+        match metadata.error_reporting {
+            ErrorReporting::None => {}
+            ErrorReporting::Annotation => permissions.push(permission("patch", None)),
+            ErrorReporting::Condition => permissions.push(permission("patch", Some("status"))),
+        }
+        match metadata.decision_log.as_ref().map(|log| log.target) {
+            None => {}
+            Some(DecisionLogTarget::Status) => {
+                permissions.push(permission("patch", Some("status")))
+            }
+            Some(DecisionLogTarget::ConfigMap) => {
+                permissions.extend(["get", "create", "update"].map(|verb| Permission {
+                    verb,
+                    group: String::new(),
+                    resource: "configmaps".to_string(),
+                    subresource: None,
+                    namespace: watch.namespace.clone(),
+                }))
+            }
+        }
+    }
+    for mapping in &metadata.watch_mappings {
+        if config.is_namespace_excluded(&mapping.namespace, metadata) {
+            continue;
+        }
+        let (ar, _) = kubernetes_service
+            .resolve_kind(&mapping.kind)
+            .await
+            .with_context(|| format!("Watch mapping for kind '{}'", mapping.kind))?;
+        permissions.extend(["list", "watch"].map(|verb| Permission {
+            verb,
+            group: ar.group.clone(),
+            resource: ar.plural.clone(),
+            subresource: None,
+            namespace: mapping.namespace.clone(),
+        }));
+        // Mapping a field lists the targets, and reconciling a target gets it.
+        let (target, _) = kubernetes_service
+            .resolve_kind(&mapping.target_kind)
+            .await
+            .with_context(|| format!("Watch mapping to kind '{}'", mapping.target_kind))?;
+        let verbs: &[&'static str] = match mapping.field {
+            Some(_) => &["get", "list"],
+            None => &["get"],
+        };
+        permissions.extend(verbs.iter().map(|verb| Permission {
+            verb,
+            group: target.group.clone(),
+            resource: target.plural.clone(),
+            subresource: None,
+            namespace: mapping.namespace.clone(),
+        }));
+    }
+    let grants = [
+        ("secrets", &metadata.readable_secrets),
+        ("configmaps", &metadata.readable_config_maps),
+    ];
+    for (resource, grants) in grants {
+        permissions.extend(grants.iter().map(|grant| Permission {
+            verb: "get",
+            group: String::new(),
+            resource: resource.to_string(),
+            subresource: None,
+            namespace: grant.namespace.clone(),
+        }));
+    }
+    permissions.sort();
+    permissions.dedup();
+    Ok(permissions)
+}
+
+/// Checks one permission and prints the outcome.
+async fn check(kubernetes_service: &KubernetesService, permission: &Permission) -> bool {
+    let review = SelfSubjectAccessReview {
+        spec: SelfSubjectAccessReviewSpec {
+            resource_attributes: Some(ResourceAttributes {
+                verb: Some(permission.verb.to_string()),
+                group: Some(permission.group.clone()),
+                resource: Some(permission.resource.clone()),
+                subresource: permission.subresource.map(str::to_string),
+                namespace: Some(permission.namespace.clone()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let api: Api<SelfSubjectAccessReview> = Api::all(kubernetes_service.client());
+    match api.create(&PostParams::default(), &review).await {
+        Ok(review) => {
+            let status = review.status.unwrap_or_default();
+            if status.allowed {
+                println!("PASS {}", permission);
+                true
+            } else {
+                let reason = status
+                    .reason
+                    .filter(|reason| !reason.is_empty())
+                    .unwrap_or_else(|| "no rule grants it".to_string());
+                println!("FAIL {}: {}", permission, reason);
+                false
+            }
+        }
+        Err(e) => {
+            println!("FAIL {}: the access review failed: {}", permission, e);
+            false
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! # Runtime Builder Module
+//!
+//! This module provides `WasmRuntimeBuilder`, which creates a `WasmRuntime` for the parent
+//! binary and for processes that embed the runtime.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use super::WasmRuntime;
+use crate::config::runtime::RuntimeConfig;
+use crate::kubernetes::KubernetesService;
+use crate::metrics;
+
+/// Creates a `WasmRuntime`, see `WasmRuntime::builder`.
+pub struct WasmRuntimeBuilder {
+    config: RuntimeConfig,
+    kubernetes_service: Option<Arc<KubernetesService>>,
+}
+
+impl WasmRuntimeBuilder {
+    pub(super) fn new() -> Self {
+        Self {
+            config: RuntimeConfig::default(),
+            kubernetes_service: None,
+        }
+    }
+
+    /// Sets the runtime configuration. Defaults to that of the `prod` profile.
+    pub fn config(mut self, config: RuntimeConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Uses an existing connection to the cluster, instead of connecting with the
+    /// `kubernetes` settings of the configuration.
+    pub fn kubernetes_service(mut self, kubernetes_service: Arc<KubernetesService>) -> Self {
+        self.kubernetes_service = Some(kubernetes_service);
+        self
+    }
+
+    /// Creates the runtime, connecting to the cluster if no connection was given.
+    ///
+    /// Metrics are global to the process, so `metrics-enabled` of the configuration applies
+    /// to all runtimes in it.
+    pub async fn build(self) -> Result<Arc<WasmRuntime>> {
+        metrics::set_enabled(self.config.metrics_enabled);
+        let kubernetes_service = match self.kubernetes_service {
+            Some(kubernetes_service) => kubernetes_service,
+            None => Arc::new(KubernetesService::new(&self.config.kubernetes).await?),
+        };
+        Ok(Arc::new(WasmRuntime::new(
+            kubernetes_service,
+            Arc::new(self.config),
+        )?))
+    }
+}
@@ -0,0 +1,206 @@
+//! # Checkpoint Module
+//!
+//! This module snapshots the whole runtime to a directory and restores it at startup with
+//! `--restore-from`, so benchmarks can start from the same state every run and developers
+//! can skip re-warming their operators. A checkpoint holds the serialized state of every
+//! operator and the informer cache, if it is enabled:
+//!
+//! ```text
+//! <dir>/checkpoint.json       manifest
+//! <dir>/informer-cache.json   objects seen by the watches
+//! <dir>/operators/<id>.mem    serialized operator state
+//! ```
+
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use k8s_openapi::chrono::Utc;
+use serde::Serialize;
+use tracing::{info, warn};
+use wasmtime::Store;
+
+use super::{snapshot, OperatorState, WasmRuntime};
+use crate::host::api::bindings;
+use crate::host::state::State;
+use crate::platform;
+
+const MANIFEST_FILE: &str = "checkpoint.json";
+
+/// How long a checkpoint waits for an operator that is busy handling a call.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Describes a checkpoint, written as its manifest.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    pub path: PathBuf,
+    pub created_at: String,
+    pub operators: Vec<String>,
+    pub informer_cache: bool,
+}
+
+/// Returns the file the informer cache is written to in a checkpoint.
+pub fn informer_cache_file(dir: &Path) -> PathBuf {
+    dir.join("informer-cache.json")
+}
+
+fn operator_file(dir: &Path, id: &str) -> PathBuf {
+    dir.join("operators").join(format!("{}.mem", id))
+}
+
+/// Whether a path names a directory inside the one it is joined to: it is relative, not
+/// empty, and has no `..` or `.` components.
+fn stays_inside(path: &Path) -> bool {
+    !path.as_os_str().is_empty()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+impl WasmRuntime {
+    /// Returns the directory checkpoints are written to when none is given.
+    pub fn default_checkpoint_dir(&self) -> PathBuf {
+        self.config.state_dir.join("checkpoint")
+    }
+
+    /// Returns a checkpoint directory given relative to the state directory, or `None` if
+    /// the path is absolute or leaves the state directory.
+    pub fn checkpoint_dir_in_state(&self, dir: &str) -> Option<PathBuf> {
+        let dir = Path::new(dir);
+        stays_inside(dir).then(|| self.config.state_dir.join(dir))
+    }
+
+    /// Serves the `checkpoint` and `request-unload` calls an operator made during the call
+    /// that just returned, along with those its extra instances made since. A requested
+    /// checkpoint is written to the default checkpoint directory right away; returns
+    /// whether the operator asked to be unloaded.
+    pub(super) async fn serve_lifecycle_requests(
+        &self,
+        id: &str,
+        op_state: &OperatorState,
+    ) -> bool {
+        let OperatorState::Loaded {
+            operator, store, ..
+        } = op_state
+        else {
+            return false;
+        };
+        let mut store = store.lock().await;
+        let mut requests = std::mem::take(&mut store.data_mut().lifecycle_requests);
+        requests.merge(self.reconcilers.take_requests(id));
+        if requests.checkpoint {
+            let path = operator_file(&self.default_checkpoint_dir(), id);
+            let written = async {
+                let memory_data = operator.call_serialize(&mut *store).await?;
+                platform::write_private(&path, &memory_data)
+                    .await
+                    .with_context(|| format!("Failed to write {}", path.display()))
+            }
+            .await;
+            match written {
+                Ok(()) => info!("Checkpointed operator {} to {:?} on its request", id, path),
+                Err(e) => warn!("Failed to checkpoint operator {}: {:#}", id, e),
+            }
+        }
+        requests.unload
+    }
+
+    /// Writes the state of all operators and the informer cache to a directory.
+    pub async fn checkpoint(&self, dir: &Path) -> Result<Checkpoint> {
+        let ids: Vec<String> = self
+            .introspection
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect();
+
+        for id in &ids {
+            let memory_data = self.serialize_operator(id).await?;
+            platform::write_private(&operator_file(dir, id), &memory_data)
+                .await
+                .with_context(|| format!("Failed to write state of operator {}", id))?;
+        }
+        let informer_cache = self.informer_cache.save_to(&informer_cache_file(dir))?;
+
+        let checkpoint = Checkpoint {
+            path: dir.to_path_buf(),
+            created_at: Utc::now().to_rfc3339(),
+            operators: ids,
+            informer_cache,
+        };
+        tokio::fs::write(
+            dir.join(MANIFEST_FILE),
+            serde_json::to_vec_pretty(&checkpoint)?,
+        )
+        .await?;
+        info!(
+            "Checkpointed {} operator(s) to {}",
+            checkpoint.operators.len(),
+            dir.display()
+        );
+        Ok(checkpoint)
+    }
+
+    /// Returns the serialized state of an operator, without unloading it.
+    pub(super) async fn serialize_operator(&self, id: &str) -> Result<Vec<u8>> {
+        let deadline = tokio::time::Instant::now() + BUSY_TIMEOUT;
+        // The entry is taken out of the map while the operator handles a call, so wait
+        // for it to come back.
+        while !self.operators.contains_key(id) {
+            if tokio::time::Instant::now() >= deadline {
+                return Err(anyhow!("Operator {} stayed busy during the checkpoint", id));
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let unloaded_state = match self.operators.get(id).as_deref() {
+            Some(OperatorState::Unloaded { state_path, .. }) => Some(state_path.clone()),
+            _ => None,
+        };
+        match unloaded_state {
+            Some(state_path) => snapshot::read(&state_path).await,
+            None => {
+                self.with_operator(id, |operator, store| {
+                    Box::pin(async move { operator.call_serialize(store).await })
+                })
+                .await
+            }
+        }
+    }
+
+    /// Restores the state of a freshly loaded operator from the checkpoint given with
+    /// `--restore-from`, if the checkpoint has state for it.
+    pub(super) async fn restore_operator(
+        &self,
+        id: &str,
+        operator: &bindings::KubeOperator,
+        store: &mut Store<State>,
+    ) -> Result<()> {
+        let Some(dir) = &self.config.restore_from else {
+            return Ok(());
+        };
+        let path = operator_file(dir, id);
+        if !path.exists() {
+            return Ok(());
+        }
+        let saved_state = snapshot::read(&path).await?;
+        operator.call_deserialize(&mut *store, &saved_state).await?;
+        info!("Restored operator {} from checkpoint {}", id, dir.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoint_dirs_stay_inside_the_state_dir() {
+        assert!(stays_inside(Path::new("before-upgrade")));
+        assert!(stays_inside(Path::new("checkpoints/nightly")));
+        assert!(!stays_inside(Path::new("")));
+        assert!(!stays_inside(Path::new("/tmp/checkpoint")));
+        assert!(!stays_inside(Path::new("../elsewhere")));
+        assert!(!stays_inside(Path::new("checkpoints/../../elsewhere")));
+    }
+}
@@ -0,0 +1,97 @@
+//! # Compile Module
+//!
+//! This module compiles the components at startup before they are loaded one by one.
+//! Compiling dominates the cold start of a parent with many operators, and loading them in
+//! order compiles them one after the other. Components are compiled on the blocking thread
+//! pool instead, up to `compile-concurrency` at a time (the number of CPUs by default), and
+//! the time each one took is logged and recorded as `wasm_operator_compile_seconds`.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use futures::StreamExt;
+use tracing::{info, warn};
+
+use super::instance::WasmInstance;
+use super::WasmRuntime;
+use crate::config::metadata::WasmComponentMetadata;
+use crate::config::runtime::SnapshotStrategy;
+use crate::host::api::bindings;
+use crate::host::extensions;
+use crate::host::state::State;
+use crate::metrics;
+
+impl WasmRuntime {
+    /// Compiles components in parallel and returns them by name. Components that fail to
+    /// compile are left out, so loading them reports the error.
+    pub(super) async fn compile_all(
+        &self,
+        components: &[WasmComponentMetadata],
+    ) -> HashMap<String, bindings::KubeOperatorPre<State>> {
+        let concurrency = self
+            .config
+            .compile_concurrency
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |cpus| cpus.get()));
+        let started = Instant::now();
+        let compiled: HashMap<_, _> = futures::stream::iter(components.iter().cloned())
+            .map(|metadata| self.compile(metadata))
+            .buffer_unordered(concurrency.max(1))
+            .filter_map(|compiled| async move { compiled })
+            .collect()
+            .await;
+        info!(
+            "Compiled {} of {} component(s) in {:?}, {} at a time",
+            compiled.len(),
+            components.len(),
+            started.elapsed(),
+            concurrency
+        );
+        if matches!(self.config.snapshot_strategy, SnapshotStrategy::PreInit) {
+            for (name, pre) in &compiled {
+                self.instance_pres.insert(name.clone(), pre.clone());
+            }
+        }
+        compiled
+    }
+
+    async fn compile(
+        &self,
+        metadata: WasmComponentMetadata,
+    ) -> Option<(String, bindings::KubeOperatorPre<State>)> {
+        let name = metadata.name.clone();
+        let engine = match self.engines.for_component(&metadata) {
+            Ok(engine) => engine,
+            Err(e) => {
+                warn!("Failed to compile component '{}': {:#}", name, e);
+                return None;
+            }
+        };
+        let names = self.config.extensions.clone();
+        let compiled = tokio::task::spawn_blocking(move || {
+            let started = Instant::now();
+            let extensions = extensions::enabled(&names)?;
+            let pre = WasmInstance::prepare(&engine, &metadata, &extensions)?;
+            anyhow::Ok((pre, started.elapsed()))
+        })
+        .await;
+        match compiled {
+            Ok(Ok((pre, elapsed))) => {
+                info!("Compiled component '{}' in {:?}", name, elapsed);
+                metrics::set_gauge(
+                    "wasm_operator_compile_seconds",
+                    &[("operator", &name)],
+                    elapsed.as_secs_f64(),
+                );
+                Some((name, pre))
+            }
+            Ok(Err(e)) => {
+                warn!("Failed to compile component '{}': {:#}", name, e);
+                None
+            }
+            Err(e) => {
+                warn!("Failed to compile component '{}': {}", name, e);
+                None
+            }
+        }
+    }
+}
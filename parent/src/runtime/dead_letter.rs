@@ -0,0 +1,122 @@
+//! # Dead Letter Module
+//!
+//! This module keeps track of reconciles that keep failing. Each object gets a bounded
+//! number of retries according to the configured retry policy; once those are exhausted,
+//! the object is parked in a dead-letter store from which it can be inspected and retried
+//! through the admin API.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use dashmap::DashMap;
+use kube::api::DynamicObject;
+use serde::Serialize;
+
+use crate::host::api::bindings::local::operator::types::EventType;
+
+/// Identifies an object as seen by a specific operator.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct ObjectRef {
+    pub operator: String,
+    pub kind: String,
+    pub namespace: String,
+    pub name: String,
+}
+
+impl ObjectRef {
+    pub fn new(operator: &str, object: &DynamicObject) -> Self {
+        Self {
+            operator: operator.to_string(),
+            kind: object
+                .types
+                .as_ref()
+                .map(|types| types.kind.clone())
+                .unwrap_or_default(),
+            namespace: object.metadata.namespace.clone().unwrap_or_default(),
+            name: object.metadata.name.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// An object whose reconcile exhausted all retries.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadLetter {
+    #[serde(flatten)]
+    pub object_ref: ObjectRef,
+    pub last_error: String,
+    pub attempts: u32,
+    pub timestamp_ms: u64,
+    /// The event and last observed state of the object, used when the reconcile is retried.
+    #[serde(skip)]
+    pub event_type: EventType,
+    #[serde(skip)]
+    pub object: DynamicObject,
+}
+
+/// Tracks retry attempts per object and the objects that exhausted them.
+#[derive(Default)]
+pub struct DeadLetterQueue {
+    attempts: DashMap<ObjectRef, u32>,
+    letters: DashMap<ObjectRef, DeadLetter>,
+}
+
+impl DeadLetterQueue {
+    /// Records a failed attempt and returns the total number of attempts so far.
+    pub fn record_failure(&self, object_ref: &ObjectRef) -> u32 {
+        let mut attempts = self.attempts.entry(object_ref.clone()).or_insert(0);
+        *attempts += 1;
+        *attempts
+    }
+
+    /// Forgets the failed attempts of an object after a successful reconcile.
+    pub fn record_success(&self, object_ref: &ObjectRef) {
+        self.attempts.remove(object_ref);
+    }
+
+    /// Moves an object into the dead-letter store.
+    pub fn push(
+        &self,
+        object_ref: ObjectRef,
+        event_type: EventType,
+        object: DynamicObject,
+        last_error: String,
+    ) {
+        let attempts = self
+            .attempts
+            .remove(&object_ref)
+            .map(|(_, a)| a)
+            .unwrap_or(0);
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        self.letters.insert(
+            object_ref.clone(),
+            DeadLetter {
+                object_ref,
+                last_error,
+                attempts,
+                timestamp_ms,
+                event_type,
+                object,
+            },
+        );
+    }
+
+    /// Lists the dead letters of an operator.
+    pub fn list(&self, operator: &str) -> Vec<DeadLetter> {
+        let mut letters: Vec<DeadLetter> = self
+            .letters
+            .iter()
+            .filter(|entry| entry.key().operator == operator)
+            .map(|entry| entry.value().clone())
+            .collect();
+        letters.sort_by_key(|letter| letter.timestamp_ms);
+        letters
+    }
+
+    /// Removes a dead letter so it can be retried.
+    pub fn take(&self, object_ref: &ObjectRef) -> Option<DeadLetter> {
+        self.letters.remove(object_ref).map(|(_, letter)| letter)
+    }
+}
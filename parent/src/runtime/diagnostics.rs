@@ -0,0 +1,159 @@
+//! # Diagnostics Module
+//!
+//! This module collects the state of the runtime into a debug bundle, a gzipped tarball
+//! to attach to bug reports:
+//!
+//! ```text
+//! debug-bundle/manifest.json                      versions and creation time
+//! debug-bundle/runtime-config.json                effective runtime configuration
+//! debug-bundle/metrics.txt                        metrics in the Prometheus text format
+//! debug-bundle/operators/<id>/metadata.json       configuration of the operator
+//! debug-bundle/operators/<id>/status.json         load state, watches, recent reconciles
+//!                                                 and errors
+//! debug-bundle/operators/<id>/dead-letters.json   objects that exhausted their retries
+//! debug-bundle/operators/<id>/state.bin           serialized state of the operator
+//! ```
+//!
+//! A redacted bundle leaves out the state of the operators, and replaces the values of
+//! environment variables, which often hold credentials, with a placeholder.
+
+use std::io::Write;
+
+use anyhow::Result;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use k8s_openapi::chrono::Utc;
+use serde_json::{json, Value};
+
+use super::{OperatorState, WasmRuntime};
+use crate::host::api::bindings::local::operator::types::LoadState;
+use crate::host::api::INTERFACE_VERSION;
+use crate::metrics;
+use crate::tarball::Tarball;
+
+const ROOT: &str = "debug-bundle";
+const REDACTED: &str = "<redacted>";
+
+impl WasmRuntime {
+    /// Collects the state of the runtime into a gzipped tarball.
+    pub async fn debug_bundle(&self, redact: bool) -> Result<Vec<u8>> {
+        let mut ids: Vec<String> = self
+            .introspection
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect();
+        ids.sort();
+
+        let mut tarball = Tarball::new(Utc::now().timestamp().max(0) as u64);
+        let manifest = json!({
+            "createdAt": Utc::now().to_rfc3339(),
+            "parentVersion": env!("CARGO_PKG_VERSION"),
+            "interfaceVersion": INTERFACE_VERSION,
+            "redacted": redact,
+            "operators": ids,
+        });
+        add_json(&mut tarball, "manifest.json", &manifest)?;
+        let mut config = serde_json::to_value(&*self.config)?;
+        if redact {
+            redact_env(&mut config);
+        }
+        add_json(&mut tarball, "runtime-config.json", &config)?;
+        add(
+            &mut tarball,
+            "metrics.txt",
+            metrics::global().render().as_bytes(),
+        )?;
+
+        for id in &ids {
+            let Some(introspection) = self.introspection.get(id).map(|entry| entry.clone()) else {
+                continue;
+            };
+            let (mut metadata, mut status) = {
+                let introspection = introspection.lock().unwrap();
+                (
+                    serde_json::to_value(introspection.metadata())?,
+                    introspection.to_debug_json(),
+                )
+            };
+            if redact {
+                redact_env(&mut metadata);
+            }
+            status["loadState"] = json!(self.load_state(id));
+
+            if !redact {
+                match self.serialize_operator(id).await {
+                    Ok(state) => add(&mut tarball, &format!("operators/{}/state.bin", id), &state)?,
+                    Err(e) => status["stateError"] = json!(format!("{:#}", e)),
+                }
+            }
+            add_json(
+                &mut tarball,
+                &format!("operators/{}/metadata.json", id),
+                &metadata,
+            )?;
+            add_json(
+                &mut tarball,
+                &format!("operators/{}/status.json", id),
+                &status,
+            )?;
+            add_json(
+                &mut tarball,
+                &format!("operators/{}/dead-letters.json", id),
+                &serde_json::to_value(self.dead_letters(id))?,
+            )?;
+        }
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tarball.finish())?;
+        Ok(encoder.finish()?)
+    }
+
+    fn load_state(&self, id: &str) -> String {
+        match self.operators.get(id).as_deref() {
+            Some(OperatorState::Loaded { .. }) => "loaded".to_string(),
+            Some(OperatorState::Unloaded { .. }) => "unloaded".to_string(),
+            Some(OperatorState::Degraded { reason }) => format!("degraded: {}", reason),
+            // The entry is taken out of the map while the operator handles a call, and
+            // while it is loaded or unloaded.
+            None => match self.lifecycle.state(id) {
+                Some(LoadState::Loaded) | None => "busy".to_string(),
+                Some(state) => format!("{:?}", state).to_lowercase(),
+            },
+        }
+    }
+}
+
+/// Replaces the values of all `env` fields with a placeholder: the values of a map, or the
+/// `value` of each entry of a list.
+fn redact_env(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                match value {
+                    Value::Object(env) if key == "env" => {
+                        env.values_mut().for_each(|value| *value = json!(REDACTED));
+                    }
+                    Value::Array(env) if key == "env" => {
+                        for entry in env.iter_mut().filter_map(Value::as_object_mut) {
+                            if let Some(value) = entry.get_mut("value") {
+                                *value = json!(REDACTED);
+                            }
+                        }
+                    }
+                    value => redact_env(value),
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact_env),
+        _ => {}
+    }
+}
+
+/// Adds a file under `ROOT`.
+fn add(tarball: &mut Tarball, path: &str, contents: &[u8]) -> Result<()> {
+    tarball.add(&format!("{}/{}", ROOT, path), contents)
+}
+
+fn add_json(tarball: &mut Tarball, path: &str, value: &Value) -> Result<()> {
+    add(tarball, path, &serde_json::to_vec_pretty(value)?)
+}
@@ -0,0 +1,122 @@
+//! # Drift Module
+//!
+//! This module notifies operators when someone else changes the objects they applied.
+//! For every kind and namespace an operator applied objects in, the runtime watches the
+//! objects carrying the operator's applied-by label. When the most recent write to such an
+//! object was made by another field manager, the operator is reconciled with a
+//! `drift-detected` event so it can re-assert the desired state right away.
+
+use std::sync::Arc;
+
+use futures::StreamExt;
+use kube::api::DynamicObject;
+use kube::runtime::watcher::{self, Event};
+use tracing::{info, warn};
+
+use super::work_queue::WorkItem;
+use super::WasmRuntime;
+use crate::host::api::bindings::local::operator::types::{EventType, ReconcileTrigger};
+use crate::host::state::APPLIED_BY_LABEL;
+use crate::kubernetes::FIELD_MANAGER;
+
+impl WasmRuntime {
+    /// Starts drift watches for the kinds and namespaces the operator applied objects in
+    /// since the last call.
+    pub(super) fn ensure_drift_watches(self: &Arc<Self>, operator_id: &str) {
+        let Some(introspection) = self.introspection.get(operator_id).map(|i| i.clone()) else {
+            return;
+        };
+        let applied = {
+            let introspection = introspection.lock().unwrap();
+            if !introspection.metadata().detect_drift {
+                return;
+            }
+            introspection.applied()
+        };
+
+        for (kind, namespace) in applied {
+            let key = (operator_id.to_string(), kind.clone(), namespace.clone());
+            if !self.drift_watches.insert(key) {
+                continue;
+            }
+            let runtime = self.clone();
+            let operator_id = operator_id.to_string();
+            tokio::task::spawn_local(async move {
+                runtime.watch_for_drift(operator_id, kind, namespace).await;
+            });
+        }
+    }
+
+    async fn watch_for_drift(
+        self: Arc<Self>,
+        operator_id: String,
+        kind: String,
+        namespace: String,
+    ) {
+        let ar = match self.kubernetes_service.resolve_kind(&kind).await {
+            Ok((ar, _)) => ar,
+            Err(e) => {
+                warn!("Not watching '{}' for drift: {}", kind, e);
+                return;
+            }
+        };
+        let selector = format!("{}={}", APPLIED_BY_LABEL, operator_id);
+        let mut watcher = watcher::watcher(
+            self.kubernetes_service.dynamic_api(ar, &namespace),
+            watcher::Config::default().labels(&selector),
+        )
+        .boxed();
+
+        info!(
+            "Watching {} objects of operator '{}' in namespace '{}' for drift",
+            kind, operator_id, namespace
+        );
+
+        while let Some(event) = watcher.next().await {
+            match event {
+                // Only changes made while watching are drift; the initial list is not.
+                Ok(Event::Apply(object)) if is_drifted(&object) => {
+                    self.enqueue_event(
+                        &operator_id,
+                        WorkItem {
+                            event_type: EventType::DriftDetected,
+                            reason: super::triggered(ReconcileTrigger::Drift),
+                            object,
+                        },
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => warn!(
+                    "Drift watch for {} in namespace '{}' encountered an error: {}",
+                    kind, namespace, e
+                ),
+            }
+        }
+    }
+}
+
+/// Returns whether the most recent write to an object was made by another field manager.
+///
+/// Writes to the status subresource are ignored, since other controllers commonly own it.
+/// When the timestamps tie, the write is assumed to be ours.
+fn is_drifted(object: &DynamicObject) -> bool {
+    let Some(managed_fields) = &object.metadata.managed_fields else {
+        return false;
+    };
+    let entries = managed_fields
+        .iter()
+        .filter(|entry| entry.subresource.as_deref() != Some("status"));
+    let ours = entries
+        .clone()
+        .filter(|entry| entry.manager.as_deref() == Some(FIELD_MANAGER))
+        .filter_map(|entry| entry.time.as_ref())
+        .max();
+    let theirs = entries
+        .filter(|entry| entry.manager.as_deref() != Some(FIELD_MANAGER))
+        .filter_map(|entry| entry.time.as_ref())
+        .max();
+    match (ours, theirs) {
+        (Some(ours), Some(theirs)) => theirs > ours,
+        _ => false,
+    }
+}
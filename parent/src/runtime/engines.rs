@@ -0,0 +1,131 @@
+//! # Engines Module
+//!
+//! This module keeps a Wasmtime engine for each compiler setting the components ask for.
+//! The compiler and its optimization level are settings of an engine, and a component can
+//! only be instantiated by the engine that compiled it, so components with different
+//! settings are compiled and run by different engines. Engines are created when the first
+//! component that uses their settings is loaded, and share the rest of their configuration.
+//!
+//! With `pooling` set, an engine allocates instances from a pool it reserves up front. The
+//! address space reserved for each memory is cut down to the largest memory allowed, so the
+//! pool also fits on aarch64 machines whose kernels give processes 39 bits of address space.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use dashmap::DashMap;
+use tracing::info;
+use wasmtime::{Engine, InstanceAllocationStrategy, PoolingAllocationConfig, Strategy};
+
+use crate::config::metadata::{Compiler, OptLevel, WasmComponentMetadata};
+use crate::config::runtime::RuntimeConfig;
+use crate::host::budget::EPOCH_TICK;
+
+/// Guard region after each pooled memory, which catches accesses just past its end.
+const POOLED_MEMORY_GUARD_BYTES: u64 = 64 << 10;
+
+/// The engines created so far, by compiler setting.
+pub struct Engines {
+    config: Arc<RuntimeConfig>,
+    engines: DashMap<(Compiler, OptLevel), Engine>,
+}
+
+impl Engines {
+    /// Creates the engine for the default compiler setting, so invalid runtime settings
+    /// fail at startup.
+    pub fn new(config: Arc<RuntimeConfig>) -> Result<Self> {
+        let engines = Self {
+            config,
+            engines: DashMap::new(),
+        };
+        engines.get(Compiler::default(), OptLevel::default())?;
+        Ok(engines)
+    }
+
+    /// Returns the engine that compiles and runs a component.
+    pub fn for_component(&self, metadata: &WasmComponentMetadata) -> Result<Engine> {
+        self.get(metadata.compiler, metadata.opt_level)
+            .with_context(|| format!("Failed to create the engine of '{}'", metadata.name))
+    }
+
+    fn get(&self, compiler: Compiler, opt_level: OptLevel) -> Result<Engine> {
+        // Winch does not optimize, so all its components share one engine.
+        let opt_level = match compiler {
+            Compiler::Cranelift => opt_level,
+            Compiler::Winch => OptLevel::default(),
+        };
+        let engine = self
+            .engines
+            .entry((compiler, opt_level))
+            .or_try_insert_with(|| self.create(compiler, opt_level))?;
+        Ok(engine.clone())
+    }
+
+    fn create(&self, compiler: Compiler, opt_level: OptLevel) -> Result<Engine> {
+        let mut engine_config = wasmtime::Config::new();
+        engine_config.async_support(true);
+        match compiler {
+            Compiler::Cranelift => {
+                engine_config
+                    .strategy(Strategy::Cranelift)
+                    .cranelift_opt_level(match opt_level {
+                        OptLevel::None => wasmtime::OptLevel::None,
+                        OptLevel::Speed => wasmtime::OptLevel::Speed,
+                        OptLevel::SpeedAndSize => wasmtime::OptLevel::SpeedAndSize,
+                    });
+                if self.config.deterministic {
+                    engine_config.cranelift_nan_canonicalization(true);
+                }
+            }
+            Compiler::Winch => {
+                if self.config.deterministic {
+                    bail!("Winch cannot canonicalize NaNs, which deterministic mode requires");
+                }
+                engine_config.strategy(Strategy::Winch);
+            }
+        }
+        if self.config.deterministic {
+            engine_config.relaxed_simd_deterministic(true);
+        }
+        #[cfg(feature = "component-model-async")]
+        engine_config
+            .wasm_component_model_async(true)
+            .wasm_component_model_async_builtins(true);
+        if self.config.reconcile_budget_ms.is_some() {
+            engine_config.epoch_interruption(true);
+        }
+        if let Some(pooling) = &self.config.pooling {
+            let mut allocator = PoolingAllocationConfig::default();
+            allocator
+                .total_component_instances(pooling.total_component_instances)
+                .total_core_instances(pooling.total_core_instances)
+                .total_memories(pooling.total_memories)
+                .total_tables(pooling.total_tables)
+                // Every call into an instance runs on a stack of its own.
+                .total_stacks(pooling.total_component_instances)
+                .max_memory_size(pooling.max_memory_bytes);
+            engine_config
+                .allocation_strategy(InstanceAllocationStrategy::Pooling(allocator))
+                .memory_reservation(pooling.max_memory_bytes as u64)
+                .memory_guard_size(POOLED_MEMORY_GUARD_BYTES)
+                .memory_reservation_for_growth(0);
+        }
+        let engine = Engine::new(&engine_config)
+            .map_err(|e| anyhow!("Failed to create a {:?} engine: {}", compiler, e))?;
+        if self.config.reconcile_budget_ms.is_some() {
+            let ticker = engine.weak();
+            std::thread::spawn(move || {
+                while let Some(engine) = ticker.upgrade() {
+                    engine.increment_epoch();
+                    drop(engine);
+                    std::thread::sleep(EPOCH_TICK);
+                }
+            });
+        }
+        info!(
+            "Created an engine for {:?} at opt level {:?}",
+            compiler, opt_level
+        );
+        Ok(engine)
+    }
+}
@@ -0,0 +1,154 @@
+//! # Error Report Module
+//!
+//! This module writes the outcome of failed reconciles back onto the reconciled object,
+//! either as annotations or as a status condition, so users debugging a stuck resource
+//! can see why without access to the parent logs.
+
+use anyhow::Result;
+use k8s_openapi::chrono::Utc;
+use kube::api::DynamicObject;
+use serde_json::{json, Value};
+
+use crate::config::metadata::ErrorReporting;
+use crate::kubernetes::KubernetesService;
+
+const LAST_ERROR_ANNOTATION: &str = "operator.wasm/last-error";
+const LAST_ERROR_TIME_ANNOTATION: &str = "operator.wasm/last-error-time";
+const ERROR_CONDITION_TYPE: &str = "ReconcileError";
+
+/// Maximum length of the error message written to the object.
+const MAX_MESSAGE_LENGTH: usize = 1024;
+
+/// Records a reconcile error on the object.
+pub async fn report_error(
+    kubernetes_service: &KubernetesService,
+    mode: ErrorReporting,
+    object: &DynamicObject,
+    message: &str,
+) -> Result<()> {
+    let message = truncate(message);
+    let now = Utc::now().to_rfc3339();
+
+    // Writing to the object triggers another watch event, so skip the write when the
+    // same error is already recorded to avoid a reconcile loop.
+    match mode {
+        ErrorReporting::None => Ok(()),
+        ErrorReporting::Annotation => {
+            let recorded = object
+                .metadata
+                .annotations
+                .as_ref()
+                .and_then(|annotations| annotations.get(LAST_ERROR_ANNOTATION));
+            if recorded == Some(&message) {
+                return Ok(());
+            }
+            let patch = json!({
+                "metadata": {
+                    "annotations": {
+                        LAST_ERROR_ANNOTATION: message,
+                        LAST_ERROR_TIME_ANNOTATION: now,
+                    }
+                }
+            });
+            kubernetes_service.merge_patch_object(object, &patch).await
+        }
+        ErrorReporting::Condition => {
+            let recorded = conditions(object).iter().any(|c| {
+                c["type"] == ERROR_CONDITION_TYPE
+                    && c["status"] == "True"
+                    && c["message"] == message
+            });
+            if recorded {
+                return Ok(());
+            }
+            let condition = json!({
+                "type": ERROR_CONDITION_TYPE,
+                "status": "True",
+                "reason": "ReconcileFailed",
+                "message": message,
+                "lastTransitionTime": now,
+            });
+            let patch = json!({ "status": { "conditions": with_condition(object, condition) } });
+            kubernetes_service
+                .merge_patch_object_status(object, &patch)
+                .await
+        }
+    }
+}
+
+/// Clears a previously recorded reconcile error from the object, if there is one.
+pub async fn clear_error(
+    kubernetes_service: &KubernetesService,
+    mode: ErrorReporting,
+    object: &DynamicObject,
+) -> Result<()> {
+    match mode {
+        ErrorReporting::None => Ok(()),
+        ErrorReporting::Annotation => {
+            let has_error = object
+                .metadata
+                .annotations
+                .as_ref()
+                .is_some_and(|annotations| annotations.contains_key(LAST_ERROR_ANNOTATION));
+            if !has_error {
+                return Ok(());
+            }
+            let patch = json!({
+                "metadata": {
+                    "annotations": {
+                        LAST_ERROR_ANNOTATION: null,
+                        LAST_ERROR_TIME_ANNOTATION: null,
+                    }
+                }
+            });
+            kubernetes_service.merge_patch_object(object, &patch).await
+        }
+        ErrorReporting::Condition => {
+            let failing = conditions(object)
+                .iter()
+                .any(|c| c["type"] == ERROR_CONDITION_TYPE && c["status"] == "True");
+            if !failing {
+                return Ok(());
+            }
+            let condition = json!({
+                "type": ERROR_CONDITION_TYPE,
+                "status": "False",
+                "reason": "ReconcileSucceeded",
+                "message": "",
+                "lastTransitionTime": Utc::now().to_rfc3339(),
+            });
+            let patch = json!({ "status": { "conditions": with_condition(object, condition) } });
+            kubernetes_service
+                .merge_patch_object_status(object, &patch)
+                .await
+        }
+    }
+}
+
+fn conditions(object: &DynamicObject) -> Vec<Value> {
+    object.data["status"]["conditions"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Returns the conditions of the object with the error condition replaced.
+fn with_condition(object: &DynamicObject, condition: Value) -> Vec<Value> {
+    let mut conditions: Vec<Value> = conditions(object)
+        .into_iter()
+        .filter(|c| c["type"] != ERROR_CONDITION_TYPE)
+        .collect();
+    conditions.push(condition);
+    conditions
+}
+
+fn truncate(message: &str) -> String {
+    if message.len() <= MAX_MESSAGE_LENGTH {
+        return message.to_string();
+    }
+    let mut end = MAX_MESSAGE_LENGTH;
+    while !message.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...", &message[..end])
+}
@@ -0,0 +1,67 @@
+//! # Events Module
+//!
+//! This module tells the event types of a watch apart. The watcher reports every object it
+//! sees as applied, whether it is new, changed, or only listed again after the watch was
+//! re-established. Each watch remembers the UID and resource version of the objects it
+//! saw, so an object is `added` the first time it is seen (or when it was recreated under
+//! the same name), `modified` when its resource version changed, and `resynced` when a
+//! relist returns it unchanged.
+
+use std::collections::HashMap;
+
+use kube::api::DynamicObject;
+
+use crate::host::api::bindings::local::operator::types::EventType;
+
+/// The UID and resource version of an object.
+type Version = (Option<String>, Option<String>);
+
+/// The objects a watch saw, by `namespace/name`.
+#[derive(Default)]
+pub struct SeenObjects {
+    objects: HashMap<String, Version>,
+}
+
+impl SeenObjects {
+    /// Starts from the objects restored from an informer cache checkpoint, so objects that
+    /// changed while the parent was down are `modified` rather than `added`.
+    pub fn restored<'a>(objects: impl IntoIterator<Item = &'a DynamicObject>) -> Self {
+        Self {
+            objects: objects
+                .into_iter()
+                .map(|object| (key(object), version(object)))
+                .collect(),
+        }
+    }
+
+    /// Records an applied object and returns its event type.
+    pub fn apply(&mut self, object: &DynamicObject) -> EventType {
+        let version = version(object);
+        match self.objects.insert(key(object), version.clone()) {
+            None => EventType::Added,
+            Some((uid, _)) if uid != version.0 => EventType::Added,
+            Some(previous) if previous == version => EventType::Resynced,
+            Some(_) => EventType::Modified,
+        }
+    }
+
+    /// Forgets a deleted object.
+    pub fn delete(&mut self, object: &DynamicObject) {
+        self.objects.remove(&key(object));
+    }
+}
+
+fn key(object: &DynamicObject) -> String {
+    format!(
+        "{}/{}",
+        object.metadata.namespace.as_deref().unwrap_or_default(),
+        object.metadata.name.as_deref().unwrap_or_default()
+    )
+}
+
+fn version(object: &DynamicObject) -> Version {
+    (
+        object.metadata.uid.clone(),
+        object.metadata.resource_version.clone(),
+    )
+}
@@ -0,0 +1,70 @@
+//! # Finalizer Module
+//!
+//! This module manages the finalizers operators declare in their watch requests. The
+//! runtime adds the finalizer to the objects it reconciles, so that deleting an object
+//! waits for the operator to clean up after it, and removes the finalizer once the
+//! operator has handled the `finalize` event.
+
+use anyhow::Result;
+use kube::api::DynamicObject;
+use serde_json::json;
+
+use crate::kubernetes::KubernetesService;
+
+pub fn has_finalizer(object: &DynamicObject, finalizer: &str) -> bool {
+    object
+        .metadata
+        .finalizers
+        .iter()
+        .flatten()
+        .any(|f| f == finalizer)
+}
+
+pub fn is_deleting(object: &DynamicObject) -> bool {
+    object.metadata.deletion_timestamp.is_some()
+}
+
+/// Adds the finalizer to the object.
+pub async fn add(
+    kubernetes_service: &KubernetesService,
+    object: &DynamicObject,
+    finalizer: &str,
+) -> Result<()> {
+    let mut finalizers = object.metadata.finalizers.clone().unwrap_or_default();
+    finalizers.push(finalizer.to_string());
+    set_finalizers(kubernetes_service, object, finalizers).await
+}
+
+/// Removes the finalizer from the object, which lets its deletion complete once no other
+/// finalizers are left.
+pub async fn remove(
+    kubernetes_service: &KubernetesService,
+    object: &DynamicObject,
+    finalizer: &str,
+) -> Result<()> {
+    let finalizers = object
+        .metadata
+        .finalizers
+        .iter()
+        .flatten()
+        .filter(|f| *f != finalizer)
+        .cloned()
+        .collect();
+    set_finalizers(kubernetes_service, object, finalizers).await
+}
+
+/// Replaces the finalizers of the object. The patch carries the resource version of the
+/// object, so it fails instead of dropping finalizers that others changed in the meantime.
+async fn set_finalizers(
+    kubernetes_service: &KubernetesService,
+    object: &DynamicObject,
+    finalizers: Vec<String>,
+) -> Result<()> {
+    let patch = json!({
+        "metadata": {
+            "finalizers": finalizers,
+            "resourceVersion": object.metadata.resource_version,
+        }
+    });
+    kubernetes_service.merge_patch_object(object, &patch).await
+}
@@ -0,0 +1,136 @@
+//! # Informer Cache Module
+//!
+//! This module keeps track of the objects seen by each watch and can checkpoint them to
+//! disk on shutdown. After a restart the watchers still list every watched kind to get
+//! back in sync with the cluster, but objects whose `resourceVersion` did not change since
+//! the checkpoint are not reconciled again, and objects that were deleted while the parent
+//! was down are reconciled as deletions.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use dashmap::DashMap;
+use kube::api::DynamicObject;
+use tracing::info;
+
+/// Objects of a single watch, by `namespace/name`.
+type Store = HashMap<String, DynamicObject>;
+
+pub struct InformerCache {
+    path: Option<PathBuf>,
+    stores: DashMap<String, Store>,
+    restored: DashMap<String, Store>,
+}
+
+impl InformerCache {
+    /// Creates the cache, restoring the checkpoint at `restore_from`, or else at `path`, if
+    /// there is one.
+    pub fn load(path: Option<PathBuf>, restore_from: Option<PathBuf>) -> Result<Self> {
+        let mut restored = DashMap::new();
+        if let Some(path) = restore_from
+            .as_ref()
+            .or(path.as_ref())
+            .filter(|path| path.exists())
+        {
+            let contents = fs::read(path)
+                .with_context(|| format!("Failed to read informer cache {}", path.display()))?;
+            let stores = parse_checkpoint(&contents)
+                .with_context(|| format!("Invalid informer cache {}", path.display()))?;
+            info!(
+                "Restored {} watch cache(s) from {}",
+                stores.len(),
+                path.display()
+            );
+            restored.extend(stores);
+        }
+        Ok(Self {
+            path,
+            stores: DashMap::new(),
+            restored,
+        })
+    }
+
+    /// Returns the key under which the objects of a watch are cached.
+    pub fn watch_key(operator_id: &str, kind: &str, namespace: &str) -> String {
+        format!("{}/{}/{}", operator_id, kind, namespace)
+    }
+
+    /// Takes the objects of a watch that were restored from the checkpoint.
+    pub fn take_restored(&self, key: &str) -> Store {
+        self.restored
+            .remove(key)
+            .map(|(_, store)| store)
+            .unwrap_or_default()
+    }
+
+    /// Records the current state of an object.
+    pub fn apply(&self, key: &str, object: &DynamicObject) {
+        if self.path.is_some() {
+            self.stores
+                .entry(key.to_string())
+                .or_default()
+                .insert(object_key(object), object.clone());
+        }
+    }
+
+    /// Forgets a deleted object.
+    pub fn delete(&self, key: &str, object: &DynamicObject) {
+        if let Some(mut store) = self.stores.get_mut(key) {
+            store.remove(&object_key(object));
+        }
+    }
+
+    /// Writes the cached objects to the checkpoint file, if one is configured.
+    pub fn save(&self) -> Result<()> {
+        match &self.path {
+            Some(path) => self.save_to(path).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the cached objects to a file, and returns whether the cache is enabled.
+    pub fn save_to(&self, path: &Path) -> Result<bool> {
+        if self.path.is_none() {
+            return Ok(false);
+        }
+        let stores: HashMap<String, Store> = self
+            .stores
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec(&stores)?)
+            .with_context(|| format!("Failed to write informer cache {}", path.display()))?;
+        info!(
+            "Checkpointed {} watch cache(s) to {}",
+            stores.len(),
+            path.display()
+        );
+        Ok(true)
+    }
+}
+
+/// Parses the contents of a checkpoint file.
+pub fn parse_checkpoint(contents: &[u8]) -> Result<HashMap<String, Store>> {
+    Ok(serde_json::from_slice(contents)?)
+}
+
+/// Removes an object from the restored objects, and returns whether it is unchanged since
+/// the checkpoint.
+pub fn unchanged_since_checkpoint(restored: &mut Store, object: &DynamicObject) -> bool {
+    restored
+        .remove(&object_key(object))
+        .is_some_and(|cached| cached.metadata.resource_version == object.metadata.resource_version)
+}
+
+fn object_key(object: &DynamicObject) -> String {
+    format!(
+        "{}/{}",
+        object.metadata.namespace.as_deref().unwrap_or_default(),
+        object.metadata.name.as_deref().unwrap_or_default()
+    )
+}
@@ -10,73 +10,203 @@ use std::sync::Arc;
 use anyhow::Result;
 use tracing::{debug, info};
 use wasmtime::component::{Component, HasSelf, Linker};
-use wasmtime::{Engine, Store};
-use wasmtime_wasi::p2::{add_to_linker_async, WasiCtxBuilder};
+use wasmtime::{Engine, Store, StoreLimitsBuilder};
+use wasmtime_wasi::p2::add_to_linker_async;
 
+use crate::bundle::{self, Bundle};
 use crate::config::metadata::WasmComponentMetadata;
+use crate::config::runtime::RuntimeConfig;
+use crate::dependencies;
 use crate::host::api::bindings;
+use crate::host::budget::Budget;
+use crate::host::extensions::{self, ExtensionData, HostExtension};
+use crate::host::handlers::Handlers;
+use crate::host::locks::LockTable;
+use crate::host::message_bus::MessageBus;
+use crate::host::ownership::OwnershipGraph;
+use crate::host::reconcile_queue::ReconcileQueue;
 use crate::host::state::State;
+use crate::host::timers::TimerQueue;
+use crate::kubernetes::lease::LeaseManager;
 use crate::kubernetes::KubernetesService;
+use crate::runtime::introspection::SharedIntrospection;
+use crate::runtime::store_pool::StorePool;
 
 pub struct WasmInstance {
     engine: Engine,
     kubernetes_service: Arc<KubernetesService>,
+    leases: Arc<LeaseManager>,
+    locks: Arc<LockTable>,
+    timers: Arc<TimerQueue>,
+    reconcile_queue: Arc<ReconcileQueue>,
+    ownership: Arc<OwnershipGraph>,
+    message_bus: Arc<MessageBus>,
+    store_pool: Arc<StorePool>,
+    config: Arc<RuntimeConfig>,
     metadata: WasmComponentMetadata,
+    introspection: SharedIntrospection,
+    pre: Option<bindings::KubeOperatorPre<State>>,
 }
 
 impl WasmInstance {
     pub fn new(
         engine: Engine,
         kubernetes_service: Arc<KubernetesService>,
+        leases: Arc<LeaseManager>,
+        locks: Arc<LockTable>,
+        config: Arc<RuntimeConfig>,
         metadata: WasmComponentMetadata,
+        introspection: SharedIntrospection,
     ) -> Self {
         Self {
             engine,
             kubernetes_service,
+            leases,
+            locks,
+            timers: Arc::default(),
+            reconcile_queue: Arc::default(),
+            ownership: Arc::default(),
+            message_bus: Arc::default(),
+            store_pool: Arc::default(),
+            config,
             metadata,
+            introspection,
+            pre: None,
         }
     }
 
+    /// Shares the timers of the runtime with the instance. Timers of an instance without
+    /// them are never delivered.
+    pub fn with_timers(mut self, timers: Arc<TimerQueue>) -> Self {
+        self.timers = timers;
+        self
+    }
+
+    /// Shares the reconcile queue of the runtime with the instance. Reconciles enqueued by
+    /// an instance without it are never dispatched.
+    pub fn with_reconcile_queue(mut self, reconcile_queue: Arc<ReconcileQueue>) -> Self {
+        self.reconcile_queue = reconcile_queue;
+        self
+    }
+
+    /// Shares the ownership graph of the runtime with the instance. Writes of an instance
+    /// without it are not visible in the admin API.
+    pub fn with_ownership(mut self, ownership: Arc<OwnershipGraph>) -> Self {
+        self.ownership = ownership;
+        self
+    }
+
+    /// Shares the message bus of the runtime with the instance. Messages published by an
+    /// instance without it are never delivered.
+    pub fn with_message_bus(mut self, message_bus: Arc<MessageBus>) -> Self {
+        self.message_bus = message_bus;
+        self
+    }
+
+    /// Shares the store pool of the runtime with the instance, so it reuses the WASI
+    /// context of a previous instance of the operator if one was kept.
+    pub fn with_store_pool(mut self, store_pool: Arc<StorePool>) -> Self {
+        self.store_pool = store_pool;
+        self
+    }
+
+    /// Instantiates from an already compiled and linked component instead of loading it
+    /// from its file again.
+    pub fn with_pre(mut self, pre: bindings::KubeOperatorPre<State>) -> Self {
+        self.pre = Some(pre);
+        self
+    }
+
+    /// Compiles the component and resolves its imports, so that it can be instantiated
+    /// any number of times without repeating this work.
+    pub fn prepare(
+        engine: &Engine,
+        metadata: &WasmComponentMetadata,
+        extensions: &[&dyn HostExtension],
+    ) -> Result<bindings::KubeOperatorPre<State>> {
+        debug!("Loading component from file: {}", metadata.wasm.display());
+        let component = if bundle::is_bundle(&metadata.wasm) {
+            let bundle = Bundle::read(&metadata.wasm)?;
+            Component::new(engine, bundle.component())
+        } else {
+            Component::from_file(engine, &metadata.wasm)
+        }
+        .map_err(|e| anyhow::anyhow!("Failed to load component '{}': {}", metadata.name, e))?;
+        debug!("Component loaded successfully: {}", metadata.name);
+        let component_type = component.component_type();
+        dependencies::check_interface_imports(
+            &metadata.name,
+            component_type.imports(engine).map(|(name, _)| name),
+        )?;
+
+        let mut linker = Linker::new(engine);
+        add_to_linker_async(&mut linker)?;
+
+        bindings::KubeOperator::add_to_linker::<_, HasSelf<_>>(&mut linker, |ctx: &mut State| ctx)?;
+        for extension in extensions {
+            extension.add_to_linker(&mut linker)?;
+        }
+
+        bindings::KubeOperatorPre::new(linker.instantiate_pre(&component)?)
+    }
+
     pub async fn load(self) -> Result<(bindings::KubeOperator, Store<State>)> {
         info!("Loading component: {}", self.metadata.name);
 
-        debug!(
-            "Loading component from file: {}",
-            self.metadata.wasm.display()
-        );
-        let component = Component::from_file(&self.engine, &self.metadata.wasm).map_err(|e| {
-            anyhow::anyhow!("Failed to load component '{}': {}", self.metadata.name, e)
-        })?;
-        debug!("Component loaded successfully: {}", self.metadata.name);
-
-        let wasi_ctx = WasiCtxBuilder::new()
-            .inherit_stdio()
-            .args(&self.metadata.args)
-            .envs(
-                &self
-                    .metadata
-                    .env
-                    .iter()
-                    .map(|e| (e.name.as_str(), e.value.as_str()))
-                    .collect::<Vec<_>>(),
-            )
-            .build();
+        let extensions = extensions::enabled(&self.config.extensions)?;
+        let pre = match self.pre {
+            Some(pre) => pre,
+            None => Self::prepare(&self.engine, &self.metadata, &extensions)?,
+        };
+
+        let wasi_ctx = self.store_pool.take(&self.metadata);
+
+        let mut limits = StoreLimitsBuilder::new();
+        if let Some(memory_limit) = self.metadata.memory_limit_bytes {
+            limits = limits.memory_size(memory_limit as usize);
+        }
+        let limits = limits.build();
+
+        let mut extension_data = ExtensionData::default();
+        for extension in &extensions {
+            if let Some(data) = extension.instance_data(&self.metadata) {
+                extension_data.insert(data);
+            }
+        }
 
         let state = State {
+            metadata: self.metadata.clone(),
+            config: self.config.clone(),
             wasi_ctx,
             kubernetes_service: self.kubernetes_service.clone(),
+            leases: self.leases.clone(),
+            locks: self.locks.clone(),
+            lock_holder: self.metadata.name.clone(),
+            timers: self.timers.clone(),
+            reconcile_queue: self.reconcile_queue.clone(),
+            ownership: self.ownership.clone(),
+            message_bus: self.message_bus.clone(),
             resources: Default::default(),
+            introspection: self.introspection.clone(),
+            limits,
+            budget: Budget::default(),
+            reconciling: None,
+            reconcile_object: None,
+            lifecycle_requests: Default::default(),
+            extensions: extension_data,
+            handlers: Handlers::default(),
         };
         let mut store = Store::new(&self.engine, state);
-
-        let mut linker = Linker::new(&self.engine);
-        add_to_linker_async(&mut linker)?;
-
-        bindings::KubeOperator::add_to_linker::<_, HasSelf<_>>(&mut linker, |ctx: &mut State| ctx)?;
+        store.limiter(|state| &mut state.limits);
+        if self.config.reconcile_budget_ms.is_some() {
+            store.set_epoch_deadline(1);
+            store.epoch_deadline_callback(|store| store.data().budget.on_epoch_tick());
+        }
 
         debug!("Instantiating component: {}", self.metadata.name);
-        let operator =
-            bindings::KubeOperator::instantiate_async(&mut store, &component, &linker).await?;
+        let instance = pre.instance_pre().instantiate_async(&mut store).await?;
+        let operator = bindings::KubeOperator::new(&mut store, &instance)?;
+        store.data_mut().handlers = Handlers::resolve(&mut store, &instance)?;
         debug!(
             "Component instantiated successfully: {}",
             self.metadata.name
@@ -0,0 +1,191 @@
+//! # Introspection Module
+//!
+//! This module keeps track of the information an operator can query about itself through
+//! the `self-info` host call: its configuration, the watches it declared, and the history
+//! of its load state transitions. It also keeps the writes of operators in shadow mode for
+//! the admin API, and their recent reconciles and errors for debug bundles.
+
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::config::metadata::WasmComponentMetadata;
+use crate::host::api::bindings::local::operator::types::{
+    LoadState, LoadTransition, SelfMetadata, WatchRequest,
+};
+use crate::host::shadow::ShadowWrite;
+
+/// Maximum number of load state transitions remembered per operator.
+const MAX_LOAD_HISTORY: usize = 32;
+
+/// Maximum number of shadow writes remembered per operator.
+const MAX_SHADOW_WRITES: usize = 256;
+
+/// Maximum number of reconciles remembered per operator.
+const MAX_RECONCILE_HISTORY: usize = 64;
+
+/// Maximum number of reconcile errors remembered per operator.
+const MAX_ERRORS: usize = 16;
+
+/// A reconcile that finished, successfully or not.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileRecord {
+    pub timestamp_ms: u64,
+    pub event: String,
+    pub kind: String,
+    pub namespace: String,
+    pub name: String,
+    pub duration_ms: u64,
+    pub outcome: String,
+}
+
+/// A failed reconcile. For host errors and traps, `error` holds the full error chain with
+/// the Wasm backtrace, and the Rust backtrace if `RUST_BACKTRACE` is set.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorRecord {
+    pub timestamp_ms: u64,
+    pub kind: String,
+    pub namespace: String,
+    pub name: String,
+    pub error: String,
+}
+
+/// Milliseconds since the Unix epoch.
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+fn push_bounded<T>(queue: &mut VecDeque<T>, item: T, max: usize) {
+    if queue.len() == max {
+        queue.pop_front();
+    }
+    queue.push_back(item);
+}
+
+/// Introspection data shared between the runtime and the host state of an operator.
+pub type SharedIntrospection = Arc<Mutex<OperatorIntrospection>>;
+
+pub struct OperatorIntrospection {
+    metadata: WasmComponentMetadata,
+    watches: Vec<WatchRequest>,
+    load_history: VecDeque<LoadTransition>,
+    /// Kinds and namespaces of the objects the operator applied.
+    applied: BTreeSet<(String, String)>,
+    shadow_writes: VecDeque<ShadowWrite>,
+    reconciles: VecDeque<ReconcileRecord>,
+    errors: VecDeque<ErrorRecord>,
+}
+
+impl OperatorIntrospection {
+    pub fn new(metadata: WasmComponentMetadata) -> SharedIntrospection {
+        Arc::new(Mutex::new(Self {
+            metadata,
+            watches: Vec::new(),
+            load_history: VecDeque::new(),
+            applied: BTreeSet::new(),
+            shadow_writes: VecDeque::new(),
+            reconciles: VecDeque::new(),
+            errors: VecDeque::new(),
+        }))
+    }
+
+    pub fn metadata(&self) -> &WasmComponentMetadata {
+        &self.metadata
+    }
+
+    pub fn watches(&self) -> &[WatchRequest] {
+        &self.watches
+    }
+
+    /// Records the watches declared by the operator.
+    pub fn set_watches(&mut self, watches: Vec<WatchRequest>) {
+        self.watches = watches;
+    }
+
+    /// Records that the operator applied an object of a kind in a namespace.
+    pub fn record_applied(&mut self, kind: &str, namespace: &str) {
+        self.applied
+            .insert((kind.to_string(), namespace.to_string()));
+    }
+
+    /// Returns the kinds and namespaces of the objects the operator applied.
+    pub fn applied(&self) -> Vec<(String, String)> {
+        self.applied.iter().cloned().collect()
+    }
+
+    /// Records a write intercepted in shadow mode, dropping the oldest one if the history
+    /// is full.
+    pub fn record_shadow_write(&mut self, write: ShadowWrite) {
+        push_bounded(&mut self.shadow_writes, write, MAX_SHADOW_WRITES);
+    }
+
+    /// Returns the writes intercepted in shadow mode, oldest first.
+    pub fn shadow_writes(&self) -> Vec<ShadowWrite> {
+        self.shadow_writes.iter().cloned().collect()
+    }
+
+    /// Records a finished reconcile, dropping the oldest one if the history is full.
+    pub fn record_reconcile(&mut self, reconcile: ReconcileRecord) {
+        push_bounded(&mut self.reconciles, reconcile, MAX_RECONCILE_HISTORY);
+    }
+
+    /// Records a failed reconcile, dropping the oldest error if the history is full.
+    pub fn record_error(&mut self, error: ErrorRecord) {
+        push_bounded(&mut self.errors, error, MAX_ERRORS);
+    }
+
+    /// Records a load state transition, dropping the oldest one if the history is full.
+    pub fn record_transition(&mut self, state: LoadState) {
+        let transition = LoadTransition {
+            state,
+            timestamp_ms: now_ms(),
+        };
+        push_bounded(&mut self.load_history, transition, MAX_LOAD_HISTORY);
+    }
+
+    /// Describes the operator for a debug bundle.
+    pub fn to_debug_json(&self) -> Value {
+        json!({
+            "watches": self.watches.iter().map(|watch| json!({
+                "kind": watch.kind,
+                "namespace": watch.namespace,
+                "skipInitialList": watch.skip_initial_list,
+                "finalizer": watch.finalizer,
+            })).collect::<Vec<_>>(),
+            "loadHistory": self.load_history.iter().map(|transition| json!({
+                "state": format!("{:?}", transition.state),
+                "timestampMs": transition.timestamp_ms,
+            })).collect::<Vec<_>>(),
+            "applied": self.applied,
+            "reconciles": self.reconciles,
+            "errors": self.errors,
+            "shadowWrites": self.shadow_writes,
+        })
+    }
+
+    /// Builds the `self-metadata` record returned to the guest.
+    pub fn to_self_metadata(&self) -> SelfMetadata {
+        SelfMetadata {
+            operator_id: self.metadata.name.clone(),
+            wasm_path: self.metadata.wasm.display().to_string(),
+            env: self
+                .metadata
+                .env
+                .iter()
+                .map(|e| (e.name.clone(), e.value.clone()))
+                .collect(),
+            args: self.metadata.args.clone(),
+            watches: self.watches.clone(),
+            memory_limit_bytes: self.metadata.memory_limit_bytes,
+            load_history: self.load_history.iter().cloned().collect(),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! # Lifecycle Module
+//!
+//! This module tracks the load state of every operator as a state machine. The entries of
+//! the operators map only say whether an operator is loaded, unloaded or degraded, and
+//! operators being loaded or unloaded are not in the map at all, so the runtime used to
+//! swap states without checking where an operator came from. Every change of load state
+//! now goes through [`WasmRuntime::transition`], which rejects the transitions the
+//! lifecycle does not allow, and emits a tracing event with target
+//! `wasm_operator::lifecycle` for the ones it does:
+//!
+//! ```text
+//! (started)  -> loading
+//! loading    -> loaded | unloaded (a reload failed)
+//! loaded     -> unloading | loading (a trapped instance is replaced)
+//! unloading  -> unloaded | loaded (the unload failed)
+//! unloaded   -> loading
+//! any        -> degraded, which lasts until the parent restarts
+//! ```
+
+use std::fmt;
+
+use anyhow::Result;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use tracing::{error, info};
+
+use super::WasmRuntime;
+use crate::host::api::bindings::local::operator::types::LoadState;
+use crate::metrics;
+
+/// Error for a load state transition the lifecycle does not allow.
+#[derive(Debug)]
+pub struct IllegalTransition {
+    pub operator: String,
+    pub from: Option<LoadState>,
+    pub to: LoadState,
+}
+
+impl fmt::Display for IllegalTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.from {
+            Some(from) => write!(
+                f,
+                "operator {} cannot go from {:?} to {:?}",
+                self.operator, from, self.to
+            ),
+            None => write!(f, "operator {} cannot start {:?}", self.operator, self.to),
+        }
+    }
+}
+
+impl std::error::Error for IllegalTransition {}
+
+/// The load state of every operator that was started.
+#[derive(Default)]
+pub struct Lifecycle {
+    states: DashMap<String, LoadState>,
+}
+
+impl Lifecycle {
+    /// Returns the load state of an operator, or `None` if it was never started.
+    pub fn state(&self, id: &str) -> Option<LoadState> {
+        self.states.get(id).map(|state| *state)
+    }
+
+    /// Moves an operator to a new load state and returns the one it left, or fails
+    /// without changing anything if the lifecycle does not allow the transition.
+    fn transition(&self, id: &str, to: LoadState) -> Result<Option<LoadState>, IllegalTransition> {
+        let entry = self.states.entry(id.to_string());
+        let from = match &entry {
+            Entry::Occupied(state) => Some(*state.get()),
+            Entry::Vacant(_) => None,
+        };
+        if !allowed(from, to) {
+            return Err(IllegalTransition {
+                operator: id.to_string(),
+                from,
+                to,
+            });
+        }
+        entry.insert(to);
+        Ok(from)
+    }
+}
+
+/// Whether the lifecycle allows an operator to go from one load state to another.
+fn allowed(from: Option<LoadState>, to: LoadState) -> bool {
+    use LoadState::*;
+
+    match (from, to) {
+        (None, Loading) => true,
+        (Some(Degraded), _) => false,
+        (Some(_), Degraded) => true,
+        (Some(from), to) => matches!(
+            (from, to),
+            (Loading, Loaded | Unloaded)
+                // A trapped instance is replaced by a new one.
+                | (Loaded, Loading | Unloading)
+                | (Unloading, Unloaded | Loaded)
+                | (Unloaded, Loading)
+        ),
+        (None, _) => false,
+    }
+}
+
+impl WasmRuntime {
+    /// Moves an operator to a new load state, recording the transition for the admin API
+    /// and debug bundles. Fails if the lifecycle does not allow it, in which case the state
+    /// is left as it was.
+    pub(super) fn transition(&self, id: &str, to: LoadState) -> Result<()> {
+        let from = match self.lifecycle.transition(id, to) {
+            Ok(from) => from,
+            Err(e) => {
+                error!(target: "wasm_operator::lifecycle", "Rejected a transition: {}", e);
+                metrics::increment(
+                    "wasm_operator_illegal_transitions_total",
+                    &[("operator", id)],
+                );
+                return Err(e.into());
+            }
+        };
+        info!(
+            target: "wasm_operator::lifecycle",
+            operator = id,
+            from = ?from,
+            to = ?to,
+            "Operator {} is {:?}",
+            id,
+            to
+        );
+
+        let loaded = matches!(to, LoadState::Loaded);
+        if loaded {
+            self.prewarmer.reset(id);
+        } else {
+            self.reconcilers.clear(id);
+        }
+        metrics::set_gauge(
+            "wasm_operator_loaded",
+            &[("operator", id)],
+            if loaded { 1.0 } else { 0.0 },
+        );
+        if let Some(introspection) = self.introspection.get(id) {
+            introspection.lock().unwrap().record_transition(to);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_the_lifecycle_transitions() {
+        use LoadState::*;
+
+        assert!(allowed(None, Loading));
+        assert!(allowed(Some(Loading), Loaded));
+        assert!(allowed(Some(Loading), Unloaded));
+        assert!(allowed(Some(Loaded), Unloading));
+        assert!(allowed(Some(Loaded), Loading));
+        assert!(allowed(Some(Unloading), Unloaded));
+        assert!(allowed(Some(Unloading), Loaded));
+        assert!(allowed(Some(Unloaded), Loading));
+        assert!(allowed(Some(Unloaded), Degraded));
+    }
+
+    #[test]
+    fn rejects_other_transitions() {
+        use LoadState::*;
+
+        assert!(!allowed(None, Loaded));
+        assert!(!allowed(None, Degraded));
+        assert!(!allowed(Some(Unloaded), Unloading));
+        assert!(!allowed(Some(Unloaded), Loaded));
+        assert!(!allowed(Some(Loading), Unloading));
+        assert!(!allowed(Some(Degraded), Loading));
+        assert!(!allowed(Some(Degraded), Degraded));
+    }
+
+    #[test]
+    fn failed_transitions_leave_the_state() {
+        let lifecycle = Lifecycle::default();
+        assert!(lifecycle
+            .transition("operator", LoadState::Unloading)
+            .is_err());
+        assert_eq!(lifecycle.state("operator"), None);
+
+        assert_eq!(
+            lifecycle
+                .transition("operator", LoadState::Loading)
+                .unwrap(),
+            None
+        );
+        assert!(lifecycle
+            .transition("operator", LoadState::Unloaded)
+            .is_ok());
+        let e = lifecycle
+            .transition("operator", LoadState::Unloading)
+            .unwrap_err();
+        assert_eq!(e.from, Some(LoadState::Unloaded));
+        assert_eq!(lifecycle.state("operator"), Some(LoadState::Unloaded));
+    }
+}
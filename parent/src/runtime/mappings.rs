@@ -0,0 +1,173 @@
+//! # Mappings Module
+//!
+//! This module reconciles objects when related objects change, for the `watch-mappings`
+//! of an operator. For each mapping, the runtime watches the related kind and maps every
+//! change to the objects it belongs to: the owners of the changed object, or the objects
+//! whose mapped field names it. These are enqueued with a `dependency` trigger, so a burst
+//! of changes to related objects reconciles each target once.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use futures::StreamExt;
+use kube::api::DynamicObject;
+use kube::runtime::watcher::{self, Event};
+use serde_json::Value;
+use tracing::{info, warn};
+
+use super::WasmRuntime;
+use crate::config::metadata::WatchMapping;
+use crate::host::api::bindings::local::operator::types::ObjectReference;
+
+impl WasmRuntime {
+    /// Starts a watch for each mapping of the operator.
+    pub(super) fn start_mapping_watches(
+        self: &Arc<Self>,
+        operator_id: &str,
+        mappings: &[WatchMapping],
+    ) {
+        for mapping in mappings {
+            let runtime = self.clone();
+            let operator_id = operator_id.to_string();
+            let mapping = mapping.clone();
+            tokio::task::spawn_local(async move {
+                runtime.watch_mapping(operator_id, mapping).await;
+            });
+        }
+    }
+
+    async fn watch_mapping(self: Arc<Self>, operator_id: String, mapping: WatchMapping) {
+        if self.is_namespace_excluded(&operator_id, &mapping.namespace) {
+            warn!(
+                "Operator '{}' may not watch kind '{}' in excluded namespace '{}'",
+                operator_id, mapping.kind, mapping.namespace
+            );
+            return;
+        }
+        let ar = match self.kubernetes_service.resolve_kind(&mapping.kind).await {
+            Ok((ar, _)) => ar,
+            Err(e) => {
+                warn!(
+                    "Not watching '{}' for operator '{}': {}",
+                    mapping.kind, operator_id, e
+                );
+                return;
+            }
+        };
+        let mut watcher = watcher::watcher(
+            self.kubernetes_service.dynamic_api(ar, &mapping.namespace),
+            watcher::Config::default(),
+        )
+        .boxed();
+
+        info!(
+            "Watching {} objects in namespace '{}' to reconcile the {} objects of operator '{}'",
+            mapping.kind, mapping.namespace, mapping.target_kind, operator_id
+        );
+
+        while let Some(event) = watcher.next().await {
+            let object = match event {
+                // The targets are reconciled by their own watch when it starts, so the
+                // initial list of related objects is not mapped.
+                Ok(Event::Apply(object)) | Ok(Event::Delete(object)) => object,
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!(
+                        "Watch of {} in namespace '{}' for operator '{}' encountered an error: {}",
+                        mapping.kind, mapping.namespace, operator_id, e
+                    );
+                    continue;
+                }
+            };
+            let targets = match self.map_targets(&mapping, &object).await {
+                Ok(targets) => targets,
+                Err(e) => {
+                    warn!(
+                        "Failed to map {} '{}' to the {} objects of operator '{}': {:#}",
+                        mapping.kind,
+                        object.metadata.name.as_deref().unwrap_or_default(),
+                        mapping.target_kind,
+                        operator_id,
+                        e
+                    );
+                    continue;
+                }
+            };
+            let related = ObjectReference {
+                kind: mapping.kind.clone(),
+                name: object.metadata.name.clone().unwrap_or_default(),
+                namespace: object.metadata.namespace.clone().unwrap_or_default(),
+            };
+            for target in targets {
+                if let Err(e) = self.reconcile_queue.enqueue(
+                    &operator_id,
+                    target,
+                    Some(related.clone()),
+                    None,
+                    Duration::ZERO,
+                ) {
+                    warn!("Dropped a mapped reconcile: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Returns the objects of the target kind a related object belongs to.
+    async fn map_targets(
+        &self,
+        mapping: &WatchMapping,
+        object: &DynamicObject,
+    ) -> Result<Vec<ObjectReference>> {
+        let name = object.metadata.name.as_deref().unwrap_or_default();
+        let namespace = object.metadata.namespace.clone().unwrap_or_default();
+        let Some(field) = &mapping.field else {
+            return Ok(object
+                .metadata
+                .owner_references
+                .iter()
+                .flatten()
+                .filter(|owner| owner.kind.eq_ignore_ascii_case(&mapping.target_kind))
+                .map(|owner| ObjectReference {
+                    kind: mapping.target_kind.clone(),
+                    name: owner.name.clone(),
+                    namespace: namespace.clone(),
+                })
+                .collect());
+        };
+        let path: Vec<&str> = field.split('.').collect();
+        let targets = self
+            .kubernetes_service
+            .list_objects(&mapping.target_kind, &namespace)
+            .await?;
+        Ok(targets
+            .into_iter()
+            .filter(|target| {
+                let data = serde_json::to_value(target).unwrap_or_default();
+                field_values(&data, &path).contains(&name)
+            })
+            .map(|target| ObjectReference {
+                kind: mapping.target_kind.clone(),
+                name: target.metadata.name.unwrap_or_default(),
+                namespace: target.metadata.namespace.unwrap_or_default(),
+            })
+            .collect())
+    }
+}
+
+/// Returns the strings at a path in a value, descending into every element of the lists
+/// along the way.
+fn field_values<'a>(value: &'a Value, path: &[&str]) -> Vec<&'a str> {
+    match (value, path.split_first()) {
+        (Value::Array(items), _) => items
+            .iter()
+            .flat_map(|item| field_values(item, path))
+            .collect(),
+        (Value::String(value), None) => vec![value.as_str()],
+        (Value::Object(fields), Some((first, rest))) => fields
+            .get(*first)
+            .map(|field| field_values(field, rest))
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
@@ -1,39 +1,95 @@
 //! # Runtime Module
 //!
 //! This module provides the core WebAssembly (Wasm) runtime capabilities for the operator.
-//! It manages the Wasmtime engine and orchestrates the execution of individual Wasm components,
+//! It manages the Wasmtime engines and orchestrates the execution of individual Wasm components,
 //! ensuring they can interact with the Kubernetes API and other host functionalities.
 
 use crate::runtime::watcher::watcher;
 use std::future::Future;
-use std::path::PathBuf;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use anyhow::Result;
-use dashmap::DashMap;
-use futures::StreamExt;
+use anyhow::{anyhow, Result};
+use dashmap::{DashMap, DashSet};
+use futures::{FutureExt, StreamExt};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::OwnerReference;
 use kube::runtime::watcher::{self, Event};
 use tokio::sync::Mutex;
-use tracing::{error, info, warn};
-use wasmtime::{Engine, Store};
+use tracing::{debug, error, info, warn};
+use wasmtime::Store;
 
-use crate::config::metadata::WasmComponentMetadata;
+use crate::config::metadata::{ErrorReporting, WasmComponentMetadata};
+use crate::config::runtime::{OversizeStrategy, RuntimeConfig, SnapshotStrategy};
 use crate::host::api::bindings;
-use crate::host::state::State;
-use crate::kubernetes::KubernetesService;
+use crate::host::api::bindings::local::operator::types::{
+    LoadState, ReconcileReason, ReconcileTrigger,
+};
+use crate::host::budget::BudgetExceeded;
+use crate::host::extensions;
+use crate::host::handlers;
+use crate::host::locks::LockTable;
+use crate::host::message_bus::{Message, MessageBus};
+use crate::host::ownership::{ManagedObject, ObjectKey, OwnershipGraph};
+use crate::host::reconcile_queue::{EnqueuedReconcile, ReconcileQueue};
+use crate::host::shadow::ShadowWrite;
+use crate::host::state::{ReconcileTarget, State};
+use crate::host::timers::TimerQueue;
+use crate::kubernetes::lease::LeaseManager;
+use crate::kubernetes::{self, KubernetesService};
+use crate::metrics;
+use crate::platform;
 
+pub use self::builder::WasmRuntimeBuilder;
+use self::dead_letter::{DeadLetter, DeadLetterQueue, ObjectRef};
+use self::engines::Engines;
+use self::events::SeenObjects;
+use self::informer_cache::InformerCache;
 use self::instance::WasmInstance;
+use self::introspection::{
+    now_ms, ErrorRecord, OperatorIntrospection, ReconcileRecord, SharedIntrospection,
+};
+use self::lifecycle::Lifecycle;
+use self::prewarm::Prewarmer;
+use self::reconcilers::Reconcilers;
+use self::store_pool::StorePool;
+use self::watchdog::{Probe, Watchdog};
+use self::work_queue::{WorkItem, WorkQueue};
 
+pub mod builder;
+pub mod checkpoint;
+pub mod compile;
+pub mod dead_letter;
+#[cfg(feature = "admin-api")]
+pub mod diagnostics;
+pub mod drift;
+pub mod engines;
+pub mod error_report;
+pub mod events;
+pub mod finalizer;
+pub mod informer_cache;
 pub mod instance;
+pub mod introspection;
+pub mod lifecycle;
+pub mod mappings;
+pub mod prewarm;
+pub mod reconcilers;
+pub mod resync;
+pub mod signals;
+pub mod snapshot;
+pub mod store_pool;
+pub mod summary;
+pub mod watchdog;
+pub mod work_queue;
 
 // A unique identifier for each operator, e.g., from its Custom Resource.
 type OperatorId = String;
 
 enum OperatorState {
     Loaded {
-        operator: bindings::KubeOperator,
+        operator: Box<bindings::KubeOperator>,
         store: Mutex<Store<State>>,
         last_active: Instant,
         metadata: WasmComponentMetadata,
@@ -44,29 +100,125 @@ enum OperatorState {
         // Path to the original .wasm component file.
         metadata: WasmComponentMetadata,
     },
+    // Taken out of service after a panic in the host.
+    Degraded {
+        reason: String,
+    },
 }
 
-/// A service that manages the wasmtime engine and the execution of Wasm components.
+/// A service that manages the wasmtime engines and the execution of Wasm components.
 pub struct WasmRuntime {
-    engine: Engine,
+    /// Engines by compiler setting, since components can ask for different ones.
+    engines: Engines,
     kubernetes_service: Arc<KubernetesService>,
+    config: Arc<RuntimeConfig>,
     operators: DashMap<OperatorId, OperatorState>,
+    /// The load state of every operator, including those being loaded or unloaded.
+    lifecycle: Lifecycle,
+    introspection: DashMap<OperatorId, SharedIntrospection>,
+    dead_letters: DeadLetterQueue,
+    informer_cache: InformerCache,
+    /// Drift watches that were started, by operator, kind and namespace.
+    drift_watches: DashSet<(OperatorId, String, String)>,
+    /// Operators with a resync in progress.
+    resyncing: DashSet<OperatorId>,
+    leases: Arc<LeaseManager>,
+    locks: Arc<LockTable>,
+    timers: Arc<TimerQueue>,
+    reconcile_queue: Arc<ReconcileQueue>,
+    ownership: Arc<OwnershipGraph>,
+    message_bus: Arc<MessageBus>,
+    /// Compiled and linked components, kept with the `pre-init` snapshot strategy.
+    instance_pres: DashMap<OperatorId, bindings::KubeOperatorPre<State>>,
+    /// Finalizers declared in watch requests, by operator and kind.
+    finalizers: DashMap<(OperatorId, String), String>,
+    /// The JSON last passed to operators with `old-objects`, by object.
+    old_objects: DashMap<ObjectRef, String>,
+    prewarmer: Prewarmer,
+    /// WASI contexts of unloaded operators, reused when they are reloaded.
+    store_pool: Arc<StorePool>,
+    /// Restarts the long-running tasks of the runtime that ended or got stuck.
+    watchdog: Watchdog,
+    /// Watch events waiting to be reconciled, by operator.
+    work_queues: DashMap<OperatorId, Arc<WorkQueue>>,
+    /// Extra instances of the operators that reconcile objects in parallel.
+    reconcilers: Reconcilers,
+}
+
+const LAST_APPLIED_ANNOTATION: &str = "kubectl.kubernetes.io/last-applied-configuration";
+/// Name of the idle unload loop for the watchdog.
+const IDLE_UNLOAD_SUBSYSTEM: &str = "idle-unload";
+/// How long unloading the idle operators may hold up the idle unload loop.
+const MAX_UNLOAD_STALL: Duration = Duration::from_secs(300);
+/// How long a due timer waits for its operator to finish a call.
+const TIMER_BUSY_RETRY: Duration = Duration::from_millis(50);
+/// How long a message, a reconcile or an HTTP request waits for an operator that is busy
+/// handling another call before it gives up.
+const MAX_BUSY_WAIT: Duration = Duration::from_secs(30);
+
+/// Error returned when an operator stayed busy handling other calls for too long.
+#[derive(Debug)]
+pub struct OperatorBusy(String);
+
+impl std::fmt::Display for OperatorBusy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Operator {} stayed busy for {:?}", self.0, MAX_BUSY_WAIT)
+    }
 }
 
-const IDLE_THRESHOLD: Duration = Duration::from_secs(300); // 5 minutes
+impl std::error::Error for OperatorBusy {}
 
 impl WasmRuntime {
+    /// Returns a builder for a runtime, which connects to the cluster unless given a
+    /// connection.
+    pub fn builder() -> WasmRuntimeBuilder {
+        WasmRuntimeBuilder::new()
+    }
+
     /// Creates a new `WasmRuntime`.
-    pub fn new(kubernetes_service: Arc<KubernetesService>) -> Result<Self> {
-        let mut config = wasmtime::Config::new();
-        config.async_support(true);
-        config.cranelift_opt_level(wasmtime::OptLevel::SpeedAndSize);
-        let engine = Engine::new(&config)?;
+    pub fn new(
+        kubernetes_service: Arc<KubernetesService>,
+        config: Arc<RuntimeConfig>,
+    ) -> Result<Self> {
+        let engines = Engines::new(config.clone())?;
+        let informer_cache = InformerCache::load(
+            config.informer_cache_path.clone(),
+            config
+                .restore_from
+                .as_deref()
+                .map(checkpoint::informer_cache_file),
+        )?;
+        for extension in extensions::enabled(&config.extensions)? {
+            info!("Host extension '{}' enabled", extension.name());
+        }
+        let leases = Arc::new(LeaseManager::new(kubernetes_service.clone()));
+        let prewarmer = Prewarmer::new(config.prewarm_concurrency);
 
         Ok(Self {
-            engine,
+            engines,
             kubernetes_service,
+            config,
             operators: DashMap::new(),
+            lifecycle: Lifecycle::default(),
+            introspection: DashMap::new(),
+            dead_letters: DeadLetterQueue::default(),
+            informer_cache,
+            drift_watches: DashSet::new(),
+            resyncing: DashSet::new(),
+            leases,
+            locks: Arc::new(LockTable::default()),
+            timers: Arc::default(),
+            reconcile_queue: Arc::default(),
+            ownership: Arc::default(),
+            message_bus: Arc::default(),
+            instance_pres: DashMap::new(),
+            finalizers: DashMap::new(),
+            old_objects: DashMap::new(),
+            prewarmer,
+            store_pool: Arc::default(),
+            watchdog: Watchdog::default(),
+            work_queues: DashMap::new(),
+            reconcilers: Reconcilers::default(),
         })
     }
 
@@ -79,25 +231,37 @@ impl WasmRuntime {
         // to the Kubernetes API server.
         let stagger_delay = Duration::from_millis(125);
 
+        if let Some(secs) = self.config.watchdog_interval_secs {
+            tokio::task::spawn_local(self.clone().watchdog_loop(Duration::from_secs(secs)));
+        }
+
+        let mut compiled = self.compile_all(&components_metadata).await;
         for metadata in components_metadata {
             tokio::time::sleep(stagger_delay).await;
 
             let operator_id = metadata.name.clone();
+            let watch_mappings = metadata.watch_mappings.clone();
+            let introspection = OperatorIntrospection::new(metadata.clone());
+            self.introspection
+                .insert(operator_id.clone(), introspection.clone());
 
-            let instance = WasmInstance::new(
-                self.engine.clone(),
-                self.kubernetes_service.clone(),
-                metadata.clone(),
-            );
+            self.transition(&operator_id, LoadState::Loading)?;
+            let mut instance = self.new_instance(metadata.clone(), introspection.clone())?;
+            if let Some(pre) = compiled.remove(&operator_id) {
+                instance = instance.with_pre(pre);
+            }
 
-            let (operator, store) = instance.load().await?;
+            let (operator, mut store) = instance.load().await?;
+            self.restore_operator(&operator_id, &operator, &mut store)
+                .await?;
             let op_state = OperatorState::Loaded {
-                operator,
+                operator: Box::new(operator),
                 store: Mutex::new(store),
                 last_active: Instant::now(),
                 metadata,
             };
             self.operators.insert(operator_id.clone(), op_state);
+            self.transition(&operator_id, LoadState::Loaded)?;
 
             // Get the watch requests from the component
             let watch_requests = self
@@ -105,6 +269,10 @@ impl WasmRuntime {
                     Box::pin(async move { operator.call_get_watch_requests(store).await })
                 })
                 .await?;
+            introspection
+                .lock()
+                .unwrap()
+                .set_watches(watch_requests.clone());
 
             for request in watch_requests {
                 info!(
@@ -112,25 +280,110 @@ impl WasmRuntime {
                     operator_id, request.kind, request.namespace
                 );
 
-                let self_clone = self.clone();
-                let operator_id_clone = operator_id.clone();
-                tokio::task::spawn_local(async move {
-                    self_clone
-                        .watch_and_reconcile(operator_id_clone, request)
-                        .await;
-                });
+                if self.is_namespace_excluded(&operator_id, &request.namespace) {
+                    error!(
+                        "Operator '{}' may not watch kind '{}' in excluded namespace '{}'",
+                        operator_id, request.kind, request.namespace
+                    );
+                    continue;
+                }
+                let name = format!(
+                    "watch/{}/{}/{}",
+                    operator_id, request.kind, request.namespace
+                );
+                let runtime = self.clone();
+                let operator_id = operator_id.clone();
+                self.watchdog.supervise(
+                    &name,
+                    Probe::None,
+                    Box::new(move || {
+                        // A degraded operator gets no more events.
+                        if runtime.is_degraded(&operator_id) {
+                            return None;
+                        }
+                        let runtime = runtime.clone();
+                        let operator_id = operator_id.clone();
+                        let request = request.clone();
+                        Some(Box::pin(async move {
+                            let watch = runtime
+                                .clone()
+                                .watch_and_reconcile(operator_id.clone(), request);
+                            if let Err(panic) = AssertUnwindSafe(watch).catch_unwind().await {
+                                runtime
+                                    .degrade_after_panic(
+                                        &operator_id,
+                                        &panic_message(panic.as_ref()),
+                                    )
+                                    .await;
+                            }
+                        }))
+                    }),
+                );
             }
+            self.start_mapping_watches(&operator_id, &watch_mappings);
         }
 
-        let runtime = Arc::clone(&self);
-        tokio::spawn(async move {
-            runtime.idle_check_loop().await;
-        });
+        let runtime = self.clone();
+        self.watchdog.supervise(
+            "timers",
+            Probe::None,
+            Box::new(move || Some(Box::pin(runtime.clone().timer_loop()))),
+        );
+        let runtime = self.clone();
+        self.watchdog.supervise(
+            "reconcile-queue",
+            Probe::None,
+            Box::new(move || Some(Box::pin(runtime.clone().reconcile_queue_loop()))),
+        );
+        let runtime = self.clone();
+        self.watchdog.supervise(
+            "message-bus",
+            Probe::None,
+            Box::new(move || Some(Box::pin(runtime.clone().message_loop()))),
+        );
+        if self.config.signal_config_maps {
+            let runtime = self.clone();
+            self.watchdog.supervise(
+                "signals",
+                Probe::None,
+                Box::new(move || Some(Box::pin(runtime.clone().signal_config_map_loop()))),
+            );
+        }
+        if let Some(secs) = self.config.status_summary_interval_secs {
+            let runtime = self.clone();
+            self.watchdog.supervise(
+                "status-summary",
+                Probe::None,
+                Box::new(move || {
+                    Some(Box::pin(
+                        runtime
+                            .clone()
+                            .status_summary_loop(Duration::from_secs(secs)),
+                    ))
+                }),
+            );
+        }
 
-        // The main event loop to keep the operator alive.
-        loop {
-            tokio::time::sleep(Duration::from_secs(60)).await;
+        if let Some(idle_unload_secs) = self.config.idle_unload_secs {
+            let idle_threshold = Duration::from_secs(idle_unload_secs);
+            let runtime = self.clone();
+            // The loop ticks every half threshold, plus the time unloading takes.
+            self.watchdog.supervise(
+                IDLE_UNLOAD_SUBSYSTEM,
+                Probe::Heartbeat(idle_threshold * 2 + MAX_UNLOAD_STALL),
+                Box::new(move || {
+                    let runtime = runtime.clone();
+                    Some(Box::pin(async move {
+                        runtime.idle_check_loop(idle_threshold).await
+                    }))
+                }),
+            );
         }
+
+        // Keep the operators alive until the parent is asked to shut down.
+        platform::shutdown_signal().await?;
+        info!("Shutting down");
+        self.informer_cache.save()
     }
 
     async fn watch_and_reconcile(
@@ -138,8 +391,17 @@ impl WasmRuntime {
         operator_id: String,
         request: bindings::local::operator::types::WatchRequest,
     ) {
+        if self.is_namespace_excluded(&operator_id, &request.namespace) {
+            error!(
+                "Operator '{}' may not watch kind '{}' in excluded namespace '{}'",
+                operator_id, request.kind, request.namespace
+            );
+            return;
+        }
+
         let client = self.kubernetes_service.clone();
-        let (ar, _) = match client.find_api_resource(&request.kind) {
+        let kind = kubernetes::qualified_kind(&request.kind, request.api_version.as_deref());
+        let (ar, _) = match client.resolve_kind(&kind).await {
             Ok(ar) => ar,
             Err(e) => {
                 error!(
@@ -151,89 +413,562 @@ impl WasmRuntime {
         };
 
         let mut watcher = watcher(
-            client.dynamic_api(ar, &request.namespace),
+            client.dynamic_api(ar.clone(), &request.namespace),
             Default::default(),
         )
         .boxed();
 
         info!("Watcher started for kind '{}' in namespace '{}'", request.kind, request.namespace);
+        if let Some(finalizer) = &request.finalizer {
+            self.finalizers
+                .insert((operator_id.clone(), ar.kind.clone()), finalizer.clone());
+        }
+
+        let cache_key = InformerCache::watch_key(&operator_id, &request.kind, &request.namespace);
+        let mut restored = self.informer_cache.take_restored(&cache_key);
+        let mut seen = SeenObjects::restored(restored.values());
+        // Only the first list is skipped; lists after the watch is re-established may
+        // contain changes that were missed in the meantime.
+        let mut skip_initial_list = request.skip_initial_list;
+        // Errors in a row, for the backoff before the watch is re-established.
+        let mut failures = 0;
+        // Resource version of the last watch event, which a re-established watch resumes
+        // from. Objects of a list are left out, since a list is not done until `InitDone`.
+        let mut resource_version: Option<String> = None;
 
         loop {
             match watcher.next().await {
                 Some(Ok(event)) => {
-                    let (event_type, object) = match event {
+                    failures = 0;
+                    self.note_watch_event(&operator_id);
+                    if let Event::Apply(obj) | Event::Delete(obj) = &event {
+                        resource_version.clone_from(&obj.metadata.resource_version);
+                    }
+                    let (event_type, trigger, object) = match event {
                         Event::Apply(obj) => {
-                            (bindings::local::operator::types::EventType::Added, obj)
+                            self.informer_cache.apply(&cache_key, &obj);
+                            (seen.apply(&obj), ReconcileTrigger::WatchApply, obj)
                         }
                         Event::Delete(obj) => {
-                            (bindings::local::operator::types::EventType::Deleted, obj)
+                            self.informer_cache.delete(&cache_key, &obj);
+                            seen.delete(&obj);
+                            (
+                                bindings::local::operator::types::EventType::Deleted,
+                                ReconcileTrigger::WatchDelete,
+                                obj,
+                            )
                         }
                         Event::InitApply(obj) => {
-                            (bindings::local::operator::types::EventType::Added, obj)
+                            self.informer_cache.apply(&cache_key, &obj);
+                            let event_type = seen.apply(&obj);
+                            if informer_cache::unchanged_since_checkpoint(&mut restored, &obj)
+                                || skip_initial_list
+                            {
+                                continue;
+                            }
+                            (event_type, ReconcileTrigger::WatchApply, obj)
+                        }
+                        Event::InitDone => {
+                            if std::mem::take(&mut skip_initial_list) {
+                                restored.clear();
+                            }
+                            // Restored objects missing from the initial list were deleted
+                            // while the parent was down.
+                            for (_, object) in restored.drain() {
+                                self.enqueue_event(
+                                    &operator_id,
+                                    WorkItem {
+                                        event_type:
+                                            bindings::local::operator::types::EventType::Deleted,
+                                        reason: triggered(ReconcileTrigger::WatchDelete),
+                                        object,
+                                    },
+                                );
+                            }
+                            continue;
                         }
-                        _ => continue, // Ignore Init and InitDone for now
+                        Event::Init => continue,
                     };
 
-                    self.dispatch_reconcile(&operator_id, event_type, &object)
-                        .await;
+                    self.enqueue_event(
+                        &operator_id,
+                        WorkItem {
+                            event_type,
+                            reason: triggered(trigger),
+                            object,
+                        },
+                    );
                 }
                 Some(Err(e)) => {
+                    failures += 1;
+                    let delay = self.config.watch_backoff.delay(failures);
                     warn!(
-                        "Watcher for kind '{}' in namespace '{}' encountered an error: {}",
-                        request.kind, request.namespace, e
+                        "Watcher for kind '{}' in namespace '{}' encountered an error, \
+                         re-establishing it in {:?}: {}",
+                        request.kind, request.namespace, delay, e
                     );
+                    // The watcher holds on to the client it was created with, so restart it
+                    // on the refreshed client once the credentials have been re-acquired.
+                    // Otherwise it resumes from the last resource version it saw when it is
+                    // polled again, or lists again if that version is too old.
+                    let mut reason = "error";
+                    if kubernetes::is_unauthorized_watch_error(&e) {
+                        match client.refresh_credentials().await {
+                            Ok(()) => {
+                                let api = client.dynamic_api(ar.clone(), &request.namespace);
+                                watcher = match resource_version.clone() {
+                                    Some(version) => kubernetes::resume_watcher(api, version),
+                                    None => watcher::watcher(api, Default::default()).boxed(),
+                                };
+                                reason = "credentials";
+                            }
+                            Err(e) => {
+                                warn!("Failed to refresh Kubernetes credentials: {}", e)
+                            }
+                        }
+                    }
+                    self.record_watch_restart(&operator_id, &request.kind, reason);
+                    tokio::time::sleep(delay).await;
                 }
                 None => {
-                    // Stream ended, might want to restart the watch.
-                    info!(
-                        "Watcher for kind '{}' in namespace '{}' stream ended.",
-                        request.kind, request.namespace
+                    // The new watcher resumes from the last resource version seen. If there
+                    // is none, or the API server no longer has it, it lists the objects
+                    // again, and the list tells the objects that changed in the meantime
+                    // from the ones that did not.
+                    failures += 1;
+                    let delay = self.config.watch_backoff.delay(failures);
+                    warn!(
+                        "Watcher for kind '{}' in namespace '{}' ended, restarting it in {:?}",
+                        request.kind, request.namespace, delay
                     );
-                    break;
+                    self.record_watch_restart(&operator_id, &request.kind, "ended");
+                    tokio::time::sleep(delay).await;
+                    let api = client.dynamic_api(ar.clone(), &request.namespace);
+                    watcher = match resource_version.clone() {
+                        Some(version) => kubernetes::resume_watcher(api, version),
+                        None => watcher::watcher(api, Default::default()).boxed(),
+                    };
                 }
             }
         }
     }
 
     async fn dispatch_reconcile(
-        &self,
+        self: &Arc<Self>,
         operator_id: &str,
-        event_type: bindings::local::operator::types::EventType,
+        mut event_type: bindings::local::operator::types::EventType,
+        reason: ReconcileReason,
         object: &kube::api::DynamicObject,
     ) {
         let name = object.metadata.name.clone().unwrap_or_default();
         let namespace = object.metadata.namespace.clone().unwrap_or_default();
-        let resource_json = match serde_json::to_string(object) {
-            Ok(json) => json,
-            Err(e) => {
-                error!("Failed to serialize resource to JSON: {}", e);
-                return;
+
+        if self.is_namespace_excluded(operator_id, &namespace) {
+            debug!(
+                "Skipping reconcile of '{}/{}' in excluded namespace for operator '{}'",
+                namespace, name, operator_id
+            );
+            return;
+        }
+
+        if self.is_paused(object) {
+            debug!(
+                "Skipping reconcile of paused object '{}/{}' for operator '{}'",
+                namespace, name, operator_id
+            );
+            metrics::increment(
+                "wasm_operator_paused_objects_skipped_total",
+                &[("operator", operator_id)],
+            );
+            return;
+        }
+
+        let finalizer = self.finalizer_for(operator_id, object);
+        if let Some(finalizer) = &finalizer {
+            use bindings::local::operator::types::EventType;
+
+            if matches!(
+                event_type,
+                EventType::Added | EventType::Modified | EventType::Resynced
+            ) {
+                if finalizer::is_deleting(object) {
+                    // The operator has already finalized the object.
+                    if !finalizer::has_finalizer(object, finalizer) {
+                        return;
+                    }
+                    event_type = EventType::Finalize;
+                } else if !finalizer::has_finalizer(object, finalizer)
+                    && self.applies_writes(operator_id)
+                {
+                    // Adding the finalizer changes the object, and the watch event for
+                    // that change reconciles it.
+                    match finalizer::add(&self.kubernetes_service, object, finalizer).await {
+                        Ok(()) => return,
+                        Err(e) => warn!(
+                            "Failed to add finalizer '{}' to '{}/{}' for operator '{}': {}",
+                            finalizer, namespace, name, operator_id, e
+                        ),
+                    }
+                }
+            }
+        }
+
+        let lazy = self
+            .operator_metadata(operator_id)
+            .is_some_and(|metadata| metadata.lazy_objects);
+        let resource_json = if lazy {
+            String::new()
+        } else {
+            match self.serialize_for_guest(operator_id, object) {
+                Ok(json) => json,
+                Err(e) => {
+                    error!(
+                        "Not passing '{}/{}' to operator '{}': {}",
+                        namespace, name, operator_id, e
+                    );
+                    return;
+                }
             }
         };
+        let reconcile_object = lazy.then(|| Arc::new(object.clone()));
 
+        let old_resource_json =
+            self.remember_object(operator_id, event_type, object, &resource_json);
         let reconcile_request = bindings::local::operator::types::ReconcileRequest {
             event_type,
             name,
             namespace,
             resource_json,
+            old_resource_json,
+            reason: reason.clone(),
+            budget_ms: self.config.reconcile_budget_ms,
         };
 
-        if let Err(e) = self
-            .with_operator(operator_id, |operator, store| {
-                Box::pin(async move { operator.call_reconcile(store, &reconcile_request).await })
+        let budget = self.config.reconcile_budget_ms.map(Duration::from_millis);
+        let target = reconcile_target(object);
+        let started = Instant::now();
+        let (outcome, on_extra_instance) = self
+            .with_reconciler(operator_id, |operator, store| {
+                Box::pin(async move {
+                    store.data_mut().budget.start(budget);
+                    store.data_mut().reconciling = target;
+                    store.data_mut().reconcile_object = reconcile_object;
+                    let result = operator
+                        .call_reconcile(&mut *store, &reconcile_request)
+                        .await;
+                    store.data_mut().reconciling = None;
+                    store.data_mut().reconcile_object = None;
+                    store.data_mut().budget.finish();
+                    result
+                })
             })
-            .await
+            .await;
+        self.record_reconcile(operator_id, event_type, object, started.elapsed(), &outcome);
+        let budget_exceeded = outcome
+            .as_ref()
+            .is_err_and(|e| e.downcast_ref::<BudgetExceeded>().is_some());
+        if budget_exceeded {
+            warn!("Operator '{}' exceeded its reconcile budget", operator_id);
+            metrics::increment(
+                "wasm_operator_budget_exceeded_total",
+                &[("operator", operator_id)],
+            );
+            // A trapped extra instance is dropped instead.
+            if !on_extra_instance {
+                self.replace_trapped_operator(operator_id).await;
+            }
+        }
+
+        let finalized = matches!(
+            (event_type, &outcome),
+            (
+                bindings::local::operator::types::EventType::Finalize,
+                Ok(bindings::local::operator::types::ReconcileResult::Ok)
+            )
+        );
+        if let Some(finalizer) = finalizer.filter(|_| finalized && self.applies_writes(operator_id))
+            && let Err(e) = finalizer::remove(&self.kubernetes_service, object, &finalizer).await
         {
-            error!(
-                "Reconciliation for operator '{}' failed: {}",
-                operator_id, e
+            warn!(
+                "Failed to remove finalizer '{}' from '{}/{}' for operator '{}': {}",
+                finalizer,
+                object.metadata.namespace.as_deref().unwrap_or_default(),
+                object.metadata.name.as_deref().unwrap_or_default(),
+                operator_id,
+                e
             );
         }
+
+        self.handle_reconcile_outcome(operator_id, event_type, reason, object, outcome);
+        self.ensure_drift_watches(operator_id);
     }
 
-    async fn idle_check_loop(&self) {
+    /// Applies the retry policy to the outcome of a reconcile.
+    fn handle_reconcile_outcome(
+        self: &Arc<Self>,
+        operator_id: &str,
+        event_type: bindings::local::operator::types::EventType,
+        reason: ReconcileReason,
+        object: &kube::api::DynamicObject,
+        outcome: Result<bindings::local::operator::types::ReconcileResult>,
+    ) {
+        let object_ref = ObjectRef::new(operator_id, object);
+        let error = match outcome {
+            Ok(bindings::local::operator::types::ReconcileResult::Ok) => {
+                self.dead_letters.record_success(&object_ref);
+                self.report_outcome(operator_id, object, None);
+                return;
+            }
+            Ok(bindings::local::operator::types::ReconcileResult::Requeue(seconds)) => {
+                self.dead_letters.record_success(&object_ref);
+                self.schedule_reconcile(
+                    operator_id,
+                    event_type,
+                    triggered(ReconcileTrigger::Timer),
+                    object.clone(),
+                    Duration::from_secs(seconds.into()),
+                );
+                return;
+            }
+            Ok(bindings::local::operator::types::ReconcileResult::Continue(token)) => {
+                self.dead_letters.record_success(&object_ref);
+                let reason = ReconcileReason {
+                    continuation: Some(token),
+                    ..triggered(ReconcileTrigger::Continuation)
+                };
+                self.schedule_reconcile(
+                    operator_id,
+                    event_type,
+                    reason,
+                    object.clone(),
+                    Duration::ZERO,
+                );
+                return;
+            }
+            Ok(bindings::local::operator::types::ReconcileResult::Error(message)) => message,
+            Err(e) => e.to_string(),
+        };
+
+        error!(
+            "Reconciliation of {} '{}/{}' for operator '{}' failed: {}",
+            object_ref.kind, object_ref.namespace, object_ref.name, operator_id, error
+        );
+
+        self.report_outcome(operator_id, object, Some(error.clone()));
+
+        let policy = &self.config.retry;
+        let attempts = self.dead_letters.record_failure(&object_ref);
+        if attempts >= policy.max_attempts {
+            warn!(
+                "Giving up on {} '{}/{}' for operator '{}' after {} attempts",
+                object_ref.kind, object_ref.namespace, object_ref.name, operator_id, attempts
+            );
+            metrics::increment(
+                "wasm_operator_dead_letters_total",
+                &[("operator", operator_id)],
+            );
+            self.dead_letters
+                .push(object_ref, event_type, object.clone(), error);
+        } else {
+            // Retries keep the reason of the original reconcile.
+            self.schedule_reconcile(
+                operator_id,
+                event_type,
+                reason,
+                object.clone(),
+                policy.backoff(attempts),
+            );
+        }
+    }
+
+    /// Writes the reconcile error, or its resolution, back onto the object if the
+    /// operator has error reporting enabled.
+    fn report_outcome(
+        &self,
+        operator_id: &str,
+        object: &kube::api::DynamicObject,
+        error: Option<String>,
+    ) {
+        let mode = match self.operator_metadata(operator_id) {
+            Some(metadata) if metadata.error_reporting != ErrorReporting::None => {
+                metadata.error_reporting
+            }
+            _ => return,
+        };
+        if !self.applies_writes(operator_id) {
+            return;
+        }
+
+        let kubernetes_service = self.kubernetes_service.clone();
+        let object = object.clone();
+        let operator_id = operator_id.to_string();
+        tokio::task::spawn_local(async move {
+            let result = match error {
+                Some(message) => {
+                    error_report::report_error(&kubernetes_service, mode, &object, &message).await
+                }
+                None => error_report::clear_error(&kubernetes_service, mode, &object).await,
+            };
+            if let Err(e) = result {
+                warn!(
+                    "Failed to report reconcile outcome on object for operator '{}': {}",
+                    operator_id, e
+                );
+            }
+        });
+    }
+
+    /// Queues a reconcile for the object after the given delay, in the work queue of the
+    /// operator, so it is merged with the events of the object and never runs at the same
+    /// time as another reconcile of it.
+    fn schedule_reconcile(
+        self: &Arc<Self>,
+        operator_id: &str,
+        event_type: bindings::local::operator::types::EventType,
+        reason: ReconcileReason,
+        object: kube::api::DynamicObject,
+        delay: Duration,
+    ) {
+        let runtime = self.clone();
+        let operator_id = operator_id.to_string();
+        tokio::task::spawn_local(async move {
+            tokio::time::sleep(delay).await;
+            runtime.requeue_event(
+                &operator_id,
+                WorkItem {
+                    event_type,
+                    reason,
+                    object,
+                },
+            );
+        });
+    }
+
+    /// Lists the writes intercepted from an operator in shadow mode, or `None` if there is
+    /// no such operator.
+    pub fn shadow_writes(&self, operator_id: &str) -> Option<Vec<ShadowWrite>> {
+        self.introspection
+            .get(operator_id)
+            .map(|introspection| introspection.lock().unwrap().shadow_writes())
+    }
+
+    /// Lists the objects an operator wrote since the parent started.
+    pub fn managed_objects(&self, operator_id: &str) -> Vec<ManagedObject> {
+        self.ownership.managed_by(operator_id)
+    }
+
+    /// Lists the operators that wrote an object since the parent started.
+    pub fn object_managers(&self, object: &ObjectKey) -> Vec<ManagedObject> {
+        self.ownership.managers_of(object)
+    }
+
+    /// Lists the objects of an operator that exhausted their retries.
+    pub fn dead_letters(&self, operator_id: &str) -> Vec<DeadLetter> {
+        self.dead_letters.list(operator_id)
+    }
+
+    /// Removes an object from the dead-letter queue and reconciles it again.
+    ///
+    /// Returns `false` if the object was not in the dead-letter queue.
+    pub fn retry_dead_letter(self: &Arc<Self>, object_ref: &ObjectRef) -> bool {
+        match self.dead_letters.take(object_ref) {
+            Some(letter) => {
+                info!(
+                    "Retrying dead letter {} '{}/{}' for operator '{}'",
+                    object_ref.kind, object_ref.namespace, object_ref.name, object_ref.operator
+                );
+                self.schedule_reconcile(
+                    &object_ref.operator,
+                    letter.event_type,
+                    triggered(ReconcileTrigger::Manual),
+                    letter.object,
+                    Duration::ZERO,
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Serializes an object for a guest, applying the configured size limit.
+    fn serialize_for_guest(
+        &self,
+        operator_id: &str,
+        object: &kube::api::DynamicObject,
+    ) -> Result<String> {
+        let limits = &self.config.size_limits;
+        let mut json = serde_json::to_string(object)?;
+
+        if json.len() > limits.max_resource_bytes
+            && limits.oversize_strategy == OversizeStrategy::StripMetadata
+        {
+            let mut stripped = object.clone();
+            stripped.metadata.managed_fields = None;
+            if let Some(annotations) = stripped.metadata.annotations.as_mut() {
+                annotations.remove(LAST_APPLIED_ANNOTATION);
+            }
+            json = serde_json::to_string(&stripped)?;
+        }
+
+        if json.len() > limits.max_resource_bytes {
+            metrics::increment(
+                "wasm_operator_oversized_payloads_total",
+                &[("operator", operator_id), ("direction", "inbound")],
+            );
+            anyhow::bail!(
+                "resource JSON of {} bytes exceeds the limit of {} bytes",
+                json.len(),
+                limits.max_resource_bytes
+            );
+        }
+
+        Ok(json)
+    }
+
+    /// Returns whether the object carries the configured paused annotation.
+    fn is_paused(&self, object: &kube::api::DynamicObject) -> bool {
+        object
+            .metadata
+            .annotations
+            .as_ref()
+            .and_then(|annotations| annotations.get(&self.config.paused_annotation))
+            .is_some_and(|value| value.eq_ignore_ascii_case("true"))
+    }
+
+    /// Forwards an HTTP request to the `handle-http` export of an operator, answering with
+    /// a 404 response if the operator does not export it. A busy operator is waited for,
+    /// and the request fails with `OperatorBusy` if it stays busy.
+    ///
+    /// Returns `None` if no operator with the given id is known.
+    pub async fn handle_http(
+        &self,
+        operator_id: &str,
+        request: bindings::local::operator::types::HttpRequest,
+    ) -> Result<Option<bindings::local::operator::types::HttpResponse>> {
+        if !self.introspection.contains_key(operator_id) {
+            return Ok(None);
+        }
+
+        let response = self
+            .with_operator_within(operator_id, MAX_BUSY_WAIT, |_, store| {
+                Box::pin(async move { handlers::handle_http(store, request).await })
+            })
+            .await?;
+
+        Ok(Some(response.unwrap_or_else(|| {
+            bindings::local::operator::types::HttpResponse {
+                status: 404,
+                headers: Vec::new(),
+                body: format!("Operator '{}' does not serve HTTP requests", operator_id)
+                    .into_bytes(),
+            }
+        })))
+    }
+
+    async fn idle_check_loop(&self, idle_threshold: Duration) {
         loop {
-            tokio::time::sleep(IDLE_THRESHOLD / 2).await;
+            self.watchdog.beat(IDLE_UNLOAD_SUBSYSTEM);
+            tokio::time::sleep(idle_threshold / 2).await;
 
             // Collect IDs of idle operators to avoid holding the map lock while unloading.
             let idle_ids: Vec<OperatorId> = self
@@ -241,7 +976,7 @@ impl WasmRuntime {
                 .iter()
                 .filter_map(|entry| {
                     if let OperatorState::Loaded { last_active, .. } = entry.value() {
-                        if last_active.elapsed() > IDLE_THRESHOLD {
+                        if last_active.elapsed() > idle_threshold {
                             Some(entry.key().clone())
                         } else {
                             None
@@ -261,6 +996,175 @@ impl WasmRuntime {
         }
     }
 
+    /// Delivers due timers to the `on-timer` export of their operators.
+    async fn timer_loop(self: Arc<Self>) {
+        loop {
+            for (operator_id, token) in self.timers.next_due().await {
+                let runtime = self.clone();
+                tokio::task::spawn_local(
+                    async move { runtime.fire_timer(&operator_id, token).await },
+                );
+            }
+        }
+    }
+
+    async fn fire_timer(&self, operator_id: &str, token: String) {
+        // The entry is taken out of the map while the operator handles a call, so try
+        // again once it is done.
+        if !self.operators.contains_key(operator_id) {
+            let _ = self.timers.schedule(operator_id, token, TIMER_BUSY_RETRY);
+            return;
+        }
+        debug!("Timer '{}' of operator '{}' is due", token, operator_id);
+        metrics::increment(
+            "wasm_operator_timers_fired_total",
+            &[("operator", operator_id)],
+        );
+        let result = self
+            .with_operator(operator_id, |_, store| {
+                Box::pin(async move { handlers::on_timer(store, token).await })
+            })
+            .await;
+        match result {
+            Ok(true) => {}
+            // Only possible if the component was upgraded to one without the export.
+            Ok(false) => warn!(
+                "Dropped a timer of operator '{}', which does not export on-timer",
+                operator_id
+            ),
+            Err(e) => warn!("Timer of operator '{}' failed: {:#}", operator_id, e),
+        }
+    }
+
+    /// Delivers published messages to the `on-message` export of the operators that
+    /// subscribe to their topic, other than the publisher.
+    async fn message_loop(self: Arc<Self>) {
+        loop {
+            for message in self.message_bus.next().await {
+                let message = Arc::new(message);
+                let subscribers: Vec<OperatorId> = self
+                    .introspection
+                    .iter()
+                    .filter(|entry| {
+                        entry.key() != &message.publisher
+                            && entry
+                                .value()
+                                .lock()
+                                .unwrap()
+                                .metadata()
+                                .subscriptions
+                                .contains(&message.topic)
+                    })
+                    .map(|entry| entry.key().clone())
+                    .collect();
+                metrics::increment(
+                    "wasm_operator_messages_published_total",
+                    &[("operator", &message.publisher), ("topic", &message.topic)],
+                );
+                for subscriber in subscribers {
+                    let runtime = self.clone();
+                    let message = message.clone();
+                    tokio::task::spawn_local(async move {
+                        runtime.deliver_message(&subscriber, message).await
+                    });
+                }
+            }
+        }
+    }
+
+    async fn deliver_message(&self, operator_id: &str, message: Arc<Message>) {
+        debug!(
+            "Delivering a message on topic '{}' from '{}' to operator '{}'",
+            message.topic, message.publisher, operator_id
+        );
+        let delivered = message.clone();
+        let result = self
+            .with_operator_within(operator_id, MAX_BUSY_WAIT, |_, store| {
+                Box::pin(async move {
+                    let (topic, payload) = (delivered.topic.clone(), delivered.payload.clone());
+                    handlers::on_message(store, topic, payload).await
+                })
+            })
+            .await;
+        match result {
+            Ok(true) => {}
+            Ok(false) => warn!(
+                "Dropped a message on topic '{}' for operator '{}', which does not export on-message",
+                message.topic, operator_id
+            ),
+            Err(e) if e.is::<OperatorBusy>() => warn!(
+                "Dropped a message on topic '{}' for operator '{}', which stayed busy for {:?}",
+                message.topic, operator_id, MAX_BUSY_WAIT
+            ),
+            Err(e) => warn!(
+                "Message on topic '{}' to operator '{}' failed: {:#}",
+                message.topic, operator_id, e
+            ),
+        }
+    }
+
+    /// Dispatches the reconciles operators enqueued once they are due.
+    async fn reconcile_queue_loop(self: Arc<Self>) {
+        loop {
+            for enqueued in self.reconcile_queue.next_due().await {
+                let runtime = self.clone();
+                tokio::task::spawn_local(async move { runtime.fire_enqueued(enqueued).await });
+            }
+        }
+    }
+
+    async fn fire_enqueued(self: &Arc<Self>, enqueued: EnqueuedReconcile) {
+        let EnqueuedReconcile {
+            operator,
+            object,
+            triggered_by,
+            requested_by,
+        } = enqueued;
+        if !self.introspection.contains_key(&operator) {
+            warn!(
+                "Operator '{}' requested a reconcile of unknown operator '{}'",
+                requested_by.as_deref().unwrap_or(&operator),
+                operator
+            );
+            return;
+        }
+        let found = self
+            .kubernetes_service
+            .find_object(&object.kind, &object.name, &object.namespace)
+            .await;
+        let object = match found {
+            Ok(Some(found)) => found,
+            Ok(None) => {
+                debug!(
+                    "Skipping enqueued reconcile of {} '{}/{}' for operator '{}', it no longer exists",
+                    object.kind, object.namespace, object.name, operator
+                );
+                return;
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to get {} '{}/{}' for an enqueued reconcile of operator '{}': {:#}",
+                    object.kind, object.namespace, object.name, operator, e
+                );
+                return;
+            }
+        };
+        let reason = ReconcileReason {
+            trigger: ReconcileTrigger::Dependency,
+            triggered_by,
+            continuation: None,
+            requested_by,
+        };
+        self.enqueue_event(
+            &operator,
+            WorkItem {
+                event_type: bindings::local::operator::types::EventType::Modified,
+                reason,
+                object,
+            },
+        );
+    }
+
     async fn unload_component(&self, id: &OperatorId) -> Result<()> {
         // Use remove-modify-insert pattern to avoid holding DashMap lock across .await
         if let Some((_id, mut op_state)) = self.operators.remove(id) {
@@ -271,22 +1175,48 @@ impl WasmRuntime {
                 ..
             } = &mut op_state
             {
+                if self.transition(id, LoadState::Unloading).is_err() {
+                    self.operators.insert(id.clone(), op_state);
+                    return Ok(());
+                }
+
+                // Surrender the leases and locks first so another holder can take over
+                // right away.
+                self.leases.release_all(id).await;
+                self.locks.release_all(id);
+
                 let mut store_guard = store.lock().await;
 
-                // 1. Ask the component to serialize its own state.
-                let memory_data = operator.call_serialize(&mut *store_guard).await?;
-                info!(
-                    "Serializing {} bytes of memory for operator {}",
-                    memory_data.len(),
-                    id
-                );
+                let written = async {
+                    // 1. Ask the component to serialize its own state.
+                    let memory_data = operator.call_serialize(&mut *store_guard).await?;
+                    info!(
+                        "Serializing {} bytes of memory for operator {}",
+                        memory_data.len(),
+                        id
+                    );
 
-                // 3. Write memory to a file asynchronously.
-                let state_path = PathBuf::from(format!("/tmp/wasm-state/{}.mem", id));
-                if let Some(parent) = state_path.parent() {
-                    tokio::fs::create_dir_all(parent).await?;
+                    // 3. Write memory to a file asynchronously.
+                    let state_path = self.config.state_dir.join(format!("{}.mem", id));
+                    snapshot::write(&state_path, &memory_data, metadata.snapshot_codec).await?;
+                    metrics::set_gauge(
+                        "wasm_operator_snapshot_bytes",
+                        &[("operator", id)],
+                        memory_data.len() as f64,
+                    );
+                    anyhow::Ok(state_path)
                 }
-                tokio::fs::write(&state_path, &memory_data).await?;
+                .await;
+                let state_path = match written {
+                    Ok(state_path) => state_path,
+                    Err(e) => {
+                        // Keep the operator running rather than losing it with its state.
+                        drop(store_guard);
+                        self.operators.insert(id.clone(), op_state);
+                        self.transition(id, LoadState::Loaded)?;
+                        return Err(e);
+                    }
+                };
 
                 // 4. Create the new Unloaded state.
                 let unloaded_state = OperatorState::Unloaded {
@@ -295,6 +1225,16 @@ impl WasmRuntime {
                 };
                 // 5. Insert the new state back into the map.
                 self.operators.insert(id.clone(), unloaded_state);
+                drop(store_guard);
+                if self.config.reuse_stores
+                    && let OperatorState::Loaded {
+                        store, metadata, ..
+                    } = op_state
+                {
+                    let state = store.into_inner().into_data();
+                    self.store_pool.put(&metadata, state.wasi_ctx);
+                }
+                self.transition(id, LoadState::Unloaded)?;
                 info!(
                     "Successfully unloaded operator {} to disk at {:?}",
                     id, &state_path
@@ -307,6 +1247,217 @@ impl WasmRuntime {
         Ok(())
     }
 
+    /// Replaces an operator that trapped with a fresh instance, because a trapped instance
+    /// cannot be entered again. State the guest did not persist is lost.
+    async fn replace_trapped_operator(&self, id: &str) {
+        let Some(metadata) = self.operator_metadata(id) else {
+            return;
+        };
+        if self.transition(id, LoadState::Loading).is_err() {
+            return;
+        }
+        let loaded = match self.new_instance(metadata.clone(), self.introspection_for(&metadata)) {
+            Ok(instance) => instance.load().await,
+            Err(e) => Err(e),
+        };
+        match loaded {
+            Ok((operator, store)) => {
+                self.operators.insert(
+                    id.to_string(),
+                    OperatorState::Loaded {
+                        operator: Box::new(operator),
+                        store: Mutex::new(store),
+                        last_active: Instant::now(),
+                        metadata,
+                    },
+                );
+                let _ = self.transition(id, LoadState::Loaded);
+            }
+            Err(e) => {
+                // The trapped instance cannot be entered again.
+                let reason = format!("Failed to replace the trapped instance: {:#}", e);
+                self.degrade(id, &reason).await;
+            }
+        }
+    }
+
+    fn new_instance(
+        &self,
+        metadata: WasmComponentMetadata,
+        introspection: SharedIntrospection,
+    ) -> Result<WasmInstance> {
+        let engine = self.engines.for_component(&metadata)?;
+        let pre = match self.config.snapshot_strategy {
+            SnapshotStrategy::Serialize => None,
+            SnapshotStrategy::PreInit => Some(match self.instance_pres.get(&metadata.name) {
+                Some(pre) => pre.clone(),
+                None => {
+                    let extensions = extensions::enabled(&self.config.extensions)?;
+                    let pre = WasmInstance::prepare(&engine, &metadata, &extensions)?;
+                    self.instance_pres
+                        .insert(metadata.name.clone(), pre.clone());
+                    pre
+                }
+            }),
+        };
+        let instance = WasmInstance::new(
+            engine,
+            self.kubernetes_service.clone(),
+            self.leases.clone(),
+            self.locks.clone(),
+            self.config.clone(),
+            metadata,
+            introspection,
+        )
+        .with_timers(self.timers.clone())
+        .with_reconcile_queue(self.reconcile_queue.clone())
+        .with_ownership(self.ownership.clone())
+        .with_message_bus(self.message_bus.clone())
+        .with_store_pool(self.store_pool.clone());
+        Ok(match pre {
+            Some(pre) => instance.with_pre(pre),
+            None => instance,
+        })
+    }
+
+    /// Records a finished reconcile, and its error if it failed, for debug bundles.
+    fn record_reconcile(
+        &self,
+        operator_id: &str,
+        event_type: bindings::local::operator::types::EventType,
+        object: &kube::api::DynamicObject,
+        duration: Duration,
+        outcome: &Result<bindings::local::operator::types::ReconcileResult>,
+    ) {
+        use bindings::local::operator::types::ReconcileResult;
+
+        let Some(introspection) = self.introspection.get(operator_id) else {
+            return;
+        };
+        let (summary, error) = match outcome {
+            Ok(ReconcileResult::Ok) => ("ok".to_string(), None),
+            Ok(ReconcileResult::Requeue(seconds)) => (format!("requeue({})", seconds), None),
+            Ok(ReconcileResult::Continue(_)) => ("continue".to_string(), None),
+            Ok(ReconcileResult::Error(message)) => ("error".to_string(), Some(message.clone())),
+            // The debug format includes the cause chain and the backtraces.
+            Err(e) => ("error".to_string(), Some(format!("{:?}", e))),
+        };
+        let object_ref = ObjectRef::new(operator_id, object);
+        let timestamp_ms = now_ms();
+
+        let mut introspection = introspection.lock().unwrap();
+        introspection.record_reconcile(ReconcileRecord {
+            timestamp_ms,
+            event: format!("{:?}", event_type),
+            kind: object_ref.kind.clone(),
+            namespace: object_ref.namespace.clone(),
+            name: object_ref.name.clone(),
+            duration_ms: duration.as_millis() as u64,
+            outcome: summary,
+        });
+        if let Some(error) = error {
+            introspection.record_error(ErrorRecord {
+                timestamp_ms,
+                kind: object_ref.kind,
+                namespace: object_ref.namespace,
+                name: object_ref.name,
+                error,
+            });
+        }
+    }
+
+    /// Whether the runtime writes to the objects of the operator on its behalf, which it
+    /// does not in read-only mode or for operators in shadow mode.
+    fn applies_writes(&self, operator_id: &str) -> bool {
+        !self.config.read_only
+            && !self
+                .operator_metadata(operator_id)
+                .is_some_and(|metadata| metadata.shadow)
+    }
+
+    /// Remembers the JSON passed to an operator with `old-objects` for an object, and
+    /// returns the JSON it was passed before on `modified` events.
+    fn remember_object(
+        &self,
+        operator_id: &str,
+        event_type: bindings::local::operator::types::EventType,
+        object: &kube::api::DynamicObject,
+        resource_json: &str,
+    ) -> Option<String> {
+        use bindings::local::operator::types::EventType;
+
+        if !self
+            .operator_metadata(operator_id)
+            .is_some_and(|metadata| metadata.old_objects)
+        {
+            return None;
+        }
+        let object_ref = ObjectRef::new(operator_id, object);
+        if matches!(event_type, EventType::Deleted) {
+            self.old_objects.remove(&object_ref);
+            return None;
+        }
+        // Operators that read objects lazily get no JSON to keep.
+        let json = if resource_json.is_empty() {
+            serde_json::to_string(object).ok()?
+        } else {
+            resource_json.to_string()
+        };
+        let previous = self.old_objects.insert(object_ref, json);
+        previous.filter(|_| matches!(event_type, EventType::Modified))
+    }
+
+    /// Returns the finalizer the operator declared for the kind of the object.
+    fn finalizer_for(
+        &self,
+        operator_id: &str,
+        object: &kube::api::DynamicObject,
+    ) -> Option<String> {
+        let kind = &object.types.as_ref()?.kind;
+        self.finalizers
+            .get(&(operator_id.to_string(), kind.clone()))
+            .map(|finalizer| finalizer.clone())
+    }
+
+    fn operator_metadata(&self, id: &str) -> Option<WasmComponentMetadata> {
+        self.introspection
+            .get(id)
+            .map(|introspection| introspection.lock().unwrap().metadata().clone())
+    }
+
+    /// Counts a watch that was re-established, by why.
+    fn record_watch_restart(&self, operator_id: &str, kind: &str, reason: &str) {
+        metrics::increment(
+            "wasm_operator_watch_restarts_total",
+            &[
+                ("operator", operator_id),
+                ("kind", kind),
+                ("reason", reason),
+            ],
+        );
+    }
+
+    /// Returns whether an operator was taken out of service after a panic.
+    fn is_degraded(&self, operator_id: &str) -> bool {
+        matches!(
+            self.operators.get(operator_id).as_deref(),
+            Some(OperatorState::Degraded { .. })
+        )
+    }
+
+    /// Returns whether the namespace is excluded, globally or for the given operator.
+    fn is_namespace_excluded(&self, operator_id: &str, namespace: &str) -> bool {
+        self.operator_metadata(operator_id)
+            .is_some_and(|metadata| self.config.is_namespace_excluded(namespace, &metadata))
+    }
+
+    fn introspection_for(&self, metadata: &WasmComponentMetadata) -> SharedIntrospection {
+        self.introspection
+            .entry(metadata.name.clone())
+            .or_insert_with(|| OperatorIntrospection::new(metadata.clone()))
+            .clone()
+    }
+
     async fn with_operator<F, T>(&self, id: &str, f: F) -> Result<T>
     where
         for<'a> F: FnOnce(
@@ -314,29 +1465,150 @@ impl WasmRuntime {
             &'a mut Store<State>,
         ) -> Pin<Box<dyn Future<Output = Result<T>> + 'a>>,
     {
-        // Use remove-modify-insert pattern to avoid holding DashMap lock across .await
-        let mut op_state = self.operators.remove(id).unwrap().1;
+        self.with_operator_within(id, Duration::ZERO, f).await
+    }
+
+    /// Like `with_operator`, but waits up to `max_wait` for the operator to finish the call
+    /// it is handling, failing with `OperatorBusy` if it does not.
+    async fn with_operator_within<F, T>(&self, id: &str, max_wait: Duration, f: F) -> Result<T>
+    where
+        for<'a> F: FnOnce(
+            &'a bindings::KubeOperator,
+            &'a mut Store<State>,
+        ) -> Pin<Box<dyn Future<Output = Result<T>> + 'a>>,
+    {
+        self.wait_for_prewarm(id).await;
+        // Use remove-modify-insert pattern to avoid holding DashMap lock across .await.
+        // The entry is out of the map while the operator handles a call.
+        let deadline = Instant::now() + max_wait;
+        let op_state = loop {
+            if let Some((_, op_state)) = self.operators.remove(id) {
+                break op_state;
+            }
+            if !self.introspection.contains_key(id) {
+                return Err(anyhow!("Operator {} is not running", id));
+            }
+            if Instant::now() >= deadline {
+                return Err(OperatorBusy(id.to_string()).into());
+            }
+            tokio::time::sleep(TIMER_BUSY_RETRY).await;
+        };
 
-        let result: Result<T>;
+        if let OperatorState::Degraded { reason } = &op_state {
+            let error = anyhow!("Operator {} is degraded: {}", id, reason);
+            self.operators.insert(id.to_string(), op_state);
+            return Err(error);
+        }
 
-        if let OperatorState::Unloaded {
-            state_path,
-            metadata,
-        } = op_state
+        // A panic in the host while serving the operator takes down only this operator.
+        match AssertUnwindSafe(self.enter_operator(id, op_state, f))
+            .catch_unwind()
+            .await
         {
-            info!("Reloading operator {} from disk...", id);
+            Ok((op_state, result)) => {
+                let unload = self.serve_lifecycle_requests(id, &op_state).await;
+                // Insert the (potentially updated) state back into the map.
+                self.operators.insert(id.to_string(), op_state);
+                if unload && let Err(e) = self.unload_component(&id.to_string()).await {
+                    warn!("Failed to unload operator {} on its request: {:#}", id, e);
+                }
+                result
+            }
+            Err(panic) => {
+                let reason = panic_message(panic.as_ref());
+                self.degrade_after_panic(id, &reason).await;
+                Err(anyhow!("Operator {} panicked: {}", id, reason))
+            }
+        }
+    }
 
+    /// Runs `f` on the operator, reloading it from disk first if it is unloaded, and
+    /// returns the new state of the operator along with the result.
+    async fn enter_operator<F, T>(
+        &self,
+        id: &str,
+        mut op_state: OperatorState,
+        f: F,
+    ) -> (OperatorState, Result<T>)
+    where
+        for<'a> F: FnOnce(
+            &'a bindings::KubeOperator,
+            &'a mut Store<State>,
+        ) -> Pin<Box<dyn Future<Output = Result<T>> + 'a>>,
+    {
+        match op_state {
+            OperatorState::Unloaded {
+                state_path,
+                metadata,
+            } => {
+                let (operator, mut store) =
+                    match self.reload_operator(id, &state_path, &metadata, None).await {
+                        Ok(reloaded) => reloaded,
+                        Err(e) => {
+                            let op_state = OperatorState::Unloaded {
+                                state_path,
+                                metadata,
+                            };
+                            return (op_state, Err(e));
+                        }
+                    };
+
+                // Call the closure with the new operator and store.
+                let result = f(&operator, &mut store).await;
+
+                // Update the state to Loaded.
+                let op_state = OperatorState::Loaded {
+                    operator: Box::new(operator),
+                    store: Mutex::new(store),
+                    last_active: Instant::now(),
+                    metadata,
+                };
+                (op_state, result)
+            }
+            OperatorState::Loaded {
+                ref operator,
+                ref store,
+                ref mut last_active,
+                ..
+            } => {
+                *last_active = Instant::now();
+                let result = {
+                    let mut store_guard = store.lock().await;
+                    f(operator, &mut store_guard).await
+                };
+                (op_state, result)
+            }
+            OperatorState::Degraded { .. } => {
+                (op_state, Err(anyhow!("Operator {} is degraded", id)))
+            }
+        }
+    }
+
+    /// Instantiates an unloaded operator and restores its state from disk, using the
+    /// compiled component if one is given.
+    async fn reload_operator(
+        &self,
+        id: &str,
+        state_path: &Path,
+        metadata: &WasmComponentMetadata,
+        pre: Option<bindings::KubeOperatorPre<State>>,
+    ) -> Result<(bindings::KubeOperator, Store<State>)> {
+        info!("Reloading operator {} from disk...", id);
+        self.transition(id, LoadState::Loading)?;
+
+        let reloaded = async {
             // 1. Load the original component and instantiate it.
-            let wasm_instance = WasmInstance::new(
-                self.engine.clone(),
-                self.kubernetes_service.clone(),
-                metadata.clone(),
-            );
+            let started = Instant::now();
+            let mut wasm_instance =
+                self.new_instance(metadata.clone(), self.introspection_for(metadata))?;
+            if let Some(pre) = pre {
+                wasm_instance = wasm_instance.with_pre(pre);
+            }
             let (operator, mut store) = wasm_instance.load().await?;
 
             // 2. Read the saved state from disk asynchronously.
-            info!("Reading saved state from {:?}", &state_path);
-            let saved_state = tokio::fs::read(&state_path).await?;
+            info!("Reading saved state from {:?}", state_path);
+            let saved_state = snapshot::read(state_path).await?;
             info!(
                 "Read {} bytes of saved state for operator {}",
                 saved_state.len(),
@@ -346,36 +1618,86 @@ impl WasmRuntime {
             // 3. Ask the new component instance to deserialize the state.
             operator.call_deserialize(&mut store, &saved_state).await?;
             info!("Successfully restored memory state for operator {}", id);
+            metrics::set_gauge(
+                "wasm_operator_reload_seconds",
+                &[("operator", id)],
+                started.elapsed().as_secs_f64(),
+            );
+            anyhow::Ok((operator, store))
+        }
+        .await;
 
-            // 5. Call the closure with the new operator and store.
-            result = f(&operator, &mut store).await;
-
-            // 6. Update the state to Loaded.
-            op_state = OperatorState::Loaded {
-                operator,
-                store: Mutex::new(store),
-                last_active: Instant::now(),
-                metadata,
-            };
-        } else if let OperatorState::Loaded {
-            operator,
-            store,
-            last_active,
-            ..
-        } = &mut op_state
-        {
-            *last_active = Instant::now();
-            let mut store_guard = store.lock().await;
-            result = f(operator, &mut store_guard).await;
-        } else {
-            // This case should not be reached with the current enum definition.
-            // We add a panic to make the compiler happy that `result` is always initialized.
-            panic!("Unexpected operator state");
+        // The callers put a reloaded operator back as loaded, and one that failed to
+        // reload as unloaded.
+        match reloaded {
+            Ok(reloaded) => {
+                self.transition(id, LoadState::Loaded)?;
+                Ok(reloaded)
+            }
+            Err(e) => {
+                let _ = self.transition(id, LoadState::Unloaded);
+                Err(e)
+            }
         }
+    }
 
-        // Insert the (potentially updated) state back into the map.
-        self.operators.insert(id.to_string(), op_state);
+    /// Takes an operator out of service after a panic in the host.
+    async fn degrade_after_panic(&self, id: &str, reason: &str) {
+        error!("Operator {} panicked: {}", id, reason);
+        metrics::increment("wasm_operator_panics_total", &[("operator", id)]);
+        self.degrade(id, reason).await;
+    }
+
+    /// Takes an operator out of service. It stays degraded until the parent restarts, and
+    /// its leases and locks are released.
+    async fn degrade(&self, id: &str, reason: &str) {
+        error!("Operator {} is now degraded: {}", id, reason);
+        self.operators.insert(
+            id.to_string(),
+            OperatorState::Degraded {
+                reason: reason.to_string(),
+            },
+        );
+        let _ = self.transition(id, LoadState::Degraded);
+        self.leases.release_all(id).await;
+        self.locks.release_all(id);
+        self.timers.cancel_all(id);
+        self.reconcile_queue.cancel_all(id);
+    }
+}
 
-        result
+/// Returns a reconcile reason without a triggering secondary object.
+fn triggered(trigger: ReconcileTrigger) -> ReconcileReason {
+    ReconcileReason {
+        trigger,
+        triggered_by: None,
+        continuation: None,
+        requested_by: None,
     }
 }
+
+/// Returns the message of a caught panic.
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// Returns the object a reconcile runs for, with a controller reference to it, or `None`
+/// if the object lacks the type metadata or UID to be referenced.
+fn reconcile_target(object: &kube::api::DynamicObject) -> Option<ReconcileTarget> {
+    let types = object.types.as_ref()?;
+    Some(ReconcileTarget {
+        owner: OwnerReference {
+            api_version: types.api_version.clone(),
+            kind: types.kind.clone(),
+            name: object.metadata.name.clone()?,
+            uid: object.metadata.uid.clone()?,
+            controller: Some(true),
+            block_owner_deletion: Some(true),
+        },
+        namespace: object.metadata.namespace.clone().unwrap_or_default(),
+    })
+}
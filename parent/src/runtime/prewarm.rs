@@ -0,0 +1,178 @@
+//! # Prewarm Module
+//!
+//! This module reloads unloaded operators in the background when watch events pile up for
+//! them, to smooth the latency spike after a quiet period in which many operators were
+//! unloaded. Normally an operator is reloaded by the first event that reaches it, and its
+//! component is compiled on the thread that dispatches events, so operators woken at the
+//! same time reload one after the other. With `prewarm-threshold` set, once that many watch
+//! events arrived for an unloaded operator, it is taken out of the map and reloaded before
+//! the events are dispatched. The component is compiled on the blocking thread pool, so up
+//! to `prewarm-concurrency` operators reload in parallel. Calls for an operator that is
+//! being prewarmed wait for it instead of failing as busy.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use dashmap::{DashMap, DashSet};
+use tokio::sync::{Mutex, Semaphore};
+use tracing::{info, warn};
+use wasmtime::Store;
+
+use super::instance::WasmInstance;
+use super::{OperatorState, WasmRuntime, TIMER_BUSY_RETRY};
+use crate::config::metadata::WasmComponentMetadata;
+use crate::config::runtime::SnapshotStrategy;
+use crate::host::api::bindings;
+use crate::host::extensions;
+use crate::host::state::State;
+use crate::metrics;
+
+/// How long a call waits for an operator that is being prewarmed.
+const PREWARM_WAIT: Duration = Duration::from_secs(60);
+
+/// Tracks the event pressure on unloaded operators and the reloads it started.
+pub struct Prewarmer {
+    /// Watch events received for each unloaded operator since it was last loaded.
+    pressure: DashMap<String, usize>,
+    /// Operators being reloaded in the background.
+    in_progress: DashSet<String>,
+    permits: Semaphore,
+}
+
+impl Prewarmer {
+    pub fn new(concurrency: usize) -> Self {
+        Self {
+            pressure: DashMap::new(),
+            in_progress: DashSet::new(),
+            permits: Semaphore::new(concurrency.max(1)),
+        }
+    }
+
+    /// Forgets the pressure on an operator once it is loaded.
+    pub fn reset(&self, id: &str) {
+        self.pressure.remove(id);
+    }
+}
+
+impl WasmRuntime {
+    /// Counts a watch event for an operator, and starts reloading it in the background once
+    /// enough events arrived while it is unloaded.
+    pub(super) fn note_watch_event(self: &Arc<Self>, id: &str) {
+        let Some(threshold) = self.config.prewarm_threshold else {
+            return;
+        };
+        if !matches!(
+            self.operators.get(id).as_deref(),
+            Some(OperatorState::Unloaded { .. })
+        ) {
+            return;
+        }
+        let pressure = {
+            let mut pressure = self.prewarmer.pressure.entry(id.to_string()).or_default();
+            *pressure += 1;
+            *pressure
+        };
+        if pressure < threshold {
+            return;
+        }
+        // Take the operator out right away, so the event that follows waits for the reload
+        // instead of starting another one.
+        let Some((
+            _,
+            OperatorState::Unloaded {
+                state_path,
+                metadata,
+            },
+        )) = self.operators.remove_if(id, |_, state| {
+            matches!(state, OperatorState::Unloaded { .. })
+        })
+        else {
+            return;
+        };
+        self.prewarmer.in_progress.insert(id.to_string());
+        metrics::increment("wasm_operator_prewarms_total", &[("operator", id)]);
+        tokio::task::spawn_local(self.clone().prewarm(id.to_string(), state_path, metadata));
+    }
+
+    async fn prewarm(
+        self: Arc<Self>,
+        id: String,
+        state_path: PathBuf,
+        metadata: WasmComponentMetadata,
+    ) {
+        let _permit = self
+            .prewarmer
+            .permits
+            .acquire()
+            .await
+            .expect("the prewarm semaphore is never closed");
+        info!(
+            "Prewarming operator {} after {} pending event(s)",
+            id,
+            self.prewarmer.pressure.get(&id).map_or(0, |p| *p)
+        );
+        let op_state = match self.compile_and_reload(&id, &state_path, &metadata).await {
+            Ok((operator, store)) => OperatorState::Loaded {
+                operator: Box::new(operator),
+                store: Mutex::new(store),
+                last_active: Instant::now(),
+                metadata,
+            },
+            Err(e) => {
+                warn!("Failed to prewarm operator {}: {:#}", id, e);
+                OperatorState::Unloaded {
+                    state_path,
+                    metadata,
+                }
+            }
+        };
+        self.operators.insert(id.clone(), op_state);
+        self.prewarmer.in_progress.remove(&id);
+    }
+
+    /// Reloads an operator like a call would, but compiles its component on the blocking
+    /// thread pool.
+    async fn compile_and_reload(
+        &self,
+        id: &str,
+        state_path: &Path,
+        metadata: &WasmComponentMetadata,
+    ) -> Result<(bindings::KubeOperator, Store<State>)> {
+        let cached = self
+            .instance_pres
+            .get(&metadata.name)
+            .map(|pre| pre.clone());
+        let pre = match cached {
+            Some(pre) => pre,
+            None => {
+                let engine = self.engines.for_component(metadata)?;
+                let names = self.config.extensions.clone();
+                let component = metadata.clone();
+                // Compiling is the costly part of a reload.
+                let pre = tokio::task::spawn_blocking(move || {
+                    let extensions = extensions::enabled(&names)?;
+                    WasmInstance::prepare(&engine, &component, &extensions)
+                })
+                .await??;
+                if matches!(self.config.snapshot_strategy, SnapshotStrategy::PreInit) {
+                    self.instance_pres
+                        .insert(metadata.name.clone(), pre.clone());
+                }
+                pre
+            }
+        };
+        self.reload_operator(id, state_path, metadata, Some(pre))
+            .await
+    }
+
+    /// Waits until an operator that is being prewarmed is put back, for at most
+    /// `PREWARM_WAIT`.
+    pub(super) async fn wait_for_prewarm(&self, id: &str) {
+        let deadline = Instant::now() + PREWARM_WAIT;
+        while self.prewarmer.in_progress.contains(id) && Instant::now() < deadline {
+            tokio::time::sleep(TIMER_BUSY_RETRY).await;
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! # Reconcilers Module
+//!
+//! This module runs the reconciles of operators with `max-concurrent-reconciles` above one
+//! in parallel. An instance of a component runs one call at a time, so the reconciles that
+//! arrive while the operator is busy run on extra instances of its component, up to one
+//! less than the limit. Extra instances are created when they are first needed, start
+//! without the state the operator keeps in memory, which is why the limit is only accepted
+//! from operators whose metadata declares them `stateless`, and are dropped when the
+//! operator leaves the `loaded` state or when a call on them fails, since a trapped
+//! instance cannot be called again. Only the operator itself serves timers, messages and
+//! HTTP requests, and only its state is serialized when it is unloaded.
+//!
+//! Each extra instance takes locks under its own name, `<operator>#<n>`, so a lock taken in
+//! one reconcile makes the parallel reconciles of the same operator wait for it; the locks
+//! of an instance that is dropped after a failed call are released. Leases stay keyed by
+//! the operator: they elect one parent replica to run the operator, and all instances of
+//! the operator in that replica act on its behalf. A `checkpoint` or `request-unload` call
+//! on an extra instance is forwarded to the operator and served once it is idle, as if the
+//! operator had made it, so the checkpoint holds the state of the operator.
+
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use dashmap::DashMap;
+use futures::FutureExt;
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+use wasmtime::Store;
+
+use super::instance::WasmInstance;
+use super::{panic_message, WasmRuntime, MAX_BUSY_WAIT};
+use crate::host::api::bindings;
+use crate::host::api::bindings::local::operator::types::LoadState;
+use crate::host::extensions;
+use crate::host::state::{LifecycleRequests, State};
+use crate::metrics;
+
+/// The extra instances of one operator.
+struct Pool {
+    /// Limits the extra instances that exist at the same time.
+    slots: Arc<Semaphore>,
+    idle: Mutex<Vec<(bindings::KubeOperator, Store<State>)>>,
+    /// The compiled component, so only the first extra instance compiles it.
+    pre: Mutex<Option<bindings::KubeOperatorPre<State>>>,
+}
+
+/// The extra instances of the operators, by operator.
+#[derive(Default)]
+pub struct Reconcilers {
+    pools: DashMap<String, Arc<Pool>>,
+    /// The lifecycle requests extra instances made, until the operator serves them.
+    requests: DashMap<String, LifecycleRequests>,
+    /// Numbers the extra instances for their lock holder names, never reusing a number
+    /// while an instance of a cleared pool may still be running.
+    created: AtomicUsize,
+}
+
+impl Reconcilers {
+    fn pool(&self, id: &str, extra: usize) -> Arc<Pool> {
+        self.pools
+            .entry(id.to_string())
+            .or_insert_with(|| {
+                Arc::new(Pool {
+                    slots: Arc::new(Semaphore::new(extra)),
+                    idle: Mutex::default(),
+                    pre: Mutex::default(),
+                })
+            })
+            .clone()
+    }
+
+    /// Drops the extra instances of an operator and the requests they made. Instances in
+    /// use are dropped once their call returns.
+    pub fn clear(&self, id: &str) {
+        self.pools.remove(id);
+        self.requests.remove(id);
+    }
+
+    /// Takes the lifecycle requests the extra instances of an operator made.
+    pub fn take_requests(&self, id: &str) -> LifecycleRequests {
+        self.requests
+            .remove(id)
+            .map(|(_, requests)| requests)
+            .unwrap_or_default()
+    }
+
+    fn forward(&self, id: &str, requests: LifecycleRequests) {
+        self.requests
+            .entry(id.to_string())
+            .or_default()
+            .merge(requests);
+    }
+}
+
+/// Whether a reconcile runs on an extra instance: only if the operator is loaded but busy
+/// and may have extra instances. An operator that is not loaded is loaded by the reconcile.
+fn runs_on_extra_instance(extra: usize, busy: bool, state: Option<LoadState>) -> bool {
+    extra > 0 && busy && matches!(state, Some(LoadState::Loaded))
+}
+
+impl WasmRuntime {
+    /// Runs a reconcile on the operator, or on an extra instance of its component if the
+    /// operator is busy and may reconcile more than one object at a time. Otherwise a busy
+    /// operator is waited for, so a reconcile does not fail because a timer, message or
+    /// HTTP request is being handled. Also returns whether it ran on an extra instance.
+    pub(super) async fn with_reconciler<F, T>(&self, id: &str, f: F) -> (Result<T>, bool)
+    where
+        for<'a> F: FnOnce(
+            &'a bindings::KubeOperator,
+            &'a mut Store<State>,
+        ) -> Pin<Box<dyn Future<Output = Result<T>> + 'a>>,
+    {
+        let extra = self.operator_metadata(id).map_or(0, |metadata| {
+            metadata.max_concurrent_reconciles.saturating_sub(1)
+        });
+        // The operator is out of the map while it serves a call.
+        let busy = !self.operators.contains_key(id);
+        if !runs_on_extra_instance(extra, busy, self.lifecycle.state(id)) {
+            return (self.with_operator_within(id, MAX_BUSY_WAIT, f).await, false);
+        }
+
+        let pool = self.reconcilers.pool(id, extra);
+        let _slot = pool
+            .slots
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the slots are never closed");
+        let idle = pool.idle.lock().unwrap().pop();
+        let (operator, mut store) = match idle {
+            Some(instance) => instance,
+            None => match self.new_reconciler(id, &pool).await {
+                Ok(instance) => instance,
+                Err(e) => return (Err(e), true),
+            },
+        };
+
+        let result = match AssertUnwindSafe(f(&operator, &mut store))
+            .catch_unwind()
+            .await
+        {
+            Ok(result) => result,
+            Err(panic) => {
+                let reason = panic_message(panic.as_ref());
+                self.degrade_after_panic(id, &reason).await;
+                return (Err(anyhow!("Operator {} panicked: {}", id, reason)), true);
+            }
+        };
+        let requests = std::mem::take(&mut store.data_mut().lifecycle_requests);
+        if result.is_ok() {
+            pool.idle.lock().unwrap().push((operator, store));
+        } else {
+            self.locks.release_all(&store.data().lock_holder);
+        }
+        if !requests.is_empty() {
+            self.serve_forwarded_requests(id, requests).await;
+        }
+        (result, true)
+    }
+
+    /// Hands the lifecycle requests of an extra instance to the operator. They are served
+    /// when its current call returns, or right away if it is idle.
+    async fn serve_forwarded_requests(&self, id: &str, requests: LifecycleRequests) {
+        self.reconcilers.forward(id, requests);
+        // Forward before checking, so a call that returns in between serves them.
+        let idle = self.operators.contains_key(id);
+        if idle && matches!(self.lifecycle.state(id), Some(LoadState::Loaded)) {
+            let served = self
+                .with_operator(id, |_, _| Box::pin(async { Ok(()) }))
+                .await;
+            if let Err(e) = served {
+                warn!("Failed to serve the requests of operator {}: {:#}", id, e);
+            }
+        }
+    }
+
+    /// Creates an extra instance of the component of an operator.
+    async fn new_reconciler(
+        &self,
+        id: &str,
+        pool: &Pool,
+    ) -> Result<(bindings::KubeOperator, Store<State>)> {
+        let metadata = self
+            .operator_metadata(id)
+            .ok_or_else(|| anyhow!("Operator {} is not running", id))?;
+        let introspection = self
+            .introspection
+            .get(id)
+            .map(|introspection| introspection.clone())
+            .ok_or_else(|| anyhow!("Operator {} is not running", id))?;
+        let kept = pool.pre.lock().unwrap().clone();
+        let pre = match kept {
+            Some(pre) => pre,
+            None => {
+                let engine = self.engines.for_component(&metadata)?;
+                let extensions = extensions::enabled(&self.config.extensions)?;
+                let pre = WasmInstance::prepare(&engine, &metadata, &extensions)?;
+                *pool.pre.lock().unwrap() = Some(pre.clone());
+                pre
+            }
+        };
+        info!("Starting an extra instance of operator {}", id);
+        metrics::increment("wasm_operator_extra_instances_total", &[("operator", id)]);
+        let (operator, mut store) = self
+            .new_instance(metadata, introspection)?
+            .with_pre(pre)
+            .load()
+            .await?;
+        let n = self.reconcilers.created.fetch_add(1, Ordering::Relaxed) + 1;
+        store.data_mut().lock_holder = format!("{}#{}", id, n);
+        Ok((operator, store))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extra_instances_only_serve_a_busy_loaded_operator() {
+        assert!(runs_on_extra_instance(1, true, Some(LoadState::Loaded)));
+        assert!(!runs_on_extra_instance(1, false, Some(LoadState::Loaded)));
+        assert!(!runs_on_extra_instance(0, true, Some(LoadState::Loaded)));
+        assert!(!runs_on_extra_instance(1, true, Some(LoadState::Unloaded)));
+        assert!(!runs_on_extra_instance(1, true, Some(LoadState::Loading)));
+        assert!(!runs_on_extra_instance(1, true, None));
+    }
+}
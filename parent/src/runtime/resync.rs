@@ -0,0 +1,87 @@
+//! # Resync Module
+//!
+//! This module reconciles all the objects an operator watches again on request, to recover
+//! from a bug that made it miss or mishandle events without restarting the parent. The
+//! objects are listed from the API server, which also picks up changes the watches may
+//! have missed, and queued one at a time at a limited rate so a resync of a large
+//! operator does not starve the others or flood the API server with its writes. They go
+//! through the work queue of the operator, so an object that is already waiting is
+//! reconciled once.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use kube::api::DynamicObject;
+use tracing::info;
+
+use super::work_queue::WorkItem;
+use super::{triggered, WasmRuntime};
+use crate::host::api::bindings::local::operator::types::{EventType, ReconcileTrigger};
+
+/// Objects reconciled per second by a resync, unless another rate is given.
+pub const DEFAULT_RESYNC_RATE: u32 = 10;
+
+/// What a resync request did.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ResyncOutcome {
+    /// The resync started and will reconcile this many objects.
+    Started(usize),
+    NotFound,
+    /// A resync of the operator is still running.
+    InProgress,
+}
+
+impl WasmRuntime {
+    /// Lists the objects the operator watches and reconciles them in the background, at
+    /// most `rate` per second.
+    pub async fn resync(self: &Arc<Self>, operator_id: &str, rate: u32) -> Result<ResyncOutcome> {
+        let Some(introspection) = self.introspection.get(operator_id).map(|i| i.clone()) else {
+            return Ok(ResyncOutcome::NotFound);
+        };
+        if !self.resyncing.insert(operator_id.to_string()) {
+            return Ok(ResyncOutcome::InProgress);
+        }
+        let watches = introspection.lock().unwrap().watches().to_vec();
+        let mut objects: Vec<DynamicObject> = Vec::new();
+        for watch in &watches {
+            match self
+                .kubernetes_service
+                .list_objects(&watch.kind, &watch.namespace)
+                .await
+            {
+                Ok(listed) => objects.extend(listed),
+                Err(e) => {
+                    self.resyncing.remove(operator_id);
+                    return Err(e);
+                }
+            }
+        }
+
+        let count = objects.len();
+        info!(
+            "Resyncing {} object(s) of operator '{}' at {} per second",
+            count, operator_id, rate
+        );
+        let interval = Duration::from_secs(1) / rate.max(1);
+        let runtime = self.clone();
+        let operator_id = operator_id.to_string();
+        tokio::task::spawn_local(async move {
+            let mut ticks = tokio::time::interval(interval);
+            for object in objects {
+                ticks.tick().await;
+                runtime.enqueue_event(
+                    &operator_id,
+                    WorkItem {
+                        event_type: EventType::Resynced,
+                        reason: triggered(ReconcileTrigger::Resync),
+                        object,
+                    },
+                );
+            }
+            runtime.resyncing.remove(&operator_id);
+            info!("Resync of operator '{}' finished", operator_id);
+        });
+        Ok(ResyncOutcome::Started(count))
+    }
+}
@@ -0,0 +1,111 @@
+//! # Signals Module
+//!
+//! This module lets systems outside the parent wake operators, for operators whose work is
+//! driven by something other than changes to the objects they watch, such as a CI system
+//! or a message queue. A signal is published on the message bus like a message from
+//! another operator, so it reaches the `on-message` export of the operators that subscribe
+//! to its topic and loads them if they are unloaded. Signals come from two sources:
+//!
+//! - `POST /signals/{topic}` on the admin API, with the payload as the body. A bridge that
+//!   consumes an external queue can forward its messages this way.
+//! - ConfigMaps in the namespace of the parent labelled `operator.wasm/signal=<topic>`,
+//!   when `signal-config-maps` is set. Every change to such a ConfigMap publishes its
+//!   `payload` key, so `kubectl` and GitOps tools can signal operators.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::StreamExt;
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::runtime::watcher::{self, Event};
+use kube::{Api, ResourceExt};
+use tracing::{debug, info, warn};
+
+use super::WasmRuntime;
+use crate::host::message_bus::{Message, MAX_PAYLOAD_BYTES};
+
+/// Label of the ConfigMaps that signal operators, set to the topic.
+pub const SIGNAL_LABEL: &str = "operator.wasm/signal";
+
+/// Publisher of the signals received by the admin API.
+pub const ADMIN_SOURCE: &str = "admin-api";
+
+impl WasmRuntime {
+    /// Publishes a signal from outside the parent to the operators that subscribe to its
+    /// topic. `source` is logged as the publisher; it never matches an operator, so every
+    /// subscriber receives the signal.
+    pub fn signal(&self, source: &str, topic: &str, payload: String) -> Result<(), String> {
+        if payload.len() > MAX_PAYLOAD_BYTES {
+            return Err(format!(
+                "The payload of {} bytes exceeds the limit of {} bytes",
+                payload.len(),
+                MAX_PAYLOAD_BYTES
+            ));
+        }
+        debug!("Signal on topic '{}' from {}", topic, source);
+        self.message_bus.publish(Message {
+            publisher: source.to_string(),
+            topic: topic.to_string(),
+            payload,
+        })
+    }
+
+    /// Publishes a signal whenever a labelled ConfigMap in the namespace of the parent is
+    /// created or changed.
+    pub(super) async fn signal_config_map_loop(self: Arc<Self>) {
+        let namespace = self.kubernetes_service.cluster_info().namespace.clone();
+        let api: Api<ConfigMap> = Api::namespaced(self.kubernetes_service.client(), &namespace);
+        let mut watcher =
+            watcher::watcher(api, watcher::Config::default().labels(SIGNAL_LABEL)).boxed();
+        info!(
+            "Watching ConfigMaps labelled '{}' in namespace '{}' for signals",
+            SIGNAL_LABEL, namespace
+        );
+
+        // The resource version each ConfigMap was last seen at, so a relist after the watch
+        // is re-established signals the changes made in the meantime, and nothing else.
+        let mut seen: HashMap<String, Option<String>> = HashMap::new();
+        let mut listing = true;
+        while let Some(event) = watcher.next().await {
+            let config_map = match event {
+                Ok(Event::Apply(config_map)) => config_map,
+                Ok(Event::InitApply(config_map)) => {
+                    let version = config_map.resource_version();
+                    let previous = seen.insert(config_map.name_any(), version.clone());
+                    // The first list only records what exists.
+                    if listing || previous == Some(version) {
+                        continue;
+                    }
+                    config_map
+                }
+                Ok(Event::Delete(config_map)) => {
+                    seen.remove(&config_map.name_any());
+                    continue;
+                }
+                Ok(Event::Init) => continue,
+                Ok(Event::InitDone) => {
+                    listing = false;
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Signal watch encountered an error: {}", e);
+                    continue;
+                }
+            };
+            seen.insert(config_map.name_any(), config_map.resource_version());
+            let Some(topic) = config_map.labels().get(SIGNAL_LABEL) else {
+                continue;
+            };
+            let payload = config_map
+                .data
+                .as_ref()
+                .and_then(|data| data.get("payload"))
+                .cloned()
+                .unwrap_or_default();
+            let source = format!("configmap/{}", config_map.name_any());
+            if let Err(e) = self.signal(&source, topic, payload) {
+                warn!("Dropped the signal of {}: {}", source, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! # Snapshot Module
+//!
+//! This module defines the on-disk format of the state of unloaded operators. A snapshot
+//! starts with a header that names the codec its payload was encoded with, so an operator
+//! can switch codecs, and new codecs can be added, without breaking the snapshots already
+//! on disk. Files without the header are snapshots written before it existed, and are
+//! read as raw state.
+//!
+//! ```text
+//! "WASMSNAP"  magic
+//! u8          format version
+//! u8          codec id
+//! ...         payload
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::config::metadata::SnapshotCodecKind;
+use crate::platform;
+
+const MAGIC: &[u8; 8] = b"WASMSNAP";
+const FORMAT_VERSION: u8 = 1;
+const HEADER_LEN: usize = MAGIC.len() + 2;
+
+/// Size of the blocks the delta codec compares.
+const DELTA_BLOCK: usize = 4096;
+
+/// Encodes the state of an operator for storage on disk.
+pub trait SnapshotCodec: Send + Sync {
+    /// Identifies the codec in the snapshot header. Ids are never reused.
+    fn id(&self) -> u8;
+
+    /// Encodes the state. `base` is the state stored next to the snapshot for codecs that
+    /// use one, see [`SnapshotCodec::uses_base`].
+    fn encode(&self, state: &[u8], base: Option<&[u8]>) -> Result<Vec<u8>>;
+
+    fn decode(&self, payload: &[u8], base: Option<&[u8]>) -> Result<Vec<u8>>;
+
+    /// Whether the codec encodes the state relative to a base state.
+    fn uses_base(&self) -> bool {
+        false
+    }
+}
+
+/// All codecs, in the order of their ids.
+const CODECS: [&dyn SnapshotCodec; 4] = [&Raw, &Zstd, &Lz4, &Delta];
+
+fn codec(kind: SnapshotCodecKind) -> &'static dyn SnapshotCodec {
+    match kind {
+        SnapshotCodecKind::Raw => &Raw,
+        SnapshotCodecKind::Zstd => &Zstd,
+        SnapshotCodecKind::Lz4 => &Lz4,
+        SnapshotCodecKind::Delta => &Delta,
+    }
+}
+
+fn codec_by_id(id: u8) -> Result<&'static dyn SnapshotCodec> {
+    CODECS
+        .into_iter()
+        .find(|codec| codec.id() == id)
+        .ok_or_else(|| anyhow!("Unknown snapshot codec {}", id))
+}
+
+struct Raw;
+
+impl SnapshotCodec for Raw {
+    fn id(&self) -> u8 {
+        0
+    }
+
+    fn encode(&self, state: &[u8], _base: Option<&[u8]>) -> Result<Vec<u8>> {
+        Ok(state.to_vec())
+    }
+
+    fn decode(&self, payload: &[u8], _base: Option<&[u8]>) -> Result<Vec<u8>> {
+        Ok(payload.to_vec())
+    }
+}
+
+struct Zstd;
+
+impl SnapshotCodec for Zstd {
+    fn id(&self) -> u8 {
+        1
+    }
+
+    fn encode(&self, state: &[u8], _base: Option<&[u8]>) -> Result<Vec<u8>> {
+        zstd::bulk::compress(state, zstd::DEFAULT_COMPRESSION_LEVEL)
+            .context("Failed to compress snapshot")
+    }
+
+    fn decode(&self, payload: &[u8], _base: Option<&[u8]>) -> Result<Vec<u8>> {
+        zstd::stream::decode_all(payload).context("Failed to decompress snapshot")
+    }
+}
+
+struct Lz4;
+
+impl SnapshotCodec for Lz4 {
+    fn id(&self) -> u8 {
+        2
+    }
+
+    fn encode(&self, state: &[u8], _base: Option<&[u8]>) -> Result<Vec<u8>> {
+        Ok(lz4_flex::compress_prepend_size(state))
+    }
+
+    fn decode(&self, payload: &[u8], _base: Option<&[u8]>) -> Result<Vec<u8>> {
+        lz4_flex::decompress_size_prepended(payload).context("Failed to decompress snapshot")
+    }
+}
+
+/// Stores the blocks that differ from the base state.
+///
+/// ```text
+/// u64         length of the state
+/// u64         checksum of the base, to detect a base that does not belong to the snapshot
+/// repeated:
+///   u32       block index
+///   ...       block contents, shorter for the last block of the state
+/// ```
+struct Delta;
+
+impl SnapshotCodec for Delta {
+    fn id(&self) -> u8 {
+        3
+    }
+
+    fn uses_base(&self) -> bool {
+        true
+    }
+
+    fn encode(&self, state: &[u8], base: Option<&[u8]>) -> Result<Vec<u8>> {
+        let base = base.ok_or_else(|| anyhow!("The delta codec needs a base"))?;
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&(state.len() as u64).to_le_bytes());
+        payload.extend_from_slice(&checksum(base).to_le_bytes());
+        for (index, block) in state.chunks(DELTA_BLOCK).enumerate() {
+            let start = index * DELTA_BLOCK;
+            if base.get(start..start + block.len()) != Some(block) {
+                payload.extend_from_slice(&(index as u32).to_le_bytes());
+                payload.extend_from_slice(block);
+            }
+        }
+        Ok(payload)
+    }
+
+    fn decode(&self, payload: &[u8], base: Option<&[u8]>) -> Result<Vec<u8>> {
+        let base = base.ok_or_else(|| anyhow!("The base of the delta snapshot is missing"))?;
+        let (len, rest) = split_u64(payload)?;
+        let (base_checksum, mut rest) = split_u64(rest)?;
+        if base_checksum != checksum(base) {
+            bail!("The base does not belong to the delta snapshot");
+        }
+        let len = usize::try_from(len).context("Snapshot too large")?;
+        let mut state = vec![0; len];
+        let common = len.min(base.len());
+        state[..common].copy_from_slice(&base[..common]);
+        while !rest.is_empty() {
+            let (index, blocks) = rest
+                .split_first_chunk::<4>()
+                .ok_or_else(|| anyhow!("Truncated delta snapshot"))?;
+            let start = (u32::from_le_bytes(*index) as usize)
+                .checked_mul(DELTA_BLOCK)
+                .filter(|start| *start < len)
+                .ok_or_else(|| anyhow!("Delta block out of range"))?;
+            let block_len = DELTA_BLOCK.min(len - start);
+            let block = blocks
+                .get(..block_len)
+                .ok_or_else(|| anyhow!("Truncated delta snapshot"))?;
+            state[start..start + block_len].copy_from_slice(block);
+            rest = &blocks[block_len..];
+        }
+        Ok(state)
+    }
+}
+
+fn split_u64(bytes: &[u8]) -> Result<(u64, &[u8])> {
+    let (value, rest) = bytes
+        .split_first_chunk::<8>()
+        .ok_or_else(|| anyhow!("Truncated delta snapshot"))?;
+    Ok((u64::from_le_bytes(*value), rest))
+}
+
+/// FNV-1a, enough to tell bases apart.
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Returns the path of the base state kept next to a snapshot.
+fn base_file(path: &Path) -> PathBuf {
+    path.with_extension("base")
+}
+
+/// Encodes the state with the codec and writes it to the snapshot file.
+pub async fn write(path: &Path, state: &[u8], kind: SnapshotCodecKind) -> Result<()> {
+    let codec = codec(kind);
+    let base = if codec.uses_base() {
+        Some(read_or_create_base(path, state).await?)
+    } else {
+        None
+    };
+    let payload = codec.encode(state, base.as_deref())?;
+    let mut snapshot = Vec::with_capacity(HEADER_LEN + payload.len());
+    snapshot.extend_from_slice(MAGIC);
+    snapshot.push(FORMAT_VERSION);
+    snapshot.push(codec.id());
+    snapshot.extend_from_slice(&payload);
+    platform::write_private(path, &snapshot)
+        .await
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Returns the base of the snapshot, storing the state as the base if there is none.
+async fn read_or_create_base(path: &Path, state: &[u8]) -> Result<Vec<u8>> {
+    let base_path = base_file(path);
+    match tokio::fs::read(&base_path).await {
+        Ok(base) => Ok(base),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            platform::write_private(&base_path, state).await?;
+            Ok(state.to_vec())
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", base_path.display())),
+    }
+}
+
+/// Reads a snapshot file and decodes the state in it.
+pub async fn read(path: &Path) -> Result<Vec<u8>> {
+    let snapshot = tokio::fs::read(path)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let Some(payload) = snapshot.strip_prefix(MAGIC) else {
+        return Ok(snapshot);
+    };
+    let [version, codec_id, payload @ ..] = payload else {
+        bail!("Truncated snapshot header in {}", path.display());
+    };
+    if *version != FORMAT_VERSION {
+        bail!(
+            "Unsupported snapshot format version {} in {}",
+            version,
+            path.display()
+        );
+    }
+    let codec = codec_by_id(*codec_id)?;
+    let base = if codec.uses_base() {
+        let base_path = base_file(path);
+        Some(
+            tokio::fs::read(&base_path)
+                .await
+                .with_context(|| format!("Failed to read {}", base_path.display()))?,
+        )
+    } else {
+        None
+    };
+    codec
+        .decode(payload, base.as_deref())
+        .with_context(|| format!("Failed to decode snapshot {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codecs_round_trip() {
+        let state: Vec<u8> = (0..3 * DELTA_BLOCK + 100).map(|i| i as u8).collect();
+        for codec in CODECS {
+            let payload = codec.encode(&state, Some(&state)).unwrap();
+            assert_eq!(codec.decode(&payload, Some(&state)).unwrap(), state);
+            assert_eq!(codec_by_id(codec.id()).unwrap().id(), codec.id());
+        }
+    }
+
+    #[test]
+    fn delta_stores_changed_blocks() {
+        let base: Vec<u8> = (0..3 * DELTA_BLOCK).map(|i| i as u8).collect();
+        let mut state = base.clone();
+        state[DELTA_BLOCK + 1] ^= 0xff;
+        state.extend_from_slice(b"tail");
+
+        let payload = Delta.encode(&state, Some(&base)).unwrap();
+        // The header, then the changed block and the new last block with their indexes.
+        assert_eq!(payload.len(), 16 + 4 + DELTA_BLOCK + 4 + 4);
+        assert_eq!(Delta.decode(&payload, Some(&base)).unwrap(), state);
+
+        let shorter = &state[..DELTA_BLOCK / 2];
+        let payload = Delta.encode(shorter, Some(&base)).unwrap();
+        assert_eq!(Delta.decode(&payload, Some(&base)).unwrap(), shorter);
+    }
+
+    #[test]
+    fn delta_rejects_a_foreign_base() {
+        let base = vec![1; DELTA_BLOCK];
+        let payload = Delta.encode(&[2; 10], Some(&base)).unwrap();
+        assert!(Delta.decode(&payload, Some(&[3; DELTA_BLOCK])).is_err());
+        assert!(Delta.decode(&payload, None).is_err());
+        assert!(Delta
+            .decode(&payload[..payload.len() - 1], Some(&base))
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_codecs() {
+        assert!(codec_by_id(CODECS.len() as u8).is_err());
+    }
+}
@@ -0,0 +1,81 @@
+//! # Store Pool Module
+//!
+//! This module keeps the WASI context of operators that were unloaded, so reloading them
+//! does not build it again. Building a WASI context copies the arguments and environment
+//! of the operator and seeds its random number generators, which is a fixed cost of every
+//! reload that the benchmark shows is noticeable for small components. A context holds no
+//! state of the instance it served, since operators get no preopened directories or
+//! sockets, so it can serve the next instance of the same operator. It is only reused if
+//! the arguments and environment of the operator did not change in the meantime, and only
+//! after a clean unload; instances that trapped are replaced with a new context.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use tracing::debug;
+use wasmtime_wasi::p2::{WasiCtx, WasiCtxBuilder};
+
+use crate::config::metadata::WasmComponentMetadata;
+use crate::metrics;
+
+/// The arguments and environment a WASI context was built with.
+type Fingerprint = (Vec<String>, Vec<(String, String)>);
+
+/// WASI contexts of unloaded operators, by component name.
+#[derive(Default)]
+pub struct StorePool {
+    contexts: Mutex<HashMap<String, (Fingerprint, WasiCtx)>>,
+}
+
+impl StorePool {
+    /// Keeps the WASI context of an unloaded operator for its next instance.
+    pub fn put(&self, metadata: &WasmComponentMetadata, wasi_ctx: WasiCtx) {
+        self.contexts
+            .lock()
+            .unwrap()
+            .insert(metadata.name.clone(), (fingerprint(metadata), wasi_ctx));
+    }
+
+    /// Returns the kept WASI context of an operator, or builds a new one if none was kept
+    /// or its arguments or environment changed.
+    pub fn take(&self, metadata: &WasmComponentMetadata) -> WasiCtx {
+        let kept = self.contexts.lock().unwrap().remove(&metadata.name);
+        match kept {
+            Some((kept, wasi_ctx)) if kept == fingerprint(metadata) => {
+                debug!("Reusing the WASI context of operator {}", metadata.name);
+                metrics::increment(
+                    "wasm_operator_store_reuses_total",
+                    &[("operator", &metadata.name)],
+                );
+                wasi_ctx
+            }
+            _ => build(metadata),
+        }
+    }
+}
+
+/// Builds the WASI context of an operator.
+fn build(metadata: &WasmComponentMetadata) -> WasiCtx {
+    WasiCtxBuilder::new()
+        .inherit_stdio()
+        .args(&metadata.args)
+        .envs(
+            &metadata
+                .env
+                .iter()
+                .map(|e| (e.name.as_str(), e.value.as_str()))
+                .collect::<Vec<_>>(),
+        )
+        .build()
+}
+
+fn fingerprint(metadata: &WasmComponentMetadata) -> Fingerprint {
+    (
+        metadata.args.clone(),
+        metadata
+            .env
+            .iter()
+            .map(|e| (e.name.clone(), e.value.clone()))
+            .collect(),
+    )
+}
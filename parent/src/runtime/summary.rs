@@ -0,0 +1,164 @@
+//! # Summary Module
+//!
+//! This module rolls the status of all operators of the parent up into one summary, so
+//! fleet operators have a single object to watch and alert on instead of one per operator.
+//! The summary counts the operators by load state and by health, and names the worst
+//! health of any of them. It is served by the admin API and, when
+//! `status-summary-interval-secs` is set, written periodically to the ConfigMap
+//! `wasm-operator-status` in the namespace of the parent.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use super::{OperatorState, WasmRuntime};
+use crate::runtime::introspection::now_ms;
+
+/// Name of the ConfigMap the summary is written to.
+pub const SUMMARY_CONFIG_MAP: &str = "wasm-operator-status";
+
+/// Label on the summary ConfigMap.
+const SUMMARY_LABEL: (&str, &str) = ("operator.wasm/status-summary", "true");
+
+/// How well an operator is doing, from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Health {
+    Ok,
+    /// Some objects exhausted their retries and are in the dead-letter queue.
+    Failing,
+    /// Taken out of service after a panic in the host.
+    Degraded,
+}
+
+impl Health {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Health::Ok => "ok",
+            Health::Failing => "failing",
+            Health::Degraded => "degraded",
+        }
+    }
+}
+
+/// The status of one operator.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperatorSummary {
+    pub name: String,
+    pub state: String,
+    pub health: Health,
+    pub dead_letters: usize,
+}
+
+/// The status of all operators of the parent.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusSummary {
+    pub updated_ms: u64,
+    /// The worst health of any operator, `ok` if there are none.
+    pub worst_health: Health,
+    pub by_state: BTreeMap<String, usize>,
+    pub by_health: BTreeMap<String, usize>,
+    pub operators: Vec<OperatorSummary>,
+}
+
+impl WasmRuntime {
+    /// Summarizes the status of all operators.
+    pub fn status_summary(&self) -> StatusSummary {
+        let mut ids: Vec<String> = self
+            .introspection
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect();
+        ids.sort();
+        let operators: Vec<OperatorSummary> = ids
+            .into_iter()
+            .map(|id| {
+                let dead_letters = self.dead_letters(&id).len();
+                let (state, degraded) = match self.operators.get(&id).as_deref() {
+                    Some(OperatorState::Loaded { .. }) => ("loaded", false),
+                    Some(OperatorState::Unloaded { .. }) => ("unloaded", false),
+                    Some(OperatorState::Degraded { .. }) => ("degraded", true),
+                    // The entry is taken out of the map while the operator handles a call.
+                    None => ("busy", false),
+                };
+                let health = if degraded {
+                    Health::Degraded
+                } else if dead_letters > 0 {
+                    Health::Failing
+                } else {
+                    Health::Ok
+                };
+                OperatorSummary {
+                    name: id,
+                    state: state.to_string(),
+                    health,
+                    dead_letters,
+                }
+            })
+            .collect();
+
+        let mut by_state = BTreeMap::new();
+        let mut by_health = BTreeMap::new();
+        for operator in &operators {
+            *by_state.entry(operator.state.clone()).or_default() += 1;
+            *by_health
+                .entry(operator.health.as_str().to_string())
+                .or_default() += 1;
+        }
+        StatusSummary {
+            updated_ms: now_ms(),
+            worst_health: operators
+                .iter()
+                .map(|operator| operator.health)
+                .max()
+                .unwrap_or(Health::Ok),
+            by_state,
+            by_health,
+            operators,
+        }
+    }
+
+    /// Writes the status summary to its ConfigMap at the given interval.
+    pub(super) async fn status_summary_loop(self: Arc<Self>, interval: Duration) {
+        if self.config.read_only {
+            info!("Read-only mode: the status summary is not written to the cluster");
+            return;
+        }
+        loop {
+            if let Err(e) = self.write_status_summary().await {
+                warn!("Failed to write the status summary: {:#}", e);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    async fn write_status_summary(&self) -> Result<()> {
+        let summary = self.status_summary();
+        let namespace = &self.kubernetes_service.cluster_info().namespace;
+        // The worst health gets a key of its own, so alerts need not parse the summary.
+        self.kubernetes_service
+            .set_config_map_value(
+                SUMMARY_CONFIG_MAP,
+                namespace,
+                "worst-health",
+                Some(summary.worst_health.as_str()),
+                SUMMARY_LABEL,
+            )
+            .await?;
+        self.kubernetes_service
+            .set_config_map_value(
+                SUMMARY_CONFIG_MAP,
+                namespace,
+                "summary.json",
+                Some(&serde_json::to_string_pretty(&summary)?),
+                SUMMARY_LABEL,
+            )
+            .await
+    }
+}
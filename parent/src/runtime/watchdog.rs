@@ -0,0 +1,174 @@
+//! # Watchdog Module
+//!
+//! This module supervises the long-running tasks of the runtime, such as the idle unload
+//! loop, the timer and message loops, the watches of the operators and the admin API. They
+//! used to be spawned and forgotten, so a task that ended or hung went unnoticed until
+//! operators stopped reacting. Every `watchdog-interval-secs` the watchdog restarts each
+//! subsystem whose task ended, whose heartbeat is older than its stall limit, or whose
+//! listener no longer accepts connections, and logs and counts the restart in
+//! `wasm_operator_subsystem_restarts_total`.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::LocalBoxFuture;
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use super::WasmRuntime;
+use crate::metrics;
+
+/// How long a connection to a supervised listener may take before it counts as unhealthy.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Starts the task of a subsystem, or returns `None` if it should no longer run, e.g.
+/// because its operator is degraded.
+pub type Spawn = Box<dyn Fn() -> Option<LocalBoxFuture<'static, ()>> + Send + Sync>;
+
+/// How the watchdog tells that a running subsystem is stuck.
+pub enum Probe {
+    /// The subsystem only fails by ending.
+    None,
+    /// The subsystem calls `beat` at least once per the given interval.
+    Heartbeat(Duration),
+    /// The subsystem accepts connections on the given address.
+    Connect(SocketAddr),
+}
+
+struct Subsystem {
+    spawn: Spawn,
+    probe: Probe,
+    task: JoinHandle<()>,
+    started: Instant,
+}
+
+/// The supervised subsystems, by name.
+#[derive(Default)]
+pub struct Watchdog {
+    subsystems: Mutex<HashMap<String, Subsystem>>,
+    heartbeats: Mutex<HashMap<String, Instant>>,
+}
+
+impl Watchdog {
+    /// Starts a subsystem and supervises it from now on. Must be called on the `LocalSet`
+    /// of the runtime.
+    pub fn supervise(&self, name: &str, probe: Probe, spawn: Spawn) {
+        let Some(task) = spawn() else {
+            return;
+        };
+        debug!("Supervising subsystem '{}'", name);
+        let subsystem = Subsystem {
+            spawn,
+            probe,
+            task: tokio::task::spawn_local(task),
+            started: Instant::now(),
+        };
+        if let Some(previous) = self
+            .subsystems
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), subsystem)
+        {
+            previous.task.abort();
+        }
+    }
+
+    /// Records that a subsystem with a heartbeat probe is making progress.
+    pub fn beat(&self, name: &str) {
+        self.heartbeats
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), Instant::now());
+    }
+
+    /// Restarts the subsystems that ended or are stuck.
+    async fn check(&self) {
+        let listeners: Vec<(String, SocketAddr)> = self
+            .subsystems
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(name, subsystem)| match subsystem.probe {
+                Probe::Connect(addr) if !subsystem.task.is_finished() => Some((name.clone(), addr)),
+                _ => None,
+            })
+            .collect();
+        let mut unhealthy = Vec::new();
+        for (name, addr) in listeners {
+            if !accepts_connections(addr).await {
+                unhealthy.push(name);
+            }
+        }
+
+        let heartbeats = self.heartbeats.lock().unwrap().clone();
+        let mut subsystems = self.subsystems.lock().unwrap();
+        let mut retired = Vec::new();
+        for (name, subsystem) in subsystems.iter_mut() {
+            let reason = if subsystem.task.is_finished() {
+                "ended"
+            } else if unhealthy.contains(name) {
+                "unhealthy"
+            } else if let Probe::Heartbeat(limit) = subsystem.probe
+                && heartbeats
+                    .get(name)
+                    .copied()
+                    .unwrap_or(subsystem.started)
+                    .elapsed()
+                    > limit
+            {
+                "stalled"
+            } else {
+                continue;
+            };
+            subsystem.task.abort();
+            let Some(task) = (subsystem.spawn)() else {
+                info!("Subsystem '{}' {} and is not restarted", name, reason);
+                retired.push(name.clone());
+                continue;
+            };
+            warn!("Subsystem '{}' {}, restarting it", name, reason);
+            metrics::increment(
+                "wasm_operator_subsystem_restarts_total",
+                &[("subsystem", name), ("reason", reason)],
+            );
+            subsystem.task = tokio::task::spawn_local(task);
+            subsystem.started = Instant::now();
+        }
+        for name in retired {
+            subsystems.remove(&name);
+        }
+    }
+}
+
+impl WasmRuntime {
+    /// Checks the supervised subsystems at the given interval.
+    pub(super) async fn watchdog_loop(self: Arc<Self>, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            self.watchdog.check().await;
+        }
+    }
+
+    /// Starts a subsystem under the supervision of the watchdog of the runtime, e.g. the
+    /// admin API of an embedding parent.
+    pub fn supervise(&self, name: &str, probe: Probe, spawn: Spawn) {
+        self.watchdog.supervise(name, probe, spawn);
+    }
+}
+
+/// Whether a listener accepts connections, connecting over loopback if it listens on all
+/// addresses.
+async fn accepts_connections(mut addr: SocketAddr) -> bool {
+    match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => addr.set_ip(Ipv4Addr::LOCALHOST.into()),
+        IpAddr::V6(ip) if ip.is_unspecified() => addr.set_ip(Ipv6Addr::LOCALHOST.into()),
+        _ => {}
+    }
+    matches!(
+        tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addr)).await,
+        Ok(Ok(_))
+    )
+}
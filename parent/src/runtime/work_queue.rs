@@ -0,0 +1,350 @@
+//! # Work Queue Module
+//!
+//! This module queues the watch events of each operator by object, like the work queue of
+//! controller-runtime. Watch events used to be reconciled inline from the watch loop, so a
+//! burst of updates to one object reconciled each of them in turn, most of them with a
+//! state that was already out of date. Events for an object that is still waiting are now
+//! merged into the one waiting, so the operator reconciles the latest state of the object
+//! once, and an object is never reconciled twice at the same time. Merged events are
+//! counted in `wasm_operator_coalesced_events_total`, and the number of objects waiting in
+//! `wasm_operator_work_queue_depth`. Up to `max-concurrent-reconciles` different objects
+//! are reconciled at the same time. Resyncs, drift events, reconciles enqueued by other
+//! operators, retries, requeues and the next steps of continuations go through the same
+//! queue, so they are merged with the events of their object too, and its worker is the
+//! only caller of `dispatch_reconcile`.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use kube::api::DynamicObject;
+use tokio::sync::{Notify, Semaphore};
+use tracing::debug;
+
+use super::dead_letter::ObjectRef;
+use super::watchdog::Probe;
+use super::WasmRuntime;
+use crate::host::api::bindings::local::operator::types::{
+    EventType, ReconcileReason, ReconcileTrigger,
+};
+use crate::metrics;
+
+/// A watch event waiting to be reconciled.
+pub struct WorkItem {
+    pub event_type: EventType,
+    pub reason: ReconcileReason,
+    pub object: DynamicObject,
+}
+
+impl WorkItem {
+    /// Merges a later event for the same object into this one. The latest state of the
+    /// object is kept, with the event type that tells the operator what changed since it
+    /// last saw the object.
+    fn merge(&mut self, later: WorkItem) {
+        self.event_type = match (self.event_type, later.event_type) {
+            (_, EventType::Deleted) => EventType::Deleted,
+            // The operator has not seen the object yet.
+            (EventType::Added, EventType::Modified | EventType::Resynced) => EventType::Added,
+            (EventType::Modified, EventType::Resynced) => EventType::Modified,
+            (_, later) => later,
+        };
+        self.reason = later.reason;
+        self.object = later.object;
+    }
+
+    /// Merges a retry, a requeue or the next step of a continuation into the event waiting
+    /// for the same object. The waiting event holds the same or a newer state of the
+    /// object, so it is kept; a continuation only passes on its token.
+    fn merge_requeue(&mut self, requeued: WorkItem) {
+        if requeued.reason.trigger == ReconcileTrigger::Continuation {
+            self.reason = requeued.reason;
+        }
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Objects waiting, in the order their first event arrived.
+    order: VecDeque<ObjectRef>,
+    waiting: HashMap<ObjectRef, WorkItem>,
+    /// Objects being reconciled, whose events wait until the reconcile is done.
+    processing: HashSet<ObjectRef>,
+}
+
+/// The watch events of one operator, by object.
+pub struct WorkQueue {
+    operator: String,
+    inner: Mutex<Inner>,
+    notify: Notify,
+}
+
+/// An object taken from the queue for reconciling. Dropping it marks the object as done,
+/// so an event that arrived in the meantime is reconciled next.
+pub struct Claim {
+    queue: Arc<WorkQueue>,
+    key: ObjectRef,
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        let mut inner = self.queue.inner.lock().unwrap();
+        inner.processing.remove(&self.key);
+        if inner.waiting.contains_key(&self.key) {
+            inner.order.push_back(self.key.clone());
+            drop(inner);
+            self.queue.notify.notify_one();
+        }
+    }
+}
+
+impl WorkQueue {
+    pub fn new(operator: &str) -> Self {
+        Self {
+            operator: operator.to_string(),
+            inner: Mutex::default(),
+            notify: Notify::new(),
+        }
+    }
+
+    /// Queues an event, merging it into the one waiting for the same object if there is
+    /// one. Returns whether it was merged.
+    pub fn add(&self, item: WorkItem) -> bool {
+        self.insert(item, WorkItem::merge)
+    }
+
+    /// Queues a reconcile the runtime asks for itself, such as a retry, merging it into the
+    /// event waiting for the same object if there is one. Returns whether it was merged.
+    pub fn requeue(&self, item: WorkItem) -> bool {
+        self.insert(item, WorkItem::merge_requeue)
+    }
+
+    fn insert(&self, item: WorkItem, merge: fn(&mut WorkItem, WorkItem)) -> bool {
+        let key = ObjectRef::new(&self.operator, &item.object);
+        let mut inner = self.inner.lock().unwrap();
+        let merged = if let Some(waiting) = inner.waiting.get_mut(&key) {
+            merge(waiting, item);
+            true
+        } else {
+            inner.waiting.insert(key.clone(), item);
+            // An object being reconciled is queued again once it is done.
+            if !inner.processing.contains(&key) {
+                inner.order.push_back(key);
+            }
+            false
+        };
+        self.record_depth(&inner);
+        drop(inner);
+
+        if merged {
+            debug!(
+                "Merged a watch event into the one waiting for operator '{}'",
+                self.operator
+            );
+            metrics::increment(
+                "wasm_operator_coalesced_events_total",
+                &[("operator", &self.operator)],
+            );
+        } else {
+            self.notify.notify_one();
+        }
+        merged
+    }
+
+    /// Waits for an object that is not being reconciled and returns its latest event.
+    pub async fn next(self: &Arc<Self>) -> (Claim, WorkItem) {
+        loop {
+            let notified = self.notify.notified();
+            {
+                let mut inner = self.inner.lock().unwrap();
+                if let Some(key) = inner.order.pop_front() {
+                    let item = inner
+                        .waiting
+                        .remove(&key)
+                        .expect("queued objects have a waiting event");
+                    inner.processing.insert(key.clone());
+                    self.record_depth(&inner);
+                    let claim = Claim {
+                        queue: self.clone(),
+                        key,
+                    };
+                    return (claim, item);
+                }
+            }
+            notified.await;
+        }
+    }
+
+    fn record_depth(&self, inner: &Inner) {
+        metrics::set_gauge(
+            "wasm_operator_work_queue_depth",
+            &[("operator", &self.operator)],
+            inner.waiting.len() as f64,
+        );
+    }
+}
+
+impl WasmRuntime {
+    /// Queues a watch event for an operator, starting the worker that reconciles its
+    /// queue on the first event.
+    pub(super) fn enqueue_event(self: &Arc<Self>, operator_id: &str, item: WorkItem) {
+        self.work_queue(operator_id).add(item);
+    }
+
+    /// Queues a retry, a requeue or the next step of a continuation for an operator.
+    pub(super) fn requeue_event(self: &Arc<Self>, operator_id: &str, item: WorkItem) {
+        self.work_queue(operator_id).requeue(item);
+    }
+
+    fn work_queue(self: &Arc<Self>, operator_id: &str) -> Arc<WorkQueue> {
+        self.work_queues
+            .entry(operator_id.to_string())
+            .or_insert_with(|| {
+                let queue = Arc::new(WorkQueue::new(operator_id));
+                self.start_worker(operator_id, queue.clone());
+                queue
+            })
+            .clone()
+    }
+
+    fn start_worker(self: &Arc<Self>, operator_id: &str, queue: Arc<WorkQueue>) {
+        let runtime = self.clone();
+        let operator_id = operator_id.to_string();
+        let concurrency = self
+            .operator_metadata(&operator_id)
+            .map_or(1, |metadata| metadata.max_concurrent_reconciles.max(1));
+        let slots = Arc::new(Semaphore::new(concurrency));
+        self.watchdog.supervise(
+            &format!("work-queue/{}", operator_id),
+            Probe::None,
+            Box::new(move || {
+                // A degraded operator gets no more events.
+                if runtime.is_degraded(&operator_id) {
+                    return None;
+                }
+                let runtime = runtime.clone();
+                let operator_id = operator_id.clone();
+                let queue = queue.clone();
+                let slots = slots.clone();
+                Some(Box::pin(async move {
+                    loop {
+                        let slot = slots
+                            .clone()
+                            .acquire_owned()
+                            .await
+                            .expect("the slots are never closed");
+                        let (claim, item) = queue.next().await;
+                        let runtime = runtime.clone();
+                        let operator_id = operator_id.clone();
+                        tokio::task::spawn_local(async move {
+                            runtime
+                                .dispatch_reconcile(
+                                    &operator_id,
+                                    item.event_type,
+                                    item.reason,
+                                    &item.object,
+                                )
+                                .await;
+                            drop(claim);
+                            drop(slot);
+                        });
+                    }
+                }))
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(event_type: EventType, trigger: ReconcileTrigger, version: &str) -> WorkItem {
+        WorkItem {
+            event_type,
+            reason: ReconcileReason {
+                trigger,
+                triggered_by: None,
+                continuation: None,
+                requested_by: None,
+            },
+            object: serde_json::from_value(serde_json::json!({
+                "apiVersion": "v1",
+                "kind": "ConfigMap",
+                "metadata": {"name": "a", "namespace": "default", "resourceVersion": version},
+            }))
+            .unwrap(),
+        }
+    }
+
+    fn version(item: &WorkItem) -> &str {
+        item.object.metadata.resource_version.as_deref().unwrap()
+    }
+
+    #[test]
+    fn merge_keeps_the_latest_object() {
+        let mut waiting = item(EventType::Added, ReconcileTrigger::WatchApply, "1");
+        waiting.merge(item(EventType::Modified, ReconcileTrigger::WatchApply, "2"));
+        assert_eq!(waiting.event_type, EventType::Added);
+        assert_eq!(version(&waiting), "2");
+
+        let mut waiting = item(EventType::Modified, ReconcileTrigger::WatchApply, "1");
+        waiting.merge(item(EventType::Resynced, ReconcileTrigger::Resync, "1"));
+        assert_eq!(waiting.event_type, EventType::Modified);
+        assert_eq!(waiting.reason.trigger, ReconcileTrigger::Resync);
+    }
+
+    #[test]
+    fn merge_keeps_deletes() {
+        let mut waiting = item(EventType::Added, ReconcileTrigger::WatchApply, "1");
+        waiting.merge(item(EventType::Deleted, ReconcileTrigger::WatchDelete, "2"));
+        assert_eq!(waiting.event_type, EventType::Deleted);
+
+        let mut waiting = item(EventType::Deleted, ReconcileTrigger::WatchDelete, "1");
+        waiting.merge(item(EventType::Added, ReconcileTrigger::WatchApply, "2"));
+        assert_eq!(waiting.event_type, EventType::Added);
+    }
+
+    #[test]
+    fn requeues_keep_the_waiting_event() {
+        let mut waiting = item(EventType::Modified, ReconcileTrigger::WatchApply, "2");
+        waiting.merge_requeue(item(EventType::Added, ReconcileTrigger::Timer, "1"));
+        assert_eq!(waiting.event_type, EventType::Modified);
+        assert_eq!(waiting.reason.trigger, ReconcileTrigger::WatchApply);
+        assert_eq!(version(&waiting), "2");
+
+        let mut continuation = item(EventType::Added, ReconcileTrigger::Continuation, "1");
+        continuation.reason.continuation = Some("step-2".to_string());
+        waiting.merge_requeue(continuation);
+        assert_eq!(waiting.reason.continuation.as_deref(), Some("step-2"));
+        assert_eq!(version(&waiting), "2");
+    }
+
+    #[test]
+    fn events_wait_while_their_object_is_reconciled() {
+        let queue = Arc::new(WorkQueue::new("operator"));
+        assert!(!queue.add(item(EventType::Added, ReconcileTrigger::WatchApply, "1")));
+        let (claim, first) = futures::executor::block_on(queue.next());
+        assert_eq!(version(&first), "1");
+
+        assert!(!queue.requeue(item(EventType::Added, ReconcileTrigger::Timer, "1")));
+        assert!(queue.add(item(EventType::Modified, ReconcileTrigger::WatchApply, "2")));
+        assert!(queue.inner.lock().unwrap().order.is_empty());
+
+        drop(claim);
+        let (_claim, next) = futures::executor::block_on(queue.next());
+        assert_eq!(next.event_type, EventType::Added);
+        assert_eq!(version(&next), "2");
+    }
+
+    #[test]
+    fn resyncs_merge_with_watch_events() {
+        let queue = Arc::new(WorkQueue::new("operator"));
+        assert!(!queue.add(item(EventType::Modified, ReconcileTrigger::WatchApply, "2")));
+        assert!(queue.add(item(EventType::Resynced, ReconcileTrigger::Resync, "2")));
+
+        let (_claim, next) = futures::executor::block_on(queue.next());
+        assert_eq!(next.event_type, EventType::Modified);
+        assert_eq!(next.reason.trigger, ReconcileTrigger::Resync);
+        let inner = queue.inner.lock().unwrap();
+        assert!(inner.order.is_empty() && inner.waiting.is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+//! # Tarball Module
+//!
+//! This module reads and writes the subset of the ustar format the parent needs for debug
+//! bundles and operator bundles: regular files with paths of up to 255 bytes. Other
+//! entries, such as directories, are skipped when reading.
+
+use anyhow::{bail, Context, Result};
+
+/// Builds a tar archive.
+pub struct Tarball {
+    data: Vec<u8>,
+    /// Modification time of all files, in seconds since the epoch.
+    mtime: u64,
+}
+
+impl Tarball {
+    pub fn new(mtime: u64) -> Self {
+        Self {
+            data: Vec::new(),
+            mtime,
+        }
+    }
+
+    pub fn add(&mut self, path: &str, contents: &[u8]) -> Result<()> {
+        let (prefix, name) = split_path(path)?;
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        write_octal(&mut header[100..108], 0o644)?;
+        write_octal(&mut header[108..116], 0)?;
+        write_octal(&mut header[116..124], 0)?;
+        write_octal(&mut header[124..136], contents.len() as u64)?;
+        write_octal(&mut header[136..148], self.mtime)?;
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+        let checksum = checksum(&header);
+        write_octal(&mut header[148..155], checksum)?;
+
+        self.data.extend_from_slice(&header);
+        self.data.extend_from_slice(contents);
+        let padding = (512 - contents.len() % 512) % 512;
+        self.data.resize(self.data.len() + padding, 0);
+        Ok(())
+    }
+
+    /// Ends the archive with two empty blocks.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.data.resize(self.data.len() + 1024, 0);
+        self.data
+    }
+}
+
+/// Returns the regular files of a tar archive as pairs of path and contents.
+pub fn read(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut files = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = data.get(offset..offset + 512) {
+        if header.iter().all(|byte| *byte == 0) {
+            return Ok(files);
+        }
+        let header: &[u8; 512] = header.try_into()?;
+        if read_octal(&header[148..156])? != checksum(header) {
+            bail!("Invalid checksum in the tar header at offset {}", offset);
+        }
+        let size = usize::try_from(read_octal(&header[124..136])?)?;
+        let contents = data
+            .get(offset + 512..offset + 512 + size)
+            .with_context(|| format!("Truncated tar entry at offset {}", offset))?;
+        if matches!(header[156], b'0' | 0) {
+            let name = read_str(&header[..100])?;
+            let prefix = read_str(&header[345..500])?;
+            let path = if prefix.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}/{}", prefix, name)
+            };
+            files.push((path, contents.to_vec()));
+        }
+        offset += 512 + size.div_ceil(512) * 512;
+    }
+    bail!("Tar archive is not terminated")
+}
+
+/// The checksum of a header, computed with its own field filled with spaces.
+fn checksum(header: &[u8; 512]) -> u64 {
+    let sum: u64 = header.iter().map(|byte| u64::from(*byte)).sum();
+    let field: u64 = header[148..156].iter().map(|byte| u64::from(*byte)).sum();
+    sum - field + 8 * u64::from(b' ')
+}
+
+/// Splits a path into the prefix and name fields of a ustar header.
+fn split_path(path: &str) -> Result<(&str, &str)> {
+    if path.len() <= 100 {
+        return Ok(("", path));
+    }
+    path.match_indices('/')
+        .map(|(index, _)| (&path[..index], &path[index + 1..]))
+        .find(|(prefix, name)| prefix.len() <= 155 && name.len() <= 100)
+        .with_context(|| format!("Path too long for a tar archive: {}", path))
+}
+
+/// Writes a number as a NUL-terminated, zero-padded octal string that fills the field.
+fn write_octal(field: &mut [u8], value: u64) -> Result<()> {
+    let digits = format!("{:0width$o}\0", value, width = field.len() - 1);
+    if digits.len() != field.len() {
+        bail!("Value {} does not fit in a tar header field", value);
+    }
+    field.copy_from_slice(digits.as_bytes());
+    Ok(())
+}
+
+/// Reads an octal number terminated by a NUL or a space.
+fn read_octal(field: &[u8]) -> Result<u64> {
+    let digits = read_str(field)?.trim_matches(' ');
+    u64::from_str_radix(digits, 8)
+        .with_context(|| format!("Invalid number in a tar header: {:?}", digits))
+}
+
+/// Reads a NUL-terminated string that may fill the field.
+fn read_str(field: &[u8]) -> Result<&str> {
+    let end = field
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(field.len());
+    std::str::from_utf8(&field[..end]).context("Invalid path in a tar header")
+}
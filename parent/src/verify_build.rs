@@ -0,0 +1,223 @@
+//! # Verify Build Module
+//!
+//! This module implements `parent verify-build`, which checks that a component was built
+//! from the source it claims to be. It exports a git ref of the operator's repository,
+//! rebuilds it in a container with a pinned Rust toolchain and compares the digest of the
+//! rebuilt component with the one given, so users who run third-party operators do not
+//! have to trust the machine that built them.
+//!
+//! Builds are made reproducible by building the exported tree, not a working copy, at a
+//! fixed path, with `SOURCE_DATE_EPOCH` set to the commit time and the build directory
+//! remapped out of the debug info. Builds that still embed something variable, such as a
+//! timestamp from a build script, cannot be verified this way.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::bundle::{self, Bundle};
+use crate::platform;
+
+/// Toolchain image the component is rebuilt in, unless another is given. The same as the
+/// builder of the parent image; pass `--image rust@sha256:<digest>` to pin it by digest.
+pub const DEFAULT_IMAGE: &str = "docker.io/library/rust:1.88.0";
+/// Command that builds the component in the root of the exported tree.
+pub const DEFAULT_BUILD_COMMAND: &str = "cargo build --release --locked --target wasm32-wasip2";
+
+/// Where the exported tree is mounted in the container.
+const SOURCE_MOUNT: &str = "/src";
+
+const USAGE: &str = "Usage: parent verify-build <component.wasm|bundle.wopr> --source <git-ref> --artifact <path> [--repo <path>] [--image <image>] [--engine docker|podman] [--build-command <command>]";
+
+/// How to rebuild a component.
+struct BuildSpec {
+    repo: PathBuf,
+    source: String,
+    /// Path of the built component, relative to the root of the repository.
+    artifact: PathBuf,
+    image: String,
+    engine: String,
+    build_command: String,
+}
+
+/// Runs the verify-build subcommand with the arguments that follow it. Returns whether the
+/// rebuilt component matches.
+pub fn run(args: &[String]) -> Result<bool> {
+    let mut component: Option<PathBuf> = None;
+    let mut source: Option<String> = None;
+    let mut artifact: Option<PathBuf> = None;
+    let mut repo = PathBuf::from(".");
+    let mut image = DEFAULT_IMAGE.to_string();
+    let mut engine = "docker".to_string();
+    let mut build_command = DEFAULT_BUILD_COMMAND.to_string();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| anyhow!("{} requires a value", arg))
+        };
+        match arg.as_str() {
+            "--source" => source = Some(value()?),
+            "--artifact" => artifact = Some(PathBuf::from(value()?)),
+            "--repo" => repo = PathBuf::from(value()?),
+            "--image" => image = value()?,
+            "--engine" => engine = value()?,
+            "--build-command" => build_command = value()?,
+            _ if component.is_none() && !arg.starts_with("--") => {
+                component = Some(PathBuf::from(arg))
+            }
+            _ => bail!("Unexpected argument: {}\n{}", arg, USAGE),
+        }
+    }
+    let (Some(component), Some(source), Some(artifact)) = (component, source, artifact) else {
+        bail!(USAGE);
+    };
+    if artifact.is_absolute() {
+        bail!("--artifact must be relative to the root of the repository");
+    }
+
+    let expected = component_digest(&component)?;
+    let spec = BuildSpec {
+        repo,
+        source,
+        artifact,
+        image,
+        engine,
+        build_command,
+    };
+    let actual = rebuild(&spec)?;
+
+    println!("Expected sha256:{}  {}", expected, component.display());
+    println!("Rebuilt  sha256:{}  {}", actual, spec.artifact.display());
+    if expected == actual {
+        println!("OK: the component was built from {}", spec.source);
+        Ok(true)
+    } else {
+        println!(
+            "MISMATCH: the component was not built from {} with {}",
+            spec.source, spec.image
+        );
+        Ok(false)
+    }
+}
+
+/// The digest of a component, or of the component in a bundle.
+fn component_digest(path: &Path) -> Result<String> {
+    if bundle::is_bundle(path) {
+        return Ok(bundle::sha256(Bundle::read(path)?.component()));
+    }
+    let component = std::fs::read(path)
+        .with_context(|| format!("Failed to read component {}", path.display()))?;
+    Ok(bundle::sha256(&component))
+}
+
+/// Rebuilds the component from the exported source and returns its digest.
+fn rebuild(spec: &BuildSpec) -> Result<String> {
+    let commit = git(
+        &spec.repo,
+        &[
+            "rev-parse",
+            "--verify",
+            &format!("{}^{{commit}}", spec.source),
+        ],
+    )
+    .with_context(|| format!("Unknown git ref '{}'", spec.source))?;
+    let epoch = git(&spec.repo, &["log", "-1", "--format=%ct", &commit])?;
+    println!("Rebuilding {} ({}) in {}", spec.source, commit, spec.image);
+
+    let work_dir =
+        std::env::temp_dir().join(format!("wasm-operator-verify-{}", std::process::id()));
+    std::fs::create_dir_all(&work_dir)
+        .with_context(|| format!("Failed to create {}", work_dir.display()))?;
+    let result = export(&spec.repo, &commit, &work_dir)
+        .and_then(|()| build(spec, &work_dir, &epoch))
+        .and_then(|()| {
+            let path = work_dir.join(&spec.artifact);
+            let component = std::fs::read(&path).with_context(|| {
+                format!(
+                    "The build did not produce {}; check --artifact",
+                    spec.artifact.display()
+                )
+            })?;
+            Ok(bundle::sha256(&component))
+        });
+    if let Err(e) = std::fs::remove_dir_all(&work_dir) {
+        eprintln!("Failed to remove {}: {}", work_dir.display(), e);
+    }
+    result
+}
+
+/// Writes the tree of a commit to a directory, without the untracked and ignored files of
+/// the working copy.
+fn export(repo: &Path, commit: &str, dir: &Path) -> Result<()> {
+    let mut archive = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["archive", "--format=tar", commit])
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to run git")?;
+    let stdout = archive.stdout.take().expect("stdout is piped");
+    let extracted = Command::new("tar")
+        .arg("-x")
+        .arg("-C")
+        .arg(dir)
+        .stdin(stdout)
+        .status()
+        .context("Failed to run tar")?;
+    let archived = archive.wait()?;
+    if !archived.success() || !extracted.success() {
+        bail!("Failed to export {} of {}", commit, repo.display());
+    }
+    Ok(())
+}
+
+/// Runs the build command in the toolchain container, with the exported tree mounted.
+fn build(spec: &BuildSpec, dir: &Path, epoch: &str) -> Result<()> {
+    // The container runs as root; hand the files it writes back to the owner of the
+    // directory so it can be removed.
+    let chown = match platform::owner(dir)? {
+        Some((uid, gid)) => format!("; chown -R {}:{} {}", uid, gid, SOURCE_MOUNT),
+        None => String::new(),
+    };
+    let script = format!(
+        "rustup target add wasm32-wasip2 && {}; status=$?{}; exit $status",
+        spec.build_command, chown
+    );
+    let status = Command::new(&spec.engine)
+        .args(["run", "--rm", "--workdir", SOURCE_MOUNT])
+        .arg("--volume")
+        .arg(format!("{}:{}", dir.display(), SOURCE_MOUNT))
+        .args(["--env", &format!("SOURCE_DATE_EPOCH={}", epoch)])
+        .args([
+            "--env",
+            &format!("RUSTFLAGS=--remap-path-prefix={}=.", SOURCE_MOUNT),
+        ])
+        .args([&spec.image, "sh", "-c", &script])
+        .status()
+        .with_context(|| format!("Failed to run {}", spec.engine))?;
+    if !status.success() {
+        bail!("The build failed in {} ({})", spec.image, status);
+    }
+    Ok(())
+}
+
+/// Runs a git command in a repository and returns its trimmed output.
+fn git(repo: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}